@@ -0,0 +1,157 @@
+//! Measures encoding and decoding a second of audio, across complexities and
+//! frame sizes and with 16-bit and float samples.
+
+#[macro_use]
+extern crate criterion;
+extern crate opus;
+
+use std::f32::consts::TAU;
+
+use criterion::{black_box, BenchmarkId, Criterion, Throughput};
+use opus::{Application, Bitrate, Channels, Decoder, Encoder, FrameDuration};
+
+/// Samples per channel in a second of 48kHz audio.
+const SECOND: usize = 48000;
+
+/// A second of 48kHz stereo music-like audio.
+fn signal() -> Vec<i16> {
+    let mut noise = 1u32;
+    (0..SECOND * 2)
+        .map(|i| {
+            noise = noise.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let t = (i / 2) as f32 / SECOND as f32;
+            let tone = (t * 440.0 * TAU).sin() * 6000.0 + (t * 1250.0 * TAU).sin() * 3000.0;
+            (tone + (noise >> 20) as f32 - 2048.0) as i16
+        })
+        .collect()
+}
+
+fn encoder(complexity: i32) -> Encoder {
+    let mut encoder = Encoder::new(48000, Channels::Stereo, Application::Audio).unwrap();
+    encoder.set_bitrate(Bitrate::Bits(64000)).unwrap();
+    encoder.set_complexity(complexity).unwrap();
+    encoder
+}
+
+fn encode_complexity(c: &mut Criterion) {
+    let pcm = signal();
+    let mut out = [0; 1500];
+
+    let mut group = c.benchmark_group("encode/complexity");
+    group.throughput(Throughput::Elements(SECOND as u64));
+    for &complexity in &[0, 5, 10] {
+        let mut encoder = encoder(complexity);
+        group.bench_function(BenchmarkId::from_parameter(complexity), |b| {
+            b.iter(|| {
+                for frame in pcm.chunks(960 * 2) {
+                    encoder.encode(black_box(frame), &mut out).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn encode_frame_size(c: &mut Criterion) {
+    let pcm = signal();
+    let mut out = [0; 1500];
+    let durations = [
+        ("2.5ms", FrameDuration::Ms2_5),
+        ("10ms", FrameDuration::Ms10),
+        ("20ms", FrameDuration::Ms20),
+        ("60ms", FrameDuration::Ms60),
+    ];
+
+    let mut group = c.benchmark_group("encode/frame_size");
+    group.throughput(Throughput::Elements(SECOND as u64));
+    for &(name, duration) in &durations {
+        let frame = duration.samples(48000).unwrap() * 2;
+        let mut encoder = encoder(10);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for frame in pcm.chunks(frame) {
+                    encoder.encode(black_box(frame), &mut out).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn encode_format(c: &mut Criterion) {
+    let pcm = signal();
+    let mut out = [0; 1500];
+
+    let mut group = c.benchmark_group("encode/format");
+    group.throughput(Throughput::Elements(SECOND as u64));
+    let mut encoder = encoder(10);
+    group.bench_function("i16", |b| {
+        b.iter(|| {
+            for frame in pcm.chunks(960 * 2) {
+                encoder.encode(black_box(frame), &mut out).unwrap();
+            }
+        })
+    });
+    #[cfg(not(feature = "disable-float-api"))]
+    {
+        let float: Vec<f32> = pcm.iter().map(|&s| s as f32 / 32768.0).collect();
+        group.bench_function("f32", |b| {
+            b.iter(|| {
+                for frame in float.chunks(960 * 2) {
+                    encoder.encode_float(black_box(frame), &mut out).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut encoder = encoder(10);
+    let packets: Vec<Vec<u8>> = signal()
+        .chunks(960 * 2)
+        .map(|frame| encoder.encode_vec(frame, 1500).unwrap())
+        .collect();
+    let mut decoder = Decoder::new(48000, Channels::Stereo).unwrap();
+    let mut out = [0i16; 960 * 2];
+
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(SECOND as u64));
+    group.bench_function("i16", |b| {
+        b.iter(|| {
+            for packet in &packets {
+                decoder.decode(black_box(packet), &mut out, false).unwrap();
+            }
+        })
+    });
+    #[cfg(not(feature = "disable-float-api"))]
+    {
+        let mut out = [0f32; 960 * 2];
+        group.bench_function("f32", |b| {
+            b.iter(|| {
+                for packet in &packets {
+                    decoder
+                        .decode_float(black_box(packet), &mut out, false)
+                        .unwrap();
+                }
+            })
+        });
+    }
+    group.bench_function("plc", |b| {
+        b.iter(|| {
+            for _ in &packets {
+                decoder.decode(&[], &mut out, false).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    encode_complexity,
+    encode_frame_size,
+    encode_format,
+    decode
+);
+criterion_main!(benches);
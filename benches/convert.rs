@@ -0,0 +1,60 @@
+//! Compares the SIMD sample conversions against the scalar reference.
+
+#[macro_use]
+extern crate criterion;
+extern crate opus;
+
+use criterion::{black_box, Criterion, Throughput};
+use opus::convert::{self, scalar};
+
+/// Samples in 20 ms of 48kHz 7.1 audio.
+const LEN: usize = 960 * 8;
+
+fn conversions(c: &mut Criterion) {
+    let pcm: Vec<i16> = (0..LEN).map(|i| (i * 37) as i16).collect();
+    let float: Vec<f32> = pcm.iter().map(|&s| s as f32 / 32768.0).collect();
+    let mut out_f32 = vec![0.0; LEN];
+    let mut out_i16 = vec![0; LEN];
+
+    let mut group = c.benchmark_group("convert");
+    group.throughput(Throughput::Elements(LEN as u64));
+    group.bench_function("i16_to_f32", |b| {
+        b.iter(|| convert::i16_to_f32(black_box(&pcm), &mut out_f32))
+    });
+    group.bench_function("i16_to_f32/scalar", |b| {
+        b.iter(|| scalar::i16_to_f32(black_box(&pcm), &mut out_f32))
+    });
+    group.bench_function("f32_to_i16", |b| {
+        b.iter(|| convert::f32_to_i16(black_box(&float), &mut out_i16))
+    });
+    group.bench_function("f32_to_i16/scalar", |b| {
+        b.iter(|| scalar::f32_to_i16(black_box(&float), &mut out_i16))
+    });
+    group.finish();
+}
+
+fn planar(c: &mut Criterion) {
+    let left: Vec<f32> = (0..LEN / 2).map(|i| i as f32).collect();
+    let right = left.clone();
+    let mut out = vec![0.0; LEN];
+    let (mut l, mut r) = (vec![0.0; LEN / 2], vec![0.0; LEN / 2]);
+
+    let mut group = c.benchmark_group("planar");
+    group.throughput(Throughput::Elements(LEN as u64));
+    group.bench_function("interleave", |b| {
+        b.iter(|| convert::interleave(&[black_box(&left), &right], &mut out))
+    });
+    group.bench_function("interleave/scalar", |b| {
+        b.iter(|| scalar::interleave2(black_box(&left), &right, &mut out))
+    });
+    group.bench_function("deinterleave", |b| {
+        b.iter(|| convert::deinterleave(black_box(&out), &mut [&mut l, &mut r]))
+    });
+    group.bench_function("deinterleave/scalar", |b| {
+        b.iter(|| scalar::deinterleave2(black_box(&out), &mut l, &mut r))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, conversions, planar);
+criterion_main!(benches);
@@ -0,0 +1,119 @@
+//! Measures demuxing and decoding ten seconds of Ogg Opus, with and without
+//! recycling packet buffers.
+
+#[macro_use]
+extern crate criterion;
+extern crate opus;
+
+use std::io::Cursor;
+
+use criterion::{black_box, Criterion, Throughput};
+use opus::ogg::page::{BOS, EOS};
+use opus::ogg::{
+    Event, OggDemuxer, OggOpusReader, OggSliceReader, OpusFile, OpusHead, OpusTags, Page,
+};
+use opus::{Application, Channels, Encoder};
+
+/// Ten seconds of stereo audio encoded at 64 kb/s, one second per page.
+fn stream() -> Vec<u8> {
+    let mut encoder = Encoder::new(48000, Channels::Stereo, Application::Audio).unwrap();
+    let mut bytes = Vec::new();
+    let mut packet = Vec::new();
+    let mut page = Page {
+        flags: BOS,
+        granule_position: Some(0),
+        serial: 1,
+        ..Page::default()
+    };
+    OpusHead::new(2, 312, 48000).write(&mut packet);
+    page.push_packet(&packet);
+    page.write(&mut bytes);
+    packet.clear();
+    OpusTags::new("opus-rs").write(&mut packet);
+    page = Page {
+        granule_position: Some(0),
+        serial: 1,
+        sequence: 1,
+        ..Page::default()
+    };
+    page.push_packet(&packet);
+    page.write(&mut bytes);
+
+    let frame: Vec<i16> = (0..960 * 2)
+        .map(|i| ((i as f32 * 0.03).sin() * 8000.0) as i16)
+        .collect();
+    for second in 0..10 {
+        page = Page {
+            flags: if second == 9 { EOS } else { 0 },
+            granule_position: Some((second + 1) * 48000),
+            serial: 1,
+            sequence: second as u32 + 2,
+            ..Page::default()
+        };
+        for _ in 0..50 {
+            page.push_packet(&encoder.encode_vec(&frame, 1500).unwrap());
+        }
+        page.write(&mut bytes);
+    }
+    bytes
+}
+
+fn demux(c: &mut Criterion) {
+    let bytes = stream();
+
+    let mut group = c.benchmark_group("ogg");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("demux", |b| {
+        b.iter(|| OggDemuxer::new().push_bytes(black_box(&bytes)).unwrap())
+    });
+    let mut demuxer = OggDemuxer::new();
+    let mut events = Vec::new();
+    group.bench_function("demux/pooled", |b| {
+        b.iter(|| {
+            demuxer
+                .push_bytes_into(black_box(&bytes), &mut events)
+                .unwrap();
+            for event in events.drain(..) {
+                if let Event::Audio(audio) = event {
+                    demuxer.recycle(audio.data);
+                }
+            }
+        })
+    });
+    group.bench_function("demux/4k_chunks", |b| {
+        b.iter(|| {
+            let mut demuxer = OggDemuxer::new();
+            for chunk in bytes.chunks(4096) {
+                demuxer.push_bytes(black_box(chunk)).unwrap();
+            }
+        })
+    });
+    group.bench_function("slice_reader", |b| {
+        b.iter(|| {
+            let mut reader = OggSliceReader::new(black_box(&bytes));
+            while let Some(audio) = reader.next_audio().unwrap() {
+                black_box(audio);
+            }
+        })
+    });
+    group.bench_function("sync_reader", |b| {
+        b.iter(|| {
+            let mut reader = OggOpusReader::new(Cursor::new(black_box(&bytes[..])));
+            while let Some(audio) = reader.next_audio().unwrap() {
+                reader.recycle(audio.data);
+            }
+        })
+    });
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            let mut file = OpusFile::new(Cursor::new(black_box(&bytes[..]))).unwrap();
+            while let Some(frame) = file.read_frame().unwrap() {
+                black_box(frame);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, demux);
+criterion_main!(benches);
@@ -0,0 +1,20 @@
+use std::env;
+
+// Pass the version of the libopus linked by opus-sys on as `opus_ge_1_4` and
+// `opus_ge_1_5`, so that the requests added in later releases are only
+// exposed when the library has them.
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(opus_ge_1_4, opus_ge_1_5)");
+    println!("cargo:rerun-if-env-changed=DEP_OPUS_VERSION");
+    let version = env::var("DEP_OPUS_VERSION").unwrap_or_default();
+    let mut parts = version
+        .split('.')
+        .map(|part| part.parse::<u32>().unwrap_or(0));
+    let found = (parts.next().unwrap_or(0), parts.next().unwrap_or(0));
+    if found >= (1, 4) {
+        println!("cargo:rustc-cfg=opus_ge_1_4");
+    }
+    if found >= (1, 5) {
+        println!("cargo:rustc-cfg=opus_ge_1_5");
+    }
+}
@@ -0,0 +1,387 @@
+//! A port of the `opus_demo` tool of libopus to the Rust API, so that its
+//! comparisons can be reproduced with this crate.
+//!
+//! ```text
+//! cargo run --release --example opus_demo -- audio 48000 2 64000 in.pcm out.pcm
+//! cargo run --release --example opus_demo -- -e voip 16000 1 16000 -loss 10 in.pcm out.bit
+//! cargo run --release --example opus_demo -- -d 48000 2 out.bit out.pcm
+//! ```
+//!
+//! Audio is raw 16-bit little-endian interleaved PCM, and bitstreams are in
+//! the format of `opus_demo`: each packet preceded by its length and the
+//! encoder's final range, as big-endian 32-bit integers, which is also the
+//! format of the official test vectors.
+
+extern crate opus;
+
+use std::env;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::process;
+
+use opus::{Application, Bandwidth, Bitrate, Channels, Decoder, Encoder, FrameDuration};
+
+const USAGE: &str = "\
+Usage: opus_demo [-e] <application> <sampling rate (Hz)> <channels (1/2)> <bits per second> [options] <input> <output>
+       opus_demo -d <sampling rate (Hz)> <channels (1/2)> [options] <input> <output>
+
+application: voip | audio | restricted-lowdelay
+
+options:
+-e                   : only runs the encoder (output the bit-stream)
+-d                   : only runs the decoder (reads the bit-stream as input)
+-cbr                 : enable constant bitrate; default: variable bitrate
+-cvbr                : enable constrained variable bitrate; default: unconstrained
+-bandwidth <NB|MB|WB|SWB|FB> : audio bandwidth (from narrowband to fullband); default: sampling rate
+-framesize <2.5|5|10|20|40|60|80|100|120> : frame size in ms; default: 20
+-max_payload <bytes> : maximum payload size in bytes, default: 1024
+-complexity <comp>   : complexity, 0 (lowest) ... 10 (highest); default: 10
+-inbandfec           : enable SILK inband FEC
+-dtx                 : enable SILK DTX
+-loss <perc>         : simulate packet loss, in percent (0-100); default: 0";
+
+/// What `opus_demo` was asked to do.
+struct Args {
+    encode: bool,
+    decode: bool,
+    application: Application,
+    sample_rate: u32,
+    channels: Channels,
+    bitrate: i32,
+    cbr: bool,
+    cvbr: bool,
+    bandwidth: Option<Bandwidth>,
+    frame_duration: FrameDuration,
+    max_payload: usize,
+    complexity: i32,
+    inband_fec: bool,
+    dtx: bool,
+    loss: u32,
+    input: String,
+    output: String,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(1);
+        }
+    };
+    if let Err(err) = run(&args) {
+        eprintln!("opus_demo: {}", err);
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut args = args.iter().map(String::as_str).peekable();
+    let (encode, decode) = match args.peek() {
+        Some(&"-e") => (true, false),
+        Some(&"-d") => (false, true),
+        _ => (true, true),
+    };
+    if !(encode && decode) {
+        args.next();
+    }
+    let mut next = |what: &str| args.next().ok_or(format!("missing {}", what));
+    let application = if encode {
+        match next("application")? {
+            "voip" => Application::Voip,
+            "audio" => Application::Audio,
+            "restricted-lowdelay" => Application::LowDelay,
+            other => return Err(format!("unknown application: {}", other)),
+        }
+    } else {
+        Application::Audio
+    };
+    let sample_rate = number(next("sampling rate")?)?;
+    let channels = match next("channels")? {
+        "1" => Channels::Mono,
+        "2" => Channels::Stereo,
+        other => return Err(format!("unsupported channel count: {}", other)),
+    };
+    let bitrate = if encode {
+        number(next("bits per second")?)?
+    } else {
+        0
+    };
+
+    let mut rest: Vec<&str> = args.collect();
+    if rest.len() < 2 {
+        return Err("missing input or output file".to_string());
+    }
+    let output = rest.pop().unwrap().to_string();
+    let input = rest.pop().unwrap().to_string();
+    let mut parsed = Args {
+        encode,
+        decode,
+        application,
+        sample_rate,
+        channels,
+        bitrate,
+        cbr: false,
+        cvbr: false,
+        bandwidth: None,
+        frame_duration: FrameDuration::Ms20,
+        max_payload: 1024,
+        complexity: 10,
+        inband_fec: false,
+        dtx: false,
+        loss: 0,
+        input,
+        output,
+    };
+    let mut options = rest.into_iter();
+    while let Some(option) = options.next() {
+        let mut value = || options.next().ok_or(format!("{} needs a value", option));
+        match option {
+            "-cbr" => parsed.cbr = true,
+            "-cvbr" => parsed.cvbr = true,
+            "-bandwidth" => {
+                parsed.bandwidth = Some(match value()? {
+                    "NB" => Bandwidth::Narrowband,
+                    "MB" => Bandwidth::Mediumband,
+                    "WB" => Bandwidth::Wideband,
+                    "SWB" => Bandwidth::Superwideband,
+                    "FB" => Bandwidth::Fullband,
+                    other => return Err(format!("unknown bandwidth: {}", other)),
+                })
+            }
+            "-framesize" => {
+                parsed.frame_duration = match value()? {
+                    "2.5" => FrameDuration::Ms2_5,
+                    "5" => FrameDuration::Ms5,
+                    "10" => FrameDuration::Ms10,
+                    "20" => FrameDuration::Ms20,
+                    "40" => FrameDuration::Ms40,
+                    "60" => FrameDuration::Ms60,
+                    "80" => FrameDuration::Ms80,
+                    "100" => FrameDuration::Ms100,
+                    "120" => FrameDuration::Ms120,
+                    other => return Err(format!("unsupported frame size: {} ms", other)),
+                }
+            }
+            "-max_payload" => parsed.max_payload = number(value()?)?,
+            "-complexity" => parsed.complexity = number(value()?)?,
+            "-inbandfec" => parsed.inband_fec = true,
+            "-dtx" => parsed.dtx = true,
+            "-loss" => parsed.loss = number(value()?)?,
+            other => return Err(format!("unknown option: {}", other)),
+        }
+    }
+    if parsed.cbr && parsed.cvbr {
+        return Err("-cbr and -cvbr cannot be combined".to_string());
+    }
+    Ok(parsed)
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("not a valid number: {}", value))
+}
+
+fn run(args: &Args) -> io::Result<()> {
+    let mut input = BufReader::new(fs::File::open(&args.input)?);
+    let mut output = BufWriter::new(fs::File::create(&args.output)?);
+    let channels = args.channels as usize;
+    let frame_size = args
+        .frame_duration
+        .samples(args.sample_rate)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "unsupported sampling rate"))?;
+
+    let mut encoder = if args.encode {
+        Some(create_encoder(args)?)
+    } else {
+        None
+    };
+    let mut decoder = if args.decode {
+        Some(Decoder::new(args.sample_rate, args.channels)?)
+    } else {
+        None
+    };
+    // the decoded audio starts late by the encoder's lookahead
+    let mut skip = match encoder.as_mut() {
+        Some(encoder) if args.decode => encoder.get_lookahead()? as usize,
+        _ => 0,
+    };
+
+    let mut loss = Loss::new(args.loss);
+    let mut previous: Option<Option<Vec<u8>>> = None;
+    let mut pcm = vec![0i16; 48000 * 2 * channels];
+    let mut packets = 0u64;
+    let mut bytes = 0u64;
+    let mut lost = 0u64;
+    while let Some((packet, final_range)) = if let Some(encoder) = encoder.as_mut() {
+        read_frame(&mut input, frame_size * channels)?
+            .map(|frame| -> io::Result<_> {
+                let packet = encoder.encode_vec(&frame, args.max_payload)?;
+                Ok((packet, encoder.get_final_range()?))
+            })
+            .transpose()?
+    } else {
+        read_packet(&mut input)?
+    } {
+        packets += 1;
+        bytes += packet.len() as u64;
+        let decoder = match decoder.as_mut() {
+            Some(decoder) => decoder,
+            None => {
+                write_packet(&mut output, &packet, final_range)?;
+                continue;
+            }
+        };
+
+        let received = if packet.is_empty() || loss.next() {
+            lost += 1;
+            None
+        } else {
+            Some(packet)
+        };
+        let samples = if args.inband_fec {
+            // decode one packet behind, so that a lost packet can be
+            // recovered from the FEC data of the one after it
+            let samples = match previous.take() {
+                Some(Some(packet)) => decoder.decode(&packet, &mut pcm, false)?,
+                Some(None) => conceal(decoder, received.as_ref(), &mut pcm, channels)?,
+                None => 0,
+            };
+            previous = Some(received);
+            samples
+        } else {
+            match received {
+                Some(packet) => decoder.decode(&packet, &mut pcm, false)?,
+                None => conceal(decoder, None, &mut pcm, channels)?,
+            }
+        };
+        skip = write_pcm(&mut output, &pcm[..samples * channels], skip * channels)? / channels;
+    }
+    if let (Some(decoder), Some(last)) = (decoder.as_mut(), previous) {
+        let samples = match last {
+            Some(packet) => decoder.decode(&packet, &mut pcm, false)?,
+            None => conceal(decoder, None, &mut pcm, channels)?,
+        };
+        write_pcm(&mut output, &pcm[..samples * channels], skip * channels)?;
+    }
+    output.flush()?;
+
+    if packets > 0 {
+        let seconds = packets as f64 * frame_size as f64 / args.sample_rate as f64;
+        eprintln!(
+            "average bitrate: {:.3} kb/s",
+            bytes as f64 * 8.0 / seconds / 1000.0
+        );
+    }
+    if args.decode && lost > 0 {
+        eprintln!("packets lost: {} of {}", lost, packets);
+    }
+    Ok(())
+}
+
+fn create_encoder(args: &Args) -> io::Result<Encoder> {
+    let mut encoder = Encoder::new(args.sample_rate, args.channels, args.application)?;
+    encoder.set_bitrate(Bitrate::Bits(args.bitrate))?;
+    encoder.set_vbr(!args.cbr)?;
+    encoder.set_vbr_constraint(args.cvbr)?;
+    if let Some(bandwidth) = args.bandwidth {
+        encoder.set_bandwidth(bandwidth)?;
+    }
+    encoder.set_complexity(args.complexity)?;
+    encoder.set_inband_fec(args.inband_fec)?;
+    encoder.set_packet_loss_perc(args.loss as i32)?;
+    encoder.set_dtx(args.dtx)?;
+    Ok(encoder)
+}
+
+// Recover a lost frame from the FEC data of the next packet if there is one,
+// or conceal it, for as long as the last frame lasted.
+fn conceal(
+    decoder: &mut Decoder,
+    next: Option<&Vec<u8>>,
+    pcm: &mut [i16],
+    channels: usize,
+) -> io::Result<usize> {
+    let duration = decoder.get_last_packet_duration()? as usize;
+    let pcm = &mut pcm[..duration * channels];
+    Ok(match next {
+        Some(packet) => decoder.decode(packet, pcm, true)?,
+        None => decoder.decode(&[], pcm, false)?,
+    })
+}
+
+// A frame of samples, padded with silence at the end of the input.
+fn read_frame<R: Read>(input: &mut R, len: usize) -> io::Result<Option<Vec<i16>>> {
+    let mut bytes = vec![0; len * 2];
+    let mut filled = 0;
+    while filled < bytes.len() {
+        match input.read(&mut bytes[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    if filled == 0 {
+        return Ok(None);
+    }
+    Ok(Some(
+        bytes
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect(),
+    ))
+}
+
+fn read_packet<R: Read>(input: &mut R) -> io::Result<Option<(Vec<u8>, u32)>> {
+    let mut header = [0; 8];
+    match input.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    let final_range = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let mut packet = vec![0; len as usize];
+    input.read_exact(&mut packet)?;
+    Ok(Some((packet, final_range)))
+}
+
+fn write_packet<W: Write>(output: &mut W, packet: &[u8], final_range: u32) -> io::Result<()> {
+    output.write_all(&(packet.len() as u32).to_be_bytes())?;
+    output.write_all(&final_range.to_be_bytes())?;
+    output.write_all(packet)
+}
+
+// Write the samples after the first `skip`, and return how many are still
+// to be skipped.
+fn write_pcm<W: Write>(output: &mut W, pcm: &[i16], skip: usize) -> io::Result<usize> {
+    let skipped = skip.min(pcm.len());
+    for sample in &pcm[skipped..] {
+        output.write_all(&sample.to_le_bytes())?;
+    }
+    Ok(skip - skipped)
+}
+
+/// Drops packets at random, but the same ones on every run.
+struct Loss {
+    percent: u32,
+    state: u32,
+}
+
+impl Loss {
+    fn new(percent: u32) -> Loss {
+        Loss {
+            percent,
+            state: 0x2545_f491,
+        }
+    }
+
+    fn next(&mut self) -> bool {
+        // xorshift32
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state % 100 < self.percent
+    }
+}
@@ -0,0 +1,31 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opus::{Channels, Decoder};
+
+// The first byte picks the decoder configuration, the rest is the packet.
+fuzz_target!(|data: &[u8]| {
+    let (&config, packet) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let rate = [8000, 12000, 16000, 24000, 48000][(config & 0x07) as usize % 5];
+    let channels = if config & 0x08 != 0 {
+        Channels::Stereo
+    } else {
+        Channels::Mono
+    };
+    let fec = config & 0x10 != 0;
+
+    let mut decoder = Decoder::new(rate, channels).unwrap();
+    let mut output = vec![0i16; 5760 * 2];
+    if let Ok(samples) = decoder.decode(packet, &mut output, fec) {
+        assert!(samples * channels as usize <= output.len());
+    }
+    let mut output = vec![0f32; 5760 * 2];
+    if let Ok(samples) = decoder.decode_float(packet, &mut output, fec) {
+        assert!(samples * channels as usize <= output.len());
+    }
+    // concealment after whatever state the packet left behind
+    let _ = decoder.decode(&[], &mut [0i16; 5760 * 2], false);
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opus::ogg::{OpusHead, OpusTags};
+
+// Whatever parses must write back to a header which parses the same.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(head) = OpusHead::parse(data) {
+        let mut out = Vec::new();
+        head.write(&mut out);
+        assert_eq!(OpusHead::parse(&out).unwrap(), head);
+    }
+    if let Ok(tags) = OpusTags::parse(data) {
+        let mut out = Vec::new();
+        tags.write(&mut out);
+        assert_eq!(OpusTags::parse(&out).unwrap(), tags);
+    }
+});
@@ -0,0 +1,53 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opus::ogg::{Event, OggDemuxer, OggSliceReader, Page};
+
+// The first byte sets the size of the chunks fed to the demuxer, the rest is
+// the Ogg stream.
+fuzz_target!(|data: &[u8]| {
+    let (&chunk, stream) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let mut offset = 0;
+    while let Ok(Some((page, len))) = Page::parse(&stream[offset..]) {
+        assert_eq!(page.len(), len);
+        offset += len;
+    }
+
+    let mut reader = OggSliceReader::new(stream);
+    let mut sliced = Vec::new();
+    let sliced_ok = loop {
+        match reader.next_audio() {
+            Ok(Some(audio)) => {
+                assert!(audio.playable() <= audio.info.duration);
+                sliced.push((audio.data.into_owned(), audio.info));
+            }
+            Ok(None) => break true,
+            Err(_) => break false,
+        }
+    };
+
+    let mut demuxer = OggDemuxer::new();
+    let mut demuxed = Vec::new();
+    let mut demuxed_ok = true;
+    for bytes in stream.chunks(chunk as usize + 1) {
+        match demuxer.push_bytes(bytes) {
+            Ok(events) => demuxed.extend(events.into_iter().filter_map(|event| match event {
+                Event::Audio(audio) => Some((audio.data, audio.info)),
+                _ => None,
+            })),
+            Err(_) => {
+                demuxed_ok = false;
+                break;
+            }
+        }
+    }
+
+    // Both readers follow the same streams when neither fails.
+    if sliced_ok && demuxed_ok && demuxer.buffered() == 0 {
+        assert_eq!(sliced, demuxed);
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opus::packet;
+
+fuzz_target!(|data: &[u8]| {
+    let parsed = match packet::parse(data) {
+        Ok(parsed) => parsed,
+        Err(_) => return,
+    };
+    let bounds = data.as_ptr_range();
+    for frame in &parsed.frames {
+        let range = frame.as_ptr_range();
+        assert!(bounds.start <= range.start && range.end <= bounds.end);
+        assert!(frame.len() <= 1275);
+    }
+    let frames: Vec<Vec<u8>> = parsed.frames.iter().map(|frame| frame.to_vec()).collect();
+
+    // Removing the padding and padding again keeps the frames intact.
+    let mut unpadded = data.to_vec();
+    let len = packet::unpad(&mut unpadded).expect("unpad a valid packet");
+    let reparsed = packet::parse(&unpadded[..len]).expect("parse an unpadded packet");
+    assert!(reparsed.frames.iter().eq(frames.iter()));
+
+    let mut padded = unpadded[..len].to_vec();
+    padded.resize(len + 300, 0);
+    packet::pad(&mut padded, len).expect("pad a valid packet");
+    let reparsed = packet::parse(&padded).expect("parse a padded packet");
+    assert!(reparsed.frames.iter().eq(frames.iter()));
+});
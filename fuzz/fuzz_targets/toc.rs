@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opus::packet;
+
+// The queries reading a packet's TOC byte must fail cleanly on any input,
+// including an empty one.
+fuzz_target!(|data: &[u8]| {
+    let _ = packet::get_bandwidth(data);
+    let _ = packet::get_nb_channels(data);
+    let _ = packet::get_nb_frames(data);
+    for &rate in &[8000, 12000, 16000, 24000, 48000] {
+        let _ = packet::get_nb_samples(data, rate);
+        let _ = packet::get_samples_per_frame(data, rate);
+    }
+});
@@ -0,0 +1,109 @@
+/* automatically generated by rust-bindgen 0.58.1 */
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusCustomEncoder {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusCustomDecoder {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusCustomMode {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn opus_custom_mode_create(
+        Fs: opus_int32,
+        frame_size: ::core::ffi::c_int,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusCustomMode;
+}
+extern "C" {
+    pub fn opus_custom_mode_destroy(mode: *mut OpusCustomMode);
+}
+extern "C" {
+    pub fn opus_custom_encoder_get_size(
+        mode: *const OpusCustomMode,
+        channels: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_custom_encoder_create(
+        mode: *const OpusCustomMode,
+        channels: ::core::ffi::c_int,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusCustomEncoder;
+}
+extern "C" {
+    pub fn opus_custom_encoder_destroy(st: *mut OpusCustomEncoder);
+}
+extern "C" {
+    pub fn opus_custom_encode_float(
+        st: *mut OpusCustomEncoder,
+        pcm: *const f32,
+        frame_size: ::core::ffi::c_int,
+        compressed: *mut ::core::ffi::c_uchar,
+        maxCompressedBytes: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_custom_encode(
+        st: *mut OpusCustomEncoder,
+        pcm: *const opus_int16,
+        frame_size: ::core::ffi::c_int,
+        compressed: *mut ::core::ffi::c_uchar,
+        maxCompressedBytes: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_custom_encoder_ctl(
+        st: *mut OpusCustomEncoder,
+        request: ::core::ffi::c_int,
+        ...
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_custom_decoder_get_size(
+        mode: *const OpusCustomMode,
+        channels: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_custom_decoder_create(
+        mode: *const OpusCustomMode,
+        channels: ::core::ffi::c_int,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusCustomDecoder;
+}
+extern "C" {
+    pub fn opus_custom_decoder_destroy(st: *mut OpusCustomDecoder);
+}
+extern "C" {
+    pub fn opus_custom_decode_float(
+        st: *mut OpusCustomDecoder,
+        data: *const ::core::ffi::c_uchar,
+        len: ::core::ffi::c_int,
+        pcm: *mut f32,
+        frame_size: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_custom_decode(
+        st: *mut OpusCustomDecoder,
+        data: *const ::core::ffi::c_uchar,
+        len: ::core::ffi::c_int,
+        pcm: *mut opus_int16,
+        frame_size: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_custom_decoder_ctl(
+        st: *mut OpusCustomDecoder,
+        request: ::core::ffi::c_int,
+        ...
+    ) -> ::core::ffi::c_int;
+}
@@ -0,0 +1,156 @@
+/* automatically generated by rust-bindgen 0.58.1 */
+
+pub const OPUS_MULTISTREAM_GET_ENCODER_STATE_REQUEST: i32 = 5120;
+pub const OPUS_MULTISTREAM_GET_DECODER_STATE_REQUEST: i32 = 5122;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusMSEncoder {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusMSDecoder {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn opus_multistream_encoder_get_size(
+        streams: ::core::ffi::c_int,
+        coupled_streams: ::core::ffi::c_int,
+    ) -> opus_int32;
+}
+extern "C" {
+    pub fn opus_multistream_surround_encoder_get_size(
+        channels: ::core::ffi::c_int,
+        mapping_family: ::core::ffi::c_int,
+    ) -> opus_int32;
+}
+extern "C" {
+    pub fn opus_multistream_encoder_create(
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        streams: ::core::ffi::c_int,
+        coupled_streams: ::core::ffi::c_int,
+        mapping: *const ::core::ffi::c_uchar,
+        application: ::core::ffi::c_int,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusMSEncoder;
+}
+extern "C" {
+    pub fn opus_multistream_surround_encoder_create(
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        mapping_family: ::core::ffi::c_int,
+        streams: *mut ::core::ffi::c_int,
+        coupled_streams: *mut ::core::ffi::c_int,
+        mapping: *mut ::core::ffi::c_uchar,
+        application: ::core::ffi::c_int,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusMSEncoder;
+}
+extern "C" {
+    pub fn opus_multistream_encoder_init(
+        st: *mut OpusMSEncoder,
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        streams: ::core::ffi::c_int,
+        coupled_streams: ::core::ffi::c_int,
+        mapping: *const ::core::ffi::c_uchar,
+        application: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_multistream_surround_encoder_init(
+        st: *mut OpusMSEncoder,
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        mapping_family: ::core::ffi::c_int,
+        streams: *mut ::core::ffi::c_int,
+        coupled_streams: *mut ::core::ffi::c_int,
+        mapping: *mut ::core::ffi::c_uchar,
+        application: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_multistream_encode(
+        st: *mut OpusMSEncoder,
+        pcm: *const opus_int16,
+        frame_size: ::core::ffi::c_int,
+        data: *mut ::core::ffi::c_uchar,
+        max_data_bytes: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_multistream_encode_float(
+        st: *mut OpusMSEncoder,
+        pcm: *const f32,
+        frame_size: ::core::ffi::c_int,
+        data: *mut ::core::ffi::c_uchar,
+        max_data_bytes: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_multistream_encoder_destroy(st: *mut OpusMSEncoder);
+}
+extern "C" {
+    pub fn opus_multistream_encoder_ctl(
+        st: *mut OpusMSEncoder,
+        request: ::core::ffi::c_int,
+        ...
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_multistream_decoder_get_size(
+        streams: ::core::ffi::c_int,
+        coupled_streams: ::core::ffi::c_int,
+    ) -> opus_int32;
+}
+extern "C" {
+    pub fn opus_multistream_decoder_create(
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        streams: ::core::ffi::c_int,
+        coupled_streams: ::core::ffi::c_int,
+        mapping: *const ::core::ffi::c_uchar,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusMSDecoder;
+}
+extern "C" {
+    pub fn opus_multistream_decoder_init(
+        st: *mut OpusMSDecoder,
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        streams: ::core::ffi::c_int,
+        coupled_streams: ::core::ffi::c_int,
+        mapping: *const ::core::ffi::c_uchar,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_multistream_decode(
+        st: *mut OpusMSDecoder,
+        data: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+        pcm: *mut opus_int16,
+        frame_size: ::core::ffi::c_int,
+        decode_fec: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_multistream_decode_float(
+        st: *mut OpusMSDecoder,
+        data: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+        pcm: *mut f32,
+        frame_size: ::core::ffi::c_int,
+        decode_fec: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_multistream_decoder_ctl(
+        st: *mut OpusMSDecoder,
+        request: ::core::ffi::c_int,
+        ...
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_multistream_decoder_destroy(st: *mut OpusMSDecoder);
+}
@@ -0,0 +1,383 @@
+/* automatically generated by rust-bindgen 0.58.1 */
+
+pub const OPUS_OK: i32 = 0;
+pub const OPUS_BAD_ARG: i32 = -1;
+pub const OPUS_BUFFER_TOO_SMALL: i32 = -2;
+pub const OPUS_INTERNAL_ERROR: i32 = -3;
+pub const OPUS_INVALID_PACKET: i32 = -4;
+pub const OPUS_UNIMPLEMENTED: i32 = -5;
+pub const OPUS_INVALID_STATE: i32 = -6;
+pub const OPUS_ALLOC_FAIL: i32 = -7;
+pub const OPUS_SET_APPLICATION_REQUEST: i32 = 4000;
+pub const OPUS_GET_APPLICATION_REQUEST: i32 = 4001;
+pub const OPUS_SET_BITRATE_REQUEST: i32 = 4002;
+pub const OPUS_GET_BITRATE_REQUEST: i32 = 4003;
+pub const OPUS_SET_MAX_BANDWIDTH_REQUEST: i32 = 4004;
+pub const OPUS_GET_MAX_BANDWIDTH_REQUEST: i32 = 4005;
+pub const OPUS_SET_VBR_REQUEST: i32 = 4006;
+pub const OPUS_GET_VBR_REQUEST: i32 = 4007;
+pub const OPUS_SET_BANDWIDTH_REQUEST: i32 = 4008;
+pub const OPUS_GET_BANDWIDTH_REQUEST: i32 = 4009;
+pub const OPUS_SET_COMPLEXITY_REQUEST: i32 = 4010;
+pub const OPUS_GET_COMPLEXITY_REQUEST: i32 = 4011;
+pub const OPUS_SET_INBAND_FEC_REQUEST: i32 = 4012;
+pub const OPUS_GET_INBAND_FEC_REQUEST: i32 = 4013;
+pub const OPUS_SET_PACKET_LOSS_PERC_REQUEST: i32 = 4014;
+pub const OPUS_GET_PACKET_LOSS_PERC_REQUEST: i32 = 4015;
+pub const OPUS_SET_DTX_REQUEST: i32 = 4016;
+pub const OPUS_GET_DTX_REQUEST: i32 = 4017;
+pub const OPUS_SET_VBR_CONSTRAINT_REQUEST: i32 = 4020;
+pub const OPUS_GET_VBR_CONSTRAINT_REQUEST: i32 = 4021;
+pub const OPUS_SET_FORCE_CHANNELS_REQUEST: i32 = 4022;
+pub const OPUS_GET_FORCE_CHANNELS_REQUEST: i32 = 4023;
+pub const OPUS_SET_SIGNAL_REQUEST: i32 = 4024;
+pub const OPUS_GET_SIGNAL_REQUEST: i32 = 4025;
+pub const OPUS_GET_LOOKAHEAD_REQUEST: i32 = 4027;
+pub const OPUS_GET_SAMPLE_RATE_REQUEST: i32 = 4029;
+pub const OPUS_GET_FINAL_RANGE_REQUEST: i32 = 4031;
+pub const OPUS_GET_PITCH_REQUEST: i32 = 4033;
+pub const OPUS_SET_GAIN_REQUEST: i32 = 4034;
+pub const OPUS_GET_GAIN_REQUEST: i32 = 4045;
+pub const OPUS_SET_LSB_DEPTH_REQUEST: i32 = 4036;
+pub const OPUS_GET_LSB_DEPTH_REQUEST: i32 = 4037;
+pub const OPUS_GET_LAST_PACKET_DURATION_REQUEST: i32 = 4039;
+pub const OPUS_SET_EXPERT_FRAME_DURATION_REQUEST: i32 = 4040;
+pub const OPUS_GET_EXPERT_FRAME_DURATION_REQUEST: i32 = 4041;
+pub const OPUS_SET_PREDICTION_DISABLED_REQUEST: i32 = 4042;
+pub const OPUS_GET_PREDICTION_DISABLED_REQUEST: i32 = 4043;
+pub const OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST: i32 = 4046;
+pub const OPUS_GET_PHASE_INVERSION_DISABLED_REQUEST: i32 = 4047;
+pub const OPUS_GET_IN_DTX_REQUEST: i32 = 4049;
+pub const OPUS_SET_DRED_DURATION_REQUEST: i32 = 4050;
+pub const OPUS_GET_DRED_DURATION_REQUEST: i32 = 4051;
+pub const OPUS_SET_DNN_BLOB_REQUEST: i32 = 4052;
+pub const OPUS_AUTO: i32 = -1000;
+pub const OPUS_BITRATE_MAX: i32 = -1;
+pub const OPUS_APPLICATION_VOIP: i32 = 2048;
+pub const OPUS_APPLICATION_AUDIO: i32 = 2049;
+pub const OPUS_APPLICATION_RESTRICTED_LOWDELAY: i32 = 2051;
+pub const OPUS_SIGNAL_VOICE: i32 = 3001;
+pub const OPUS_SIGNAL_MUSIC: i32 = 3002;
+pub const OPUS_BANDWIDTH_NARROWBAND: i32 = 1101;
+pub const OPUS_BANDWIDTH_MEDIUMBAND: i32 = 1102;
+pub const OPUS_BANDWIDTH_WIDEBAND: i32 = 1103;
+pub const OPUS_BANDWIDTH_SUPERWIDEBAND: i32 = 1104;
+pub const OPUS_BANDWIDTH_FULLBAND: i32 = 1105;
+pub const OPUS_FRAMESIZE_ARG: i32 = 5000;
+pub const OPUS_FRAMESIZE_2_5_MS: i32 = 5001;
+pub const OPUS_FRAMESIZE_5_MS: i32 = 5002;
+pub const OPUS_FRAMESIZE_10_MS: i32 = 5003;
+pub const OPUS_FRAMESIZE_20_MS: i32 = 5004;
+pub const OPUS_FRAMESIZE_40_MS: i32 = 5005;
+pub const OPUS_FRAMESIZE_60_MS: i32 = 5006;
+pub const OPUS_FRAMESIZE_80_MS: i32 = 5007;
+pub const OPUS_FRAMESIZE_100_MS: i32 = 5008;
+pub const OPUS_FRAMESIZE_120_MS: i32 = 5009;
+pub const OPUS_RESET_STATE: i32 = 4028;
+pub type opus_int16 = i16;
+pub type opus_uint16 = u16;
+pub type opus_int32 = i32;
+pub type opus_uint32 = u32;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusEncoder {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusDecoder {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusDREDDecoder {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusDRED {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusRepacketizer {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn opus_encoder_get_size(channels: ::core::ffi::c_int) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_encoder_create(
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        application: ::core::ffi::c_int,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusEncoder;
+}
+extern "C" {
+    pub fn opus_encoder_init(
+        st: *mut OpusEncoder,
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        application: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_encode(
+        st: *mut OpusEncoder,
+        pcm: *const opus_int16,
+        frame_size: ::core::ffi::c_int,
+        data: *mut ::core::ffi::c_uchar,
+        max_data_bytes: opus_int32,
+    ) -> opus_int32;
+}
+extern "C" {
+    pub fn opus_encode_float(
+        st: *mut OpusEncoder,
+        pcm: *const f32,
+        frame_size: ::core::ffi::c_int,
+        data: *mut ::core::ffi::c_uchar,
+        max_data_bytes: opus_int32,
+    ) -> opus_int32;
+}
+extern "C" {
+    pub fn opus_encoder_destroy(st: *mut OpusEncoder);
+}
+extern "C" {
+    pub fn opus_encoder_ctl(
+        st: *mut OpusEncoder,
+        request: ::core::ffi::c_int,
+        ...
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_decoder_get_size(channels: ::core::ffi::c_int) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_decoder_create(
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusDecoder;
+}
+extern "C" {
+    pub fn opus_decoder_init(
+        st: *mut OpusDecoder,
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_decode(
+        st: *mut OpusDecoder,
+        data: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+        pcm: *mut opus_int16,
+        frame_size: ::core::ffi::c_int,
+        decode_fec: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_decode_float(
+        st: *mut OpusDecoder,
+        data: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+        pcm: *mut f32,
+        frame_size: ::core::ffi::c_int,
+        decode_fec: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_decoder_ctl(
+        st: *mut OpusDecoder,
+        request: ::core::ffi::c_int,
+        ...
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_decoder_destroy(st: *mut OpusDecoder);
+}
+extern "C" {
+    pub fn opus_dred_decoder_get_size() -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_dred_decoder_create(error: *mut ::core::ffi::c_int) -> *mut OpusDREDDecoder;
+}
+extern "C" {
+    pub fn opus_dred_decoder_init(dec: *mut OpusDREDDecoder) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_dred_decoder_destroy(dec: *mut OpusDREDDecoder);
+}
+extern "C" {
+    pub fn opus_dred_decoder_ctl(
+        dred_dec: *mut OpusDREDDecoder,
+        request: ::core::ffi::c_int,
+        ...
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_dred_get_size() -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_dred_alloc(error: *mut ::core::ffi::c_int) -> *mut OpusDRED;
+}
+extern "C" {
+    pub fn opus_dred_free(dec: *mut OpusDRED);
+}
+extern "C" {
+    pub fn opus_dred_parse(
+        dred_dec: *mut OpusDREDDecoder,
+        dred: *mut OpusDRED,
+        data: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+        max_dred_samples: opus_int32,
+        sampling_rate: opus_int32,
+        dred_end: *mut ::core::ffi::c_int,
+        defer_processing: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_dred_process(
+        dred_dec: *mut OpusDREDDecoder,
+        src: *const OpusDRED,
+        dst: *mut OpusDRED,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_decoder_dred_decode(
+        st: *mut OpusDecoder,
+        dred: *const OpusDRED,
+        dred_offset: opus_int32,
+        pcm: *mut opus_int16,
+        frame_size: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_decoder_dred_decode_float(
+        st: *mut OpusDecoder,
+        dred: *const OpusDRED,
+        dred_offset: opus_int32,
+        pcm: *mut f32,
+        frame_size: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_packet_parse(
+        data: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+        out_toc: *mut ::core::ffi::c_uchar,
+        frames: *mut *const ::core::ffi::c_uchar,
+        size: *mut opus_int16,
+        payload_offset: *mut ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_packet_get_bandwidth(data: *const ::core::ffi::c_uchar) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_packet_get_samples_per_frame(
+        data: *const ::core::ffi::c_uchar,
+        Fs: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_packet_get_nb_channels(data: *const ::core::ffi::c_uchar) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_packet_get_nb_frames(
+        packet: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_packet_get_nb_samples(
+        packet: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+        Fs: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_decoder_get_nb_samples(
+        dec: *const OpusDecoder,
+        packet: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_pcm_soft_clip(
+        pcm: *mut f32,
+        frame_size: ::core::ffi::c_int,
+        channels: ::core::ffi::c_int,
+        softclip_mem: *mut f32,
+    );
+}
+extern "C" {
+    pub fn opus_repacketizer_get_size() -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_repacketizer_init(rp: *mut OpusRepacketizer) -> *mut OpusRepacketizer;
+}
+extern "C" {
+    pub fn opus_repacketizer_create() -> *mut OpusRepacketizer;
+}
+extern "C" {
+    pub fn opus_repacketizer_destroy(rp: *mut OpusRepacketizer);
+}
+extern "C" {
+    pub fn opus_repacketizer_cat(
+        rp: *mut OpusRepacketizer,
+        data: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_repacketizer_out_range(
+        rp: *mut OpusRepacketizer,
+        begin: ::core::ffi::c_int,
+        end: ::core::ffi::c_int,
+        data: *mut ::core::ffi::c_uchar,
+        maxlen: opus_int32,
+    ) -> opus_int32;
+}
+extern "C" {
+    pub fn opus_repacketizer_get_nb_frames(rp: *mut OpusRepacketizer) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_repacketizer_out(
+        rp: *mut OpusRepacketizer,
+        data: *mut ::core::ffi::c_uchar,
+        maxlen: opus_int32,
+    ) -> opus_int32;
+}
+extern "C" {
+    pub fn opus_packet_pad(
+        data: *mut ::core::ffi::c_uchar,
+        len: opus_int32,
+        new_len: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_packet_unpad(data: *mut ::core::ffi::c_uchar, len: opus_int32) -> opus_int32;
+}
+extern "C" {
+    pub fn opus_multistream_packet_pad(
+        data: *mut ::core::ffi::c_uchar,
+        len: opus_int32,
+        new_len: opus_int32,
+        nb_streams: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_multistream_packet_unpad(
+        data: *mut ::core::ffi::c_uchar,
+        len: opus_int32,
+        nb_streams: ::core::ffi::c_int,
+    ) -> opus_int32;
+}
+extern "C" {
+    pub fn opus_strerror(error: ::core::ffi::c_int) -> *const ::core::ffi::c_char;
+}
+extern "C" {
+    pub fn opus_get_version_string() -> *const ::core::ffi::c_char;
+}
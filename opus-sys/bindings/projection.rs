@@ -0,0 +1,130 @@
+/* automatically generated by rust-bindgen 0.58.1 */
+
+pub const OPUS_PROJECTION_GET_DEMIXING_MATRIX_GAIN_REQUEST: i32 = 6001;
+pub const OPUS_PROJECTION_GET_DEMIXING_MATRIX_SIZE_REQUEST: i32 = 6003;
+pub const OPUS_PROJECTION_GET_DEMIXING_MATRIX_REQUEST: i32 = 6005;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusProjectionEncoder {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusProjectionDecoder {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn opus_projection_ambisonics_encoder_get_size(
+        channels: ::core::ffi::c_int,
+        mapping_family: ::core::ffi::c_int,
+    ) -> opus_int32;
+}
+extern "C" {
+    pub fn opus_projection_ambisonics_encoder_create(
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        mapping_family: ::core::ffi::c_int,
+        streams: *mut ::core::ffi::c_int,
+        coupled_streams: *mut ::core::ffi::c_int,
+        application: ::core::ffi::c_int,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusProjectionEncoder;
+}
+extern "C" {
+    pub fn opus_projection_ambisonics_encoder_init(
+        st: *mut OpusProjectionEncoder,
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        mapping_family: ::core::ffi::c_int,
+        streams: *mut ::core::ffi::c_int,
+        coupled_streams: *mut ::core::ffi::c_int,
+        application: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_projection_encode(
+        st: *mut OpusProjectionEncoder,
+        pcm: *const opus_int16,
+        frame_size: ::core::ffi::c_int,
+        data: *mut ::core::ffi::c_uchar,
+        max_data_bytes: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_projection_encode_float(
+        st: *mut OpusProjectionEncoder,
+        pcm: *const f32,
+        frame_size: ::core::ffi::c_int,
+        data: *mut ::core::ffi::c_uchar,
+        max_data_bytes: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_projection_encoder_destroy(st: *mut OpusProjectionEncoder);
+}
+extern "C" {
+    pub fn opus_projection_encoder_ctl(
+        st: *mut OpusProjectionEncoder,
+        request: ::core::ffi::c_int,
+        ...
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_projection_decoder_get_size(
+        channels: ::core::ffi::c_int,
+        streams: ::core::ffi::c_int,
+        coupled_streams: ::core::ffi::c_int,
+    ) -> opus_int32;
+}
+extern "C" {
+    pub fn opus_projection_decoder_create(
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        streams: ::core::ffi::c_int,
+        coupled_streams: ::core::ffi::c_int,
+        demixing_matrix: *mut ::core::ffi::c_uchar,
+        demixing_matrix_size: opus_int32,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusProjectionDecoder;
+}
+extern "C" {
+    pub fn opus_projection_decoder_init(
+        st: *mut OpusProjectionDecoder,
+        Fs: opus_int32,
+        channels: ::core::ffi::c_int,
+        streams: ::core::ffi::c_int,
+        coupled_streams: ::core::ffi::c_int,
+        demixing_matrix: *mut ::core::ffi::c_uchar,
+        demixing_matrix_size: opus_int32,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_projection_decode(
+        st: *mut OpusProjectionDecoder,
+        data: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+        pcm: *mut opus_int16,
+        frame_size: ::core::ffi::c_int,
+        decode_fec: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_projection_decode_float(
+        st: *mut OpusProjectionDecoder,
+        data: *const ::core::ffi::c_uchar,
+        len: opus_int32,
+        pcm: *mut f32,
+        frame_size: ::core::ffi::c_int,
+        decode_fec: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_projection_decoder_ctl(
+        st: *mut OpusProjectionDecoder,
+        request: ::core::ffi::c_int,
+        ...
+    ) -> ::core::ffi::c_int;
+}
+extern "C" {
+    pub fn opus_projection_decoder_destroy(st: *mut OpusProjectionDecoder);
+}
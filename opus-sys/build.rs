@@ -1,15 +1,41 @@
 use std::env;
-use std::fs::{self, File};
-use std::io::{self, Write};
-use std::path::PathBuf;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 type DynError = Box<dyn std::error::Error>;
 
+// the directories holding opus.h and the library, which are passed to bindgen
+// and to the build scripts of dependents as DEP_OPUS_INCLUDE and DEP_OPUS_LIB
 #[derive(Debug)]
 struct Paths {
     include_paths: Vec<PathBuf>,
     link_paths: Vec<PathBuf>,
+    version: Option<String>,
+}
+
+impl Paths {
+    // The version of the linked libopus: the one built from source, the one
+    // pkg-config reports, or else the oldest one with the requests found in
+    // the headers.
+    fn version(&self) -> String {
+        if let Some(version) = &self.version {
+            return version.clone();
+        }
+        let defines = self
+            .include_paths
+            .iter()
+            .find_map(|dir| fs::read_to_string(dir.join("opus_defines.h")).ok())
+            .unwrap_or_default();
+        if defines.contains("OPUS_SET_DRED_DURATION_REQUEST") {
+            "1.5".to_string()
+        } else if defines.contains("OPUS_GET_IN_DTX_REQUEST") {
+            "1.4".to_string()
+        } else {
+            "1.3".to_string()
+        }
+    }
 }
 
 impl Default for Paths {
@@ -17,6 +43,7 @@ impl Default for Paths {
         Self {
             include_paths: vec![search().join("include").join("opus")],
             link_paths: vec![search().join("lib")],
+            version: Some(version()),
         }
     }
 }
@@ -26,12 +53,46 @@ impl From<pkg_config::Library> for Paths {
         Self {
             include_paths: val.include_paths,
             link_paths: val.link_paths,
+            version: Some(val.version),
+        }
+    }
+}
+
+impl From<vcpkg::Library> for Paths {
+    fn from(val: vcpkg::Library) -> Self {
+        Self {
+            // the opus port puts its headers in an `opus` subdirectory
+            include_paths: val.include_paths.iter().map(|x| x.join("opus")).collect(),
+            link_paths: val.link_paths,
+            version: None,
         }
     }
 }
 
 fn version() -> String {
-    "1.3.1".to_string()
+    println!("cargo:rerun-if-env-changed=OPUS_VERSION");
+    if let Ok(version) = env::var("OPUS_VERSION") {
+        return version.trim_start_matches('v').to_string();
+    }
+    if env::var_os("CARGO_FEATURE_LIBOPUS_1_5").is_some() {
+        "1.5.2".to_string()
+    } else if env::var_os("CARGO_FEATURE_LIBOPUS_1_4").is_some() {
+        "1.4".to_string()
+    } else {
+        "1.3.1".to_string()
+    }
+}
+
+fn version_at_least(major: u32, minor: u32) -> bool {
+    version_ge(&version(), major, minor)
+}
+
+fn version_ge(version: &str, major: u32, minor: u32) -> bool {
+    let mut parts = version
+        .split('.')
+        .map(|part| part.parse::<u32>().unwrap_or(0));
+    let found = (parts.next().unwrap_or(0), parts.next().unwrap_or(0));
+    found >= (major, minor)
 }
 
 fn output() -> PathBuf {
@@ -45,24 +106,90 @@ fn source() -> PathBuf {
 fn search() -> PathBuf {
     let mut absolute = env::current_dir().unwrap();
     absolute.push(&output());
-    absolute.push("dist");
+    // keep builds of different versions apart
+    absolute.push(format!("dist-{}", version()));
 
     absolute
 }
 
+fn local_source() -> Option<PathBuf> {
+    println!("cargo:rerun-if-env-changed=OPUS_SOURCE_DIR");
+    let dir = PathBuf::from(env::var_os("OPUS_SOURCE_DIR")?);
+    println!("cargo:rerun-if-changed={}", dir.display());
+    Some(dir)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+// Copy a local checkout or unpacked release into the build directory, so that
+// it is not touched by the build, replacing the results of any earlier build.
+fn copy_source(dir: &Path) -> io::Result<()> {
+    for stale in &[source(), search()] {
+        if stale.exists() {
+            fs::remove_dir_all(stale)?;
+        }
+    }
+    copy_dir(dir, &source())
+}
+
 fn fetch() -> io::Result<()> {
+    println!("cargo:rerun-if-env-changed=OPUS_GIT_URL");
+    let custom_url = env::var("OPUS_GIT_URL").ok();
+    let pinned = match pinned_commit() {
+        Some(pinned) => pinned,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "no commit is pinned for libopus {}{}, \
+                     set OPUS_GIT_COMMIT to the expected commit hash",
+                    version(),
+                    if custom_url.is_some() {
+                        " from OPUS_GIT_URL"
+                    } else {
+                        ""
+                    }
+                ),
+            ))
+        }
+    };
+
     #[cfg(windows)]
     let configure = "CMakeLists.txt";
     #[cfg(unix)]
     let configure = "autogen.sh";
-    let configure_path = &output()
-        .join(format!("opus-{}", version()))
-        .join(configure);
-    if fs::metadata(configure_path).is_ok() {
-        return Ok(());
-    }
-    let url =
-        env::var("OPUS_GIT_URL").unwrap_or_else(|_| "https://github.com/xiph/opus".to_string());
+    if fs::metadata(source().join(configure)).is_ok() {
+        // sources left by an earlier build are checked like fresh ones, and
+        // fetched again if the pin has moved on
+        if source_commit().map_or(false, |head| head.eq_ignore_ascii_case(&pinned)) {
+            return Ok(());
+        }
+        fs::remove_dir_all(&source())?;
+    }
+
+    let url = custom_url.unwrap_or_else(|| "https://github.com/xiph/opus".to_string());
+    println!("cargo:rerun-if-env-changed=OPUS_NO_NETWORK");
+    if env_flag("OPUS_NO_NETWORK") {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            offline_help(&url, "OPUS_NO_NETWORK forbids downloading it"),
+        ));
+    }
     let status = Command::new("git")
         .current_dir(&output())
         .arg("clone")
@@ -70,15 +197,243 @@ fn fetch() -> io::Result<()> {
         .arg("1")
         .arg("-b")
         .arg(format!("v{}", version()))
-        .arg(url)
+        .arg(&url)
         .arg(format!("opus-{}", version()))
-        .status()?;
+        .status();
+    let reason = match status {
+        Ok(status) if status.success() => None,
+        Ok(status) => Some(format!("`git clone` failed ({})", status)),
+        Err(err) => Some(format!("`git` could not be run ({})", err)),
+    };
+    if let Some(reason) = reason {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            offline_help(&url, &reason),
+        ));
+    }
+
+    let head = source_commit().unwrap_or_default();
+    if !head.eq_ignore_ascii_case(&pinned) {
+        // don't leave the sources behind for the next build to pick up
+        fs::remove_dir_all(&source())?;
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "fetched libopus {} is at commit {}, expected {}",
+                version(),
+                head,
+                pinned
+            ),
+        ));
+    }
+    Ok(())
+}
 
-    if status.success() {
-        Ok(())
+// Get the commit checked out in the fetched sources, or `None` if they are not
+// a git checkout of their own.
+fn source_commit() -> Option<String> {
+    if !source().join(".git").exists() {
+        return None;
+    }
+    let head = Command::new("git")
+        .current_dir(&source())
+        .args(&["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&head.stdout).trim().to_string())
+}
+
+// Explain the ways to provide libopus without the network, for sandboxed and
+// offline builds.
+fn offline_help(url: &str, reason: &str) -> String {
+    let mut help = format!(
+        "libopus {version} has to be built from the sources at {url}, but {reason}. \
+         Any of these avoids the download:\n\
+         \x20 - set OPUS_SOURCE_DIR to a checkout or unpacked release tarball of libopus {version}\n\
+         \x20 - set OPUS_LIB_DIR, and OPUS_INCLUDE_DIR unless it is ../include, to a \
+         libopus built beforehand\n",
+        version = version(),
+        url = url,
+        reason = reason,
+    );
+    if let Some(cache) = build_cache() {
+        help.push_str(&format!(
+            "  - copy the same build from another machine to {}\n",
+            cache.display()
+        ));
+    }
+    let features = [
+        (vendored(), "`vendored` or OPUS_VENDOR"),
+        (fixed_point(), "`fixed-point`"),
+        (disable_float_api(), "`disable-float-api`"),
+        (custom_modes(), "`custom`"),
+        (dred(), "`dred`"),
+        (osce(), "`osce`"),
+        (deep_plc(), "`deep-plc`"),
+        (debug_libopus(), "`debug-libopus`"),
+        (
+            Simd::new().map_or(false, |simd| simd.configured()),
+            "the SIMD features",
+        ),
+    ];
+    let requested = features
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, name)| *name)
+        .collect::<Vec<&str>>();
+    if requested.is_empty() {
+        help.push_str(
+            "  - install libopus where pkg-config, vcpkg, Homebrew, or MacPorts finds it, \
+             such as the libopus-dev or opus-devel package",
+        );
     } else {
-        Err(io::Error::new(io::ErrorKind::Other, "fetch failed"))
+        help.push_str(&format!(
+            "  - install libopus where pkg-config finds it, and drop {} (they \
+             require building it from source)",
+            requested.join(", ")
+        ));
+    }
+    help
+}
+
+// The commit each release tag should point to, so that a compromised mirror
+// cannot substitute other sources. Other versions are only fetched when
+// `OPUS_GIT_COMMIT` pins them.
+fn pinned_commit() -> Option<String> {
+    println!("cargo:rerun-if-env-changed=OPUS_GIT_COMMIT");
+    if let Ok(commit) = env::var("OPUS_GIT_COMMIT") {
+        return Some(commit);
     }
+    let commit = match version().as_str() {
+        "1.3.1" => "e85ed7726db5d677c9c0677298ea0cb9c65bdd23",
+        "1.4" => "82ac57d9f1aaf575800cf17373348e45b7ce6c0d",
+        "1.5.2" => "ddbe48383984d56acd9e1ab6a090c54ca6b735a6",
+        _ => return None,
+    };
+    Some(commit.to_string())
+}
+
+fn fixed_point() -> bool {
+    env::var_os("CARGO_FEATURE_FIXED_POINT").is_some()
+}
+
+fn disable_float_api() -> bool {
+    env::var_os("CARGO_FEATURE_DISABLE_FLOAT_API").is_some()
+}
+
+fn custom_modes() -> bool {
+    env::var_os("CARGO_FEATURE_CUSTOM").is_some()
+}
+
+fn dred() -> bool {
+    env::var_os("CARGO_FEATURE_DRED").is_some()
+}
+
+fn osce() -> bool {
+    env::var_os("CARGO_FEATURE_OSCE").is_some()
+}
+
+fn deep_plc() -> bool {
+    env::var_os("CARGO_FEATURE_DEEP_PLC").is_some()
+}
+
+// The enabled features building the neural networks of libopus 1.5.
+fn neural_features() -> Vec<&'static str> {
+    [
+        (dred(), "`dred`"),
+        (osce(), "`osce`"),
+        (deep_plc(), "`deep-plc`"),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, name)| *name)
+    .collect()
+}
+
+fn debug_libopus() -> bool {
+    env::var_os("CARGO_FEATURE_DEBUG_LIBOPUS").is_some()
+}
+
+fn feature(name: &str) -> bool {
+    env::var_os(format!("CARGO_FEATURE_{}", name)).is_some()
+}
+
+// The SIMD features pin what libopus compiles instead of leaving it to the
+// configure defaults: `intrinsics` and `disable-intrinsics` switch the SIMD
+// code on or off, `disable-rtcd` drops the run-time CPU detection so only the
+// presumed instruction sets are used, and the `presume-*` features assume the
+// target CPU has SSE4.1, AVX (AVX2 and FMA from libopus 1.5), or NEON.
+struct Simd {
+    intrinsics: Option<bool>,
+    rtcd: bool,
+    sse4_1: bool,
+    avx: bool,
+    neon: bool,
+}
+
+impl Simd {
+    fn new() -> io::Result<Simd> {
+        let presume =
+            feature("PRESUME_SSE4_1") || feature("PRESUME_AVX") || feature("PRESUME_NEON");
+        let disable = feature("DISABLE_INTRINSICS");
+        if disable && (feature("INTRINSICS") || presume) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "The `disable-intrinsics` feature excludes `intrinsics` and `presume-*`!",
+            ));
+        }
+        let intrinsics = if disable {
+            Some(false)
+        } else if feature("INTRINSICS") || presume {
+            Some(true)
+        } else {
+            None
+        };
+        Ok(Simd {
+            intrinsics,
+            rtcd: !feature("DISABLE_RTCD"),
+            sse4_1: feature("PRESUME_SSE4_1"),
+            avx: feature("PRESUME_AVX"),
+            neon: feature("PRESUME_NEON"),
+        })
+    }
+
+    fn configured(&self) -> bool {
+        self.intrinsics.is_some() || !self.rtcd
+    }
+
+    // the compiler flags which make configure presume the instruction sets
+    #[cfg(unix)]
+    fn cflags(&self) -> Vec<&'static str> {
+        let mut cflags = Vec::new();
+        if self.sse4_1 {
+            cflags.push("-msse4.1");
+        }
+        if self.avx && version_at_least(1, 5) {
+            cflags.extend(&["-mavx", "-mavx2", "-mfma"]);
+        } else if self.avx {
+            cflags.push("-mavx");
+        }
+        if self.neon && env::var("CARGO_CFG_TARGET_ARCH").map_or(false, |v| v == "arm") {
+            cflags.push("-mfpu=neon");
+        }
+        cflags
+    }
+}
+
+fn vendored() -> bool {
+    println!("cargo:rerun-if-env-changed=OPUS_VENDOR");
+    env::var_os("CARGO_FEATURE_VENDORED").is_some() || env_flag("OPUS_VENDOR")
+}
+
+fn dynamic() -> bool {
+    println!("cargo:rerun-if-env-changed=OPUS_DYNAMIC");
+    env::var_os("CARGO_FEATURE_DYNAMIC").is_some() || env_flag("OPUS_DYNAMIC")
+}
+
+fn system_only() -> bool {
+    env::var_os("CARGO_FEATURE_SYSTEM").is_some()
 }
 
 fn check_prog(name: &str, args: &[&str]) -> bool {
@@ -89,23 +444,337 @@ fn check_prog(name: &str, args: &[&str]) -> bool {
     }
 }
 
+// The NDK toolchain for an Android target. Only CMake builds, which use the
+// NDK's toolchain file, are possible on Windows hosts.
+#[cfg_attr(windows, allow(dead_code))]
+struct AndroidNdk {
+    root: PathBuf,
+    bin: PathBuf,
+    triple: &'static str,
+    abi: &'static str,
+    api: String,
+    cc: PathBuf,
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+impl AndroidNdk {
+    fn tool(&self, name: &str) -> PathBuf {
+        self.bin.join(name)
+    }
+
+    fn sysroot(&self) -> PathBuf {
+        self.bin.parent().unwrap().join("sysroot")
+    }
+}
+
+// Find the NDK for Android targets from `ANDROID_NDK_HOME`, deriving the
+// compiler and `--host` from the Cargo target rather than the linker name.
+fn android_ndk() -> io::Result<Option<AndroidNdk>> {
+    if env::var("CARGO_CFG_TARGET_OS").map_or(true, |v| v != "android") {
+        return Ok(None);
+    }
+    println!("cargo:rerun-if-env-changed=ANDROID_NDK_HOME");
+    println!("cargo:rerun-if-env-changed=ANDROID_PLATFORM");
+    let root = env::var_os("ANDROID_NDK_HOME")
+        .or_else(|| env::var_os("ANDROID_NDK_ROOT"))
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "Set ANDROID_NDK_HOME to the Android NDK to build for Android!",
+            )
+        })?;
+    let (triple, abi) = match env::var("CARGO_CFG_TARGET_ARCH").unwrap().as_str() {
+        "aarch64" => ("aarch64-linux-android", "arm64-v8a"),
+        "arm" => ("armv7a-linux-androideabi", "armeabi-v7a"),
+        "x86" => ("i686-linux-android", "x86"),
+        "x86_64" => ("x86_64-linux-android", "x86_64"),
+        arch => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("The Android architecture `{}` is not supported!", arch),
+            ))
+        }
+    };
+    // the oldest API level supported by current NDKs
+    let api = env::var("ANDROID_PLATFORM")
+        .map(|v| v.trim_start_matches("android-").to_string())
+        .unwrap_or_else(|_| "21".to_string());
+    let host = env::var("HOST").unwrap();
+    let host_tag = if host.contains("windows") {
+        "windows-x86_64"
+    } else if host.contains("darwin") {
+        "darwin-x86_64"
+    } else {
+        "linux-x86_64"
+    };
+    let bin = root
+        .join("toolchains/llvm/prebuilt")
+        .join(host_tag)
+        .join("bin");
+    let cc = bin.join(format!("{}{}-clang", triple, api));
+
+    Ok(Some(AndroidNdk {
+        root,
+        bin,
+        triple,
+        abi,
+        api,
+        cc,
+    }))
+}
+
+// The SDK for an iOS or tvOS target, device or simulator.
+struct AppleSdk {
+    sdk: &'static str,
+    system: &'static str,
+    arch: &'static str,
+    host: &'static str,
+    deployment_target: String,
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+impl AppleSdk {
+    fn xcrun(&self, args: &[&str]) -> io::Result<String> {
+        let output = Command::new("xcrun")
+            .arg("--sdk")
+            .arg(self.sdk)
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "xcrun failed for the `{}` SDK, is Xcode installed?",
+                    self.sdk
+                ),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn min_version_flag(&self) -> String {
+        let flag = match self.sdk {
+            "iphoneos" => "-mios-version-min",
+            "iphonesimulator" => "-mios-simulator-version-min",
+            "appletvos" => "-mtvos-version-min",
+            _ => "-mtvos-simulator-version-min",
+        };
+        format!("{}={}", flag, self.deployment_target)
+    }
+}
+
+// Pick the SDK for iOS and tvOS targets, so the library is built for the
+// device or simulator the Cargo target names and can be linked into an Xcode
+// project.
+fn apple_sdk() -> io::Result<Option<AppleSdk>> {
+    let os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let (system, deployment_env) = match os.as_str() {
+        "ios" => ("iOS", "IPHONEOS_DEPLOYMENT_TARGET"),
+        "tvos" => ("tvOS", "TVOS_DEPLOYMENT_TARGET"),
+        _ => return Ok(None),
+    };
+    println!("cargo:rerun-if-env-changed={}", deployment_env);
+    let (arch, host) = match env::var("CARGO_CFG_TARGET_ARCH").unwrap().as_str() {
+        "aarch64" => ("arm64", "aarch64-apple-darwin"),
+        "x86_64" => ("x86_64", "x86_64-apple-darwin"),
+        arch => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("The {} architecture `{}` is not supported!", system, arch),
+            ))
+        }
+    };
+    // x86_64 only exists as a simulator, and aarch64 ones end in `-sim`
+    let simulator = arch == "x86_64" || env::var("TARGET").unwrap().ends_with("-sim");
+    let sdk = match (system, simulator) {
+        ("iOS", false) => "iphoneos",
+        ("iOS", true) => "iphonesimulator",
+        (_, false) => "appletvos",
+        (_, true) => "appletvsimulator",
+    };
+    let deployment_target = env::var(deployment_env).unwrap_or_else(|_| "12.0".to_string());
+
+    Ok(Some(AppleSdk {
+        sdk,
+        system,
+        arch,
+        host,
+        deployment_target,
+    }))
+}
+
+// The C toolchain for a WebAssembly target: emcc for Emscripten, or the clang
+// of the wasi-sdk at `WASI_SDK_PATH` for WASI.
+struct WasmToolchain {
+    target: &'static str,
+    cc: PathBuf,
+    ar: PathBuf,
+    ranlib: PathBuf,
+    sysroot: Option<PathBuf>,
+    cmake_toolchain: Option<PathBuf>,
+}
+
+fn wasm_toolchain() -> io::Result<Option<WasmToolchain>> {
+    if env::var("CARGO_CFG_TARGET_ARCH").map_or(true, |v| v != "wasm32") {
+        return Ok(None);
+    }
+    match env::var("CARGO_CFG_TARGET_OS").unwrap_or_default().as_str() {
+        "emscripten" => {
+            println!("cargo:rerun-if-env-changed=EMSDK");
+            let emscripten = env::var_os("EMSDK")
+                .map(|sdk| PathBuf::from(sdk).join("upstream").join("emscripten"));
+            Ok(Some(WasmToolchain {
+                target: "wasm32-unknown-emscripten",
+                cc: "emcc".into(),
+                ar: "emar".into(),
+                ranlib: "emranlib".into(),
+                sysroot: emscripten.as_ref().map(|dir| dir.join("cache/sysroot")),
+                cmake_toolchain: emscripten
+                    .map(|dir| dir.join("cmake/Modules/Platform/Emscripten.cmake")),
+            }))
+        }
+        "wasi" => {
+            println!("cargo:rerun-if-env-changed=WASI_SDK_PATH");
+            let sdk = env::var_os("WASI_SDK_PATH")
+                .map(PathBuf::from)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "Set WASI_SDK_PATH to the wasi-sdk to build for WASI!",
+                    )
+                })?;
+            Ok(Some(WasmToolchain {
+                target: "wasm32-wasi",
+                cc: sdk.join("bin/clang"),
+                ar: sdk.join("bin/llvm-ar"),
+                ranlib: sdk.join("bin/llvm-ranlib"),
+                sysroot: Some(sdk.join("share/wasi-sysroot")),
+                cmake_toolchain: Some(sdk.join("share/cmake/wasi-sdk.cmake")),
+            }))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::Other,
+            "libopus needs a C library, build for wasm32-unknown-emscripten or wasm32-wasi!",
+        )),
+    }
+}
+
+// A toolchain variable such as `CC`, `CFLAGS`, `AR`, or `RANLIB` for the
+// target, looked up the way the cc crate does: `CC_<target>`, with dashes or
+// underscores, then `TARGET_CC` when cross compiling or `HOST_CC` otherwise,
+// and then `CC`.
+fn target_env(name: &str) -> Option<String> {
+    let target = env::var("TARGET").unwrap();
+    let kind = if target == env::var("HOST").unwrap() {
+        "HOST"
+    } else {
+        "TARGET"
+    };
+    let vars = [
+        format!("{}_{}", name, target),
+        format!("{}_{}", name, target.replace('-', "_")),
+        format!("{}_{}", kind, name),
+        name.to_string(),
+    ];
+    for var in &vars {
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+    vars.iter().find_map(|var| env::var(var).ok())
+}
+
+// The configure host and C compiler for a musl target: the `CC` variables, then
+// `musl-gcc` when only the C library differs from the host, and a
+// musl-cross-make toolchain otherwise.
+#[cfg(unix)]
+fn musl_toolchain() -> (String, String) {
+    let target = env::var("TARGET").unwrap();
+    let host = target.replacen("-unknown-", "-", 1);
+    if let Some(cc) = target_env("CC") {
+        return (host, cc);
+    }
+    let same_arch = env::var("HOST")
+        .unwrap()
+        .split('-')
+        .next()
+        .map_or(false, |arch| target.starts_with(arch));
+    if same_arch && check_prog("musl-gcc", &["--version"]) {
+        (host, "musl-gcc".to_string())
+    } else {
+        let cc = format!("{}-gcc", host);
+        (host, cc)
+    }
+}
+
+// The configure host and C compiler for a MinGW-w64 target, such as
+// `x86_64-w64-mingw32` and `x86_64-w64-mingw32-gcc`, unless the `CC`
+// variables say otherwise.
+#[cfg(unix)]
+fn mingw_toolchain() -> (String, String) {
+    let target = env::var("TARGET").unwrap();
+    let arch = target.split('-').next().unwrap();
+    let host = format!("{}-w64-mingw32", arch);
+    let cc = target_env("CC").unwrap_or_else(|| format!("{}-gcc", host));
+    (host, cc)
+}
+
+fn cmake_build() -> bool {
+    env::var_os("CARGO_FEATURE_CMAKE_BUILD").is_some()
+}
+
 #[cfg(windows)]
 fn build() -> io::Result<Paths> {
-    let is_target_env_gnu = env::var("CARGO_CFG_TARGET_ENV").map_or(false, |v| v == "gnu");
+    check_dynamic()?;
+    build_cmake()
+}
 
-    // make sure the `make/nmake` exists
-    let (make_prog_name, make_prog_args) = if is_target_env_gnu {
-        ("make", ["--version"])
+#[cfg(unix)]
+fn build() -> io::Result<Paths> {
+    check_dynamic()?;
+    // use CMake when asked to, or when the autotools are missing but it is not
+    let autotools =
+        check_prog("autoreconf", &["--version"]) && check_prog("libtool", &["--version"]);
+    if cmake_build() || (!autotools && check_prog("cmake", &["--version"])) {
+        build_cmake()
     } else {
-        ("nmake", ["/?"])
-    };
-    if !check_prog(make_prog_name, &make_prog_args) {
+        build_autotools()
+    }
+}
+
+fn check_dynamic() -> io::Result<()> {
+    if dynamic() && wasm_toolchain()?.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "WebAssembly targets can only link libopus statically!",
+        ));
+    }
+    if dynamic() && crt_static() && !is_target_env_msvc() {
         return Err(io::Error::new(
             io::ErrorKind::Other,
-            format!("The `{}` not found, install or add to PATH and try again!", make_prog_name),
+            "A statically linked C runtime cannot load a shared libopus, disable `dynamic`!",
         ));
     }
+    Ok(())
+}
+
+// Release for release profiles, and otherwise RelWithDebInfo, which keeps the
+// symbols without switching to the debug C runtime on MSVC. `debug-libopus`
+// asks for an unoptimized Debug build.
+fn build_type() -> String {
+    println!("cargo:rerun-if-env-changed=OPUS_BUILD_TYPE");
+    if let Ok(build_type) = env::var("OPUS_BUILD_TYPE") {
+        return build_type;
+    }
+    if debug_libopus() {
+        return "Debug".to_string();
+    }
+    match env::var("PROFILE").as_ref().map(String::as_str) {
+        Ok("release") => "Release".to_string(),
+        _ => "RelWithDebInfo".to_string(),
+    }
+}
 
+fn build_cmake() -> io::Result<Paths> {
     // make sure the `cmake` exists
     if !check_prog("cmake", &["--version"]) {
         return Err(io::Error::new(
@@ -114,58 +783,135 @@ fn build() -> io::Result<Paths> {
         ));
     }
 
-    let generator = if is_target_env_gnu {
-        "Unix Makefiles"
+    // the cmake crate picks the generator, sets up the MSVC environment and
+    // cross compilers, and builds with `NUM_JOBS` jobs
+    let mut config = cmake::Config::new(source());
+    config.out_dir(search());
+    config.profile(&build_type());
+    // link the same C runtime as Rust; it uses the release one in all profiles
+    let crt_static = crt_static();
+    config.static_crt(crt_static);
+    config.define("CMAKE_POLICY_DEFAULT_CMP0091", "NEW");
+    config.define(
+        "CMAKE_MSVC_RUNTIME_LIBRARY",
+        if crt_static {
+            "MultiThreaded"
+        } else {
+            "MultiThreadedDLL"
+        },
+    );
+    // keep the libraries where `probe_prebuilt` looks, not in lib64
+    config.define("CMAKE_INSTALL_LIBDIR", "lib");
+    config.define("CMAKE_POSITION_INDEPENDENT_CODE", "ON");
+    config.define("OPUS_STACK_PROTECTOR", "OFF");
+    // the cmake crate takes the compiler and CFLAGS from the environment
+    // already, so add the archiver
+    if let Some(ar) = target_env("AR") {
+        config.define("CMAKE_AR", ar);
+    }
+    if let Some(ranlib) = target_env("RANLIB") {
+        config.define("CMAKE_RANLIB", ranlib);
+    }
+    if let Some(ndk) = android_ndk()? {
+        let toolchain = ndk.root.join("build/cmake/android.toolchain.cmake");
+        config.define("CMAKE_TOOLCHAIN_FILE", toolchain);
+        config.define("ANDROID_ABI", ndk.abi);
+        config.define("ANDROID_PLATFORM", format!("android-{}", ndk.api));
+    }
+    if let Some(wasm) = wasm_toolchain()? {
+        let toolchain = wasm.cmake_toolchain.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "Set EMSDK to the emsdk to build for Emscripten with CMake!",
+            )
+        })?;
+        config.define("CMAKE_TOOLCHAIN_FILE", toolchain);
+        config.define("OPUS_DISABLE_INTRINSICS", "ON");
+    }
+    if let Some(sdk) = apple_sdk()? {
+        config.define("CMAKE_SYSTEM_NAME", sdk.system);
+        config.define("CMAKE_OSX_SYSROOT", sdk.sdk);
+        config.define("CMAKE_OSX_ARCHITECTURES", sdk.arch);
+        config.define("CMAKE_OSX_DEPLOYMENT_TARGET", &sdk.deployment_target);
+    }
+    if dynamic() {
+        config.define("BUILD_SHARED_LIBS", "ON");
+    }
+    if fixed_point() {
+        config.define("OPUS_FIXED_POINT", "ON");
+    }
+    if disable_float_api() {
+        config.define("OPUS_DISABLE_FLOAT_API", "ON");
+    }
+    if custom_modes() {
+        config.define("OPUS_CUSTOM_MODES", "ON");
+    }
+    if dred() {
+        config.define("OPUS_DRED", "ON");
+    }
+    if osce() {
+        config.define("OPUS_OSCE", "ON");
+    }
+    if deep_plc() {
+        config.define("OPUS_DEEP_PLC", "ON");
+    }
+    if debug_libopus() {
+        config.define("OPUS_ASSERTIONS", "ON");
+        config.define("OPUS_CHECK_ASM", "ON");
+    }
+    let simd = Simd::new()?;
+    let avx = if version_at_least(1, 5) {
+        "AVX2"
     } else {
-        "NMake Makefiles"
+        "AVX"
     };
-    let mut configure = Command::new("cmake");
-    configure.current_dir(&source());
-    configure.args(&["-G", generator]);
-    configure.arg(format!("-DCMAKE_BUILD_TYPE={}", "Release"));
-    configure.arg(format!("-DCMAKE_INSTALL_PREFIX={}", search().to_string_lossy()));
-    configure.arg("-DOPUS_STACK_PROTECTOR=OFF");
-
-    // run ./configure
-    let output = configure
-        .output()
-        .unwrap_or_else(|_| panic!("{:?} failed", configure));
-    if !output.status.success() {
-        println!("configure: {}", String::from_utf8_lossy(&output.stdout));
-
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!(
-                "configure failed {}",
-                String::from_utf8_lossy(&output.stderr)
-            ),
-        ));
+    if let Some(intrinsics) = simd.intrinsics {
+        config.define(
+            "OPUS_DISABLE_INTRINSICS",
+            if intrinsics { "OFF" } else { "ON" },
+        );
     }
-
-    // run make
-    if !Command::new(make_prog_name)
-        .current_dir(&source())
-        .status()?
-        .success()
-    {
-        return Err(io::Error::new(io::ErrorKind::Other, "make failed"));
+    if !simd.rtcd {
+        for may_have in &["SSE", "SSE2", "SSE4_1", avx] {
+            config.define(format!("OPUS_X86_MAY_HAVE_{}", may_have), "OFF");
+        }
+        config.define("OPUS_MAY_HAVE_NEON", "OFF");
     }
-
-    // run make install
-    if !Command::new(make_prog_name)
-        .arg("install")
-        .current_dir(&source())
-        .status()?
-        .success()
-    {
-        return Err(io::Error::new(io::ErrorKind::Other, "make install failed"));
+    if simd.sse4_1 {
+        config.define("OPUS_X86_PRESUME_SSE4_1", "ON");
+    }
+    if simd.avx {
+        config.define(format!("OPUS_X86_PRESUME_{}", avx), "ON");
     }
+    if simd.neon {
+        config.define("OPUS_PRESUME_NEON", "ON");
+    }
+    if version_at_least(1, 5) {
+        // leave out the neural network features added in 1.5
+        config.define("OPUS_DEEP_PLC", "OFF");
+        config.define("OPUS_DRED", "OFF");
+        config.define("OPUS_OSCE", "OFF");
+    }
+    config.build();
 
     Ok(Paths::default())
 }
 
+// Add to the CFLAGS set for configure by the cross toolchains, or to the
+// optimization it uses by default.
 #[cfg(unix)]
-fn build() -> io::Result<Paths> {
+fn add_cflags(configure: &mut Command, flags: &str) {
+    let cflags = configure
+        .get_envs()
+        .find(|(key, _)| *key == "CFLAGS")
+        .and_then(|(_, value)| value)
+        .map(|value| value.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "-O2".to_string());
+    configure.env("CFLAGS", format!("{} {}", cflags, flags));
+}
+
+#[cfg(unix)]
+fn build_autotools() -> io::Result<Paths> {
     // make sure the `make` exists
     if !check_prog("make", &["--version"]) {
         return Err(io::Error::new(
@@ -197,7 +943,64 @@ fn build() -> io::Result<Paths> {
     configure.current_dir(&source());
     configure.arg(format!("--prefix={}", search().to_string_lossy()));
 
-    if env::var("TARGET").unwrap() != env::var("HOST").unwrap() {
+    let cross = env::var("TARGET").unwrap() != env::var("HOST").unwrap();
+    if let Some(ndk) = android_ndk()? {
+        configure.arg(format!("--host={}", ndk.triple));
+        configure.env("CC", &ndk.cc);
+        configure.env("AR", ndk.tool("llvm-ar"));
+        configure.env("RANLIB", ndk.tool("llvm-ranlib"));
+        let mut cflags = format!("-O2 --sysroot={}", ndk.sysroot().display());
+        if ndk.abi == "armeabi-v7a" {
+            cflags.push_str(" -mfpu=neon");
+        }
+        configure.env("CFLAGS", cflags);
+        // NEON is present on all the Android ABIs which have ARM code
+        configure.arg("--enable-intrinsics");
+    } else if let Some(wasm) = wasm_toolchain()? {
+        configure.arg(format!("--host={}", wasm.target));
+        configure.env("CC", &wasm.cc);
+        configure.env("AR", &wasm.ar);
+        configure.env("RANLIB", &wasm.ranlib);
+        let mut cflags = "-O2".to_string();
+        // emcc knows its own sysroot, the wasi-sdk clang is told where to look
+        if let (Some(sysroot), "wasm32-wasi") = (&wasm.sysroot, wasm.target) {
+            cflags.push_str(&format!(
+                " --target={} --sysroot={}",
+                wasm.target,
+                sysroot.display()
+            ));
+        }
+        configure.env("CFLAGS", cflags);
+        // there is no SIMD code or CPU detection for WebAssembly
+        configure.arg("--disable-intrinsics");
+        configure.arg("--disable-rtcd");
+        configure.arg("--disable-asm");
+        configure.arg("--disable-stack-protector");
+    } else if let Some(sdk) = apple_sdk()? {
+        configure.arg(format!("--host={}", sdk.host));
+        configure.env("CC", sdk.xcrun(&["--find", "clang"])?);
+        configure.env(
+            "CFLAGS",
+            format!(
+                "-O2 -arch {} -isysroot {} {}",
+                sdk.arch,
+                sdk.xcrun(&["--show-sdk-path"])?,
+                sdk.min_version_flag()
+            ),
+        );
+    } else if is_target_env_musl() && cross {
+        let (host, cc) = musl_toolchain();
+        configure.arg(format!("--host={}", host));
+        configure.env("CC", cc);
+    } else if is_target_os_windows() && !is_target_env_msvc() && cross {
+        let (host, cc) = mingw_toolchain();
+        configure.arg(format!("--host={}", host));
+        configure.env("CC", cc);
+        configure.env("AR", format!("{}-ar", host));
+        configure.env("RANLIB", format!("{}-ranlib", host));
+        // -fstack-protector would need libssp, which Rust does not link
+        configure.arg("--disable-stack-protector");
+    } else if cross {
         let target = env::var("TARGET").unwrap();
         let linker = env::var("RUSTC_LINKER").expect("Missing RUSTC_LINKER for cross compile");
         if linker.contains(&target) {
@@ -208,19 +1011,85 @@ fn build() -> io::Result<Paths> {
         }
     }
 
-    // make it static
-    configure.arg("--enable-static");
-    configure.arg("--disable-shared");
+    // the toolchain from the environment wins over the one picked above,
+    // for custom toolchains, sanitizers, and hardening flags
+    for tool in &["CC", "AR", "RANLIB"] {
+        if let Some(value) = target_env(tool) {
+            configure.env(tool, value);
+        }
+    }
+
+    // make it static, unless a shared library is asked for
+    if dynamic() {
+        configure.arg("--enable-shared");
+        configure.arg("--disable-static");
+    } else {
+        configure.arg("--enable-static");
+        configure.arg("--disable-shared");
+    }
 
     // don't build docs and programs
     configure.arg("--disable-doc");
     configure.arg("--disable-extra-programs");
     configure.arg("--with-pic");
 
-    // run ./autogen.sh
-    let _output = autogen_sh
-        .output()
-        .unwrap_or_else(|_| panic!("{:?} failed", autogen_sh));
+    if fixed_point() {
+        configure.arg("--enable-fixed-point");
+    }
+    if disable_float_api() {
+        configure.arg("--disable-float-api");
+    }
+    if custom_modes() {
+        configure.arg("--enable-custom-modes");
+    }
+    if dred() {
+        configure.arg("--enable-dred");
+    }
+    if osce() {
+        configure.arg("--enable-osce");
+    }
+    if deep_plc() {
+        configure.arg("--enable-deep-plc");
+    }
+    if debug_libopus() {
+        configure.arg("--enable-assertions");
+        configure.arg("--enable-check-asm");
+        add_cflags(&mut configure, "-O0 -g");
+    }
+    let simd = Simd::new()?;
+    match simd.intrinsics {
+        Some(true) => {
+            configure.arg("--enable-intrinsics");
+        }
+        Some(false) => {
+            configure.arg("--disable-intrinsics");
+        }
+        None => {}
+    }
+    if !simd.rtcd {
+        configure.arg("--disable-rtcd");
+    }
+    let simd_cflags = simd.cflags();
+    if !simd_cflags.is_empty() {
+        add_cflags(&mut configure, &simd_cflags.join(" "));
+    }
+    // last, so that they can override the flags above
+    if let Some(cflags) = target_env("CFLAGS") {
+        add_cflags(&mut configure, &cflags);
+    }
+    if version_at_least(1, 5) {
+        // leave out the neural network features added in 1.5
+        configure.arg("--disable-deep-plc");
+        configure.arg("--disable-dred");
+        configure.arg("--disable-osce");
+    }
+
+    // run ./autogen.sh, unless this is a release tarball which ships configure
+    if !source().join("configure").exists() {
+        let _output = autogen_sh
+            .output()
+            .unwrap_or_else(|_| panic!("{:?} failed", autogen_sh));
+    }
 
     // run ./configure
     let output = configure
@@ -241,7 +1110,7 @@ fn build() -> io::Result<Paths> {
     // run make
     if !Command::new("make")
         .arg("-j")
-        .arg(num_cpus::get().to_string())
+        .arg(env::var("NUM_JOBS").unwrap_or_else(|_| num_cpus::get().to_string()))
         .current_dir(&source())
         .status()?
         .success()
@@ -262,71 +1131,615 @@ fn build() -> io::Result<Paths> {
     Ok(Paths::default())
 }
 
+fn env_flag(name: &str) -> bool {
+    env::var(name).map_or(false, |v| v != "0" && !v.is_empty())
+}
+
+fn probe_env() -> Option<Paths> {
+    println!("cargo:rerun-if-env-changed=OPUS_LIB_DIR");
+    println!("cargo:rerun-if-env-changed=OPUS_INCLUDE_DIR");
+    println!("cargo:rerun-if-env-changed=OPUS_STATIC");
+    if vendored() {
+        return None;
+    }
+    let lib_path = PathBuf::from(env::var_os("OPUS_LIB_DIR")?);
+    let include_path = env::var_os("OPUS_INCLUDE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| lib_path.join("..").join("include"));
+    // accept both the directory holding opus.h and its parent
+    let include_path = if include_path.join("opus.h").is_file() {
+        include_path
+    } else {
+        include_path.join("opus")
+    };
+
+    let kind = if env_flag("OPUS_STATIC") {
+        "static"
+    } else {
+        "dylib"
+    };
+    println!("cargo:rustc-link-search=native={}", lib_path.display());
+    println!("cargo:rustc-link-lib={}={}", kind, "opus");
+
+    Some(Paths {
+        include_paths: vec![include_path],
+        link_paths: vec![lib_path],
+        version: None,
+    })
+}
+
 fn probe_prebuilt() -> Result<Paths, DynError> {
-    let lib_name = if env::var("CARGO_CFG_TARGET_ENV").map_or(false, |v| v == "gnu") {
-        "libopus.a"
+    if has_library(&search()) {
+        Ok(Paths::default())
     } else {
-        "opus.lib"
+        Err(Box::new(io::Error::new(io::ErrorKind::NotFound, "")))
+    }
+}
+
+fn has_library(dir: &Path) -> bool {
+    // the names follow the target, not the host: MinGW builds produce
+    // `libopus.a`, and its autotools and CMake name the DLL differently
+    let os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let lib_names: &[&str] = match (dynamic(), os.as_str(), is_target_env_msvc()) {
+        (false, _, true) => &["lib/opus.lib"],
+        (false, _, false) => &["lib/libopus.a"],
+        (true, "windows", true) => &["bin/opus.dll"],
+        (true, "windows", false) => &["bin/libopus-0.dll", "bin/libopus.dll"],
+        (true, "macos", _) | (true, "ios", _) | (true, "tvos", _) => &["lib/libopus.dylib"],
+        (true, _, _) => &["lib/libopus.so"],
     };
 
-    match fs::metadata(&search().join("lib").join(lib_name)) {
-        Ok(_) => Ok(Paths::default()),
-        Err(_) => Err(Box::new(io::Error::new(io::ErrorKind::NotFound, ""))),
+    lib_names.iter().any(|name| dir.join(name).is_file())
+}
+
+// The directory in `OPUS_BUILD_CACHE` for the libopus built by this build
+// script, if the cache is enabled. The name holds the version and target,
+// and a hash of everything else affecting the build, so that builds are only
+// shared between target directories and workspaces when they are identical.
+// Builds from `OPUS_SOURCE_DIR` are never cached, as its contents may change.
+fn build_cache() -> Option<PathBuf> {
+    println!("cargo:rerun-if-env-changed=OPUS_BUILD_CACHE");
+    let cache = PathBuf::from(env::var_os("OPUS_BUILD_CACHE")?);
+    let target = env::var("TARGET").unwrap();
+
+    let mut key = vec![
+        build_type(),
+        pinned_commit().unwrap_or_default(),
+        env::var("OPUS_GIT_URL").unwrap_or_default(),
+    ];
+    let mut features = env::vars()
+        .map(|(name, _)| name)
+        .filter(|name| name.starts_with("CARGO_FEATURE_"))
+        .collect::<Vec<String>>();
+    features.sort();
+    key.extend(features);
+    let vars = [
+        "CARGO_CFG_TARGET_FEATURE",
+        "ANDROID_PLATFORM",
+        "IPHONEOS_DEPLOYMENT_TARGET",
+        "TVOS_DEPLOYMENT_TARGET",
+    ];
+    for var in &vars {
+        key.push(format!("{}={}", var, env::var(var).unwrap_or_default()));
+    }
+    for tool in &["CC", "CFLAGS", "AR", "RANLIB"] {
+        key.push(format!("{}={}", tool, target_env(tool).unwrap_or_default()));
+    }
+    // FNV-1a, which unlike the std hasher stays the same across Rust releases
+    let hash = key
+        .join("\n")
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+
+    Some(cache.join(format!("opus-{}-{}-{:016x}", version(), target, hash)))
+}
+
+fn restore_cached(cache: &Path) -> io::Result<Paths> {
+    if !has_library(cache) {
+        return Err(io::Error::new(io::ErrorKind::NotFound, ""));
+    }
+    copy_dir(cache, &search())?;
+    Ok(Paths::default())
+}
+
+// Copy the build into the cache under a temporary name first, so that
+// concurrent builds never see a partial copy.
+fn store_cached(cache: &Path) -> io::Result<()> {
+    let partial = PathBuf::from(format!("{}.tmp-{}", cache.display(), std::process::id()));
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+    copy_dir(&search(), &partial)?;
+    match fs::rename(&partial, cache) {
+        Ok(()) => Ok(()),
+        // another build stored the same libopus first
+        Err(_) if has_library(cache) => fs::remove_dir_all(&partial),
+        Err(err) => Err(err),
+    }
+}
+
+fn probe_system() -> Result<Paths, DynError> {
+    if vendored()
+        || fixed_point()
+        || disable_float_api()
+        || custom_modes()
+        || !neural_features().is_empty()
+        || debug_libopus()
+        || Simd::new()?.configured()
+    {
+        return Err(
+            "the `system` feature forbids building libopus from source, but \
+             `vendored`, `fixed-point`, `disable-float-api`, `custom`, `debug-libopus`, \
+             or one of the SIMD or neural network features requires it"
+                .into(),
+        );
+    }
+    match pkg_config::probe_library("opus") {
+        Ok(library) => Ok(Paths::from(library)),
+        Err(_) if is_target_env_msvc() => probe_vcpkg().ok_or_else(|| {
+            "the `system` feature requires a system libopus, but vcpkg could not find \
+             it; run `vcpkg install opus` or set VCPKG_ROOT to the vcpkg installation"
+                .into()
+        }),
+        Err(_) if is_target_os_macos() => probe_macos().ok_or_else(|| {
+            "the `system` feature requires a system libopus, but neither pkg-config \
+             nor the Homebrew and MacPorts prefixes have it; run `brew install opus` \
+             or `port install libopus`"
+                .into()
+        }),
+        Err(err) => Err(format!(
+            "the `system` feature requires a system libopus, but pkg-config could not \
+             find it; install the libopus development package or set PKG_CONFIG_PATH \
+             to the directory holding opus.pc ({})",
+            err
+        )
+        .into()),
+    }
+}
+
+fn is_target_env_msvc() -> bool {
+    env::var("CARGO_CFG_TARGET_ENV").map_or(false, |v| v == "msvc")
+}
+
+fn is_target_os_windows() -> bool {
+    env::var("CARGO_CFG_TARGET_OS").map_or(false, |v| v == "windows")
+}
+
+fn is_target_env_musl() -> bool {
+    env::var("CARGO_CFG_TARGET_ENV").map_or(false, |v| v == "musl")
+}
+
+// Whether the C runtime is linked statically, which is the default on musl
+// targets and what `-C target-feature=+crt-static` asks for elsewhere.
+fn crt_static() -> bool {
+    env::var("CARGO_CFG_TARGET_FEATURE").map_or(false, |features| {
+        features.split(',').any(|f| f == "crt-static")
+    })
+}
+
+// Look for an opus port installed by vcpkg, which is how libraries are
+// usually managed on Windows. The vcpkg crate emits the link flags.
+fn probe_vcpkg() -> Option<Paths> {
+    if !is_target_env_msvc() {
+        return None;
+    }
+    vcpkg::find_package("opus").ok().map(Paths::from)
+}
+
+fn is_target_os_macos() -> bool {
+    env::var("CARGO_CFG_TARGET_OS").map_or(false, |v| v == "macos")
+}
+
+// Look in the Homebrew and MacPorts prefixes, for macOS hosts where they
+// are installed but pkg-config is not.
+fn probe_macos() -> Option<Paths> {
+    if !is_target_os_macos() {
+        return None;
+    }
+    let prefixes = [
+        "/opt/homebrew/opt/opus",
+        "/opt/homebrew",
+        "/usr/local/opt/opus",
+        "/usr/local",
+        "/opt/local",
+    ];
+    let prefix = prefixes
+        .iter()
+        .map(PathBuf::from)
+        .find(|prefix| prefix.join("include/opus/opus.h").is_file())?;
+    let lib_path = prefix.join("lib");
+    let kind = if env_flag("OPUS_STATIC") || !lib_path.join("libopus.dylib").is_file() {
+        "static"
+    } else {
+        "dylib"
+    };
+    println!("cargo:rustc-link-search=native={}", lib_path.display());
+    println!("cargo:rustc-link-lib={}={}", kind, "opus");
+
+    Some(Paths {
+        include_paths: vec![prefix.join("include").join("opus")],
+        link_paths: vec![lib_path],
+        version: None,
+    })
+}
+
+// Tell opus-sys how the libopus built from source was configured, which the
+// library cannot report itself.
+fn emit_build_info() -> io::Result<()> {
+    let simd = Simd::new()?;
+    let wasm = wasm_toolchain()?.is_some();
+    println!("cargo:rustc-env=OPUS_SYS_VENDORED=1");
+    if simd.intrinsics.unwrap_or(!wasm) {
+        println!("cargo:rustc-env=OPUS_SYS_INTRINSICS=1");
+    }
+    if simd.rtcd && !wasm {
+        println!("cargo:rustc-env=OPUS_SYS_RTCD=1");
+    }
+    let mut presume = Vec::new();
+    if simd.sse4_1 {
+        presume.push("sse4.1");
+    }
+    if simd.avx && version_at_least(1, 5) {
+        presume.push("avx2");
+    } else if simd.avx {
+        presume.push("avx");
+    }
+    if simd.neon {
+        presume.push("neon");
+    }
+    println!("cargo:rustc-env=OPUS_SYS_PRESUME={}", presume.join(" "));
+    // DRED needs the deep PLC, so configure enables it as well
+    if deep_plc() || dred() {
+        println!("cargo:rustc-env=OPUS_SYS_DEEP_PLC=1");
+    }
+    if osce() {
+        println!("cargo:rustc-env=OPUS_SYS_OSCE=1");
+    }
+    Ok(())
+}
+
+// With `links = "opus"`, these reach the build scripts of the crates depending
+// on opus-sys as DEP_OPUS_*, so that C code compiled by them, such as opusfile
+// or libopusenc, uses the same headers and library instead of another copy.
+fn emit_metadata(paths: &Paths, built: bool) -> Result<(), DynError> {
+    let include = env::join_paths(&paths.include_paths)?;
+    let lib = env::join_paths(&paths.link_paths)?;
+    println!("cargo:include={}", include.to_string_lossy());
+    println!("cargo:lib={}", lib.to_string_lossy());
+    println!("cargo:version={}", paths.version());
+    if built {
+        println!("cargo:root={}", search().display());
+        println!("cargo:static={}", if dynamic() { 0 } else { 1 });
+    }
+    Ok(())
+}
+
+// `opus_ge_1_4` and `opus_ge_1_5` for the code of this crate. The opus crate
+// derives the same from DEP_OPUS_VERSION in its own build script.
+fn emit_version_cfgs(version: &str) {
+    println!("cargo:rustc-check-cfg=cfg(opus_ge_1_4, opus_ge_1_5)");
+    if version_ge(version, 1, 4) {
+        println!("cargo:rustc-cfg=opus_ge_1_4");
+    }
+    if version_ge(version, 1, 5) {
+        println!("cargo:rustc-cfg=opus_ge_1_5");
     }
 }
 
 fn main() -> Result<(), DynError> {
-    let paths = pkg_config::probe_library("opus").map_or_else(
-        |_| {
-            let paths = probe_prebuilt()
-                .or_else(|_| {
-                    fs::create_dir_all(&output()).expect("Failed to create build directory");
-                    fetch().unwrap();
-                    build()
-                })
-                .expect("Unable to build libopus from source");
-
-            let lib_path = search().join("lib");
-            println!("cargo:rustc-link-search=native={}", lib_path.display());
-            println!("cargo:rustc-link-lib={}={}", "static", "opus");
-
-            paths
-        },
-        Paths::from,
-    );
+    let neural = neural_features();
+    if !neural.is_empty() && !version_at_least(1, 5) {
+        return Err(format!(
+            "{} need libopus 1.5 or later, but OPUS_VERSION selects {}",
+            neural.join(", "),
+            version()
+        )
+        .into());
+    }
 
-    let include_paths = paths
+    // explicitly given artifacts are used as they are; otherwise a system
+    // library is most likely a default floating point build, so build from
+    // source whenever a particular configuration is requested, or the
+    // vendored build is asked for to keep the build reproducible
+    let system = if let Some(paths) = probe_env() {
+        Some(paths)
+    } else if system_only() {
+        Some(probe_system()?)
+    } else if vendored()
+        || fixed_point()
+        || disable_float_api()
+        || custom_modes()
+        || !neural_features().is_empty()
+        || debug_libopus()
+        || Simd::new()?.configured()
+    {
+        None
+    } else if crt_static() && !is_target_env_msvc() {
+        // system libraries are shared and built against another C runtime,
+        // so a fully static binary needs its own libopus
+        None
+    } else {
+        pkg_config::probe_library("opus")
+            .ok()
+            .map(Paths::from)
+            .or_else(probe_vcpkg)
+            .or_else(probe_macos)
+    };
+    let built = system.is_none();
+    let paths = system.unwrap_or_else(|| {
+        let fetch_and_build = || {
+            fs::create_dir_all(&output()).expect("Failed to create build directory");
+            fetch()?;
+            build()
+        };
+        let paths = match local_source() {
+            Some(dir) => copy_source(&dir).and_then(|_| build()),
+            None => probe_prebuilt().or_else(|_| match build_cache() {
+                Some(cache) => restore_cached(&cache).or_else(|_| {
+                    let paths = fetch_and_build()?;
+                    if let Err(err) = store_cached(&cache) {
+                        println!("cargo:warning=Failed to cache the libopus build: {}", err);
+                    }
+                    Ok(paths)
+                }),
+                None => fetch_and_build(),
+            }),
+        }
+        .unwrap_or_else(|err| panic!("Unable to build libopus from source: {}", err));
+
+        let lib_path = search().join("lib");
+        println!("cargo:rustc-link-search=native={}", lib_path.display());
+        let kind = if dynamic() { "dylib" } else { "static" };
+        println!("cargo:rustc-link-lib={}={}", kind, "opus");
+
+        paths
+    });
+    if built {
+        emit_build_info()?;
+    }
+    emit_metadata(&paths, built)?;
+    emit_version_cfgs(&paths.version());
+
+    generate_bindings(&paths)?;
+    generate_ctl()
+}
+
+// The enabled modules of opus-sys besides the one for opus.h: the name, which
+// is also the name of its header without the `opus_` prefix, and the prefix
+// of its types.
+fn binding_modules() -> Vec<(&'static str, &'static str)> {
+    let mut modules = Vec::new();
+    if feature("MULTISTREAM") {
+        modules.push(("multistream", "OpusMS"));
+    }
+    if feature("PROJECTION") {
+        modules.push(("projection", "OpusProjection"));
+    }
+    if custom_modes() {
+        modules.push(("custom", "OpusCustom"));
+    }
+    modules
+}
+
+// Generate opus.rs from opus.h, and a file for each other header with only
+// its own items, which use the types of opus.rs.
+#[cfg(feature = "buildtime-bindgen")]
+fn generate_bindings(paths: &Paths) -> Result<(), DynError> {
+    let mut clang_args = paths
         .include_paths
         .iter()
         .map(|x| format!("-I{}", x.display()))
         .collect::<Vec<String>>();
+    if let Some(wasm) = wasm_toolchain()? {
+        clang_args.push(format!("--target={}", wasm.target));
+        if let Some(sysroot) = wasm.sysroot {
+            clang_args.push(format!("--sysroot={}", sysroot.display()));
+        }
+    }
 
-    let wrapper_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("wrapper.h");
-    let wrapper_path = wrapper_path.to_str().unwrap();
-    let mut wrapper = File::create(wrapper_path).unwrap();
-    writeln!(wrapper, "#include <opus.h>")?;
+    let builder = || {
+        bindgen::Builder::default()
+            .default_enum_style(bindgen::EnumVariation::Rust {
+                non_exhaustive: false,
+            })
+            .default_macro_constant_type(bindgen::MacroTypeVariation::Signed)
+            .generate_comments(false)
+            .use_core()
+            .ctypes_prefix("::core::ffi")
+            .clang_args(&clang_args)
+    };
 
-    let bindings = bindgen::Builder::default()
-        .header(wrapper_path)
-        .default_enum_style(bindgen::EnumVariation::Rust {
-            non_exhaustive: false,
-        })
-        .default_macro_constant_type(bindgen::MacroTypeVariation::Signed)
+    let opus = builder()
+        .header_contents("opus_wrapper.h", "#include <opus.h>")
         .allowlist_function("^opus_.*")
         .allowlist_type("^opus_.*")
         .allowlist_type("^OPUS_.*")
         .allowlist_type("^Opus.*")
         .allowlist_var("^OPUS_.*")
-        .use_core()
-        .clang_args(include_paths)
         .generate()
         .expect("Unable to generate bindings");
-
-    // Write the bindings to the $OUT_DIR/bindings.rs file.
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-    bindings
-        .write_to_file(out_path.join("bindings.rs"))
+    opus.write_to_file(output().join("opus.rs"))
         .expect("Couldn't write bindings!");
 
+    for (module, types) in binding_modules() {
+        let bindings = builder()
+            .header_contents(
+                &format!("{}_wrapper.h", module),
+                &format!("#include <opus_{}.h>", module),
+            )
+            .allowlist_function(format!("^opus_{}_.*", module))
+            // opus_multistream_packet_pad and unpad are declared by opus.h
+            .blocklist_function("^opus_multistream_packet_.*")
+            .allowlist_type(format!("^{}.*", types))
+            .allowlist_var(format!("^OPUS_{}_.*", module.to_uppercase()))
+            // the integer types come from opus.rs
+            .blocklist_type("^opus_.*")
+            .generate()
+            .expect("Unable to generate bindings");
+        bindings
+            .write_to_file(output().join(format!("{}.rs", module)))
+            .expect("Couldn't write bindings!");
+    }
+
+    Ok(())
+}
+
+// Use the bindings pregenerated for each minor release of the linked libopus,
+// which spares the build from needing libclang.
+#[cfg(not(feature = "buildtime-bindgen"))]
+fn generate_bindings(paths: &Paths) -> Result<(), DynError> {
+    let version = paths.version();
+    let name = if version_ge(&version, 1, 5) {
+        "opus_1_5.rs"
+    } else if version_ge(&version, 1, 4) {
+        "opus_1_4.rs"
+    } else {
+        // older releases, as some distributions still ship, lack a few of
+        // these functions, which only fail to link if they are used
+        if !version_ge(&version, 1, 3) {
+            println!(
+                "cargo:warning=No bindings are pregenerated for libopus {}, using those \
+                 of 1.3; enable the `buildtime-bindgen` feature to generate them",
+                version
+            );
+        }
+        "opus_1_3.rs"
+    };
+    let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR")?).join("bindings");
+    fs::copy(dir.join(name), output().join("opus.rs"))?;
+    for (module, _) in binding_modules() {
+        let name = format!("{}.rs", module);
+        fs::copy(dir.join(&name), output().join(&name))?;
+    }
+
+    Ok(())
+}
+
+// The groups of allowed CTL values turned into enums, by the prefix of their
+// constants.
+const CTL_VALUES: &[(&str, &str, &str)] = &[
+    (
+        "Application",
+        "OPUS_APPLICATION_",
+        "The applications of `OPUS_SET_APPLICATION_REQUEST`.",
+    ),
+    (
+        "Signal",
+        "OPUS_SIGNAL_",
+        "The signal types of `OPUS_SET_SIGNAL_REQUEST`.",
+    ),
+    (
+        "Bandwidth",
+        "OPUS_BANDWIDTH_",
+        "The bandwidths of `OPUS_SET_BANDWIDTH_REQUEST` and `OPUS_SET_MAX_BANDWIDTH_REQUEST`.",
+    ),
+    (
+        "FrameSize",
+        "OPUS_FRAMESIZE_",
+        "The frame durations of `OPUS_SET_EXPERT_FRAME_DURATION_REQUEST`.",
+    ),
+];
+
+// Generate ctl.rs from the constants of the bindings: a `CtlRequest` enum of
+// the CTL requests and an enum for each group of their allowed values, so
+// that they match the linked release and the enabled modules.
+fn generate_ctl() -> Result<(), DynError> {
+    let mut constants = Vec::new();
+    let files = ["opus"]
+        .iter()
+        .cloned()
+        .chain(binding_modules().into_iter().map(|(module, _)| module));
+    for file in files {
+        let bindings = fs::read_to_string(output().join(format!("{}.rs", file)))?;
+        for line in bindings.lines() {
+            let rest = match line.trim().strip_prefix("pub const ") {
+                Some(rest) => rest,
+                None => continue,
+            };
+            let mut parts = rest.trim_end_matches(';').splitn(2, ": i32 = ");
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                if let Ok(value) = value.trim().parse::<i32>() {
+                    constants.push((name.to_string(), value));
+                }
+            }
+        }
+    }
+
+    let mut requests = Vec::new();
+    for (name, value) in &constants {
+        if name.ends_with("_REQUEST") || name == "OPUS_RESET_STATE" {
+            let variant = camel_case(
+                name.trim_start_matches("OPUS_")
+                    .trim_end_matches("_REQUEST"),
+            );
+            requests.push((variant, name.as_str(), *value));
+        }
+    }
+    let mut code = String::new();
+    push_enum(
+        &mut code,
+        "CtlRequest",
+        "The requests of the `opus_*_ctl` functions.",
+        &requests,
+    );
+    for (enum_name, prefix, doc) in CTL_VALUES {
+        let variants = constants
+            .iter()
+            .filter(|(name, _)| name.starts_with(prefix))
+            .map(|(name, value)| {
+                let rest = &name[prefix.len()..];
+                // OPUS_FRAMESIZE_2_5_MS and the like
+                let variant = match rest.strip_suffix("_MS") {
+                    Some(ms) => format!("Ms{}", ms),
+                    None => camel_case(rest),
+                };
+                (variant, name.as_str(), *value)
+            })
+            .collect::<Vec<_>>();
+        push_enum(&mut code, enum_name, doc, &variants);
+    }
+    fs::write(output().join("ctl.rs"), code)?;
+
     Ok(())
 }
+
+// Append a `#[repr(i32)]` enum, with `from_raw` to convert the constants back.
+fn push_enum(code: &mut String, name: &str, doc: &str, variants: &[(String, &str, i32)]) {
+    code.push_str(&format!(
+        "/// {}\n#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n#[repr(i32)]\npub enum {} {{\n",
+        doc, name
+    ));
+    for (variant, constant, value) in variants {
+        code.push_str(&format!(
+            "    /// `{}`\n    {} = {},\n",
+            constant, variant, value
+        ));
+    }
+    code.push_str(&format!(
+        "}}\n\nimpl {} {{\n    /// The variant of the given value, if any.\n    \
+         pub fn from_raw(value: i32) -> Option<Self> {{\n        match value {{\n",
+        name
+    ));
+    for (variant, _, value) in variants {
+        code.push_str(&format!(
+            "            {} => Some({}::{}),\n",
+            value, name, variant
+        ));
+    }
+    code.push_str("            _ => None,\n        }\n    }\n}\n\n");
+}
+
+// "SET_BITRATE" to "SetBitrate"
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_string() + &chars.as_str().to_lowercase(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
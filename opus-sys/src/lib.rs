@@ -2,8 +2,74 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(clippy::op_ref)]
+#![cfg_attr(not(test), no_std)]
 
-include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+/// The codec API of `opus.h`.
+pub mod opus {
+    include!(concat!(env!("OUT_DIR"), "/opus.rs"));
+}
+
+/// The multistream API of `opus_multistream.h`.
+#[cfg(feature = "multistream")]
+pub mod multistream {
+    use super::opus::*;
+    include!(concat!(env!("OUT_DIR"), "/multistream.rs"));
+}
+
+/// The ambisonics projection API of `opus_projection.h`.
+#[cfg(feature = "projection")]
+pub mod projection {
+    use super::opus::*;
+    include!(concat!(env!("OUT_DIR"), "/projection.rs"));
+}
+
+/// The custom modes API of `opus_custom.h`.
+#[cfg(feature = "custom")]
+pub mod custom {
+    use super::opus::*;
+    include!(concat!(env!("OUT_DIR"), "/custom.rs"));
+}
+
+/// The CTL requests and their allowed values as enums, generated from the
+/// constants of the modules above.
+pub mod ctl {
+    include!(concat!(env!("OUT_DIR"), "/ctl.rs"));
+}
+
+pub use ctl::*;
+#[cfg(feature = "custom")]
+pub use custom::*;
+#[cfg(feature = "multistream")]
+pub use multistream::*;
+pub use opus::*;
+#[cfg(feature = "projection")]
+pub use projection::*;
+
+/// Whether libopus was built from source by this crate, so that the
+/// `OPUS_SYS_*` constants below describe it. They are all unset for a
+/// library found on the system.
+pub const OPUS_SYS_VENDORED: bool = option_env!("OPUS_SYS_VENDORED").is_some();
+
+/// Whether the libopus built from source has its SIMD intrinsics.
+pub const OPUS_SYS_INTRINSICS: bool = option_env!("OPUS_SYS_INTRINSICS").is_some();
+
+/// Whether the libopus built from source detects the CPU features at run time.
+pub const OPUS_SYS_RTCD: bool = option_env!("OPUS_SYS_RTCD").is_some();
+
+/// The instruction sets the libopus built from source was told to presume,
+/// separated by spaces, beyond those of the target's baseline.
+pub const OPUS_SYS_PRESUME: &str = match option_env!("OPUS_SYS_PRESUME") {
+    Some(presume) => presume,
+    None => "",
+};
+
+/// Whether the libopus built from source has the deep PLC of libopus 1.5,
+/// on its own or as a part of DRED.
+pub const OPUS_SYS_DEEP_PLC: bool = option_env!("OPUS_SYS_DEEP_PLC").is_some();
+
+/// Whether the libopus built from source has the LACE and NoLACE speech
+/// enhancement (OSCE) of libopus 1.5.
+pub const OPUS_SYS_OSCE: bool = option_env!("OPUS_SYS_OSCE").is_some();
 
 #[cfg(test)]
 mod tests {
@@ -11,7 +77,47 @@ mod tests {
 
     #[test]
     fn test_version() {
-        let cstr = unsafe { std::ffi::CStr::from_ptr(opus_get_version_string()) };
-        assert_eq!(cstr.to_str(), Ok("libopus 1.3.1"));
+        let cstr = unsafe { core::ffi::CStr::from_ptr(opus_get_version_string()) };
+        // the version depends on the system library or the selected release
+        assert!(cstr.to_str().unwrap().starts_with("libopus 1."));
+    }
+
+    #[test]
+    fn test_ctl_request() {
+        let request = CtlRequest::SetBitrate as i32;
+        assert_eq!(request, OPUS_SET_BITRATE_REQUEST);
+        assert_eq!(CtlRequest::from_raw(request), Some(CtlRequest::SetBitrate));
+        assert_eq!(
+            FrameSize::from_raw(OPUS_FRAMESIZE_2_5_MS),
+            Some(FrameSize::Ms2_5)
+        );
+        assert_eq!(Bandwidth::from_raw(OPUS_AUTO), None);
+    }
+
+    // With `-C target-feature=+crt-static`, as on musl, libopus must be linked
+    // into an executable without a dynamic loader (no PT_INTERP header).
+    #[test]
+    #[cfg(all(target_os = "linux", target_feature = "crt-static"))]
+    fn test_static_all() {
+        let exe = std::fs::read("/proc/self/exe").unwrap();
+        assert_eq!(&exe[..4], b"\x7fELF");
+        let read = |offset: usize, len: usize| {
+            let bytes = &exe[offset..offset + len];
+            let fold = |acc: u64, b: &u8| (acc << 8) | u64::from(*b);
+            if exe[5] == 1 {
+                bytes.iter().rev().fold(0, fold) as usize
+            } else {
+                bytes.iter().fold(0, fold) as usize
+            }
+        };
+        let (phoff, phentsize, phnum) = if exe[4] == 2 {
+            (read(0x20, 8), read(0x36, 2), read(0x38, 2))
+        } else {
+            (read(0x1c, 4), read(0x2a, 2), read(0x2c, 2))
+        };
+        const PT_INTERP: usize = 3;
+        let interp = (0..phnum).any(|i| read(phoff + i * phentsize, 4) == PT_INTERP);
+        assert!(!interp, "the test executable is dynamically linked");
+        assert!(!unsafe { opus_get_version_string() }.is_null());
     }
 }
@@ -0,0 +1,118 @@
+//! Bitrate ladders for adaptive-bitrate streaming.
+//!
+//! A streaming server encodes its input once per rung of a ladder of
+//! bitrates, and each client is sent the packets of the rung its connection
+//! can carry. As every rung's encoder is fed the same frames, the packets of
+//! all rungs cover the same span of time, so a client can switch rungs
+//! between any two packets.
+
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use super::chunk::{FrameChunker, Sample, MAX_PACKET};
+use super::{Bitrate, Channels, Encoder, EncoderOptions, Error, Result};
+
+/// Encoders of the same input at several bitrates, with aligned frames.
+#[derive(Debug)]
+pub struct Ladder<T> {
+    encoders: Vec<Encoder>,
+    bitrates: Vec<i32>,
+    chunker: FrameChunker<T>,
+    buffer: Vec<u8>,
+}
+
+impl<T: Sample> Ladder<T> {
+    /// Create a ladder with a rung for each bitrate, in bits per second,
+    /// whose encoders are built from `options` with the bitrate replaced.
+    ///
+    /// Frames have the duration of `options`, or 20 ms if it has none.
+    pub fn new(
+        options: &EncoderOptions,
+        sample_rate: u32,
+        channels: Channels,
+        bitrates: &[i32],
+    ) -> Result<Ladder<T>> {
+        if bitrates.is_empty() {
+            return Err(Error::bad_arg("Ladder::new"));
+        }
+        let mut encoders = Vec::with_capacity(bitrates.len());
+        for &bitrate in bitrates {
+            let mut encoder = options.build(sample_rate, channels)?;
+            encoder.set_bitrate(Bitrate::Bits(bitrate))?;
+            encoders.push(encoder);
+        }
+        let frame_size = encoders[0].frame_size()?;
+        Ok(Ladder {
+            encoders,
+            bitrates: bitrates.to_vec(),
+            chunker: FrameChunker::new(frame_size, channels),
+            buffer: vec![0; MAX_PACKET],
+        })
+    }
+
+    /// Get the bitrate of each rung.
+    pub fn bitrates(&self) -> &[i32] {
+        &self.bitrates
+    }
+
+    /// Get the number of rungs.
+    pub fn rungs(&self) -> usize {
+        self.encoders.len()
+    }
+
+    /// Get the encoder of a rung.
+    pub fn encoder(&mut self, rung: usize) -> Option<&mut Encoder> {
+        self.encoders.get_mut(rung)
+    }
+
+    /// Get the number of interleaved samples waiting for a complete frame.
+    pub fn pending(&self) -> usize {
+        self.chunker.pending()
+    }
+
+    /// Push interleaved samples, returning for each frame completed the
+    /// packet of every rung, in the order of `bitrates`.
+    pub fn push(&mut self, pcm: &[T]) -> Result<Vec<Vec<Vec<u8>>>> {
+        let mut frames = Vec::new();
+        let encoders = &mut self.encoders;
+        let buffer = &mut self.buffer;
+        self.chunker
+            .push(pcm, |frame| encode(encoders, buffer, frame, &mut frames))?;
+        Ok(frames)
+    }
+
+    /// Pad any buffered samples with silence to a complete frame and return
+    /// its packets, or `None` if no samples are buffered.
+    pub fn flush(&mut self) -> Result<Option<Vec<Vec<u8>>>> {
+        let mut frames = Vec::new();
+        let encoders = &mut self.encoders;
+        let buffer = &mut self.buffer;
+        self.chunker
+            .flush(|frame| encode(encoders, buffer, frame, &mut frames))?;
+        Ok(frames.pop())
+    }
+
+    /// Reset every rung's encoder and discard any buffered samples.
+    pub fn reset(&mut self) -> Result<()> {
+        self.chunker.clear();
+        for encoder in &mut self.encoders {
+            encoder.reset_state()?;
+        }
+        Ok(())
+    }
+}
+
+fn encode<T: Sample>(
+    encoders: &mut [Encoder],
+    buffer: &mut [u8],
+    frame: &[T],
+    frames: &mut Vec<Vec<Vec<u8>>>,
+) -> Result<()> {
+    let mut packets = Vec::with_capacity(encoders.len());
+    for encoder in encoders {
+        let len = T::encode(encoder, frame, buffer)?;
+        packets.push(buffer[..len].to_vec());
+    }
+    frames.push(packets);
+    Ok(())
+}
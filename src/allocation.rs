@@ -0,0 +1,95 @@
+//! Dividing the bitrate of a multistream encoder between its streams.
+
+use std::ptr;
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use libc::c_int;
+
+use super::{ffi, Bitrate, Error, Result, StreamLayout};
+use super::{OPUS_AUTO, OPUS_BITRATE_MAX, OPUS_MULTISTREAM_GET_ENCODER_STATE, OPUS_SET_BITRATE};
+
+// The weight of a channel, a coupled pair, and an LFE channel, after the
+// ratios libopus uses for surround: coupling saves a quarter of the bits of
+// two channels, and an LFE needs an eighth of those of one.
+const MONO_WEIGHT: i64 = 8;
+const COUPLED_WEIGHT: i64 = 12;
+const LFE_WEIGHT: i64 = 1;
+
+// The least bitrate libopus accepts.
+const MIN_BITRATE: i64 = 500;
+
+impl StreamLayout {
+    /// Divide a total bitrate between the streams, weighted by the
+    /// importance of their channels.
+    ///
+    /// A coupled stream gets one and a half times the bitrate of a mono
+    /// stream, and the stream of `lfe_stream`, if given, an eighth, with no
+    /// stream getting less than the 500 bit/s minimum of libopus.
+    pub fn weighted_bitrates(&self, total: i32, lfe_stream: Option<u8>) -> Vec<Bitrate> {
+        let weights: Vec<i64> = (0..self.streams)
+            .map(|s| {
+                if Some(s) == lfe_stream {
+                    LFE_WEIGHT
+                } else if s < self.coupled_streams {
+                    COUPLED_WEIGHT
+                } else {
+                    MONO_WEIGHT
+                }
+            })
+            .collect();
+        let sum: i64 = weights.iter().sum::<i64>().max(1);
+        weights
+            .iter()
+            .map(|w| Bitrate::Bits((total as i64 * w / sum).max(MIN_BITRATE) as i32))
+            .collect()
+    }
+}
+
+/// Find the stream of the LFE channel of a surround layout, the last
+/// channel of the 5.1, 6.1, and 7.1 layouts of mapping family 1.
+pub(crate) fn lfe_stream(layout: &StreamLayout, mapping_family: u8) -> Option<u8> {
+    if mapping_family != 1 || layout.channels < 6 {
+        return None;
+    }
+    let coupled = layout.coupled_streams;
+    match *layout.mapping.last()? {
+        255 => None,
+        m if m < 2 * coupled => Some(m / 2),
+        m => Some(m - coupled),
+    }
+}
+
+/// Get the encoder of stream `s` of a multistream encoder.
+pub(crate) fn stream_encoder(ptr: *mut ffi::OpusMSEncoder, s: u8) -> Result<*mut ffi::OpusEncoder> {
+    let mut state: *mut ffi::OpusEncoder = ptr::null_mut();
+    let code = unsafe {
+        ffi::opus_multistream_encoder_ctl(
+            ptr,
+            OPUS_MULTISTREAM_GET_ENCODER_STATE,
+            s as c_int,
+            &mut state,
+        )
+    };
+    if code < 0 || state.is_null() {
+        return Err(Error::from_code(
+            "opus_multistream_encoder_ctl(OPUS_MULTISTREAM_GET_ENCODER_STATE)",
+            code.min(ffi::OPUS_INTERNAL_ERROR),
+        ));
+    }
+    Ok(state)
+}
+
+/// Set the bitrate of each stream's encoder.
+pub(crate) fn set_bitrates(ptr: *mut ffi::OpusMSEncoder, bitrates: &[Bitrate]) -> Result<()> {
+    for (s, &bitrate) in bitrates.iter().enumerate() {
+        let value = match bitrate {
+            Bitrate::Auto => OPUS_AUTO,
+            Bitrate::Max => OPUS_BITRATE_MAX,
+            Bitrate::Bits(b) => b,
+        };
+        let state = stream_encoder(ptr, s as u8)?;
+        ffi!(opus_encoder_ctl, state, OPUS_SET_BITRATE, value);
+    }
+    Ok(())
+}
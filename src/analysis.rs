@@ -0,0 +1,227 @@
+//! Objective quality metrics for testing lossy round trips.
+//!
+//! Audio which went through a lossy codec can't be compared sample for
+//! sample with the original, so tests assert instead that it is close enough
+//! by a measure of quality. `segmental_snr` compares the waveforms frame by
+//! frame, and `log_spectral_distance` their spectra, which also suits the
+//! parametric modes of Opus which do not preserve the waveform. Decoded
+//! audio lags the input by the codec delay, which `delay` finds and `measure`
+//! compensates for.
+
+use std::f64::consts::PI;
+
+/// The range per-frame SNRs are clamped to by `segmental_snr`, in dB, so
+/// that silent and perfect frames don't dominate the average.
+pub const SNR_RANGE: (f64, f64) = (-10.0, 35.0);
+
+/// The dynamic range of the spectra compared by `log_spectral_distance`, in
+/// dB below the loudest bin of each reference frame.
+pub const SPECTRAL_RANGE: f64 = 50.0;
+
+// Frames quieter than -70 dBFS in the reference are skipped.
+const SILENCE: f64 = 1e-7;
+
+/// The quality of decoded audio against the original, as found by
+/// `measure`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    /// How many samples per channel the decoded audio lags the original.
+    pub delay: usize,
+    /// The segmental SNR in dB; higher is better.
+    pub segmental_snr: f64,
+    /// The mean log-spectral distance in dB; lower is better.
+    pub spectral_distance: f64,
+}
+
+/// Compare decoded audio with the original it was encoded from, both with
+/// `channels` interleaved at `sample_rate`.
+///
+/// The codec delay is found by searching up to 20 ms, and both metrics use
+/// frames of 20 ms.
+pub fn measure(reference: &[i16], decoded: &[i16], channels: usize, sample_rate: u32) -> Metrics {
+    let frame = sample_rate as usize / 50;
+    let delay = delay(reference, decoded, channels, frame);
+    let decoded = &decoded[(delay * channels).min(decoded.len())..];
+    Metrics {
+        delay,
+        segmental_snr: segmental_snr(reference, decoded, channels, frame),
+        spectral_distance: log_spectral_distance(reference, decoded, channels, frame),
+    }
+}
+
+/// Find how many samples per channel, up to `max_delay`, `decoded` lags
+/// `reference`, by the peak of their normalized cross-correlation.
+pub fn delay(reference: &[i16], decoded: &[i16], channels: usize, max_delay: usize) -> usize {
+    let reference = downmix(reference, channels);
+    let decoded = downmix(decoded, channels);
+    let mut best = (0, f64::MIN);
+    for lag in 0..=max_delay.min(decoded.len()) {
+        let len = reference.len().min(decoded.len() - lag);
+        let (mut dot, mut energy) = (0.0, 0.0);
+        for (&r, &d) in reference[..len].iter().zip(&decoded[lag..lag + len]) {
+            dot += r * d;
+            energy += d * d;
+        }
+        if energy > 0.0 {
+            let score = dot / energy.sqrt();
+            if score > best.1 {
+                best = (lag, score);
+            }
+        }
+    }
+    best.0
+}
+
+/// Compute the segmental SNR of `decoded` against `reference` in dB: the
+/// mean SNR of frames of `frame` samples per channel, each clamped to
+/// `SNR_RANGE`.
+///
+/// Frames which are silent in the reference are skipped, and only the
+/// length both signals share is compared. Returns the upper end of
+/// `SNR_RANGE` if there is nothing to compare.
+pub fn segmental_snr(reference: &[i16], decoded: &[i16], channels: usize, frame: usize) -> f64 {
+    let len = frame * channels;
+    let (mut total, mut frames) = (0.0, 0);
+    for (r, d) in reference.chunks_exact(len).zip(decoded.chunks_exact(len)) {
+        let (mut signal, mut noise) = (0.0, 0.0);
+        for (&r, &d) in r.iter().zip(d) {
+            let (r, d) = (normalize(r), normalize(d));
+            signal += r * r;
+            noise += (r - d) * (r - d);
+        }
+        if signal / (len as f64) < SILENCE {
+            continue;
+        }
+        let snr = if noise > 0.0 {
+            10.0 * (signal / noise).log10()
+        } else {
+            SNR_RANGE.1
+        };
+        total += snr.clamp(SNR_RANGE.0, SNR_RANGE.1);
+        frames += 1;
+    }
+    if frames == 0 {
+        SNR_RANGE.1
+    } else {
+        total / frames as f64
+    }
+}
+
+/// Compute the mean log-spectral distance of `decoded` from `reference` in
+/// dB: the RMS difference of their power spectra in dB over Hann-windowed
+/// frames of `frame` samples per channel.
+///
+/// Both spectra are floored at `SPECTRAL_RANGE` below the loudest bin of
+/// the reference frame. Frames which are silent in the reference are
+/// skipped, and 0 is returned if there is nothing to compare.
+pub fn log_spectral_distance(
+    reference: &[i16],
+    decoded: &[i16],
+    channels: usize,
+    frame: usize,
+) -> f64 {
+    let size = frame.next_power_of_two();
+    let window: Vec<f64> = (0..frame)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / frame as f64).cos())
+        .collect();
+    let mut ref_power = vec![0.0; size / 2 + 1];
+    let mut dec_power = vec![0.0; size / 2 + 1];
+    let len = frame * channels;
+    let (mut total, mut frames) = (0.0, 0);
+    for (r, d) in reference.chunks_exact(len).zip(decoded.chunks_exact(len)) {
+        for channel in 0..channels {
+            let energy: f64 = r[channel..]
+                .iter()
+                .step_by(channels)
+                .map(|&s| normalize(s) * normalize(s))
+                .sum();
+            if energy / (frame as f64) < SILENCE {
+                continue;
+            }
+            spectrum(r, channel, channels, &window, &mut ref_power);
+            spectrum(d, channel, channels, &window, &mut dec_power);
+            let peak = ref_power.iter().cloned().fold(0.0, f64::max);
+            let floor = peak * 10f64.powf(-SPECTRAL_RANGE / 10.0);
+            let sum: f64 = ref_power
+                .iter()
+                .zip(&dec_power)
+                .map(|(&r, &d)| {
+                    let diff = 10.0 * (r.max(floor) / d.max(floor)).log10();
+                    diff * diff
+                })
+                .sum();
+            total += (sum / ref_power.len() as f64).sqrt();
+            frames += 1;
+        }
+    }
+    if frames == 0 {
+        0.0
+    } else {
+        total / frames as f64
+    }
+}
+
+fn normalize(sample: i16) -> f64 {
+    sample as f64 / 32768.0
+}
+
+fn downmix(pcm: &[i16], channels: usize) -> Vec<f64> {
+    pcm.chunks_exact(channels)
+        .map(|frame| frame.iter().map(|&s| normalize(s)).sum::<f64>() / channels as f64)
+        .collect()
+}
+
+// The power spectrum of one channel of an interleaved frame, windowed and
+// zero-padded to the length of a power of two.
+fn spectrum(pcm: &[i16], channel: usize, channels: usize, window: &[f64], power: &mut [f64]) {
+    let size = (power.len() - 1) * 2;
+    let mut re = vec![0.0; size];
+    let mut im = vec![0.0; size];
+    for (i, (&s, w)) in pcm[channel..]
+        .iter()
+        .step_by(channels)
+        .zip(window)
+        .enumerate()
+    {
+        re[i] = normalize(s) * w;
+    }
+    fft(&mut re, &mut im);
+    for (k, p) in power.iter_mut().enumerate() {
+        *p = re[k] * re[k] + im[k] * im[k];
+    }
+}
+
+// An in-place iterative radix-2 FFT.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (wr, wi) = ((angle * k as f64).cos(), (angle * k as f64).sin());
+                let (a, b) = (start + k, start + k + len / 2);
+                let tr = re[b] * wr - im[b] * wi;
+                let ti = re[b] * wi + im[b] * wr;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
@@ -0,0 +1,76 @@
+//! Encoding many independent PCM segments in parallel with `rayon`.
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::{Channels, EncoderOptions, Error, Result};
+
+/// Encodes batches of independent segments on the `rayon` thread pool.
+///
+/// Each worker thread builds its own encoder once and reuses it, resetting
+/// its state between segments, so every segment is encoded exactly as if by
+/// a fresh encoder.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchEncoder {
+    options: EncoderOptions,
+    sample_rate: u32,
+    channels: Channels,
+}
+
+impl BatchEncoder {
+    /// Create a batch encoder building its encoders from `options`.
+    pub fn new(options: EncoderOptions, sample_rate: u32, channels: Channels) -> BatchEncoder {
+        BatchEncoder {
+            options,
+            sample_rate,
+            channels,
+        }
+    }
+
+    /// Encode each segment of interleaved PCM into its packets.
+    ///
+    /// Segments are split into frames of the configured duration, or of
+    /// 20 ms if it has none, the last padded with silence. The result holds
+    /// one list of packets per segment, in input order.
+    pub fn encode_batch<I, T>(&self, segments: I) -> Result<Vec<Vec<Vec<u8>>>>
+    where
+        I: IntoParallelIterator<Item = T>,
+        I::Iter: rayon::iter::IndexedParallelIterator,
+        T: AsRef<[i16]>,
+    {
+        segments
+            .into_par_iter()
+            .map_init(
+                || self.options.build(self.sample_rate, self.channels),
+                |encoder, pcm| {
+                    let encoder = match *encoder {
+                        Ok(ref mut encoder) => encoder,
+                        Err(ref err) => {
+                            return Err(Error {
+                                function: err.function,
+                                code: err.code,
+                            })
+                        }
+                    };
+                    encoder.reset_state()?;
+                    encoder.encode_iter(pcm.as_ref()).collect()
+                },
+            )
+            .collect()
+    }
+}
+
+/// Encode each segment of interleaved PCM in parallel with the given
+/// settings. See `BatchEncoder::encode_batch`.
+pub fn encode_batch<I, T>(
+    options: &EncoderOptions,
+    sample_rate: u32,
+    channels: Channels,
+    segments: I,
+) -> Result<Vec<Vec<Vec<u8>>>>
+where
+    I: IntoParallelIterator<Item = T>,
+    I::Iter: rayon::iter::IndexedParallelIterator,
+    T: AsRef<[i16]>,
+{
+    BatchEncoder::new(*options, sample_rate, channels).encode_batch(segments)
+}
@@ -0,0 +1,490 @@
+//! Inspect Ogg Opus files in the manner of `opusinfo`: print the headers, the
+//! granule position of every page, and a breakdown of the packets, and point
+//! out where a file departs from RFC 7845.
+//!
+//! ```text
+//! cargo run --features tools --bin opusinfo -- [-v] file.opus...
+//! ```
+//!
+//! With `-v` every audio packet is listed as well. The exit status is 1 if a
+//! file could not be read or broke the specification.
+
+extern crate opus;
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::process;
+
+use opus::ogg::{OpusHead, OpusTags, PacketAssembler, PageRef};
+use opus::pipeline::CLOCK_RATE;
+use opus::{packet, Bandwidth};
+
+fn main() {
+    let mut verbose = false;
+    let mut files = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "-v" => verbose = true,
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        eprintln!("Usage: opusinfo [-v] <file.opus>...");
+        process::exit(1);
+    }
+
+    let mut failed = false;
+    for (i, file) in files.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("Processing file \"{}\"...", file);
+        match fs::read(file) {
+            Ok(data) => {
+                let mut info = Info::new(verbose);
+                info.scan(&data);
+                failed |= info.violations > 0;
+            }
+            Err(err) => {
+                eprintln!("opusinfo: {}: {}", file, err);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// The state of one file being inspected.
+struct Info {
+    verbose: bool,
+    streams: Vec<Stream>,
+    violations: usize,
+}
+
+/// A logical stream of the file.
+struct Stream {
+    serial: u32,
+    opus: bool,
+    head: Option<OpusHead>,
+    headers: usize,
+    assembler: PacketAssembler,
+    next_sequence: u32,
+    pages: u64,
+    eos: bool,
+    // granule position of the last page completing an audio packet
+    granule: Option<u64>,
+    packets: u64,
+    bytes: u64,
+    min_size: usize,
+    max_size: usize,
+    samples: u64,
+    // (mode, bandwidth, frame size in 0.1 ms) to packet count
+    breakdown: BTreeMap<(&'static str, &'static str, u32), u64>,
+}
+
+impl Stream {
+    fn new(serial: u32) -> Stream {
+        Stream {
+            serial,
+            opus: true,
+            head: None,
+            headers: 0,
+            assembler: PacketAssembler::new(),
+            next_sequence: 0,
+            pages: 0,
+            eos: false,
+            granule: None,
+            packets: 0,
+            bytes: 0,
+            min_size: usize::MAX,
+            max_size: 0,
+            samples: 0,
+            breakdown: BTreeMap::new(),
+        }
+    }
+}
+
+impl Info {
+    fn new(verbose: bool) -> Info {
+        Info {
+            verbose,
+            streams: Vec::new(),
+            violations: 0,
+        }
+    }
+
+    fn violation(&mut self, message: String) {
+        println!("WARNING: {}", message);
+        self.violations += 1;
+    }
+
+    fn scan(&mut self, data: &[u8]) {
+        let mut offset = 0;
+        while offset < data.len() {
+            match PageRef::parse(&data[offset..]) {
+                Ok(Some((page, len))) => {
+                    self.page(&page);
+                    offset += len;
+                }
+                Ok(None) => {
+                    self.violation(format!("truncated page at offset {}", offset));
+                    break;
+                }
+                Err(err) => {
+                    self.violation(format!("{} at offset {}", err, offset));
+                    // resynchronize on the next capture pattern
+                    match data[offset + 1..].windows(4).position(|w| w == b"OggS") {
+                        Some(skip) => offset += 1 + skip,
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        for i in 0..self.streams.len() {
+            if !self.streams[i].opus {
+                continue;
+            }
+            if !self.streams[i].eos {
+                let serial = self.streams[i].serial;
+                self.violation(format!("stream {:08x} ended without an EOS page", serial));
+            }
+            summarize(&self.streams[i]);
+        }
+        if self.streams.is_empty() {
+            self.violation("no Ogg pages found".to_string());
+        }
+    }
+
+    fn page(&mut self, page: &PageRef) {
+        let index = match self.streams.iter().position(|s| s.serial == page.serial) {
+            Some(index) => {
+                if page.is_bos() {
+                    self.violation(format!(
+                        "stream {:08x}: BOS flag on page {}",
+                        page.serial, page.sequence
+                    ));
+                }
+                index
+            }
+            None => {
+                if !page.is_bos() {
+                    self.violation(format!(
+                        "stream {:08x} starts without a BOS page",
+                        page.serial
+                    ));
+                }
+                println!("New logical stream {:08x} found", page.serial);
+                self.streams.push(Stream::new(page.serial));
+                self.streams.len() - 1
+            }
+        };
+        let mut stream = std::mem::replace(&mut self.streams[index], Stream::new(page.serial));
+        self.stream_page(&mut stream, page);
+        self.streams[index] = stream;
+    }
+
+    fn stream_page(&mut self, stream: &mut Stream, page: &PageRef) {
+        if stream.pages > 0 && page.sequence != stream.next_sequence {
+            self.violation(format!(
+                "stream {:08x}: page sequence jumps from {} to {}, pages were lost",
+                stream.serial, stream.next_sequence, page.sequence
+            ));
+        }
+        if stream.eos {
+            self.violation(format!(
+                "stream {:08x}: page {} after the EOS page",
+                stream.serial, page.sequence
+            ));
+        }
+        stream.next_sequence = page.sequence.wrapping_add(1);
+        stream.pages += 1;
+        stream.eos |= page.is_eos();
+
+        let mut packets: Vec<Cow<[u8]>> = Vec::new();
+        stream
+            .assembler
+            .push_ref(page, |packet| packets.push(packet));
+        if !stream.opus {
+            return;
+        }
+        println!(
+            "Page {} of stream {:08x}: granule {}, {} packets, {} bytes{}{}{}",
+            page.sequence,
+            page.serial,
+            match page.granule_position {
+                Some(granule) => granule.to_string(),
+                None => "-1".to_string(),
+            },
+            packets.len(),
+            page.body.len(),
+            if page.is_bos() { ", BOS" } else { "" },
+            if page.is_continued() {
+                ", continued"
+            } else {
+                ""
+            },
+            if page.is_eos() { ", EOS" } else { "" },
+        );
+
+        let mut samples = 0;
+        let mut audio = 0;
+        for packet in &packets {
+            match stream.headers {
+                0 => {
+                    stream.headers += 1;
+                    if !packet.starts_with(b"OpusHead") {
+                        println!("Stream {:08x} is not Opus, skipped", stream.serial);
+                        stream.opus = false;
+                        return;
+                    }
+                    self.head(stream, page, packets.len(), packet);
+                }
+                1 => {
+                    stream.headers += 1;
+                    self.tags(stream, page, packet);
+                }
+                _ => {
+                    if let Some(duration) = self.audio(stream, packet) {
+                        samples += duration;
+                    }
+                    audio += 1;
+                }
+            }
+        }
+        if audio > 0 && audio < packets.len() {
+            self.violation(format!(
+                "stream {:08x}: audio data on page {} with the OpusTags header",
+                stream.serial, page.sequence
+            ));
+        }
+        if audio > 0 {
+            self.granule(stream, page, samples);
+        } else if packets.is_empty() && page.granule_position.is_some() {
+            self.violation(format!(
+                "stream {:08x}: page {} completes no packet but has granule position {}",
+                stream.serial,
+                page.sequence,
+                page.granule_position.unwrap()
+            ));
+        }
+    }
+
+    fn head(&mut self, stream: &mut Stream, page: &PageRef, packets: usize, packet: &[u8]) {
+        if packets != 1 || page.lacing.last() == Some(&255) {
+            self.violation(format!(
+                "stream {:08x}: OpusHead is not alone on the first page",
+                stream.serial
+            ));
+        }
+        if page.granule_position != Some(0) {
+            self.violation(format!(
+                "stream {:08x}: OpusHead page has a non-zero granule position",
+                stream.serial
+            ));
+        }
+        match OpusHead::parse(packet) {
+            Ok(head) => {
+                println!("Opus stream {:08x}:", stream.serial);
+                println!("\tEncapsulation version: {}", head.version);
+                println!("\tChannels: {}", head.channels);
+                println!("\tPre-skip: {}", head.pre_skip);
+                println!("\tOriginal sample rate: {} Hz", head.input_sample_rate);
+                if head.output_gain != 0 {
+                    println!("\tOutput gain: {:.2} dB", head.output_gain as f64 / 256.0);
+                }
+                println!(
+                    "\tChannel mapping family: {}, {} streams, {} coupled",
+                    head.mapping_family, head.stream_count, head.coupled_count
+                );
+                if !head.mapping.is_empty() {
+                    println!("\tChannel mapping: {:?}", head.mapping);
+                }
+                stream.head = Some(head);
+            }
+            Err(err) => self.violation(format!("stream {:08x}: {}", stream.serial, err)),
+        }
+    }
+
+    fn tags(&mut self, stream: &mut Stream, page: &PageRef, packet: &[u8]) {
+        if page.granule_position.unwrap_or(0) != 0 {
+            self.violation(format!(
+                "stream {:08x}: OpusTags page has a non-zero granule position",
+                stream.serial
+            ));
+        }
+        match OpusTags::parse(packet) {
+            Ok(tags) => {
+                println!("\tVendor: {}", tags.vendor);
+                if !tags.comments.is_empty() {
+                    println!("\tUser comments section follows...");
+                    for (field, value) in &tags.comments {
+                        println!("\t\t{}={}", field, value);
+                    }
+                }
+            }
+            Err(err) => self.violation(format!("stream {:08x}: {}", stream.serial, err)),
+        }
+    }
+
+    // The duration of an audio packet, or `None` if it is invalid.
+    fn audio(&mut self, stream: &mut Stream, data: &[u8]) -> Option<u64> {
+        let index = stream.packets;
+        stream.packets += 1;
+        stream.bytes += data.len() as u64;
+        stream.min_size = stream.min_size.min(data.len());
+        stream.max_size = stream.max_size.max(data.len());
+        if data.is_empty() {
+            self.violation(format!(
+                "stream {:08x}: audio packet {} is empty",
+                stream.serial, index
+            ));
+            return None;
+        }
+
+        // multistream packets can't be split without their mapping, but
+        // all streams share the duration of the first
+        let single = match stream.head {
+            Some(ref head) => head.stream_count == 1,
+            None => true,
+        };
+        let checked = if single {
+            packet::parse(data).map(|_| ())
+        } else {
+            Ok(())
+        };
+        let duration = match checked.and_then(|_| packet::get_nb_samples(data, CLOCK_RATE)) {
+            Ok(duration) => duration as u64,
+            Err(err) => {
+                self.violation(format!(
+                    "stream {:08x}: audio packet {} is invalid: {}",
+                    stream.serial, index, err
+                ));
+                return None;
+            }
+        };
+
+        let (mode, bandwidth, frame) = describe(data);
+        *stream
+            .breakdown
+            .entry((mode, bandwidth, frame))
+            .or_insert(0) += 1;
+        if self.verbose {
+            println!(
+                "\tpacket {}: {} bytes, {} {}, {} frames of {} ms{}",
+                index,
+                data.len(),
+                mode,
+                bandwidth,
+                packet::get_nb_frames(data).unwrap_or(0),
+                frame as f64 / 10.0,
+                if data[0] & 0x04 != 0 { ", stereo" } else { "" },
+            );
+        }
+        stream.samples += duration;
+        Some(duration)
+    }
+
+    fn granule(&mut self, stream: &mut Stream, page: &PageRef, samples: u64) {
+        let granule = match page.granule_position {
+            Some(granule) => granule,
+            None => {
+                self.violation(format!(
+                    "stream {:08x}: page {} completes audio packets but has no granule position",
+                    stream.serial, page.sequence
+                ));
+                return;
+            }
+        };
+        match stream.granule {
+            None if granule < samples && !page.is_eos() => self.violation(format!(
+                "stream {:08x}: first audio page granule position {} is less than its {} samples",
+                stream.serial, granule, samples
+            )),
+            Some(previous) if granule < previous => self.violation(format!(
+                "stream {:08x}: granule position decreases from {} to {} on page {}",
+                stream.serial, previous, granule, page.sequence
+            )),
+            Some(previous) if granule > previous + samples => self.violation(format!(
+                "stream {:08x}: granule position {} on page {} is past the {} samples decoded",
+                stream.serial,
+                granule,
+                page.sequence,
+                previous + samples
+            )),
+            Some(previous) if granule < previous + samples && !page.is_eos() => {
+                self.violation(format!(
+                    "stream {:08x}: page {} trims samples but is not the last page",
+                    stream.serial, page.sequence
+                ))
+            }
+            _ => {}
+        }
+        stream.granule = Some(granule);
+    }
+}
+
+// The mode, bandwidth, and frame size in 0.1 ms of a packet's TOC byte.
+fn describe(data: &[u8]) -> (&'static str, &'static str, u32) {
+    let config = data[0] >> 3;
+    let mode = match config {
+        0..=11 => "SILK",
+        12..=15 => "Hybrid",
+        _ => "CELT",
+    };
+    let bandwidth = match packet::get_bandwidth(data) {
+        Ok(Bandwidth::Narrowband) => "NB",
+        Ok(Bandwidth::Mediumband) => "MB",
+        Ok(Bandwidth::Wideband) => "WB",
+        Ok(Bandwidth::Superwideband) => "SWB",
+        Ok(Bandwidth::Fullband) => "FB",
+        _ => "?",
+    };
+    let samples = packet::get_samples_per_frame(data, CLOCK_RATE).unwrap_or(0);
+    (mode, bandwidth, samples as u32 * 10 / 48)
+}
+
+fn summarize(stream: &Stream) {
+    println!("Opus stream {:08x} summary:", stream.serial);
+    println!("\tPages: {}", stream.pages);
+    if stream.packets == 0 {
+        println!("\tNo audio packets");
+        return;
+    }
+    let pre_skip = stream.head.as_ref().map_or(0, |head| head.pre_skip as u64);
+    let played = stream
+        .granule
+        .unwrap_or(stream.samples)
+        .saturating_sub(pre_skip);
+    let seconds = played as f64 / CLOCK_RATE as f64;
+    println!(
+        "\tPlayback length: {}m:{:06.3}s",
+        (seconds / 60.0) as u64,
+        seconds % 60.0
+    );
+    if seconds > 0.0 {
+        println!(
+            "\tAverage bitrate: {:.2} kb/s",
+            stream.bytes as f64 * 8.0 / seconds / 1000.0
+        );
+    }
+    println!(
+        "\tPackets: {}, {} to {} bytes, {} total",
+        stream.packets, stream.min_size, stream.max_size, stream.bytes
+    );
+    println!("\tPacket breakdown:");
+    for (&(mode, bandwidth, frame), count) in &stream.breakdown {
+        println!(
+            "\t\t{} {} {} ms: {} ({:.1}%)",
+            mode,
+            bandwidth,
+            frame as f64 / 10.0,
+            count,
+            *count as f64 * 100.0 / stream.packets as f64
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! Recording from a `cpal` input device straight to Opus packets.
+//!
+//! The device's native sample format, rate, and channel layout are converted
+//! on the audio thread: samples are mapped to the requested channel count,
+//! linearly resampled to 48kHz, and encoded as soon as a frame is complete.
+
+use std::io;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{Device, SampleFormat, Stream, StreamConfig};
+
+use super::pipeline::{BoxedProcessor, PacketInfo, PushEncoder};
+use super::{Channels, EncoderOptions};
+
+/// Sample rate the captured audio is encoded at.
+const ENCODE_RATE: u32 = 48000;
+
+type SharedError = Arc<Mutex<Option<io::Error>>>;
+
+/// The receiving end of a capture's packet channel.
+pub type PacketReceiver = Receiver<(Vec<u8>, PacketInfo)>;
+
+/// A running capture from an input device.
+///
+/// Capture stops when this is dropped.
+pub struct Capture {
+    stream: Stream,
+    error: SharedError,
+}
+
+impl Capture {
+    /// Start capturing from `device` in its default input configuration,
+    /// calling `on_packet` on the audio thread with each encoded packet.
+    pub fn start<F>(
+        device: &Device,
+        options: &EncoderOptions,
+        channels: Channels,
+        on_packet: F,
+    ) -> io::Result<Capture>
+    where
+        F: FnMut(Vec<u8>, PacketInfo) + Send + 'static,
+    {
+        Capture::start_with(device, options, channels, Vec::new(), on_packet)
+    }
+
+    /// Start capturing from `device` as `start` does, running `processors`
+    /// in order on each 48kHz frame before it is encoded.
+    pub fn start_with<F>(
+        device: &Device,
+        options: &EncoderOptions,
+        channels: Channels,
+        processors: Vec<BoxedProcessor<f32>>,
+        on_packet: F,
+    ) -> io::Result<Capture>
+    where
+        F: FnMut(Vec<u8>, PacketInfo) + Send + 'static,
+    {
+        let supported = device.default_input_config().map_err(io::Error::other)?;
+        let format = supported.sample_format();
+        let config: StreamConfig = supported.config();
+        let mut encoder = PushEncoder::new(options.build(ENCODE_RATE, channels)?)?;
+        for processor in processors {
+            encoder.add_processor(processor);
+        }
+        let state = State {
+            input_channels: config.channels as usize,
+            channels: channels as usize,
+            resampler: Resampler::new(config.sample_rate.0, channels as usize),
+            encoder,
+            mapped: Vec::new(),
+            resampled: Vec::new(),
+            on_packet,
+        };
+        let error = Arc::new(Mutex::new(None));
+        let stream = match format {
+            SampleFormat::F32 => build(device, &config, state, &error, |s: f32| s),
+            SampleFormat::I16 => build(device, &config, state, &error, |s: i16| s as f32 / 32768.0),
+            SampleFormat::U16 => build(device, &config, state, &error, |s: u16| {
+                (s as f32 - 32768.0) / 32768.0
+            }),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "unsupported input sample format",
+                ))
+            }
+        }?;
+        stream.play().map_err(io::Error::other)?;
+        Ok(Capture { stream, error })
+    }
+
+    /// Start capturing from `device`, delivering packets through a channel.
+    pub fn start_channel(
+        device: &Device,
+        options: &EncoderOptions,
+        channels: Channels,
+    ) -> io::Result<(Capture, PacketReceiver)> {
+        let (sender, receiver) = mpsc::channel();
+        let capture = Capture::start(device, options, channels, move |packet, info| {
+            let _ = sender.send((packet, info));
+        })?;
+        Ok((capture, receiver))
+    }
+
+    /// Resume a paused capture.
+    pub fn play(&self) -> io::Result<()> {
+        self.stream.play().map_err(io::Error::other)
+    }
+
+    /// Pause the capture.
+    pub fn pause(&self) -> io::Result<()> {
+        self.stream.pause().map_err(io::Error::other)
+    }
+
+    /// Take the first error reported by the device or the encoder since the
+    /// last call, if any. Audio is dropped until the error is taken.
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.error.lock().ok().and_then(|mut error| error.take())
+    }
+}
+
+fn build<T, C, F>(
+    device: &Device,
+    config: &StreamConfig,
+    mut state: State<F>,
+    error: &SharedError,
+    convert: C,
+) -> io::Result<Stream>
+where
+    T: cpal::SizedSample,
+    C: Fn(T) -> f32 + Send + 'static,
+    F: FnMut(Vec<u8>, PacketInfo) + Send + 'static,
+{
+    let data_error = error.clone();
+    let stream_error = error.clone();
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                let mut error = match data_error.lock() {
+                    Ok(error) => error,
+                    Err(_) => return,
+                };
+                if error.is_none() {
+                    if let Err(err) = state.process(data.iter().map(|&s| convert(s))) {
+                        *error = Some(err);
+                    }
+                }
+            },
+            move |err| {
+                if let Ok(mut error) = stream_error.lock() {
+                    error.get_or_insert(io::Error::other(err));
+                }
+            },
+            None,
+        )
+        .map_err(io::Error::other)
+}
+
+struct State<F> {
+    input_channels: usize,
+    channels: usize,
+    resampler: Resampler,
+    encoder: PushEncoder<f32>,
+    mapped: Vec<f32>,
+    resampled: Vec<f32>,
+    on_packet: F,
+}
+
+impl<F: FnMut(Vec<u8>, PacketInfo)> State<F> {
+    fn process<I: Iterator<Item = f32>>(&mut self, samples: I) -> io::Result<()> {
+        // map the device's channels onto the encoded layout
+        self.mapped.clear();
+        let mut frame = Vec::with_capacity(self.input_channels);
+        for sample in samples {
+            frame.push(sample);
+            if frame.len() < self.input_channels {
+                continue;
+            }
+            if self.channels == 1 {
+                self.mapped
+                    .push(frame.iter().sum::<f32>() / frame.len() as f32);
+            } else {
+                self.mapped.push(frame[0]);
+                self.mapped.push(*frame.get(1).unwrap_or(&frame[0]));
+            }
+            frame.clear();
+        }
+
+        self.resampled.clear();
+        self.resampler.process(&self.mapped, &mut self.resampled);
+        self.encoder.push(&self.resampled)?;
+        while let Some((packet, info)) = self.encoder.poll_packet() {
+            (self.on_packet)(packet, info);
+        }
+        Ok(())
+    }
+}
+
+/// A linear-interpolation resampler to 48kHz, carrying its phase across
+/// calls.
+struct Resampler {
+    step: f64,
+    pos: f64,
+    channels: usize,
+    last: Vec<f32>,
+}
+
+impl Resampler {
+    fn new(rate: u32, channels: usize) -> Resampler {
+        Resampler {
+            step: rate as f64 / ENCODE_RATE as f64,
+            pos: 0.0,
+            channels,
+            last: vec![0.0; channels],
+        }
+    }
+
+    fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let channels = self.channels;
+        let frames = input.len() / channels;
+        if self.step == 1.0 || frames == 0 {
+            output.extend_from_slice(&input[..frames * channels]);
+            return;
+        }
+        // frame 0 is the last frame of the previous call
+        let last = &self.last;
+        let at = |i: usize, c: usize| {
+            if i == 0 {
+                last[c]
+            } else {
+                input[(i - 1) * channels + c]
+            }
+        };
+        let mut pos = self.pos;
+        while pos < frames as f64 {
+            let i = pos as usize;
+            let frac = (pos - i as f64) as f32;
+            for c in 0..channels {
+                output.push(at(i, c) * (1.0 - frac) + at(i + 1, c) * frac);
+            }
+            pos += self.step;
+        }
+        self.pos = pos;
+        self.pos -= frames as f64;
+        self.last
+            .copy_from_slice(&input[(frames - 1) * channels..frames * channels]);
+    }
+}
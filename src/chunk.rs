@@ -0,0 +1,164 @@
+//! Splitting arbitrary lengths of PCM into complete Opus frames.
+
+use std::result;
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use super::{Channels, Encoder, Error, Result};
+
+/// Recommended maximum packet size to allocate, per libopus documentation.
+pub const MAX_PACKET: usize = 4000;
+
+/// A PCM sample type accepted by the encoder.
+pub trait Sample: Copy + Default {
+    /// Encode a single frame of these samples.
+    fn encode(encoder: &mut Encoder, input: &[Self], output: &mut [u8]) -> Result<usize>;
+}
+
+impl Sample for i16 {
+    fn encode(encoder: &mut Encoder, input: &[i16], output: &mut [u8]) -> Result<usize> {
+        encoder.encode(input, output)
+    }
+}
+
+#[cfg(not(feature = "disable-float-api"))]
+impl Sample for f32 {
+    fn encode(encoder: &mut Encoder, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        encoder.encode_float(input, output)
+    }
+}
+
+/// Buffers interleaved PCM and hands it out in complete frames.
+///
+/// Input of any length may be pushed; whole frames are passed to a callback
+/// as soon as they are available and the remainder is kept for the next
+/// push. `flush` pads the final partial frame with silence.
+#[derive(Debug, Clone)]
+pub struct FrameChunker<T> {
+    frame_len: usize,
+    buffer: Vec<T>,
+}
+
+impl<T: Copy + Default> FrameChunker<T> {
+    /// Create a chunker producing frames of `frame_size` samples per channel.
+    pub fn new(frame_size: usize, channels: Channels) -> FrameChunker<T> {
+        let frame_len = frame_size * channels as usize;
+        FrameChunker {
+            frame_len,
+            buffer: Vec::with_capacity(frame_len),
+        }
+    }
+
+    /// Get the length of each frame in interleaved samples.
+    pub fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    /// Get the number of interleaved samples waiting for a complete frame.
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Discard any buffered samples.
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Push interleaved samples, calling `f` with each completed frame.
+    pub fn push<E, F>(&mut self, mut pcm: &[T], mut f: F) -> result::Result<(), E>
+    where
+        F: FnMut(&[T]) -> result::Result<(), E>,
+    {
+        if self.frame_len == 0 {
+            return Ok(());
+        }
+        if !self.buffer.is_empty() {
+            let needed = self.frame_len - self.buffer.len();
+            let take = needed.min(pcm.len());
+            self.buffer.extend_from_slice(&pcm[..take]);
+            pcm = &pcm[take..];
+            if self.buffer.len() < self.frame_len {
+                return Ok(());
+            }
+            let result = f(&self.buffer);
+            self.buffer.clear();
+            result?;
+        }
+        let mut frames = pcm.chunks_exact(self.frame_len);
+        for frame in &mut frames {
+            f(frame)?;
+        }
+        self.buffer.extend_from_slice(frames.remainder());
+        Ok(())
+    }
+
+    /// Pad any buffered samples with silence to a complete frame and pass it
+    /// to `f`. Does nothing if no samples are buffered.
+    pub fn flush<E, F>(&mut self, mut f: F) -> result::Result<(), E>
+    where
+        F: FnMut(&[T]) -> result::Result<(), E>,
+    {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.buffer.resize(self.frame_len, T::default());
+        let result = f(&self.buffer);
+        self.buffer.clear();
+        result
+    }
+}
+
+/// An iterator encoding a PCM buffer frame by frame, returned from
+/// `Encoder::encode_iter`.
+#[derive(Debug)]
+pub struct EncodeIter<'a> {
+    encoder: &'a mut Encoder,
+    pcm: &'a [i16],
+    frame_len: usize,
+    error: Option<Error>,
+    tail: Vec<i16>,
+}
+
+impl<'a> EncodeIter<'a> {
+    pub(crate) fn new(encoder: &'a mut Encoder, pcm: &'a [i16]) -> EncodeIter<'a> {
+        let channels = encoder.channels as usize;
+        let (frame_len, error) = match encoder.frame_size() {
+            Ok(size) => (size * channels, None),
+            Err(err) => (0, Some(err)),
+        };
+        EncodeIter {
+            encoder,
+            pcm,
+            frame_len,
+            error,
+            tail: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Iterator for EncodeIter<'a> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Result<Vec<u8>>> {
+        if let Some(err) = self.error.take() {
+            self.pcm = &[];
+            return Some(Err(err));
+        }
+        let frame_len = self.frame_len;
+        if self.pcm.is_empty() || frame_len == 0 {
+            return None;
+        }
+        let frame = if self.pcm.len() >= frame_len {
+            let (frame, rest) = self.pcm.split_at(frame_len);
+            self.pcm = rest;
+            frame
+        } else {
+            self.tail.clear();
+            self.tail.extend_from_slice(self.pcm);
+            self.tail.resize(frame_len, 0);
+            self.pcm = &[];
+            &self.tail
+        };
+        Some(self.encoder.encode_vec(frame, MAX_PACKET))
+    }
+}
@@ -0,0 +1,328 @@
+//! Conversion between sample formats and between interleaved and planar
+//! layouts.
+//!
+//! On x86-64, SSE2 is used for whole blocks of samples; the tail of each
+//! buffer, and every buffer on other targets, goes through the reference
+//! implementations in `scalar`. Both paths produce identical results.
+//!
+//! Conversions to `i16` scale by 32768, saturate, and round to nearest even,
+//! as libopus does. NaN converts to 32767.
+
+/// A sample type which can be interleaved and deinterleaved.
+pub trait Planar: Copy {
+    /// Interleave two planes of equal length into `out`.
+    fn interleave2(left: &[Self], right: &[Self], out: &mut [Self]);
+
+    /// Split interleaved stereo into two planes.
+    fn deinterleave2(input: &[Self], left: &mut [Self], right: &mut [Self]);
+}
+
+/// Convert `i16` samples to `f32` within [-1, 1).
+///
+/// Converts as many samples as the shorter buffer holds.
+pub fn i16_to_f32(input: &[i16], output: &mut [f32]) {
+    let len = input.len().min(output.len());
+    let done = simd::i16_to_f32(&input[..len], &mut output[..len]);
+    scalar::i16_to_f32(&input[done..len], &mut output[done..len]);
+}
+
+/// Convert `f32` samples to `i16`.
+///
+/// Converts as many samples as the shorter buffer holds.
+pub fn f32_to_i16(input: &[f32], output: &mut [i16]) {
+    let len = input.len().min(output.len());
+    let done = simd::f32_to_i16(&input[..len], &mut output[..len]);
+    scalar::f32_to_i16(&input[done..len], &mut output[done..len]);
+}
+
+/// Interleave equally long planes, one per channel, into `out`.
+///
+/// # Panics
+///
+/// Panics if the planes differ in length or `out` is too short.
+pub fn interleave<T: Planar>(planes: &[&[T]], out: &mut [T]) {
+    let frames = planes.first().map_or(0, |plane| plane.len());
+    assert!(planes.iter().all(|plane| plane.len() == frames));
+    assert!(out.len() >= frames * planes.len());
+    match planes.len() {
+        0 => {}
+        1 => out[..frames].copy_from_slice(planes[0]),
+        2 => T::interleave2(planes[0], planes[1], &mut out[..frames * 2]),
+        _ => scalar::interleave(planes, out),
+    }
+}
+
+/// Split interleaved samples into planes, one per channel.
+///
+/// # Panics
+///
+/// Panics if the planes differ in length or `input` is too short.
+pub fn deinterleave<T: Planar>(input: &[T], planes: &mut [&mut [T]]) {
+    let frames = planes.first().map_or(0, |plane| plane.len());
+    assert!(planes.iter().all(|plane| plane.len() == frames));
+    assert!(input.len() >= frames * planes.len());
+    match *planes {
+        [] => {}
+        [ref mut mono] => mono.copy_from_slice(&input[..frames]),
+        [ref mut left, ref mut right] => T::deinterleave2(&input[..frames * 2], left, right),
+        _ => scalar::deinterleave(input, planes),
+    }
+}
+
+/// Interleave planes into frames of a channel count fixed at compile time.
+///
+/// Converts as many frames as the shortest buffer holds.
+pub fn interleave_frames<T: Copy, const C: usize>(planes: [&[T]; C], out: &mut [[T; C]]) {
+    let frames = planes
+        .iter()
+        .fold(out.len(), |len, plane| len.min(plane.len()));
+    for (i, frame) in out[..frames].iter_mut().enumerate() {
+        for c in 0..C {
+            frame[c] = planes[c][i];
+        }
+    }
+}
+
+/// Split frames of a channel count fixed at compile time into planes.
+///
+/// Converts as many frames as the shortest buffer holds.
+pub fn deinterleave_frames<T: Copy, const C: usize>(input: &[[T; C]], planes: [&mut [T]; C]) {
+    let frames = planes
+        .iter()
+        .fold(input.len(), |len, plane| len.min(plane.len()));
+    for (i, frame) in input[..frames].iter().enumerate() {
+        for c in 0..C {
+            planes[c][i] = frame[c];
+        }
+    }
+}
+
+impl Planar for i16 {
+    fn interleave2(left: &[i16], right: &[i16], out: &mut [i16]) {
+        let done = simd::interleave2_i16(left, right, out);
+        scalar::interleave2(&left[done..], &right[done..], &mut out[done * 2..]);
+    }
+
+    fn deinterleave2(input: &[i16], left: &mut [i16], right: &mut [i16]) {
+        let done = simd::deinterleave2_i16(input, left, right);
+        scalar::deinterleave2(&input[done * 2..], &mut left[done..], &mut right[done..]);
+    }
+}
+
+impl Planar for f32 {
+    fn interleave2(left: &[f32], right: &[f32], out: &mut [f32]) {
+        let done = simd::interleave2_f32(left, right, out);
+        scalar::interleave2(&left[done..], &right[done..], &mut out[done * 2..]);
+    }
+
+    fn deinterleave2(input: &[f32], left: &mut [f32], right: &mut [f32]) {
+        let done = simd::deinterleave2_f32(input, left, right);
+        scalar::deinterleave2(&input[done * 2..], &mut left[done..], &mut right[done..]);
+    }
+}
+
+/// Reference implementations, used on targets without a SIMD path and for
+/// benchmarking against it.
+pub mod scalar {
+    /// Convert `i16` samples to `f32` within [-1, 1).
+    pub fn i16_to_f32(input: &[i16], output: &mut [f32]) {
+        for (out, &sample) in output.iter_mut().zip(input) {
+            *out = sample as f32 * (1.0 / 32768.0);
+        }
+    }
+
+    /// Convert `f32` samples to `i16`.
+    pub fn f32_to_i16(input: &[f32], output: &mut [i16]) {
+        for (out, &sample) in output.iter_mut().zip(input) {
+            let scaled = sample * 32768.0;
+            // SSE's `min` yields its second operand when either is NaN
+            let clamped = if scaled.is_nan() {
+                32767.0
+            } else {
+                scaled.clamp(-32768.0, 32767.0)
+            };
+            // adding and removing 1.5 * 2^23 rounds to even without `std`
+            *out = ((clamped + 12582912.0) - 12582912.0) as i16;
+        }
+    }
+
+    /// Interleave two planes of equal length into `out`.
+    pub fn interleave2<T: Copy>(left: &[T], right: &[T], out: &mut [T]) {
+        for ((frame, &l), &r) in out.chunks_exact_mut(2).zip(left).zip(right) {
+            frame[0] = l;
+            frame[1] = r;
+        }
+    }
+
+    /// Split interleaved stereo into two planes.
+    pub fn deinterleave2<T: Copy>(input: &[T], left: &mut [T], right: &mut [T]) {
+        for ((frame, l), r) in input.chunks_exact(2).zip(left).zip(right) {
+            *l = frame[0];
+            *r = frame[1];
+        }
+    }
+
+    /// Interleave planes of equal length into `out`.
+    pub fn interleave<T: Copy>(planes: &[&[T]], out: &mut [T]) {
+        let channels = planes.len();
+        for (c, plane) in planes.iter().enumerate() {
+            for (i, &sample) in plane.iter().enumerate() {
+                out[i * channels + c] = sample;
+            }
+        }
+    }
+
+    /// Split interleaved samples into planes of equal length.
+    pub fn deinterleave<T: Copy>(input: &[T], planes: &mut [&mut [T]]) {
+        let channels = planes.len();
+        for (c, plane) in planes.iter_mut().enumerate() {
+            for (i, sample) in plane.iter_mut().enumerate() {
+                *sample = input[i * channels + c];
+            }
+        }
+    }
+}
+
+/// SSE2 kernels, each returning the number of samples or frames handled.
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use std::arch::x86_64::*;
+
+    pub fn i16_to_f32(input: &[i16], output: &mut [f32]) -> usize {
+        let blocks = input.len().min(output.len()) / 8;
+        for i in 0..blocks {
+            // SSE2 is part of the x86-64 baseline, and all accesses are in
+            // bounds of whole blocks.
+            unsafe {
+                let v = _mm_loadu_si128(input.as_ptr().add(i * 8) as *const __m128i);
+                let lo = _mm_srai_epi32(_mm_unpacklo_epi16(v, v), 16);
+                let hi = _mm_srai_epi32(_mm_unpackhi_epi16(v, v), 16);
+                let scale = _mm_set1_ps(1.0 / 32768.0);
+                let out = output.as_mut_ptr().add(i * 8);
+                _mm_storeu_ps(out, _mm_mul_ps(_mm_cvtepi32_ps(lo), scale));
+                _mm_storeu_ps(out.add(4), _mm_mul_ps(_mm_cvtepi32_ps(hi), scale));
+            }
+        }
+        blocks * 8
+    }
+
+    pub fn f32_to_i16(input: &[f32], output: &mut [i16]) -> usize {
+        let blocks = input.len().min(output.len()) / 8;
+        for i in 0..blocks {
+            // See `i16_to_f32`.
+            unsafe {
+                let scale = _mm_set1_ps(32768.0);
+                let max = _mm_set1_ps(32767.0);
+                let min = _mm_set1_ps(-32768.0);
+                let src = input.as_ptr().add(i * 8);
+                let a = _mm_max_ps(_mm_min_ps(_mm_mul_ps(_mm_loadu_ps(src), scale), max), min);
+                let b = _mm_max_ps(
+                    _mm_min_ps(_mm_mul_ps(_mm_loadu_ps(src.add(4)), scale), max),
+                    min,
+                );
+                let packed = _mm_packs_epi32(_mm_cvtps_epi32(a), _mm_cvtps_epi32(b));
+                _mm_storeu_si128(output.as_mut_ptr().add(i * 8) as *mut __m128i, packed);
+            }
+        }
+        blocks * 8
+    }
+
+    pub fn interleave2_i16(left: &[i16], right: &[i16], out: &mut [i16]) -> usize {
+        let blocks = left.len().min(right.len()).min(out.len() / 2) / 8;
+        for i in 0..blocks {
+            // See `i16_to_f32`.
+            unsafe {
+                let l = _mm_loadu_si128(left.as_ptr().add(i * 8) as *const __m128i);
+                let r = _mm_loadu_si128(right.as_ptr().add(i * 8) as *const __m128i);
+                let dst = out.as_mut_ptr().add(i * 16) as *mut __m128i;
+                _mm_storeu_si128(dst, _mm_unpacklo_epi16(l, r));
+                _mm_storeu_si128(dst.add(1), _mm_unpackhi_epi16(l, r));
+            }
+        }
+        blocks * 8
+    }
+
+    pub fn deinterleave2_i16(input: &[i16], left: &mut [i16], right: &mut [i16]) -> usize {
+        let blocks = left.len().min(right.len()).min(input.len() / 2) / 8;
+        for i in 0..blocks {
+            // See `i16_to_f32`.
+            unsafe {
+                let src = input.as_ptr().add(i * 16) as *const __m128i;
+                let a = _mm_loadu_si128(src);
+                let b = _mm_loadu_si128(src.add(1));
+                // sign-extend the even and odd lanes, then narrow losslessly
+                let la = _mm_srai_epi32(_mm_slli_epi32(a, 16), 16);
+                let lb = _mm_srai_epi32(_mm_slli_epi32(b, 16), 16);
+                let ra = _mm_srai_epi32(a, 16);
+                let rb = _mm_srai_epi32(b, 16);
+                _mm_storeu_si128(
+                    left.as_mut_ptr().add(i * 8) as *mut __m128i,
+                    _mm_packs_epi32(la, lb),
+                );
+                _mm_storeu_si128(
+                    right.as_mut_ptr().add(i * 8) as *mut __m128i,
+                    _mm_packs_epi32(ra, rb),
+                );
+            }
+        }
+        blocks * 8
+    }
+
+    pub fn interleave2_f32(left: &[f32], right: &[f32], out: &mut [f32]) -> usize {
+        let blocks = left.len().min(right.len()).min(out.len() / 2) / 4;
+        for i in 0..blocks {
+            // See `i16_to_f32`.
+            unsafe {
+                let l = _mm_loadu_ps(left.as_ptr().add(i * 4));
+                let r = _mm_loadu_ps(right.as_ptr().add(i * 4));
+                let dst = out.as_mut_ptr().add(i * 8);
+                _mm_storeu_ps(dst, _mm_unpacklo_ps(l, r));
+                _mm_storeu_ps(dst.add(4), _mm_unpackhi_ps(l, r));
+            }
+        }
+        blocks * 4
+    }
+
+    pub fn deinterleave2_f32(input: &[f32], left: &mut [f32], right: &mut [f32]) -> usize {
+        let blocks = left.len().min(right.len()).min(input.len() / 2) / 4;
+        for i in 0..blocks {
+            // See `i16_to_f32`.
+            unsafe {
+                let src = input.as_ptr().add(i * 8);
+                let a = _mm_loadu_ps(src);
+                let b = _mm_loadu_ps(src.add(4));
+                _mm_storeu_ps(left.as_mut_ptr().add(i * 4), _mm_shuffle_ps(a, b, 0x88));
+                _mm_storeu_ps(right.as_mut_ptr().add(i * 4), _mm_shuffle_ps(a, b, 0xdd));
+            }
+        }
+        blocks * 4
+    }
+}
+
+/// No SIMD path; everything is left to `scalar`.
+#[cfg(not(target_arch = "x86_64"))]
+mod simd {
+    pub fn i16_to_f32(_: &[i16], _: &mut [f32]) -> usize {
+        0
+    }
+
+    pub fn f32_to_i16(_: &[f32], _: &mut [i16]) -> usize {
+        0
+    }
+
+    pub fn interleave2_i16(_: &[i16], _: &[i16], _: &mut [i16]) -> usize {
+        0
+    }
+
+    pub fn deinterleave2_i16(_: &[i16], _: &mut [i16], _: &mut [i16]) -> usize {
+        0
+    }
+
+    pub fn interleave2_f32(_: &[f32], _: &[f32], _: &mut [f32]) -> usize {
+        0
+    }
+
+    pub fn deinterleave2_f32(_: &[f32], _: &mut [f32], _: &mut [f32]) -> usize {
+        0
+    }
+}
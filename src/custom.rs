@@ -0,0 +1,384 @@
+//! CELT-only coding with the custom modes of libopus.
+//!
+//! A custom mode fixes the sample rate and frame size of its coders, allowing
+//! frames shorter than the 2.5 ms of standard Opus and rates other than the
+//! usual five. The streams are not Opus: they can only be decoded by a
+//! `CustomDecoder` created from the same mode. `CustomPreset` collects
+//! configurations for intercoms and other links where latency and memory
+//! matter more than interoperability.
+
+use libc::c_int;
+use std::ptr;
+
+use super::{
+    ffi, len, Channels, Error, Result, OPUS_RESET_STATE, OPUS_SET_BITRATE, OPUS_SET_COMPLEXITY,
+    OPUS_SET_VBR,
+};
+
+macro_rules! custom_enc_ctl {
+	($this:ident, $ctl:ident $(, $rest:expr)*) => {
+		ctl!(opus_custom_encoder_ctl, $this, $ctl, $($rest),*)
+	}
+}
+
+macro_rules! custom_dec_ctl {
+	($this:ident, $ctl:ident $(, $rest:expr)*) => {
+		ctl!(opus_custom_decoder_ctl, $this, $ctl, $($rest),*)
+	}
+}
+
+/// A sample rate and frame size shared by custom encoders and decoders.
+#[derive(Debug)]
+pub struct CustomMode {
+    ptr: *mut ffi::OpusCustomMode,
+    sample_rate: u32,
+    frame_size: usize,
+}
+
+impl CustomMode {
+    /// Create a mode with frames of `frame_size` samples per channel.
+    ///
+    /// The sample rate must be between 8 and 96 kHz, and the frame size even,
+    /// between 40 and 1024, and at least 1 ms long.
+    pub fn new(sample_rate: u32, frame_size: usize) -> Result<CustomMode> {
+        let mut error = 0;
+        let ptr = unsafe {
+            ffi::opus_custom_mode_create(sample_rate as i32, frame_size as c_int, &mut error)
+        };
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            Err(Error::from_code("opus_custom_mode_create", error))
+        } else {
+            Ok(CustomMode {
+                ptr,
+                sample_rate,
+                frame_size,
+            })
+        }
+    }
+
+    /// Get the sample rate of the mode.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Get the number of samples per channel in each frame.
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    /// Get the size in bytes of the state of an encoder in this mode.
+    pub fn encoder_state_size(&self, channels: Channels) -> usize {
+        unsafe { ffi::opus_custom_encoder_get_size(self.ptr, channels as c_int) as usize }
+    }
+
+    /// Get the size in bytes of the state of a decoder in this mode.
+    pub fn decoder_state_size(&self, channels: Channels) -> usize {
+        unsafe { ffi::opus_custom_decoder_get_size(self.ptr, channels as c_int) as usize }
+    }
+}
+
+impl Drop for CustomMode {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_custom_mode_destroy(self.ptr) }
+    }
+}
+
+// A mode is only read after creation, so it may be shared between the coders
+// of several threads.
+unsafe impl Send for CustomMode {}
+unsafe impl Sync for CustomMode {}
+
+/// A CELT encoder in a custom mode.
+#[derive(Debug)]
+pub struct CustomEncoder<'m> {
+    ptr: *mut ffi::OpusCustomEncoder,
+    mode: &'m CustomMode,
+    channels: Channels,
+}
+
+impl<'m> CustomEncoder<'m> {
+    /// Create and initialize an encoder.
+    pub fn new(mode: &'m CustomMode, channels: Channels) -> Result<CustomEncoder<'m>> {
+        let mut error = 0;
+        let ptr =
+            unsafe { ffi::opus_custom_encoder_create(mode.ptr, channels as c_int, &mut error) };
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            Err(Error::from_code("opus_custom_encoder_create", error))
+        } else {
+            Ok(CustomEncoder {
+                ptr,
+                mode,
+                channels,
+            })
+        }
+    }
+
+    /// Get the mode the encoder was created with.
+    pub fn mode(&self) -> &'m CustomMode {
+        self.mode
+    }
+
+    fn check_input(&self, input: usize, what: &'static str) -> Result<()> {
+        if input < self.mode.frame_size * self.channels as usize {
+            return Err(Error::bad_arg(what));
+        }
+        Ok(())
+    }
+
+    /// Encode one frame, which must hold the mode's frame size of
+    /// interleaved samples.
+    pub fn encode(&mut self, input: &[i16], output: &mut [u8]) -> Result<usize> {
+        self.check_input(input.len(), "opus_custom_encode")?;
+        let len = ffi!(
+            opus_custom_encode,
+            self.ptr,
+            input.as_ptr(),
+            self.mode.frame_size as c_int,
+            output.as_mut_ptr(),
+            len(output)
+        );
+        Ok(len as usize)
+    }
+
+    /// Encode one frame from floating point input.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        self.check_input(input.len(), "opus_custom_encode_float")?;
+        let len = ffi!(
+            opus_custom_encode_float,
+            self.ptr,
+            input.as_ptr(),
+            self.mode.frame_size as c_int,
+            output.as_mut_ptr(),
+            len(output)
+        );
+        Ok(len as usize)
+    }
+
+    /// Reset the codec state to be equivalent to a freshly initialized state.
+    pub fn reset_state(&mut self) -> Result<()> {
+        custom_enc_ctl!(self, OPUS_RESET_STATE);
+        Ok(())
+    }
+
+    /// Sets the target bitrate in bits per second.
+    pub fn set_bitrate(&mut self, value: i32) -> Result<()> {
+        custom_enc_ctl!(self, OPUS_SET_BITRATE, value);
+        Ok(())
+    }
+
+    /// Configures the computational complexity, from 0 to 10 inclusive.
+    pub fn set_complexity(&mut self, value: i32) -> Result<()> {
+        custom_enc_ctl!(self, OPUS_SET_COMPLEXITY, value);
+        Ok(())
+    }
+
+    /// Enables or disables variable bitrate.
+    pub fn set_vbr(&mut self, vbr: bool) -> Result<()> {
+        let value: i32 = if vbr { 1 } else { 0 };
+        custom_enc_ctl!(self, OPUS_SET_VBR, value);
+        Ok(())
+    }
+}
+
+impl<'m> Drop for CustomEncoder<'m> {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_custom_encoder_destroy(self.ptr) }
+    }
+}
+
+// See `unsafe impl Send for Encoder`.
+unsafe impl<'m> Send for CustomEncoder<'m> {}
+
+/// A CELT decoder in a custom mode.
+#[derive(Debug)]
+pub struct CustomDecoder<'m> {
+    ptr: *mut ffi::OpusCustomDecoder,
+    mode: &'m CustomMode,
+    channels: Channels,
+}
+
+impl<'m> CustomDecoder<'m> {
+    /// Create and initialize a decoder.
+    pub fn new(mode: &'m CustomMode, channels: Channels) -> Result<CustomDecoder<'m>> {
+        let mut error = 0;
+        let ptr =
+            unsafe { ffi::opus_custom_decoder_create(mode.ptr, channels as c_int, &mut error) };
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            Err(Error::from_code("opus_custom_decoder_create", error))
+        } else {
+            Ok(CustomDecoder {
+                ptr,
+                mode,
+                channels,
+            })
+        }
+    }
+
+    /// Get the mode the decoder was created with.
+    pub fn mode(&self) -> &'m CustomMode {
+        self.mode
+    }
+
+    fn check_output(&self, output: usize, what: &'static str) -> Result<()> {
+        if output < self.mode.frame_size * self.channels as usize {
+            return Err(Error::bad_arg(what));
+        }
+        Ok(())
+    }
+
+    /// Decode one frame, or conceal a lost one if `input` is empty.
+    ///
+    /// `output` must have room for the mode's frame size of interleaved
+    /// samples. Returns the number of samples per channel decoded.
+    pub fn decode(&mut self, input: &[u8], output: &mut [i16]) -> Result<usize> {
+        self.check_output(output.len(), "opus_custom_decode")?;
+        let ptr = match input.len() {
+            0 => ptr::null(),
+            _ => input.as_ptr(),
+        };
+        let len = ffi!(
+            opus_custom_decode,
+            self.ptr,
+            ptr,
+            len(input),
+            output.as_mut_ptr(),
+            self.mode.frame_size as c_int
+        );
+        Ok(len as usize)
+    }
+
+    /// Decode one frame with floating point output.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn decode_float(&mut self, input: &[u8], output: &mut [f32]) -> Result<usize> {
+        self.check_output(output.len(), "opus_custom_decode_float")?;
+        let ptr = match input.len() {
+            0 => ptr::null(),
+            _ => input.as_ptr(),
+        };
+        let len = ffi!(
+            opus_custom_decode_float,
+            self.ptr,
+            ptr,
+            len(input),
+            output.as_mut_ptr(),
+            self.mode.frame_size as c_int
+        );
+        Ok(len as usize)
+    }
+
+    /// Reset the codec state to be equivalent to a freshly initialized state.
+    pub fn reset_state(&mut self) -> Result<()> {
+        custom_dec_ctl!(self, OPUS_RESET_STATE);
+        Ok(())
+    }
+}
+
+impl<'m> Drop for CustomDecoder<'m> {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_custom_decoder_destroy(self.ptr) }
+    }
+}
+
+// See `unsafe impl Send for Encoder`.
+unsafe impl<'m> Send for CustomDecoder<'m> {}
+
+// ============================================================================
+// Presets
+
+/// Custom mode configurations for low latency and small memory.
+///
+/// The state sizes depend on how libopus was built, for example with the
+/// `fixed-point` feature, so `CustomConfig::state_size` reports them for the
+/// running build. Mono states are roughly half the size of stereo ones, and
+/// both shrink with the sample rate and frame size.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum CustomPreset {
+    /// 48 kHz mono with 2.5 ms frames of 120 samples at 64 kbit/s, the
+    /// shortest frame of standard Opus.
+    Intercom,
+    /// 48 kHz mono with 1.33 ms frames of 64 samples at 96 kbit/s, close to
+    /// the shortest frame custom modes allow.
+    UltraLowLatency,
+    /// 16 kHz mono with 2.5 ms frames of 40 samples at 32 kbit/s and the
+    /// lowest complexity, for the smallest states and least CPU on
+    /// microcontrollers.
+    Embedded,
+}
+
+/// The settings of a custom mode and the coders created in it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct CustomConfig {
+    /// The sample rate of the mode.
+    pub sample_rate: u32,
+    /// The number of samples per channel in each frame.
+    pub frame_size: usize,
+    /// The channel count of the coders.
+    pub channels: Channels,
+    /// The target bitrate in bits per second.
+    pub bitrate: i32,
+    /// The computational complexity, from 0 to 10 inclusive.
+    pub complexity: i32,
+    /// Whether variable bitrate is enabled.
+    pub vbr: bool,
+}
+
+impl CustomConfig {
+    /// Get the settings for a preset.
+    pub fn preset(preset: CustomPreset) -> CustomConfig {
+        let intercom = CustomConfig {
+            sample_rate: 48000,
+            frame_size: 120,
+            channels: Channels::Mono,
+            bitrate: 64000,
+            complexity: 5,
+            vbr: false,
+        };
+        match preset {
+            CustomPreset::Intercom => intercom,
+            CustomPreset::UltraLowLatency => CustomConfig {
+                frame_size: 64,
+                bitrate: 96000,
+                ..intercom
+            },
+            CustomPreset::Embedded => CustomConfig {
+                sample_rate: 16000,
+                frame_size: 40,
+                bitrate: 32000,
+                complexity: 0,
+                ..intercom
+            },
+        }
+    }
+
+    /// Get the size in bytes of each encoded frame, with `vbr` disabled.
+    pub fn packet_size(&self) -> usize {
+        self.bitrate as usize * self.frame_size / (8 * self.sample_rate as usize)
+    }
+
+    /// Create the mode shared by the coders.
+    pub fn mode(&self) -> Result<CustomMode> {
+        CustomMode::new(self.sample_rate, self.frame_size)
+    }
+
+    /// Create an encoder in `mode` configured with these settings.
+    pub fn encoder<'m>(&self, mode: &'m CustomMode) -> Result<CustomEncoder<'m>> {
+        let mut encoder = CustomEncoder::new(mode, self.channels)?;
+        encoder.set_bitrate(self.bitrate)?;
+        encoder.set_complexity(self.complexity)?;
+        encoder.set_vbr(self.vbr)?;
+        Ok(encoder)
+    }
+
+    /// Create a decoder in `mode`.
+    pub fn decoder<'m>(&self, mode: &'m CustomMode) -> Result<CustomDecoder<'m>> {
+        CustomDecoder::new(mode, self.channels)
+    }
+
+    /// Get the size in bytes of one encoder state and one decoder state, not
+    /// counting the mode itself.
+    pub fn state_size(&self) -> Result<usize> {
+        let mode = self.mode()?;
+        Ok(mode.encoder_state_size(self.channels) + mode.decoder_state_size(self.channels))
+    }
+}
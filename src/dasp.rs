@@ -0,0 +1,85 @@
+//! Encoding and decoding `dasp` frames directly.
+//!
+//! Any `dasp_frame::Frame` whose channel count matches the coder, such as
+//! `[i16; 2]` or `[f32; 1]`, can be passed instead of interleaved slices.
+//! Samples are converted through `f32`.
+
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use dasp_frame::Frame;
+use dasp_sample::{FromSample, Sample, ToSample};
+
+use super::{Decoder, Encoder, Error, Result};
+
+/// Samples per channel in the longest possible packet at 48kHz.
+const MAX_FRAME: usize = 48000 * 120 / 1000;
+
+impl Encoder {
+    /// Encode an Opus frame from `dasp` frames.
+    pub fn encode_frames<F>(&mut self, input: &[F], output: &mut [u8]) -> Result<usize>
+    where
+        F: Frame,
+        F::Sample: ToSample<f32>,
+    {
+        if F::CHANNELS != self.channels as usize {
+            return Err(Error::bad_arg("Encoder::encode_frames"));
+        }
+        let pcm: Vec<f32> = input
+            .iter()
+            .flat_map(|frame| frame.channels())
+            .map(|sample| sample.to_sample::<f32>())
+            .collect();
+        self.encode_float(&pcm, output)
+    }
+
+    /// Encode an Opus frame from `dasp` frames to a new buffer.
+    pub fn encode_vec_frames<F>(&mut self, input: &[F], max_size: usize) -> Result<Vec<u8>>
+    where
+        F: Frame,
+        F::Sample: ToSample<f32>,
+    {
+        let mut output = vec![0; max_size];
+        let len = self.encode_frames(input, &mut output)?;
+        output.truncate(len);
+        Ok(output)
+    }
+}
+
+impl Decoder {
+    /// Decode an Opus packet into `dasp` frames, returning the number of
+    /// frames written.
+    ///
+    /// Unlike `decode`, this allocates a scratch buffer for the conversion.
+    pub fn decode_frames<F>(&mut self, input: &[u8], output: &mut [F], fec: bool) -> Result<usize>
+    where
+        F: Frame,
+        F::Sample: FromSample<f32>,
+    {
+        let channels = self.channels as usize;
+        if F::CHANNELS != channels {
+            return Err(Error::bad_arg("Decoder::decode_frames"));
+        }
+        let mut pcm = vec![0.0; output.len().min(MAX_FRAME) * channels];
+        let len = self.decode_float(input, &mut pcm, fec)?;
+        for (frame, samples) in output
+            .iter_mut()
+            .zip(pcm[..len * channels].chunks(channels))
+        {
+            *frame = F::from_fn(|c| F::Sample::from_sample(samples[c]));
+        }
+        Ok(len)
+    }
+
+    /// Decode an Opus packet into a new buffer of `dasp` frames.
+    pub fn decode_vec_frames<F>(&mut self, input: &[u8], fec: bool) -> Result<Vec<F>>
+    where
+        F: Frame,
+        F::Sample: FromSample<f32>,
+    {
+        let mut output = vec![F::EQUILIBRIUM; MAX_FRAME];
+        let len = self.decode_frames(input, &mut output, fec)?;
+        output.truncate(len);
+        Ok(output)
+    }
+}
@@ -0,0 +1,162 @@
+//! Recovering lost audio from the Deep REDundancy (DRED) of libopus 1.5.
+//!
+//! An encoder given `Encoder::set_dred_duration` adds to each packet a
+//! compressed description of the audio before it, up to about a second. When
+//! packets are lost, a `DredDecoder` parses that data out of the next packet
+//! received into a `Dred`, from which `Decoder::decode_dred` synthesizes the
+//! missing audio. A `JitterBuffer` with `enable_dred` does this by itself for
+//! the lost packets in-band FEC cannot recover.
+//!
+//! Both sides need a libopus built with DRED, as the `dred` feature does;
+//! otherwise creating a `DredDecoder` fails with `ErrorCode::Unimplemented`.
+
+use libc::c_int;
+
+use super::{ffi, len, Decoder, Error, Result, Storage};
+
+/// Parses the DRED data of packets with the neural network of libopus.
+///
+/// One decoder can parse the packets of any number of streams.
+#[derive(Debug)]
+pub struct DredDecoder {
+    ptr: *mut ffi::OpusDREDDecoder,
+}
+
+impl DredDecoder {
+    /// Create and initialize a DRED decoder.
+    pub fn new() -> Result<DredDecoder> {
+        let mut error = 0;
+        let ptr = unsafe { ffi::opus_dred_decoder_create(&mut error) };
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            Err(Error::from_code("opus_dred_decoder_create", error))
+        } else {
+            Ok(DredDecoder { ptr })
+        }
+    }
+
+    /// Parse the DRED data of a packet into `dred`, keeping up to
+    /// `max_samples` of the audio before the packet at `sample_rate`.
+    ///
+    /// Returns the number of samples per channel before the packet that can
+    /// be recovered, which is 0 if the packet carries no DRED data.
+    pub fn parse(
+        &mut self,
+        dred: &mut Dred,
+        packet: &[u8],
+        max_samples: usize,
+        sample_rate: u32,
+    ) -> Result<usize> {
+        let mut end: c_int = 0;
+        dred.available = 0;
+        dred.end = 0;
+        let available = ffi!(
+            opus_dred_parse,
+            self.ptr,
+            dred.ptr,
+            packet.as_ptr(),
+            len(packet),
+            max_samples as i32,
+            sample_rate as i32,
+            &mut end,
+            0
+        );
+        dred.available = available as usize;
+        dred.end = end as usize;
+        Ok(dred.available)
+    }
+}
+
+impl Drop for DredDecoder {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_dred_decoder_destroy(self.ptr) }
+    }
+}
+
+// The states are only touched through `&mut self`, so they may move between
+// threads.
+unsafe impl Send for DredDecoder {}
+
+/// The DRED data parsed out of a packet by a `DredDecoder`.
+#[derive(Debug)]
+pub struct Dred {
+    ptr: *mut ffi::OpusDRED,
+    available: usize,
+    end: usize,
+}
+
+impl Dred {
+    /// Allocate the state for parsed DRED data.
+    pub fn new() -> Result<Dred> {
+        let mut error = 0;
+        let ptr = unsafe { ffi::opus_dred_alloc(&mut error) };
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            Err(Error::from_code("opus_dred_alloc", error))
+        } else {
+            Ok(Dred {
+                ptr,
+                available: 0,
+                end: 0,
+            })
+        }
+    }
+
+    /// Get the number of samples per channel before the last parsed packet
+    /// that can be recovered.
+    pub fn available(&self) -> usize {
+        self.available
+    }
+
+    /// Get the number of samples per channel of silence at the oldest end of
+    /// the recoverable audio, which the encoder had no audio for.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+}
+
+impl Drop for Dred {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_dred_free(self.ptr) }
+    }
+}
+
+unsafe impl Send for Dred {}
+
+impl<S: Storage> Decoder<S> {
+    /// Decode lost audio from the DRED data of a later packet.
+    ///
+    /// `offset` is how many samples per channel before the start of that
+    /// packet the audio to decode begins, at most `dred.available()`; the
+    /// length of `output` sets how much is decoded. Returns the number of
+    /// samples per channel decoded.
+    pub fn decode_dred(&mut self, dred: &Dred, offset: usize, output: &mut [i16]) -> Result<usize> {
+        let len = ffi!(
+            opus_decoder_dred_decode,
+            self.ptr,
+            dred.ptr,
+            offset as i32,
+            output.as_mut_ptr(),
+            len(output) / self.channels as c_int
+        );
+        Ok(len as usize)
+    }
+
+    /// Decode lost audio from the DRED data of a later packet with floating
+    /// point output.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn decode_dred_float(
+        &mut self,
+        dred: &Dred,
+        offset: usize,
+        output: &mut [f32],
+    ) -> Result<usize> {
+        let len = ffi!(
+            opus_decoder_dred_decode_float,
+            self.ptr,
+            dred.ptr,
+            offset as i32,
+            output.as_mut_ptr(),
+            len(output) / self.channels as c_int
+        );
+        Ok(len as usize)
+    }
+}
@@ -0,0 +1,167 @@
+//! Receive-side handling of discontinuous transmission.
+//!
+//! A sender using DTX stops emitting packets during silence, leaving gaps in
+//! the timestamp sequence. The `DtxFiller` tracks where the next packet is
+//! expected and fills any gap with decoder concealment, which libopus renders
+//! as comfort noise after a DTX packet, so the playout clock never starves.
+
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use super::time::{Timestamp48k, CLOCK_RATE};
+use super::{Decoder, Result};
+
+/// Longest concealment chunk passed to libopus, in 48kHz samples.
+const CHUNK: u32 = CLOCK_RATE / 50;
+
+/// Smallest frame libopus can conceal, in 48kHz samples.
+const QUANTUM: u32 = CLOCK_RATE / 400;
+
+/// Fills timestamp gaps left by DTX with comfort noise.
+#[derive(Debug, Clone)]
+pub struct DtxFiller {
+    next_timestamp: Option<u32>,
+    max_gap: u32,
+    dtx: bool,
+    concealed: u64,
+    scratch: Vec<i16>,
+}
+
+impl Default for DtxFiller {
+    fn default() -> DtxFiller {
+        DtxFiller::new()
+    }
+}
+
+impl DtxFiller {
+    /// Create a filler which fills gaps of up to ten seconds.
+    pub fn new() -> DtxFiller {
+        DtxFiller {
+            next_timestamp: None,
+            max_gap: 10 * CLOCK_RATE,
+            dtx: false,
+            concealed: 0,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Set the longest gap, in 48kHz samples, which will be filled. Larger
+    /// jumps in the timestamp are treated as a stream restart.
+    pub fn set_max_gap(&mut self, max_gap: u32) {
+        self.max_gap = max_gap;
+    }
+
+    /// Determine whether the last packet received was a DTX packet.
+    pub fn in_dtx(&self) -> bool {
+        self.dtx
+    }
+
+    /// Get the 48kHz timestamp at which the next packet is expected.
+    pub fn next_timestamp(&self) -> Option<u32> {
+        self.next_timestamp
+    }
+
+    /// Get the total number of 48kHz samples synthesized so far.
+    pub fn concealed(&self) -> u64 {
+        self.concealed
+    }
+
+    /// Forget the expected timestamp, as after a seek or stream restart.
+    pub fn reset(&mut self) {
+        self.next_timestamp = None;
+        self.dtx = false;
+    }
+
+    /// Decode a packet with the given 48kHz timestamp, appending interleaved
+    /// samples to `output`.
+    ///
+    /// Any gap between the previous packet and this one is filled first.
+    /// Audio overlapping time that was already concealed by `tick` is
+    /// discarded. Returns the number of samples per channel appended.
+    pub fn decode(
+        &mut self,
+        decoder: &mut Decoder,
+        timestamp: u32,
+        packet: &[u8],
+        output: &mut Vec<i16>,
+    ) -> Result<usize> {
+        let channels = decoder.channels as usize;
+        let rate = decoder.get_sample_rate()?;
+        let start = output.len();
+
+        let mut overlap = 0;
+        if let Some(expected) = self.next_timestamp {
+            let gap = timestamp.wrapping_sub(expected) as i32;
+            if gap > 0 && gap as u32 <= self.max_gap {
+                self.conceal(decoder, gap as u32, rate, output)?;
+            } else if gap < 0 && gap.unsigned_abs() <= self.max_gap {
+                overlap = to_rate(gap.unsigned_abs(), rate);
+            }
+        }
+
+        let frame = 120 * rate as usize / 1000;
+        self.scratch.resize(frame * channels, 0);
+        let len = decoder.decode(packet, &mut self.scratch, false)?;
+        let skip = overlap.min(len);
+        output.extend_from_slice(&self.scratch[skip * channels..len * channels]);
+
+        let duration = Timestamp48k::from_samples(len as u64, rate).samples() as u32;
+        self.next_timestamp = Some(timestamp.wrapping_add(duration));
+        self.dtx = packet.len() <= 2;
+        Ok((output.len() - start) / channels)
+    }
+
+    /// Produce concealment for a playout slot in which no packet arrived,
+    /// filling all of `output` and advancing the expected timestamp.
+    ///
+    /// The output length must be a multiple of 2.5 ms at the decoder's
+    /// sample rate. Returns the number of samples per channel written.
+    pub fn tick(&mut self, decoder: &mut Decoder, output: &mut [i16]) -> Result<usize> {
+        let channels = decoder.channels as usize;
+        let rate = decoder.get_sample_rate()?;
+        let mut written = 0;
+        while written < output.len() {
+            let remaining = (output.len() - written) / channels;
+            let chunk = remaining.min(to_rate(CHUNK, rate));
+            let len =
+                decoder.decode(&[], &mut output[written..written + chunk * channels], false)?;
+            if len == 0 {
+                break;
+            }
+            written += len * channels;
+        }
+        let samples = written / channels;
+        let duration = Timestamp48k::from_samples(samples as u64, rate).samples() as u32;
+        self.concealed += duration as u64;
+        if let Some(expected) = self.next_timestamp {
+            self.next_timestamp = Some(expected.wrapping_add(duration));
+        }
+        Ok(samples)
+    }
+
+    fn conceal(
+        &mut self,
+        decoder: &mut Decoder,
+        gap: u32,
+        rate: u32,
+        output: &mut Vec<i16>,
+    ) -> Result<()> {
+        let channels = decoder.channels as usize;
+        let mut remaining = gap - gap % QUANTUM;
+        while remaining > 0 {
+            let chunk = remaining.min(CHUNK);
+            let start = output.len();
+            output.resize(start + to_rate(chunk, rate) * channels, 0);
+            let len = decoder.decode(&[], &mut output[start..], false)?;
+            output.truncate(start + len * channels);
+            self.concealed += chunk as u64;
+            remaining -= chunk;
+        }
+        Ok(())
+    }
+}
+
+/// Convert a duration in 48kHz samples to samples at `rate`.
+fn to_rate(samples: u32, rate: u32) -> usize {
+    Timestamp48k(samples as u64).to_samples(rate) as usize
+}
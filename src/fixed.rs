@@ -0,0 +1,136 @@
+//! Coders whose channel count is part of their type.
+//!
+//! Audio is passed as slices of frames such as `[i16; 2]`, so a buffer
+//! holding a partial frame cannot be expressed and the channel count never
+//! needs checking at run time.
+
+use std::slice;
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use super::{Application, Channels, Decoder, Encoder, Result};
+
+/// Get the channel layout for a channel count, rejecting anything other than
+/// mono or stereo at compile time.
+const fn channels<const C: usize>() -> Channels {
+    match C {
+        1 => Channels::Mono,
+        2 => Channels::Stereo,
+        _ => panic!("only mono and stereo are supported"),
+    }
+}
+
+fn flatten<T, const C: usize>(frames: &[[T; C]]) -> &[T] {
+    // `[T; C]` has the size of `C` consecutive `T`s and no padding.
+    unsafe { slice::from_raw_parts(frames.as_ptr() as *const T, frames.len() * C) }
+}
+
+fn flatten_mut<T, const C: usize>(frames: &mut [[T; C]]) -> &mut [T] {
+    // See `flatten`.
+    unsafe { slice::from_raw_parts_mut(frames.as_mut_ptr() as *mut T, frames.len() * C) }
+}
+
+/// An encoder for exactly `C` channels.
+#[derive(Debug)]
+pub struct FixedEncoder<const C: usize> {
+    encoder: Encoder,
+}
+
+/// An encoder for mono audio.
+pub type MonoEncoder = FixedEncoder<1>;
+
+/// An encoder for interleaved stereo audio.
+pub type StereoEncoder = FixedEncoder<2>;
+
+impl<const C: usize> FixedEncoder<C> {
+    const CHANNELS: Channels = channels::<C>();
+
+    /// Create and initialize an encoder.
+    pub fn new(sample_rate: u32, mode: Application) -> Result<FixedEncoder<C>> {
+        Ok(FixedEncoder {
+            encoder: Encoder::new(sample_rate, Self::CHANNELS, mode)?,
+        })
+    }
+
+    /// Encode an Opus frame.
+    pub fn encode(&mut self, input: &[[i16; C]], output: &mut [u8]) -> Result<usize> {
+        self.encoder.encode(flatten(input), output)
+    }
+
+    /// Encode an Opus frame from floating point input.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_float(&mut self, input: &[[f32; C]], output: &mut [u8]) -> Result<usize> {
+        self.encoder.encode_float(flatten(input), output)
+    }
+
+    /// Encode an Opus frame to a new buffer.
+    pub fn encode_vec(&mut self, input: &[[i16; C]], max_size: usize) -> Result<Vec<u8>> {
+        self.encoder.encode_vec(flatten(input), max_size)
+    }
+
+    /// Encode an Opus frame from floating point input to a new buffer.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_vec_float(&mut self, input: &[[f32; C]], max_size: usize) -> Result<Vec<u8>> {
+        self.encoder.encode_vec_float(flatten(input), max_size)
+    }
+
+    /// Get the underlying encoder, for its CTLs.
+    pub fn encoder(&mut self) -> &mut Encoder {
+        &mut self.encoder
+    }
+
+    /// Unwrap the underlying encoder.
+    pub fn into_inner(self) -> Encoder {
+        self.encoder
+    }
+}
+
+/// A decoder for exactly `C` channels.
+#[derive(Debug)]
+pub struct FixedDecoder<const C: usize> {
+    decoder: Decoder,
+}
+
+/// A decoder for mono audio.
+pub type MonoDecoder = FixedDecoder<1>;
+
+/// A decoder for interleaved stereo audio.
+pub type StereoDecoder = FixedDecoder<2>;
+
+impl<const C: usize> FixedDecoder<C> {
+    const CHANNELS: Channels = channels::<C>();
+
+    /// Create and initialize a decoder.
+    pub fn new(sample_rate: u32) -> Result<FixedDecoder<C>> {
+        Ok(FixedDecoder {
+            decoder: Decoder::new(sample_rate, Self::CHANNELS)?,
+        })
+    }
+
+    /// Decode an Opus packet, returning the number of frames written.
+    pub fn decode(&mut self, input: &[u8], output: &mut [[i16; C]], fec: bool) -> Result<usize> {
+        self.decoder.decode(input, flatten_mut(output), fec)
+    }
+
+    /// Decode an Opus packet with floating point output, returning the
+    /// number of frames written.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn decode_float(
+        &mut self,
+        input: &[u8],
+        output: &mut [[f32; C]],
+        fec: bool,
+    ) -> Result<usize> {
+        self.decoder.decode_float(input, flatten_mut(output), fec)
+    }
+
+    /// Get the underlying decoder, for its CTLs.
+    pub fn decoder(&mut self) -> &mut Decoder {
+        &mut self.decoder
+    }
+
+    /// Unwrap the underlying decoder.
+    pub fn into_inner(self) -> Decoder {
+        self.decoder
+    }
+}
@@ -0,0 +1,283 @@
+//! Opus packet framing, in the standard form and the self-delimiting form
+//! used within multistream packets (RFC 6716, section 3 and appendix B).
+
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use super::{ffi, Error, Result};
+
+/// Largest frame count of a single Opus packet.
+const MAX_FRAMES: usize = 48;
+
+/// Largest size in bytes of a single Opus frame.
+const MAX_FRAME_LEN: usize = 1275;
+
+/// A packet of a multistream encoder, holding one Opus packet per stream.
+///
+/// Every stream's packet but the last is self-delimiting, so that where one
+/// ends and the next starts can be told without decoding. `split` finds
+/// them, and `unpack` rewrites them as standard packets which a `Decoder` of
+/// each stream's channels accepts, so that streams can be routed on their
+/// own, such as to serve one of several languages. `join` goes the other
+/// way.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct MultistreamPacket<'a> {
+    data: &'a [u8],
+    streams: u8,
+}
+
+impl<'a> MultistreamPacket<'a> {
+    /// Wrap a packet of a multistream encoder with `streams` streams, as
+    /// given by its `StreamLayout`.
+    pub fn new(data: &'a [u8], streams: u8) -> MultistreamPacket<'a> {
+        MultistreamPacket { data, streams }
+    }
+
+    /// Get the bytes of the packet.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Get the number of streams.
+    pub fn streams(&self) -> u8 {
+        self.streams
+    }
+
+    /// Split the packet into the packet of each stream, as they lie in it:
+    /// self-delimiting for all but the last.
+    pub fn split(&self) -> Result<Vec<&'a [u8]>> {
+        let parsed = self.parse()?;
+        Ok(parsed.into_iter().map(|(packet, _)| packet).collect())
+    }
+
+    /// Split the packet into a standard Opus packet for each stream.
+    pub fn unpack(&self) -> Result<Vec<Vec<u8>>> {
+        let mut packets = Vec::with_capacity(self.streams as usize);
+        let parsed = self.parse()?;
+        let last = parsed.len() - 1;
+        for (i, (data, parsed)) in parsed.into_iter().enumerate() {
+            // the last is standard already
+            if i == last {
+                packets.push(data.to_vec());
+                break;
+            }
+            let mut packet = vec![0; packet_len(&parsed.frames, false)];
+            write(parsed.toc, &parsed.frames, false, &mut packet)?;
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+
+    /// Join standard Opus packets, one for each stream in order, into a
+    /// multistream packet in `output`, returning its length.
+    pub fn join(packets: &[&[u8]], output: &mut [u8]) -> Result<usize> {
+        let (last, rest) = match packets.split_last() {
+            Some(split) => split,
+            None => return Err(Error::bad_arg("MultistreamPacket::join")),
+        };
+        let mut pos = 0;
+        for packet in rest {
+            pos += reframe(packet, &mut output[pos..])?;
+        }
+        parse(last, false)?;
+        match output.get_mut(pos..pos + last.len()) {
+            Some(out) => out.copy_from_slice(last),
+            None => return Err(buffer_too_small()),
+        }
+        Ok(pos + last.len())
+    }
+
+    fn parse(&self) -> Result<Vec<(&'a [u8], Parsed<'a>)>> {
+        if self.streams == 0 {
+            return Err(Error::bad_arg("MultistreamPacket::split"));
+        }
+        let mut packets = Vec::with_capacity(self.streams as usize);
+        let mut rest = self.data;
+        for stream in 0..self.streams {
+            let parsed = parse(rest, stream + 1 < self.streams)?;
+            let (packet, tail) = rest.split_at(parsed.len);
+            packets.push((packet, parsed));
+            rest = tail;
+        }
+        Ok(packets)
+    }
+}
+
+/// The frames of a packet, and the number of bytes it spans.
+#[derive(Debug)]
+pub(crate) struct Parsed<'a> {
+    pub toc: u8,
+    pub frames: Vec<&'a [u8]>,
+    pub len: usize,
+}
+
+/// Parse the packet at the start of `data`, which is self-delimiting or
+/// else fills all of `data`.
+pub(crate) fn parse(data: &[u8], self_delimited: bool) -> Result<Parsed<'_>> {
+    let toc = *data.first().ok_or_else(invalid)?;
+    let mut pos = 1;
+    let mut sizes = [0; MAX_FRAMES];
+    let mut padding = 0;
+    let (count, cbr) = match toc & 3 {
+        0 => (1, false),
+        1 => (2, true),
+        2 => {
+            sizes[0] = read_size(data, &mut pos)?;
+            (2, false)
+        }
+        _ => {
+            let byte = *data.get(pos).ok_or_else(invalid)?;
+            pos += 1;
+            let count = (byte & 0x3f) as usize;
+            if count == 0 || count > MAX_FRAMES {
+                return Err(invalid());
+            }
+            if byte & 0x40 != 0 {
+                loop {
+                    let len = *data.get(pos).ok_or_else(invalid)? as usize;
+                    pos += 1;
+                    padding += len.min(254);
+                    if len < 255 {
+                        break;
+                    }
+                }
+            }
+            let cbr = byte & 0x80 == 0;
+            if !cbr {
+                for size in &mut sizes[..count - 1] {
+                    *size = read_size(data, &mut pos)?;
+                }
+            }
+            (count, cbr)
+        }
+    };
+
+    // the last frame's size is given when self-delimiting, and otherwise
+    // takes the rest of the packet
+    if self_delimited {
+        let size = read_size(data, &mut pos)?;
+        let first = if cbr { 0 } else { count - 1 };
+        for s in &mut sizes[first..count] {
+            *s = size;
+        }
+    } else {
+        let available = data.len().checked_sub(pos + padding).ok_or_else(invalid)?;
+        if cbr {
+            if available % count != 0 {
+                return Err(invalid());
+            }
+            for s in &mut sizes[..count] {
+                *s = available / count;
+            }
+        } else {
+            let others: usize = sizes[..count - 1].iter().sum();
+            sizes[count - 1] = available.checked_sub(others).ok_or_else(invalid)?;
+        }
+    }
+
+    let mut frames = Vec::with_capacity(count);
+    for &size in &sizes[..count] {
+        if size > MAX_FRAME_LEN {
+            return Err(invalid());
+        }
+        frames.push(data.get(pos..pos + size).ok_or_else(invalid)?);
+        pos += size;
+    }
+    pos += padding;
+    if pos > data.len() {
+        return Err(invalid());
+    }
+    let len = if self_delimited { pos } else { data.len() };
+    Ok(Parsed { toc, frames, len })
+}
+
+/// Rewrite a standard packet with self-delimiting framing into `output`,
+/// returning its new length.
+pub(crate) fn reframe(packet: &[u8], output: &mut [u8]) -> Result<usize> {
+    let parsed = parse(packet, false)?;
+    write(parsed.toc, &parsed.frames, true, output)
+}
+
+// Get the length of a packet of `frames` as `write` frames it.
+fn packet_len(frames: &[&[u8]], self_delimited: bool) -> usize {
+    let mut header = [0; 2 + 2 * MAX_FRAMES];
+    let header = write_header(0, frames, self_delimited, &mut header);
+    header + frames.iter().map(|f| f.len()).sum::<usize>()
+}
+
+// Write a packet of `frames` with the configuration of `toc`, as a single
+// frame or a code 3 packet of several, without padding.
+fn write(toc: u8, frames: &[&[u8]], self_delimited: bool, output: &mut [u8]) -> Result<usize> {
+    let mut header = [0; 2 + 2 * MAX_FRAMES];
+    let mut len = write_header(toc, frames, self_delimited, &mut header);
+    let body: usize = frames.iter().map(|f| f.len()).sum();
+    if output.len() < len + body {
+        return Err(buffer_too_small());
+    }
+    output[..len].copy_from_slice(&header[..len]);
+    for frame in frames {
+        output[len..len + frame.len()].copy_from_slice(frame);
+        len += frame.len();
+    }
+    Ok(len)
+}
+
+fn write_header(toc: u8, frames: &[&[u8]], self_delimited: bool, header: &mut [u8]) -> usize {
+    let toc = toc & 0xfc;
+    let (last, rest) = match frames.split_last() {
+        Some(split) => split,
+        None => return 0,
+    };
+    let mut len = if rest.is_empty() {
+        header[0] = toc;
+        1
+    } else {
+        let vbr = rest.iter().any(|f| f.len() != last.len());
+        header[0] = toc | 3;
+        header[1] = frames.len() as u8 | if vbr { 0x80 } else { 0 };
+        let mut len = 2;
+        if vbr {
+            for frame in rest {
+                len += write_size(frame.len(), &mut header[len..]);
+            }
+        }
+        len
+    };
+    if self_delimited {
+        len += write_size(last.len(), &mut header[len..]);
+    }
+    len
+}
+
+// Read a frame length in the one or two byte Opus encoding.
+fn read_size(data: &[u8], pos: &mut usize) -> Result<usize> {
+    let first = *data.get(*pos).ok_or_else(invalid)? as usize;
+    if first < 252 {
+        *pos += 1;
+        return Ok(first);
+    }
+    let second = *data.get(*pos + 1).ok_or_else(invalid)? as usize;
+    *pos += 2;
+    Ok(first + 4 * second)
+}
+
+// Write a frame length in the one or two byte Opus encoding.
+fn write_size(size: usize, out: &mut [u8]) -> usize {
+    if size < 252 {
+        out[0] = size as u8;
+        1
+    } else {
+        let first = 252 + (size & 3);
+        out[0] = first as u8;
+        out[1] = ((size - first) >> 2) as u8;
+        2
+    }
+}
+
+fn invalid() -> Error {
+    Error::from_code("MultistreamPacket", ffi::OPUS_INVALID_PACKET)
+}
+
+fn buffer_too_small() -> Error {
+    Error::from_code("MultistreamPacket", ffi::OPUS_BUFFER_TOO_SMALL)
+}
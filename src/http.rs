@@ -0,0 +1,182 @@
+//! Reading remote files over HTTP, via `ureq`.
+//!
+//! `HttpReader` fetches only the parts of a file which are read, with HTTP
+//! range requests, so that `OpusFile::open_url` can play a podcast from the
+//! start or from any point in it without downloading the whole file first.
+
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// The longest seek forward which is read through in the current response
+/// rather than making a new request.
+const SKIP_LIMIT: u64 = 64 * 1024;
+
+/// A remote file read over HTTP.
+///
+/// Sequential reads continue a single response. After a seek, the next read
+/// makes a new range request, unless the seek was a short one forward, which
+/// is skipped through in the current response. A server which ignores range
+/// requests lets the file be read from the start only, and seeking is then
+/// limited to skipping forward.
+pub struct HttpReader {
+    agent: ureq::Agent,
+    url: String,
+    len: Option<u64>,
+    seekable: bool,
+    pos: u64,
+    // the body of the current response, and the offset it has reached
+    body: Option<(Box<dyn Read + Send + Sync>, u64)>,
+}
+
+impl HttpReader {
+    /// Start reading the file at `url`.
+    pub fn open(url: &str) -> io::Result<HttpReader> {
+        HttpReader::with_agent(ureq::agent(), url)
+    }
+
+    /// Start reading the file at `url` with an agent of one's own, such as
+    /// one with timeouts or a proxy set.
+    pub fn with_agent(agent: ureq::Agent, url: &str) -> io::Result<HttpReader> {
+        let mut reader = HttpReader {
+            agent,
+            url: url.to_string(),
+            len: None,
+            seekable: false,
+            pos: 0,
+            body: None,
+        };
+        let body = reader.request(0)?;
+        reader.body = Some((body, 0));
+        Ok(reader)
+    }
+
+    /// Get the URL being read.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Get the length of the file, if the server gave it.
+    pub fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// Determine whether the file is known to be empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == Some(0)
+    }
+
+    /// Determine whether the server accepts range requests, so that the file
+    /// can be read from any position.
+    pub fn is_seekable(&self) -> bool {
+        self.seekable
+    }
+
+    fn request(&mut self, offset: u64) -> io::Result<Box<dyn Read + Send + Sync>> {
+        let response = self
+            .agent
+            .get(&self.url)
+            .set("Range", &format!("bytes={}-", offset))
+            .call()
+            .map_err(from_ureq)?;
+        if response.status() == 206 {
+            self.seekable = true;
+            if let Some(len) = response.header("Content-Range").and_then(range_len) {
+                self.len = Some(len);
+            }
+        } else if offset > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "HTTP server does not support range requests",
+            ));
+        } else {
+            self.seekable = false;
+            self.len = response
+                .header("Content-Length")
+                .and_then(|len| len.parse().ok());
+        }
+        Ok(response.into_reader())
+    }
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.len.is_some_and(|len| self.pos >= len) {
+            return Ok(0);
+        }
+        let pos = self.pos;
+        let reusable = match self.body {
+            Some((_, offset)) => offset <= pos && pos - offset <= SKIP_LIMIT,
+            None => false,
+        };
+        if !reusable {
+            self.body = None;
+            let body = self.request(pos)?;
+            self.body = Some((body, pos));
+        }
+        let len = match self.body {
+            Some((ref mut body, ref mut offset)) => {
+                if *offset < pos {
+                    let skip = pos - *offset;
+                    let skipped = io::copy(&mut body.by_ref().take(skip), &mut io::sink())?;
+                    *offset += skipped;
+                    if skipped < skip {
+                        // the file is shorter than the position
+                        return Ok(0);
+                    }
+                }
+                let len = body.read(buf)?;
+                *offset += len as u64;
+                len
+            }
+            None => 0,
+        };
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for HttpReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+            SeekFrom::End(delta) => match self.len {
+                Some(len) => len.checked_add_signed(delta),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "HTTP server did not give the file length",
+                    ))
+                }
+            },
+        };
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+impl fmt::Debug for HttpReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpReader")
+            .field("url", &self.url)
+            .field("len", &self.len)
+            .field("seekable", &self.seekable)
+            .field("pos", &self.pos)
+            .finish()
+    }
+}
+
+// Get the full length from a `Content-Range` header such as
+// `bytes 0-1023/4096`.
+fn range_len(header: &str) -> Option<u64> {
+    header.rsplit('/').next()?.trim().parse().ok()
+}
+
+fn from_ureq(err: ureq::Error) -> io::Error {
+    match err {
+        ureq::Error::Status(status, _) => io::Error::other(format!("HTTP status {}", status)),
+        ureq::Error::Transport(err) => io::Error::other(err),
+    }
+}
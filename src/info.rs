@@ -0,0 +1,263 @@
+//! Reporting how the linked libopus was built and which code paths it uses.
+//!
+//! `runtime_info` gathers what is known about the library: its version,
+//! whether it uses fixed-point arithmetic, and for each SIMD instruction set
+//! libopus has code for on the target architecture, whether that code was
+//! compiled in and whether it is used on the running CPU. The SIMD details are
+//! only known for a libopus built from source by `opus-sys`. `capabilities`
+//! reports which of the neural network features of libopus 1.5 it has.
+
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use super::{ffi, version};
+
+/// How the linked libopus was built, as reported by `runtime_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeInfo {
+    /// The libopus version string, such as `"libopus 1.3.1"`.
+    pub version: &'static str,
+    /// Whether libopus uses fixed-point arithmetic rather than floating point.
+    pub fixed_point: bool,
+    /// Whether libopus was built from source rather than found on the system.
+    pub vendored: bool,
+    /// Whether libopus detects the CPU features at run time, if known.
+    pub rtcd: Option<bool>,
+    /// The SIMD code paths libopus has for the target architecture.
+    pub simd: Vec<SimdPath>,
+}
+
+/// A SIMD code path of libopus, as reported by `runtime_info`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SimdPath {
+    /// The instruction set, such as `"sse4.1"` or `"neon"`.
+    pub name: &'static str,
+    /// Whether the code path was compiled in, if known.
+    pub compiled: Option<bool>,
+    /// Whether the running CPU supports the instruction set, if known.
+    pub supported: Option<bool>,
+    /// Whether libopus uses the code path on the running CPU, if known.
+    pub active: Option<bool>,
+}
+
+/// The neural network features of libopus 1.5 compiled into the linked
+/// library, as reported by `capabilities`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    /// Whether Deep REDundancy can be encoded and decoded.
+    pub dred: bool,
+    /// Whether the decoder conceals lost packets with the deep PLC from
+    /// complexity 5, if known.
+    pub deep_plc: Option<bool>,
+    /// Whether the decoder enhances speech with LACE and NoLACE from
+    /// complexity 6, if known.
+    pub osce: Option<bool>,
+}
+
+/// Report which neural network features the linked libopus has.
+///
+/// All of them are missing before libopus 1.5. Whether DRED is there is
+/// checked at run time; the others are only known for a libopus built from
+/// source by `opus-sys`, or implied by DRED, which needs the deep PLC.
+pub fn capabilities() -> Capabilities {
+    if !version_at_least(version(), 1, 5) {
+        return Capabilities {
+            dred: false,
+            deep_plc: Some(false),
+            osce: Some(false),
+        };
+    }
+    let dred = dred_supported();
+    let vendored = ffi::OPUS_SYS_VENDORED;
+    Capabilities {
+        dred,
+        deep_plc: if dred || vendored {
+            Some(dred || ffi::OPUS_SYS_DEEP_PLC)
+        } else {
+            None
+        },
+        osce: if vendored {
+            Some(ffi::OPUS_SYS_OSCE)
+        } else {
+            None
+        },
+    }
+}
+
+/// Report the build configuration as a `tracing` event, when the feature is
+/// enabled, the first time a coder is created.
+pub(crate) fn trace_build_config() {
+    #[cfg(feature = "tracing")]
+    {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        static REPORTED: AtomicBool = AtomicBool::new(false);
+        if REPORTED.load(Ordering::Relaxed) {
+            return;
+        }
+        REPORTED.store(true, Ordering::Relaxed);
+        let info = runtime_info();
+        event!(
+            info,
+            version = info.version,
+            fixed_point = info.fixed_point,
+            vendored = info.vendored,
+            rtcd = ?info.rtcd,
+            simd = ?info.simd,
+            capabilities = ?capabilities(),
+            "libopus build configuration"
+        );
+    }
+}
+
+#[cfg(opus_ge_1_5)]
+fn dred_supported() -> bool {
+    super::dred::DredDecoder::new().is_ok()
+}
+
+// without the headers of libopus 1.5 there are no bindings to check with
+#[cfg(not(opus_ge_1_5))]
+fn dred_supported() -> bool {
+    false
+}
+
+/// Report how the linked libopus was built and which SIMD code paths it uses
+/// on the running CPU.
+///
+/// CPU features are detected at run time with the `std` feature; without it
+/// only those enabled at compile time are known.
+pub fn runtime_info() -> RuntimeInfo {
+    let version = version();
+    let vendored = ffi::OPUS_SYS_VENDORED;
+    let v1_5 = version_at_least(version, 1, 5);
+    let simd = simd_names(v1_5)
+        .iter()
+        .map(|&name| {
+            let supported = cpu_supports(name);
+            if !vendored {
+                return SimdPath {
+                    name,
+                    compiled: None,
+                    supported,
+                    active: None,
+                };
+            }
+            let presumed = baseline(name) || ffi::OPUS_SYS_PRESUME.split(' ').any(|n| n == name);
+            let compiled = ffi::OPUS_SYS_INTRINSICS && (ffi::OPUS_SYS_RTCD || presumed);
+            let active = if !compiled {
+                Some(false)
+            } else if presumed {
+                Some(true)
+            } else {
+                supported
+            };
+            SimdPath {
+                name,
+                compiled: Some(compiled),
+                supported,
+                active,
+            }
+        })
+        .collect();
+
+    RuntimeInfo {
+        version,
+        fixed_point: version.contains("-fixed"),
+        vendored,
+        rtcd: if vendored {
+            Some(ffi::OPUS_SYS_RTCD)
+        } else {
+            None
+        },
+        simd,
+    }
+}
+
+impl fmt::Display for RuntimeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arithmetic = if self.fixed_point {
+            "fixed-point"
+        } else {
+            "floating-point"
+        };
+        let origin = if self.vendored { "vendored" } else { "system" };
+        write!(f, "{} ({}, {})", self.version, arithmetic, origin)?;
+        for path in &self.simd {
+            let state = match (path.compiled, path.active) {
+                (Some(false), _) => "not compiled",
+                (_, Some(true)) => "active",
+                (_, Some(false)) => "inactive",
+                (_, None) => "unknown",
+            };
+            write!(f, ", {}: {}", path.name, state)?;
+        }
+        Ok(())
+    }
+}
+
+// "libopus 1.5.2" or "libopus 1.3.1-fixed" and the like
+fn version_at_least(version: &str, major: u32, minor: u32) -> bool {
+    let mut numbers = version
+        .trim_start_matches("libopus ")
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|n| n.parse::<u32>().unwrap_or(0));
+    let found = (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0));
+    found >= (major, minor)
+}
+
+// the instruction sets libopus has code for on the target architecture;
+// libopus 1.5 replaced its AVX code with AVX2 and FMA
+fn simd_names(v1_5: bool) -> &'static [&'static str] {
+    if cfg!(any(target_arch = "x86", target_arch = "x86_64")) {
+        if v1_5 {
+            &["sse", "sse2", "sse4.1", "avx2"]
+        } else {
+            &["sse", "sse2", "sse4.1", "avx"]
+        }
+    } else if cfg!(any(target_arch = "arm", target_arch = "aarch64")) {
+        &["neon"]
+    } else {
+        &[]
+    }
+}
+
+// the instruction sets the C compiler assumes for the target without flags
+fn baseline(name: &str) -> bool {
+    match name {
+        "sse" | "sse2" => cfg!(target_arch = "x86_64"),
+        "neon" => cfg!(target_arch = "aarch64"),
+        _ => false,
+    }
+}
+
+#[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+fn cpu_supports(name: &str) -> Option<bool> {
+    Some(match name {
+        "sse" => is_x86_feature_detected!("sse"),
+        "sse2" => is_x86_feature_detected!("sse2"),
+        "sse4.1" => is_x86_feature_detected!("sse4.1"),
+        "avx" => is_x86_feature_detected!("avx"),
+        "avx2" => is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma"),
+        _ => return None,
+    })
+}
+
+#[cfg(not(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64"))))]
+fn cpu_supports(name: &str) -> Option<bool> {
+    let enabled = match name {
+        "sse" => cfg!(target_feature = "sse"),
+        "sse2" => cfg!(target_feature = "sse2"),
+        "sse4.1" => cfg!(target_feature = "sse4.1"),
+        "avx" => cfg!(target_feature = "avx"),
+        "avx2" => cfg!(all(target_feature = "avx2", target_feature = "fma")),
+        "neon" => cfg!(any(target_arch = "aarch64", target_feature = "neon")),
+        _ => false,
+    };
+    // a feature left out at compile time may still be present
+    if enabled {
+        Some(true)
+    } else {
+        None
+    }
+}
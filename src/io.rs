@@ -0,0 +1,317 @@
+//! `std::io` adapters between PCM byte streams and Opus packets.
+//!
+//! PCM bytes are interleaved little-endian samples in the chosen
+//! `SampleFormat`.
+
+use std::io::{self, Read, Write};
+
+use super::chunk::FrameChunker;
+use super::pipeline::{PacketSink, PacketSource, Packetizer};
+use super::{Decoder, Encoder};
+
+/// The encoding of PCM samples in a byte stream.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SampleFormat {
+    /// Signed 16-bit integer samples.
+    I16,
+    /// 32-bit floating point samples, nominally within [-1, 1].
+    #[cfg(not(feature = "disable-float-api"))]
+    F32,
+}
+
+impl SampleFormat {
+    /// Get the size of a single sample in bytes.
+    pub fn sample_size(self) -> usize {
+        match self {
+            SampleFormat::I16 => 2,
+            #[cfg(not(feature = "disable-float-api"))]
+            SampleFormat::F32 => 4,
+        }
+    }
+}
+
+/// Decodes packets from a `PacketSource` and exposes the audio through
+/// `Read`.
+#[derive(Debug)]
+pub struct DecodedReader<S> {
+    decoder: Decoder,
+    source: S,
+    format: SampleFormat,
+    frame_len: usize,
+    pcm_i16: Vec<i16>,
+    #[cfg(not(feature = "disable-float-api"))]
+    pcm_f32: Vec<f32>,
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl<S: PacketSource> DecodedReader<S> {
+    /// Create a reader decoding packets from `source` into samples of the
+    /// given format.
+    pub fn new(mut decoder: Decoder, source: S, format: SampleFormat) -> io::Result<Self> {
+        let rate = decoder.get_sample_rate()? as usize;
+        // the longest possible packet is 120 ms
+        let frame_len = rate * 120 / 1000 * decoder.channels as usize;
+        Ok(DecodedReader {
+            decoder,
+            source,
+            format,
+            frame_len,
+            pcm_i16: Vec::new(),
+            #[cfg(not(feature = "disable-float-api"))]
+            pcm_f32: Vec::new(),
+            buffer: Vec::new(),
+            pos: 0,
+        })
+    }
+
+    /// Get the sample format produced by this reader.
+    pub fn format(&self) -> SampleFormat {
+        self.format
+    }
+
+    /// Get the decoder.
+    pub fn decoder(&mut self) -> &mut Decoder {
+        &mut self.decoder
+    }
+
+    /// Get the packet source.
+    pub fn source(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Unwrap the decoder and packet source, discarding any unread audio.
+    pub fn into_inner(self) -> (Decoder, S) {
+        (self.decoder, self.source)
+    }
+
+    /// Decode packets until some audio is available. Returns `false` at the
+    /// end of the stream.
+    fn fill(&mut self) -> io::Result<bool> {
+        while self.pos == self.buffer.len() {
+            let (packet, _) = match self.source.next_packet()? {
+                Some(next) => next,
+                None => return Ok(false),
+            };
+            let channels = self.decoder.channels as usize;
+            self.buffer.clear();
+            self.pos = 0;
+            match self.format {
+                SampleFormat::I16 => {
+                    self.pcm_i16.resize(self.frame_len, 0);
+                    let len = self.decoder.decode(&packet, &mut self.pcm_i16, false)?;
+                    for sample in &self.pcm_i16[..len * channels] {
+                        self.buffer.extend_from_slice(&sample.to_le_bytes());
+                    }
+                }
+                #[cfg(not(feature = "disable-float-api"))]
+                SampleFormat::F32 => {
+                    self.pcm_f32.resize(self.frame_len, 0.0);
+                    let len = self
+                        .decoder
+                        .decode_float(&packet, &mut self.pcm_f32, false)?;
+                    for sample in &self.pcm_f32[..len * channels] {
+                        self.buffer.extend_from_slice(&sample.to_le_bytes());
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<S: PacketSource> Read for DecodedReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || !self.fill()? {
+            return Ok(0);
+        }
+        let available = &self.buffer[self.pos..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[derive(Debug)]
+enum Chunker {
+    I16(FrameChunker<i16>, Vec<i16>),
+    #[cfg(not(feature = "disable-float-api"))]
+    F32(FrameChunker<f32>, Vec<f32>),
+}
+
+/// Encodes PCM bytes written through `Write` and forwards the packets to a
+/// `PacketSink`.
+///
+/// Input is split into frames of the encoder's configured frame duration, or
+/// 20 ms if none was set. Call `finish` at the end of the stream to encode
+/// the final partial frame, padded with silence.
+///
+/// If a frame fails to encode or send, `write` reports the bytes up to the
+/// end of that frame as written, and the error is returned by the next call.
+#[derive(Debug)]
+pub struct EncodingWriter<S> {
+    packetizer: Packetizer<S>,
+    chunker: Chunker,
+    partial: Vec<u8>,
+    error: Option<io::Error>,
+}
+
+impl<S: PacketSink> EncodingWriter<S> {
+    /// Create a writer encoding samples of the given format and sending the
+    /// packets to `sink`.
+    pub fn new(mut encoder: Encoder, sink: S, format: SampleFormat) -> io::Result<Self> {
+        let frame_size = encoder.frame_size()?;
+        let channels = encoder.channels;
+        let chunker = match format {
+            SampleFormat::I16 => Chunker::I16(FrameChunker::new(frame_size, channels), Vec::new()),
+            #[cfg(not(feature = "disable-float-api"))]
+            SampleFormat::F32 => Chunker::F32(FrameChunker::new(frame_size, channels), Vec::new()),
+        };
+        Ok(EncodingWriter {
+            packetizer: Packetizer::new(encoder, sink, super::chunk::MAX_PACKET),
+            chunker,
+            partial: Vec::new(),
+            error: None,
+        })
+    }
+
+    /// Get the sample format accepted by this writer.
+    pub fn format(&self) -> SampleFormat {
+        match self.chunker {
+            Chunker::I16(..) => SampleFormat::I16,
+            #[cfg(not(feature = "disable-float-api"))]
+            Chunker::F32(..) => SampleFormat::F32,
+        }
+    }
+
+    /// Get the underlying packetizer.
+    pub fn packetizer(&mut self) -> &mut Packetizer<S> {
+        &mut self.packetizer
+    }
+
+    /// Encode any buffered samples as a final frame padded with silence, and
+    /// flush the sink.
+    ///
+    /// Trailing bytes which do not form a whole sample are discarded.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.take_error()?;
+        self.partial.clear();
+        let packetizer = &mut self.packetizer;
+        match self.chunker {
+            Chunker::I16(ref mut chunker, _) => {
+                chunker.flush(|frame| packetizer.write_frame(frame).map(|_| ()))?
+            }
+            #[cfg(not(feature = "disable-float-api"))]
+            Chunker::F32(ref mut chunker, _) => {
+                chunker.flush(|frame| packetizer.write_frame_float(frame).map(|_| ()))?
+            }
+        }
+        self.packetizer.flush()
+    }
+
+    /// Unwrap the encoder and sink, discarding any buffered samples.
+    pub fn into_inner(self) -> (Encoder, S) {
+        self.packetizer.into_inner()
+    }
+
+    // Return the error of a frame which failed during an earlier `write`.
+    fn take_error(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S: PacketSink> Write for EncodingWriter<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.take_error()?;
+        let size = self.format().sample_size();
+        let mut bytes = buf;
+
+        // complete a sample split across writes
+        if !self.partial.is_empty() {
+            let take = (size - self.partial.len()).min(bytes.len());
+            self.partial.extend_from_slice(&bytes[..take]);
+            bytes = &bytes[take..];
+            if self.partial.len() < size {
+                return Ok(buf.len());
+            }
+        }
+
+        let whole = bytes.len() / size * size;
+        let (samples, rest) = bytes.split_at(whole);
+        let partial = std::mem::take(&mut self.partial);
+        let packetizer = &mut self.packetizer;
+        let (used, result) = match self.chunker {
+            Chunker::I16(ref mut chunker, ref mut scratch) => {
+                scratch.clear();
+                scratch.extend(
+                    partial
+                        .chunks_exact(2)
+                        .chain(samples.chunks_exact(2))
+                        .map(|b| i16::from_le_bytes([b[0], b[1]])),
+                );
+                push_frames(chunker, scratch, |frame| {
+                    packetizer.write_frame(frame).map(|_| ())
+                })
+            }
+            #[cfg(not(feature = "disable-float-api"))]
+            Chunker::F32(ref mut chunker, ref mut scratch) => {
+                scratch.clear();
+                scratch.extend(
+                    partial
+                        .chunks_exact(4)
+                        .chain(samples.chunks_exact(4))
+                        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                );
+                push_frames(chunker, scratch, |frame| {
+                    packetizer.write_frame_float(frame).map(|_| ())
+                })
+            }
+        };
+        match result {
+            Ok(()) => {
+                self.partial.extend_from_slice(rest);
+                Ok(buf.len())
+            }
+            Err(err) => {
+                // the failed frame took at least one sample of `buf`, so some
+                // bytes were written, and the samples after it were not
+                self.error = Some(err);
+                let completed = buf.len() - bytes.len();
+                Ok(completed + (used - partial.len() / size) * size)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.take_error()?;
+        self.packetizer.flush()
+    }
+}
+
+// Push `samples` through `chunker`, returning how many were consumed: all of
+// them, or on error those up to the end of the frame which failed.
+fn push_frames<T, F>(
+    chunker: &mut FrameChunker<T>,
+    samples: &[T],
+    mut f: F,
+) -> (usize, io::Result<()>)
+where
+    T: Copy + Default,
+    F: FnMut(&[T]) -> io::Result<()>,
+{
+    let pending = chunker.pending();
+    let frame_len = chunker.frame_len();
+    let mut frames = 0;
+    let result = chunker.push(samples, |frame| {
+        frames += 1;
+        f(frame)
+    });
+    match result {
+        Ok(()) => (samples.len(), Ok(())),
+        Err(err) => (frames * frame_len - pending, Err(err)),
+    }
+}
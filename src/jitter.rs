@@ -0,0 +1,581 @@
+//! Adaptive jitter buffer for receiving Opus packets over lossy transports.
+//!
+//! Packets are pushed in arrival order together with their RTP-style sequence
+//! number and 48kHz timestamp. The buffer reorders them, drops packets which
+//! arrive too late to be played, and on every playout tick decides whether to
+//! decode normally, recover the missing frame from in-band FEC data in the
+//! following packet, or fall back to packet loss concealment. With libopus 1.5
+//! and `enable_dred`, frames FEC cannot recover are reconstructed from the
+//! Deep REDundancy of a later packet where it reaches back far enough.
+
+#[cfg(not(feature = "std"))]
+use std::boxed::Box;
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+#[cfg(opus_ge_1_5)]
+use super::dred::{Dred, DredDecoder};
+use super::time::{Timestamp48k, CLOCK_RATE};
+use super::{Decoder, Result};
+
+/// Duration assumed for concealment before the first packet has been decoded.
+const DEFAULT_DURATION: u32 = CLOCK_RATE / 50;
+
+/// Events reported to a `DelayPolicy` as the buffer operates.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Event {
+    /// A packet was played out on time.
+    Played,
+    /// A packet arrived after its playout slot had passed and was dropped.
+    Late,
+    /// A packet was never received and had to be recovered or concealed.
+    Lost,
+    /// The buffer ran dry while playing.
+    Underrun,
+}
+
+/// Adjusts the target playout delay of a `JitterBuffer` in response to events.
+pub trait DelayPolicy {
+    /// Handle an event and return the new target delay in 48kHz samples.
+    fn on_event(&mut self, event: Event, target_delay: u32) -> u32;
+}
+
+/// A policy which never changes the target delay.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FixedDelay;
+
+impl DelayPolicy for FixedDelay {
+    fn on_event(&mut self, _event: Event, target_delay: u32) -> u32 {
+        target_delay
+    }
+}
+
+/// A policy which grows the delay on late packets and underruns, and slowly
+/// shrinks it again while playout is steady.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveDelay {
+    /// Lower bound for the target delay, in 48kHz samples.
+    pub min_delay: u32,
+    /// Upper bound for the target delay, in 48kHz samples.
+    pub max_delay: u32,
+    /// Amount the delay is changed by on each adjustment, in 48kHz samples.
+    pub step: u32,
+    /// Number of consecutive on-time packets before the delay is decreased.
+    pub decay_after: u32,
+    steady: u32,
+}
+
+impl AdaptiveDelay {
+    /// Create an adaptive policy bounded by `min_delay` and `max_delay`.
+    pub fn new(min_delay: u32, max_delay: u32) -> AdaptiveDelay {
+        AdaptiveDelay {
+            min_delay,
+            max_delay,
+            step: DEFAULT_DURATION,
+            decay_after: 500,
+            steady: 0,
+        }
+    }
+
+    /// Create an adaptive policy for packets of `frame_duration` 48kHz
+    /// samples, which adjusts the delay a frame at a time and waits as long
+    /// before decreasing it as the default does for 20 ms packets.
+    pub fn for_frames(min_delay: u32, max_delay: u32, frame_duration: u32) -> AdaptiveDelay {
+        let frame_duration = frame_duration.max(1);
+        AdaptiveDelay {
+            step: frame_duration,
+            decay_after: 500 * DEFAULT_DURATION / frame_duration,
+            ..AdaptiveDelay::new(min_delay, max_delay)
+        }
+    }
+}
+
+impl DelayPolicy for AdaptiveDelay {
+    fn on_event(&mut self, event: Event, target_delay: u32) -> u32 {
+        match event {
+            Event::Played => {
+                self.steady += 1;
+                if self.steady < self.decay_after {
+                    return target_delay;
+                }
+                self.steady = 0;
+                target_delay.saturating_sub(self.step).max(self.min_delay)
+            }
+            Event::Late | Event::Underrun => {
+                self.steady = 0;
+                (target_delay + self.step).min(self.max_delay)
+            }
+            Event::Lost => target_delay,
+        }
+    }
+}
+
+/// Counters describing what the jitter buffer has done so far.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct Stats {
+    /// Packets accepted into the buffer.
+    pub received: u64,
+    /// Packets dropped because their playout slot had already passed.
+    pub late: u64,
+    /// Packets dropped because the same sequence number was already buffered.
+    pub duplicates: u64,
+    /// Packets dropped because their TOC byte or frame count is invalid.
+    pub malformed: u64,
+    /// Packets that were never received in time to be played.
+    pub lost: u64,
+    /// Lost packets recovered from the FEC data of the following packet.
+    pub fec_recovered: u64,
+    /// Lost packets reconstructed from the DRED data of a later packet.
+    pub dred_recovered: u64,
+    /// Frames produced by packet loss concealment, including underruns.
+    pub concealed: u64,
+    /// Times the buffer ran empty while playing.
+    pub underruns: u64,
+}
+
+/// The outcome of a single playout tick.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Playout {
+    /// Not enough audio is buffered yet; no output was produced.
+    Buffering,
+    /// A received packet was decoded, producing this many samples per channel.
+    Decoded(usize),
+    /// A lost packet was recovered from in-band FEC or DRED data.
+    Recovered(usize),
+    /// A lost packet was concealed by the decoder.
+    Concealed(usize),
+}
+
+impl Playout {
+    /// Get the number of samples per channel written to the output.
+    pub fn samples(self) -> usize {
+        match self {
+            Playout::Buffering => 0,
+            Playout::Decoded(n) | Playout::Recovered(n) | Playout::Concealed(n) => n,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    timestamp: u32,
+    duration: u32,
+    packet: Vec<u8>,
+}
+
+enum Action {
+    Decode(Vec<u8>),
+    Recover(u64),
+    // the sequence number of a later packet, and how long before it the lost
+    // frame starts in 48kHz samples
+    #[cfg(opus_ge_1_5)]
+    Reconstruct(u64, u32),
+    Conceal,
+}
+
+#[cfg(opus_ge_1_5)]
+struct DredState {
+    decoder: DredDecoder,
+    dred: Dred,
+    // the packet last parsed into `dred`
+    parsed: Option<u64>,
+}
+
+/// Reorders incoming packets and drives a `Decoder` with FEC/PLC decisions.
+pub struct JitterBuffer {
+    packets: BTreeMap<u64, Entry>,
+    highest_seq: Option<u64>,
+    next_seq: Option<u64>,
+    buffered: u32,
+    target_delay: u32,
+    last_duration: u32,
+    last_timestamp: Option<u32>,
+    playing: bool,
+    stats: Stats,
+    policy: Box<dyn DelayPolicy + Send>,
+    #[cfg(opus_ge_1_5)]
+    dred: Option<DredState>,
+}
+
+impl JitterBuffer {
+    /// Create a jitter buffer with a fixed target delay in 48kHz samples.
+    pub fn new(target_delay: u32) -> JitterBuffer {
+        JitterBuffer {
+            packets: BTreeMap::new(),
+            highest_seq: None,
+            next_seq: None,
+            buffered: 0,
+            target_delay,
+            last_duration: DEFAULT_DURATION,
+            last_timestamp: None,
+            playing: false,
+            stats: Stats::default(),
+            policy: Box::new(FixedDelay),
+            #[cfg(opus_ge_1_5)]
+            dred: None,
+        }
+    }
+
+    /// Reconstruct the lost frames which in-band FEC cannot recover from the
+    /// Deep REDundancy of later packets, when it reaches back far enough.
+    ///
+    /// Only available when libopus 1.5 or later is linked, and fails with
+    /// `ErrorCode::Unimplemented` unless libopus was built with DRED.
+    #[cfg(opus_ge_1_5)]
+    pub fn enable_dred(&mut self) -> Result<()> {
+        self.dred = Some(DredState {
+            decoder: DredDecoder::new()?,
+            dred: Dred::new()?,
+            parsed: None,
+        });
+        Ok(())
+    }
+
+    /// Replace the policy used to adapt the target delay.
+    pub fn set_policy<P: DelayPolicy + Send + 'static>(&mut self, policy: P) {
+        self.policy = Box::new(policy);
+    }
+
+    /// Get the current target delay in 48kHz samples.
+    pub fn target_delay(&self) -> u32 {
+        self.target_delay
+    }
+
+    /// Set the target delay in 48kHz samples.
+    pub fn set_target_delay(&mut self, target_delay: u32) {
+        self.target_delay = target_delay;
+    }
+
+    /// Get the total duration of the buffered packets in 48kHz samples.
+    pub fn buffered(&self) -> u32 {
+        self.buffered
+    }
+
+    /// Get the number of buffered packets.
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Determine whether the buffer holds no packets.
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Get the counters accumulated since creation or the last `reset`.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Get the 48kHz timestamp of the most recently played packet.
+    pub fn last_timestamp(&self) -> Option<u32> {
+        self.last_timestamp
+    }
+
+    /// Discard all buffered packets and counters and start buffering afresh.
+    pub fn reset(&mut self) {
+        self.packets.clear();
+        self.highest_seq = None;
+        self.next_seq = None;
+        self.buffered = 0;
+        self.last_duration = DEFAULT_DURATION;
+        self.last_timestamp = None;
+        self.playing = false;
+        self.stats = Stats::default();
+        #[cfg(opus_ge_1_5)]
+        {
+            if let Some(state) = &mut self.dred {
+                state.parsed = None;
+            }
+        }
+    }
+
+    /// Add a received packet to the buffer.
+    ///
+    /// Returns `false` if the packet was dropped as late, duplicate, or
+    /// malformed.
+    pub fn push(&mut self, seq: u16, timestamp: u32, packet: Vec<u8>) -> bool {
+        let seq = self.extend_seq(seq);
+        if self.next_seq.is_some_and(|next| seq < next) {
+            event!(debug, seq, timestamp, "dropped late packet");
+            self.stats.late += 1;
+            self.notify(Event::Late);
+            return false;
+        }
+        if self.packets.contains_key(&seq) {
+            event!(debug, seq, timestamp, "dropped duplicate packet");
+            self.stats.duplicates += 1;
+            return false;
+        }
+        self.insert(seq, timestamp, packet)
+    }
+
+    /// Add a redundant copy of a packet, such as one recovered from a RED
+    /// payload.
+    ///
+    /// Unlike `push`, copies of packets which were already received or whose
+    /// playout slot has passed are discarded without being counted as late
+    /// or duplicate.
+    pub fn push_redundant(&mut self, seq: u16, timestamp: u32, packet: Vec<u8>) -> bool {
+        let seq = self.extend_seq(seq);
+        if self.next_seq.is_some_and(|next| seq < next) || self.packets.contains_key(&seq) {
+            return false;
+        }
+        self.insert(seq, timestamp, packet)
+    }
+
+    fn insert(&mut self, seq: u64, timestamp: u32, packet: Vec<u8>) -> bool {
+        let duration = match Timestamp48k::of_packet(&packet) {
+            Ok(duration) => duration.samples() as u32,
+            Err(_) => {
+                event!(debug, seq, timestamp, "dropped malformed packet");
+                self.stats.malformed += 1;
+                return false;
+            }
+        };
+        self.highest_seq = Some(self.highest_seq.map_or(seq, |high| high.max(seq)));
+        self.buffered += duration;
+        self.stats.received += 1;
+        self.packets.insert(
+            seq,
+            Entry {
+                timestamp,
+                duration,
+                packet,
+            },
+        );
+        true
+    }
+
+    /// Produce the next frame of audio into `output`.
+    pub fn pop(&mut self, decoder: &mut Decoder, output: &mut [i16]) -> Result<Playout> {
+        let output = match self.next_action() {
+            None => return Ok(Playout::Buffering),
+            Some(action) => {
+                let out_len = self.conceal_len(decoder, output.len())?;
+                match action {
+                    Action::Decode(packet) => {
+                        Playout::Decoded(decoder.decode(&packet, output, false)?)
+                    }
+                    Action::Recover(seq) => Playout::Recovered(decoder.decode(
+                        &self.packets[&seq].packet,
+                        &mut output[..out_len],
+                        true,
+                    )?),
+                    #[cfg(opus_ge_1_5)]
+                    Action::Reconstruct(seq, distance) => {
+                        match (self.dred_offset(decoder, seq, distance)?, &self.dred) {
+                            (Some(offset), Some(state)) => {
+                                let dred = &state.dred;
+                                Playout::Recovered(decoder.decode_dred(
+                                    dred,
+                                    offset,
+                                    &mut output[..out_len],
+                                )?)
+                            }
+                            _ => Playout::Concealed(decoder.decode(
+                                &[],
+                                &mut output[..out_len],
+                                false,
+                            )?),
+                        }
+                    }
+                    Action::Conceal => {
+                        Playout::Concealed(decoder.decode(&[], &mut output[..out_len], false)?)
+                    }
+                }
+            }
+        };
+        Ok(output)
+    }
+
+    /// Produce the next frame of audio into `output` as floating point.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn pop_float(&mut self, decoder: &mut Decoder, output: &mut [f32]) -> Result<Playout> {
+        let output = match self.next_action() {
+            None => return Ok(Playout::Buffering),
+            Some(action) => {
+                let out_len = self.conceal_len(decoder, output.len())?;
+                match action {
+                    Action::Decode(packet) => {
+                        Playout::Decoded(decoder.decode_float(&packet, output, false)?)
+                    }
+                    Action::Recover(seq) => Playout::Recovered(decoder.decode_float(
+                        &self.packets[&seq].packet,
+                        &mut output[..out_len],
+                        true,
+                    )?),
+                    #[cfg(opus_ge_1_5)]
+                    Action::Reconstruct(seq, distance) => {
+                        match (self.dred_offset(decoder, seq, distance)?, &self.dred) {
+                            (Some(offset), Some(state)) => {
+                                let dred = &state.dred;
+                                Playout::Recovered(decoder.decode_dred_float(
+                                    dred,
+                                    offset,
+                                    &mut output[..out_len],
+                                )?)
+                            }
+                            _ => Playout::Concealed(decoder.decode_float(
+                                &[],
+                                &mut output[..out_len],
+                                false,
+                            )?),
+                        }
+                    }
+                    Action::Conceal => Playout::Concealed(decoder.decode_float(
+                        &[],
+                        &mut output[..out_len],
+                        false,
+                    )?),
+                }
+            }
+        };
+        Ok(output)
+    }
+
+    fn next_action(&mut self) -> Option<Action> {
+        if !self.playing {
+            if self.packets.is_empty() || self.buffered < self.target_delay {
+                return None;
+            }
+            self.playing = true;
+            if self.next_seq.is_none() {
+                self.next_seq = self.packets.keys().next().cloned();
+            }
+            event!(
+                debug,
+                buffered = self.buffered,
+                target_delay = self.target_delay,
+                "started playout"
+            );
+        }
+
+        let seq = self.next_seq?;
+        if let Some(entry) = self.packets.remove(&seq) {
+            self.next_seq = Some(seq + 1);
+            self.buffered -= entry.duration;
+            self.last_duration = entry.duration;
+            self.last_timestamp = Some(entry.timestamp);
+            self.notify(Event::Played);
+            return Some(Action::Decode(entry.packet));
+        }
+
+        if self.packets.is_empty() {
+            // Nothing to go on; conceal without giving up on the packet so it
+            // may still be played if it turns up before the next tick.
+            event!(debug, seq, "underrun, concealing");
+            self.stats.underruns += 1;
+            self.stats.concealed += 1;
+            self.notify(Event::Underrun);
+            return Some(Action::Conceal);
+        }
+
+        self.next_seq = Some(seq + 1);
+        self.stats.lost += 1;
+        self.notify(Event::Lost);
+        if let Some(timestamp) = self.last_timestamp {
+            self.last_timestamp = Some(timestamp.wrapping_add(self.last_duration));
+        }
+        if self.packets.contains_key(&(seq + 1)) {
+            event!(debug, seq, "lost packet, recovering from FEC");
+            self.stats.fec_recovered += 1;
+            return Some(Action::Recover(seq + 1));
+        }
+        #[cfg(opus_ge_1_5)]
+        {
+            // every buffered packet follows the lost one
+            if let (Some(_), Some(timestamp), Some((&later, entry))) =
+                (&self.dred, self.last_timestamp, self.packets.iter().next())
+            {
+                let distance = entry.timestamp.wrapping_sub(timestamp);
+                event!(
+                    debug,
+                    seq,
+                    distance,
+                    "lost packet, reconstructing from DRED"
+                );
+                return Some(Action::Reconstruct(later, distance));
+            }
+        }
+        event!(debug, seq, "lost packet, concealing");
+        self.stats.concealed += 1;
+        Some(Action::Conceal)
+    }
+
+    /// Parse the DRED data of packet `seq` unless it was already, and get the
+    /// offset to decode a frame starting `distance` 48kHz samples before it,
+    /// in samples of the decoder's rate, if the data reaches back that far.
+    #[cfg(opus_ge_1_5)]
+    fn dred_offset(
+        &mut self,
+        decoder: &mut Decoder,
+        seq: u64,
+        distance: u32,
+    ) -> Result<Option<usize>> {
+        let rate = decoder.get_sample_rate()?;
+        let offset = Timestamp48k(distance as u64).to_samples(rate) as usize;
+        let state = match self.dred {
+            Some(ref mut state) => state,
+            None => return Ok(None),
+        };
+        if state.parsed != Some(seq) {
+            // parse a second, about all there can be, so that the packet
+            // serves the lost frames after this one as well; broken DRED data
+            // only means concealing, as the packet may still decode
+            state.parsed = Some(seq);
+            let packet = &self.packets[&seq].packet;
+            let _ = state
+                .decoder
+                .parse(&mut state.dred, packet, rate as usize, rate);
+        }
+        if offset > 0 && offset <= state.dred.available() {
+            self.stats.dred_recovered += 1;
+            Ok(Some(offset))
+        } else {
+            self.stats.concealed += 1;
+            Ok(None)
+        }
+    }
+
+    /// Get the output length, in interleaved samples, used for a concealed or
+    /// recovered frame.
+    fn conceal_len(&self, decoder: &mut Decoder, available: usize) -> Result<usize> {
+        let rate = decoder.get_sample_rate()?;
+        let samples = Timestamp48k(self.last_duration as u64).to_samples(rate);
+        let len = samples as usize * decoder.channels as usize;
+        Ok(len.min(available))
+    }
+
+    fn extend_seq(&self, seq: u16) -> u64 {
+        match self.highest_seq {
+            None => seq as u64 + (1 << 16),
+            Some(high) => {
+                let delta = seq.wrapping_sub(high as u16) as i16 as i64;
+                (high as i64 + delta).max(0) as u64
+            }
+        }
+    }
+
+    fn notify(&mut self, event: Event) {
+        let target_delay = self.policy.on_event(event, self.target_delay);
+        if target_delay != self.target_delay {
+            event!(debug, ?event, target_delay, "changed target delay");
+        }
+        self.target_delay = target_delay;
+    }
+}
+
+impl fmt::Debug for JitterBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JitterBuffer")
+            .field("packets", &self.packets.len())
+            .field("buffered", &self.buffered)
+            .field("target_delay", &self.target_delay)
+            .field("next_seq", &self.next_seq)
+            .field("playing", &self.playing)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
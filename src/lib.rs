@@ -10,50 +10,160 @@
 //!
 //! Only brief descriptions are included here. For detailed information, consult
 //! the [libopus documentation](https://opus-codec.org/docs/opus_api-1.1.2/).
+//!
+//! Without the default `std` feature the crate is `no_std`, needing only
+//! `alloc`. The encoder, decoder, and packet utilities remain available, while
+//! I/O adapters, containers, and threaded pipelines are left out.
 #![warn(missing_docs)]
-
+#![deny(clippy::unwrap_used, clippy::expect_used)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+
+#[cfg(feature = "cpal")]
+extern crate cpal;
+#[cfg(feature = "dasp")]
+extern crate dasp_frame;
+#[cfg(feature = "dasp")]
+extern crate dasp_sample;
+#[cfg(feature = "tokio")]
+extern crate futures_core;
+#[cfg(feature = "tokio")]
+extern crate futures_sink;
+#[cfg(feature = "hound")]
+extern crate hound;
 extern crate libc;
+#[cfg(feature = "memmap2")]
+extern crate memmap2;
 extern crate opus_sys as ffi;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "rodio")]
+extern crate rodio;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "symphonia")]
+extern crate symphonia_core;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "http")]
+extern crate ureq;
+
+#[cfg(all(
+    feature = "disable-float-api",
+    any(
+        feature = "cpal",
+        feature = "dasp",
+        feature = "hound",
+        feature = "resample",
+        feature = "symphonia"
+    )
+))]
+compile_error!(
+    "the `cpal`, `dasp`, `hound`, `resample`, and `symphonia` features need the float API"
+);
+
+/// The parts of `std` available from `core` and `alloc`, so that paths
+/// through `std` resolve the same way on `no_std` targets.
+#[cfg(not(feature = "std"))]
+mod std {
+    pub use alloc::{boxed, collections, vec};
+    pub use core::*;
+}
 
 use std::ffi::CStr;
 use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
 
 use libc::c_int;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// Tracing
+
+// Emit a `tracing` event at the level of the macro `$level` of `tracing`,
+// when the feature is enabled.
+macro_rules! event {
+	($level:ident, $($arg:tt)+) => {
+		#[cfg(feature = "tracing")]
+		::tracing::$level!(target: "opus", $($arg)+);
+	};
+}
+
+// Enter a trace level `tracing` span until the end of the enclosing block,
+// when the feature is enabled.
+macro_rules! span {
+	($($arg:tt)+) => {
+		#[cfg(feature = "tracing")]
+		let _span = ::tracing::trace_span!(target: "opus", $($arg)+).entered();
+	};
+}
 
 // ============================================================================
 // Constants
 
 // Generic CTLs
-const OPUS_RESET_STATE: c_int = 4028; // void
-const OPUS_GET_FINAL_RANGE: c_int = 4031; // out *u32
-const OPUS_GET_BANDWIDTH: c_int = 4009; // out *i32
-const OPUS_GET_SAMPLE_RATE: c_int = 4029; // out *i32
+const OPUS_RESET_STATE: c_int = ffi::CtlRequest::ResetState as c_int; // void
+const OPUS_GET_FINAL_RANGE: c_int = ffi::CtlRequest::GetFinalRange as c_int; // out *u32
+const OPUS_GET_BANDWIDTH: c_int = ffi::CtlRequest::GetBandwidth as c_int; // out *i32
+const OPUS_GET_SAMPLE_RATE: c_int = ffi::CtlRequest::GetSampleRate as c_int; // out *i32
 
 // Encoder CTLs
-const OPUS_SET_BITRATE: c_int = 4002; // in i32
-const OPUS_GET_BITRATE: c_int = 4003; // out *i32
-const OPUS_SET_VBR: c_int = 4006; // in i32
-const OPUS_GET_VBR: c_int = 4007; // out *i32
-const OPUS_SET_VBR_CONSTRAINT: c_int = 4020; // in i32
-const OPUS_GET_VBR_CONSTRAINT: c_int = 4021; // out *i32
-const OPUS_SET_INBAND_FEC: c_int = 4012; // in i32
-const OPUS_GET_INBAND_FEC: c_int = 4013; // out *i32
-const OPUS_SET_PACKET_LOSS_PERC: c_int = 4014; // in i32
-const OPUS_GET_PACKET_LOSS_PERC: c_int = 4015; // out *i32
-const OPUS_GET_LOOKAHEAD: c_int = 4027; // out *i32
+const OPUS_SET_BITRATE: c_int = ffi::CtlRequest::SetBitrate as c_int; // in i32
+const OPUS_GET_BITRATE: c_int = ffi::CtlRequest::GetBitrate as c_int; // out *i32
+const OPUS_SET_MAX_BANDWIDTH: c_int = ffi::CtlRequest::SetMaxBandwidth as c_int; // in i32
+const OPUS_GET_MAX_BANDWIDTH: c_int = ffi::CtlRequest::GetMaxBandwidth as c_int; // out *i32
+const OPUS_SET_VBR: c_int = ffi::CtlRequest::SetVbr as c_int; // in i32
+const OPUS_GET_VBR: c_int = ffi::CtlRequest::GetVbr as c_int; // out *i32
+const OPUS_SET_BANDWIDTH: c_int = ffi::CtlRequest::SetBandwidth as c_int; // in i32
+const OPUS_SET_FORCE_CHANNELS: c_int = ffi::CtlRequest::SetForceChannels as c_int; // in i32
+const OPUS_GET_FORCE_CHANNELS: c_int = ffi::CtlRequest::GetForceChannels as c_int; // out *i32
+const OPUS_SET_COMPLEXITY: c_int = ffi::CtlRequest::SetComplexity as c_int; // in i32
+const OPUS_GET_COMPLEXITY: c_int = ffi::CtlRequest::GetComplexity as c_int; // out *i32
+const OPUS_SET_VBR_CONSTRAINT: c_int = ffi::CtlRequest::SetVbrConstraint as c_int; // in i32
+const OPUS_GET_VBR_CONSTRAINT: c_int = ffi::CtlRequest::GetVbrConstraint as c_int; // out *i32
+const OPUS_SET_INBAND_FEC: c_int = ffi::CtlRequest::SetInbandFec as c_int; // in i32
+const OPUS_GET_INBAND_FEC: c_int = ffi::CtlRequest::GetInbandFec as c_int; // out *i32
+const OPUS_SET_PACKET_LOSS_PERC: c_int = ffi::CtlRequest::SetPacketLossPerc as c_int; // in i32
+const OPUS_GET_PACKET_LOSS_PERC: c_int = ffi::CtlRequest::GetPacketLossPerc as c_int; // out *i32
+const OPUS_SET_DTX: c_int = ffi::CtlRequest::SetDtx as c_int; // in i32
+const OPUS_GET_DTX: c_int = ffi::CtlRequest::GetDtx as c_int; // out *i32
+const OPUS_SET_SIGNAL: c_int = ffi::CtlRequest::SetSignal as c_int; // in i32
+const OPUS_GET_SIGNAL: c_int = ffi::CtlRequest::GetSignal as c_int; // out *i32
+const OPUS_GET_LOOKAHEAD: c_int = ffi::CtlRequest::GetLookahead as c_int; // out *i32
+const OPUS_SET_EXPERT_FRAME_DURATION: c_int = ffi::CtlRequest::SetExpertFrameDuration as c_int; // in i32
+const OPUS_GET_EXPERT_FRAME_DURATION: c_int = ffi::CtlRequest::GetExpertFrameDuration as c_int; // out *i32
+#[cfg(opus_ge_1_4)]
+const OPUS_GET_IN_DTX: c_int = ffi::CtlRequest::GetInDtx as c_int; // out *i32
+#[cfg(opus_ge_1_5)]
+const OPUS_SET_DRED_DURATION: c_int = ffi::CtlRequest::SetDredDuration as c_int; // in i32
+#[cfg(opus_ge_1_5)]
+const OPUS_GET_DRED_DURATION: c_int = ffi::CtlRequest::GetDredDuration as c_int; // out *i32
+
+// Multistream CTLs
+const OPUS_MULTISTREAM_GET_ENCODER_STATE: c_int =
+    ffi::CtlRequest::MultistreamGetEncoderState as c_int; // in i32, out **OpusEncoder
 
 // Decoder CTLs
-const OPUS_SET_GAIN: c_int = 4034; // in i32
-const OPUS_GET_GAIN: c_int = 4045; // out *i32
-const OPUS_GET_LAST_PACKET_DURATION: c_int = 4039; // out *i32
-const OPUS_GET_PITCH: c_int = 4033; // out *i32
+const OPUS_SET_GAIN: c_int = ffi::CtlRequest::SetGain as c_int; // in i32
+const OPUS_GET_GAIN: c_int = ffi::CtlRequest::GetGain as c_int; // out *i32
+const OPUS_GET_LAST_PACKET_DURATION: c_int = ffi::CtlRequest::GetLastPacketDuration as c_int; // out *i32
+const OPUS_GET_PITCH: c_int = ffi::CtlRequest::GetPitch as c_int; // out *i32
 
 // Bitrate
-const OPUS_AUTO: c_int = -1000;
-const OPUS_BITRATE_MAX: c_int = -1;
+const OPUS_AUTO: c_int = ffi::OPUS_AUTO;
+const OPUS_BITRATE_MAX: c_int = ffi::OPUS_BITRATE_MAX;
 
 /// The possible applications for the codec.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Application {
     /// Best for most VoIP/videoconference applications where listening quality
     /// and intelligibility matter most.
@@ -67,6 +177,7 @@ pub enum Application {
 
 /// The available channel setings.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Channels {
     /// One channel.
     Mono = 1,
@@ -76,6 +187,7 @@ pub enum Channels {
 
 /// The available bandwidth level settings.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Bandwidth {
     /// Auto/default setting.
     Auto = -1000,
@@ -112,6 +224,94 @@ impl Bandwidth {
     }
 }
 
+/// The type of signal being encoded, used as a hint for mode selection.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Signal {
+    /// Let the encoder detect the signal type.
+    Auto = -1000,
+    /// Bias thresholds towards choosing LPC or hybrid modes.
+    Voice = 3001,
+    /// Bias thresholds towards choosing MDCT modes.
+    Music = 3002,
+}
+
+impl Signal {
+    fn from_int(value: i32) -> Option<Signal> {
+        Some(match value {
+            -1000 => Signal::Auto,
+            3001 => Signal::Voice,
+            3002 => Signal::Music,
+            _ => return None,
+        })
+    }
+}
+
+/// The frame durations an encoder can be restricted to.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FrameDuration {
+    /// Use the duration of the input passed to each encode call (default).
+    Arg = 5000,
+    /// 2.5 ms frames.
+    Ms2_5 = 5001,
+    /// 5 ms frames.
+    Ms5 = 5002,
+    /// 10 ms frames.
+    Ms10 = 5003,
+    /// 20 ms frames.
+    Ms20 = 5004,
+    /// 40 ms frames.
+    Ms40 = 5005,
+    /// 60 ms frames.
+    Ms60 = 5006,
+    /// 80 ms frames.
+    Ms80 = 5007,
+    /// 100 ms frames.
+    Ms100 = 5008,
+    /// 120 ms frames.
+    Ms120 = 5009,
+}
+
+impl FrameDuration {
+    fn from_int(value: i32) -> Option<FrameDuration> {
+        use FrameDuration::*;
+        Some(match value {
+            5000 => Arg,
+            5001 => Ms2_5,
+            5002 => Ms5,
+            5003 => Ms10,
+            5004 => Ms20,
+            5005 => Ms40,
+            5006 => Ms60,
+            5007 => Ms80,
+            5008 => Ms100,
+            5009 => Ms120,
+            _ => return None,
+        })
+    }
+
+    /// Get the number of samples per channel in a frame of this duration, or
+    /// `None` for `Arg`.
+    pub fn samples(self, sample_rate: u32) -> Option<usize> {
+        use FrameDuration::*;
+        // durations in units of 2.5 ms
+        let units = match self {
+            Arg => return None,
+            Ms2_5 => 1,
+            Ms5 => 2,
+            Ms10 => 4,
+            Ms20 => 8,
+            Ms40 => 16,
+            Ms60 => 24,
+            Ms80 => 32,
+            Ms100 => 40,
+            Ms120 => 48,
+        };
+        Some(sample_rate as usize * units / 400)
+    }
+}
+
 /// Possible error codes.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum ErrorCode {
@@ -153,12 +353,13 @@ impl ErrorCode {
         // should always be ASCII and non-null for any input
         unsafe { CStr::from_ptr(ffi::opus_strerror(self as c_int)) }
             .to_str()
-            .unwrap()
+            .unwrap_or("unknown error")
     }
 }
 
 /// Possible bitrates.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Bitrate {
     /// Explicit bitrate choice (in bits/second).
     Bits(i32),
@@ -168,6 +369,18 @@ pub enum Bitrate {
     Auto,
 }
 
+/// How `Decoder::decode_all` treats a packet which fails to decode.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ErrorPolicy {
+    /// Leave the packet out of the output.
+    Skip,
+    /// Replace the packet with loss concealment of the last packet's
+    /// duration, or 20 ms before any packet has been decoded.
+    Plc,
+    /// Stop and return the error, keeping the output of earlier packets.
+    Fail,
+}
+
 /// Get the libopus version string.
 ///
 /// Applications may look for the substring "-fixed" in the version string to
@@ -177,9 +390,12 @@ pub fn version() -> &'static str {
     // verison string should always be ASCII
     unsafe { CStr::from_ptr(ffi::opus_get_version_string()) }
         .to_str()
-        .unwrap()
+        .unwrap_or("unknown")
 }
 
+mod info;
+pub use info::{capabilities, runtime_info, Capabilities, RuntimeInfo, SimdPath};
+
 macro_rules! ffi {
 	($f:ident $(, $rest:expr)*) => {
 		match unsafe { ffi::$f($($rest),*) } {
@@ -211,10 +427,19 @@ macro_rules! enc_ctl {
 }
 
 /// An Opus encoder with associated state.
+///
+/// The state is allocated by libopus unless the encoder was created with
+/// `new_in`, in which case it lives in the given `Storage`.
+///
+/// An encoder is `Send` but not `Sync`: libopus lets a state move between
+/// threads but not be used by two at once. `SyncEncoder` wraps one in a
+/// mutex for shared use.
 #[derive(Debug)]
-pub struct Encoder {
+pub struct Encoder<S: Storage = Heap> {
     ptr: *mut ffi::OpusEncoder,
     channels: Channels,
+    storage: S,
+    stats: EncoderStats,
 }
 
 impl Encoder {
@@ -229,18 +454,65 @@ impl Encoder {
                 &mut error,
             )
         };
+        info::trace_build_config();
         if error != ffi::OPUS_OK || ptr.is_null() {
             Err(Error::from_code("opus_encoder_create", error))
         } else {
             Ok(Encoder {
                 ptr: ptr,
                 channels: channels,
+                storage: Heap,
+                stats: EncoderStats::default(),
             })
         }
     }
 
+    /// Get the size in bytes of the state of an encoder with the given
+    /// number of channels, as required by `new_in`.
+    pub fn state_size(channels: Channels) -> usize {
+        unsafe { ffi::opus_encoder_get_size(channels as c_int) as usize }
+    }
+
+    /// Encode a PCM buffer of any length frame by frame.
+    ///
+    /// Frames have the duration set by `set_expert_frame_duration`, or 20 ms
+    /// if none was set. The final partial frame is padded with silence.
+    pub fn encode_iter<'a>(&'a mut self, pcm: &'a [i16]) -> chunk::EncodeIter<'a> {
+        chunk::EncodeIter::new(self, pcm)
+    }
+}
+
+impl<S: Storage> Encoder<S> {
+    /// Initialize an encoder in caller-provided storage, without allocating.
+    ///
+    /// The storage must hold at least `Encoder::state_size(channels)` bytes
+    /// aligned to `storage::STATE_ALIGN`.
+    pub fn new_in(
+        mut storage: S,
+        sample_rate: u32,
+        channels: Channels,
+        mode: Application,
+    ) -> Result<Encoder<S>> {
+        info::trace_build_config();
+        let size = unsafe { ffi::opus_encoder_get_size(channels as c_int) };
+        let ptr = storage::place(&mut storage, "opus_encoder_init", size)? as *mut ffi::OpusEncoder;
+        let code = unsafe {
+            ffi::opus_encoder_init(ptr, sample_rate as i32, channels as c_int, mode as c_int)
+        };
+        if code != ffi::OPUS_OK {
+            return Err(Error::from_code("opus_encoder_init", code));
+        }
+        Ok(Encoder {
+            ptr,
+            channels,
+            storage,
+            stats: EncoderStats::default(),
+        })
+    }
+
     /// Encode an Opus frame.
     pub fn encode(&mut self, input: &[i16], output: &mut [u8]) -> Result<usize> {
+        span!("Encoder::encode", samples = input.len());
         let len = ffi!(
             opus_encode,
             self.ptr,
@@ -249,11 +521,17 @@ impl Encoder {
             output.as_mut_ptr(),
             len(output)
         );
+        self.record(&output[..len as usize]);
         Ok(len as usize)
     }
 
     /// Encode an Opus frame from floating point input.
+    ///
+    /// With the `fixed-point` feature the samples are converted to integers
+    /// first, so `encode` is cheaper.
+    #[cfg(not(feature = "disable-float-api"))]
     pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        span!("Encoder::encode_float", samples = input.len());
         let len = ffi!(
             opus_encode_float,
             self.ptr,
@@ -262,9 +540,27 @@ impl Encoder {
             output.as_mut_ptr(),
             len(output)
         );
+        self.record(&output[..len as usize]);
         Ok(len as usize)
     }
 
+    /// Get the counts and sizes of the packets produced since the encoder
+    /// was created or `reset_stats` was called.
+    pub fn stats(&self) -> &EncoderStats {
+        &self.stats
+    }
+
+    /// Reset the counts of `stats` to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = EncoderStats::default();
+    }
+
+    fn record(&mut self, packet: &[u8]) {
+        let duration = packet::get_nb_samples(packet, 48000).unwrap_or(0);
+        event!(trace, bytes = packet.len(), duration, "encoded packet");
+        self.stats.record(packet, duration);
+    }
+
     /// Encode an Opus frame to a new buffer.
     pub fn encode_vec(&mut self, input: &[i16], max_size: usize) -> Result<Vec<u8>> {
         let mut output: Vec<u8> = vec![0; max_size];
@@ -274,6 +570,7 @@ impl Encoder {
     }
 
     /// Encode an Opus frame from floating point input to a new buffer.
+    #[cfg(not(feature = "disable-float-api"))]
     pub fn encode_vec_float(&mut self, input: &[f32], max_size: usize) -> Result<Vec<u8>> {
         let mut output: Vec<u8> = vec![0; max_size];
         let result = self.encode_float(input, output.as_mut_slice())?;
@@ -398,12 +695,143 @@ impl Encoder {
         Ok(value)
     }
 
+    /// Sets the encoder's computational complexity, from 0 to 10 inclusive.
+    ///
+    /// Higher values buy quality with CPU time; 10 is the default. With
+    /// libopus 1.5, the DRED encoder adds the cost of a neural network at any
+    /// complexity once `set_dred_duration` enables it.
+    pub fn set_complexity(&mut self, value: i32) -> Result<()> {
+        enc_ctl!(self, OPUS_SET_COMPLEXITY, value);
+        Ok(())
+    }
+
+    /// Gets the encoder's complexity configuration.
+    pub fn get_complexity(&mut self) -> Result<i32> {
+        let mut value: i32 = 0;
+        enc_ctl!(self, OPUS_GET_COMPLEXITY, &mut value);
+        Ok(value)
+    }
+
+    /// Sets the maximum bandpass that the encoder will select automatically.
+    pub fn set_max_bandwidth(&mut self, value: Bandwidth) -> Result<()> {
+        enc_ctl!(self, OPUS_SET_MAX_BANDWIDTH, value as i32);
+        Ok(())
+    }
+
+    /// Gets the encoder's configured maximum allowed bandpass.
+    pub fn get_max_bandwidth(&mut self) -> Result<Bandwidth> {
+        let mut value: i32 = 0;
+        enc_ctl!(self, OPUS_GET_MAX_BANDWIDTH, &mut value);
+        Bandwidth::decode(value, "opus_encoder_ctl(OPUS_GET_MAX_BANDWIDTH)")
+    }
+
+    /// Sets the encoder's bandpass to a specific value, or `Auto` to let the
+    /// encoder choose.
+    pub fn set_bandwidth(&mut self, value: Bandwidth) -> Result<()> {
+        enc_ctl!(self, OPUS_SET_BANDWIDTH, value as i32);
+        Ok(())
+    }
+
+    /// Configures the type of signal being encoded.
+    pub fn set_signal(&mut self, value: Signal) -> Result<()> {
+        enc_ctl!(self, OPUS_SET_SIGNAL, value as i32);
+        Ok(())
+    }
+
+    /// Gets the encoder's configured signal type.
+    pub fn get_signal(&mut self) -> Result<Signal> {
+        let mut value: i32 = 0;
+        enc_ctl!(self, OPUS_GET_SIGNAL, &mut value);
+        Signal::from_int(value).ok_or_else(|| Error::bad_arg("opus_encoder_ctl(OPUS_GET_SIGNAL)"))
+    }
+
+    /// Configures the encoder's use of discontinuous transmission (DTX).
+    pub fn set_dtx(&mut self, value: bool) -> Result<()> {
+        let value: i32 = if value { 1 } else { 0 };
+        enc_ctl!(self, OPUS_SET_DTX, value);
+        Ok(())
+    }
+
+    /// Gets encoder's configured use of discontinuous transmission.
+    pub fn get_dtx(&mut self) -> Result<bool> {
+        let mut value: i32 = 0;
+        enc_ctl!(self, OPUS_GET_DTX, &mut value);
+        Ok(value != 0)
+    }
+
+    /// Determine whether the last frame was encoded in discontinuous
+    /// transmission, as silence which the receiver fills in.
+    ///
+    /// Only available when libopus 1.4 or later is linked.
+    #[cfg(opus_ge_1_4)]
+    pub fn in_dtx(&mut self) -> Result<bool> {
+        let mut value: i32 = 0;
+        enc_ctl!(self, OPUS_GET_IN_DTX, &mut value);
+        Ok(value != 0)
+    }
+
+    /// Set how much audio, in units of 10 ms, each packet carries as Deep
+    /// REDundancy (DRED), from which a receiver can reconstruct up to about a
+    /// second of audio lost before it. 0 disables DRED, the default.
+    ///
+    /// DRED is only sent when the expected packet loss set with
+    /// `set_packet_loss_perc` is above zero and the bitrate leaves room for
+    /// it. Only available when libopus 1.5 or later is linked, and fails with
+    /// `ErrorCode::Unimplemented` unless libopus was built with DRED, as the
+    /// `dred` feature does.
+    #[cfg(opus_ge_1_5)]
+    pub fn set_dred_duration(&mut self, value: i32) -> Result<()> {
+        enc_ctl!(self, OPUS_SET_DRED_DURATION, value);
+        Ok(())
+    }
+
+    /// Get the amount of Deep REDundancy added to each packet, in units of
+    /// 10 ms.
+    ///
+    /// Only available when libopus 1.5 or later is linked.
+    #[cfg(opus_ge_1_5)]
+    pub fn get_dred_duration(&mut self) -> Result<i32> {
+        let mut value: i32 = 0;
+        enc_ctl!(self, OPUS_GET_DRED_DURATION, &mut value);
+        Ok(value)
+    }
+
+    /// Restricts the encoder to a fixed frame duration.
+    ///
+    /// Input passed to the encode functions must then be at least this long,
+    /// and only the first frame's worth of samples is encoded.
+    pub fn set_expert_frame_duration(&mut self, value: FrameDuration) -> Result<()> {
+        enc_ctl!(self, OPUS_SET_EXPERT_FRAME_DURATION, value as i32);
+        Ok(())
+    }
+
+    /// Gets the encoder's configured frame duration.
+    pub fn get_expert_frame_duration(&mut self) -> Result<FrameDuration> {
+        let mut value: i32 = 0;
+        enc_ctl!(self, OPUS_GET_EXPERT_FRAME_DURATION, &mut value);
+        FrameDuration::from_int(value)
+            .ok_or_else(|| Error::bad_arg("opus_encoder_ctl(OPUS_GET_EXPERT_FRAME_DURATION)"))
+    }
+
+    /// Gets the number of samples per channel in each frame, as configured
+    /// by `set_expert_frame_duration` or 20 ms by default.
+    fn frame_size(&mut self) -> Result<usize> {
+        let rate = self.get_sample_rate()?;
+        let duration = match self.get_expert_frame_duration()? {
+            FrameDuration::Arg => FrameDuration::Ms20,
+            duration => duration,
+        };
+        Ok(duration.samples(rate).unwrap_or(0))
+    }
+
     // TODO: Encoder-specific CTLs
 }
 
-impl Drop for Encoder {
+impl<S: Storage> Drop for Encoder<S> {
     fn drop(&mut self) {
-        unsafe { ffi::opus_encoder_destroy(self.ptr) }
+        if storage::is_heap(&mut self.storage) {
+            unsafe { ffi::opus_encoder_destroy(self.ptr) }
+        }
     }
 }
 
@@ -417,7 +845,48 @@ impl Drop for Encoder {
 // compilation mode intended for embedded platforms forbids multithreaded use
 // of the library as a whole rather than on a per-state basis, but the opus-sys
 // crate does not use this mode.
-unsafe impl Send for Encoder {}
+//
+// Nothing in a state is safe to use concurrently, so none are `Sync`.
+unsafe impl<S: Storage + Send> Send for Encoder<S> {}
+
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "std")]
+pub use shared::SyncEncoder;
+
+// ============================================================================
+// Encoder Options
+
+mod options;
+pub use options::{EncoderOptions, Preset};
+
+// ============================================================================
+// Frame Chunking
+
+pub mod chunk;
+
+// ============================================================================
+// Buffer Pools
+
+pub mod pool;
+
+// ============================================================================
+// Sample Conversion
+
+pub mod convert;
+
+// ============================================================================
+// Coder Storage
+
+pub mod storage;
+pub use storage::{memory_requirements, MemoryConfig, MemoryReport};
+use storage::{Heap, Storage};
+
+// ============================================================================
+// Coder Statistics
+
+mod stats;
+pub use stats::{DecoderStats, EncoderStats, STATS_WINDOW};
 
 // ============================================================================
 // Decoder
@@ -429,10 +898,16 @@ macro_rules! dec_ctl {
 }
 
 /// An Opus decoder with associated state.
+///
+/// As with `Encoder`, the state may be placed in caller-provided `Storage`
+/// with `new_in`, and the decoder may be sent to another thread but not
+/// shared between threads without locking.
 #[derive(Debug)]
-pub struct Decoder {
+pub struct Decoder<S: Storage = Heap> {
     ptr: *mut ffi::OpusDecoder,
     channels: Channels,
+    storage: S,
+    stats: DecoderStats,
 }
 
 impl Decoder {
@@ -441,18 +916,53 @@ impl Decoder {
         let mut error = 0;
         let ptr =
             unsafe { ffi::opus_decoder_create(sample_rate as i32, channels as c_int, &mut error) };
+        info::trace_build_config();
         if error != ffi::OPUS_OK || ptr.is_null() {
             Err(Error::from_code("opus_decoder_create", error))
         } else {
             Ok(Decoder {
                 ptr: ptr,
                 channels: channels,
+                storage: Heap,
+                stats: DecoderStats::default(),
             })
         }
     }
 
+    /// Get the size in bytes of the state of a decoder with the given
+    /// number of channels, as required by `new_in`.
+    pub fn state_size(channels: Channels) -> usize {
+        unsafe { ffi::opus_decoder_get_size(channels as c_int) as usize }
+    }
+}
+
+impl<S: Storage> Decoder<S> {
+    /// Initialize a decoder in caller-provided storage, without allocating.
+    ///
+    /// The storage must hold at least `Decoder::state_size(channels)` bytes
+    /// aligned to `storage::STATE_ALIGN`.
+    pub fn new_in(mut storage: S, sample_rate: u32, channels: Channels) -> Result<Decoder<S>> {
+        info::trace_build_config();
+        let size = unsafe { ffi::opus_decoder_get_size(channels as c_int) };
+        let ptr = storage::place(&mut storage, "opus_decoder_init", size)? as *mut ffi::OpusDecoder;
+        let code = unsafe { ffi::opus_decoder_init(ptr, sample_rate as i32, channels as c_int) };
+        if code != ffi::OPUS_OK {
+            return Err(Error::from_code("opus_decoder_init", code));
+        }
+        Ok(Decoder {
+            ptr,
+            channels,
+            storage,
+            stats: DecoderStats::default(),
+        })
+    }
+
     /// Decode an Opus packet.
+    ///
+    /// The audio is written straight into `output` without allocating, so
+    /// this may be called from real-time audio threads.
     pub fn decode(&mut self, input: &[u8], output: &mut [i16], fec: bool) -> Result<usize> {
+        span!("Decoder::decode", bytes = input.len(), fec);
         let ptr = match input.len() {
             0 => std::ptr::null(),
             _ => input.as_ptr(),
@@ -466,11 +976,17 @@ impl Decoder {
             len(output) / self.channels as c_int,
             fec as c_int
         );
+        self.stats.record(input, fec);
         Ok(len as usize)
     }
 
     /// Decode an Opus packet with floating point output.
+    ///
+    /// With the `fixed-point` feature the samples are converted from integers
+    /// afterwards, so `decode` is cheaper.
+    #[cfg(not(feature = "disable-float-api"))]
     pub fn decode_float(&mut self, input: &[u8], output: &mut [f32], fec: bool) -> Result<usize> {
+        span!("Decoder::decode_float", bytes = input.len(), fec);
         let ptr = match input.len() {
             0 => std::ptr::null(),
             _ => input.as_ptr(),
@@ -484,9 +1000,87 @@ impl Decoder {
             len(output) / self.channels as c_int,
             fec as c_int
         );
+        self.stats.record(input, fec);
         Ok(len as usize)
     }
 
+    /// Decode an Opus packet into possibly uninitialized memory.
+    ///
+    /// Returns the initialized prefix of `output` holding the decoded
+    /// interleaved samples. Like `decode`, this never allocates.
+    pub fn decode_into_uninit<'a>(
+        &mut self,
+        input: &[u8],
+        output: &'a mut [MaybeUninit<i16>],
+        fec: bool,
+    ) -> Result<&'a mut [i16]> {
+        span!("Decoder::decode_into_uninit", bytes = input.len(), fec);
+        let ptr = match input.len() {
+            0 => std::ptr::null(),
+            _ => input.as_ptr(),
+        };
+        let len = ffi!(
+            opus_decode,
+            self.ptr,
+            ptr,
+            len(input),
+            output.as_mut_ptr() as *mut i16,
+            len(output) / self.channels as c_int,
+            fec as c_int
+        );
+        self.stats.record(input, fec);
+        let samples = len as usize * self.channels as usize;
+        // libopus has initialized exactly `samples` values.
+        Ok(unsafe { std::slice::from_raw_parts_mut(output.as_mut_ptr() as *mut i16, samples) })
+    }
+
+    /// Decode an Opus packet with floating point output into possibly
+    /// uninitialized memory.
+    ///
+    /// Returns the initialized prefix of `output` holding the decoded
+    /// interleaved samples. Like `decode_float`, this never allocates.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn decode_float_into_uninit<'a>(
+        &mut self,
+        input: &[u8],
+        output: &'a mut [MaybeUninit<f32>],
+        fec: bool,
+    ) -> Result<&'a mut [f32]> {
+        span!(
+            "Decoder::decode_float_into_uninit",
+            bytes = input.len(),
+            fec
+        );
+        let ptr = match input.len() {
+            0 => std::ptr::null(),
+            _ => input.as_ptr(),
+        };
+        let len = ffi!(
+            opus_decode_float,
+            self.ptr,
+            ptr,
+            len(input),
+            output.as_mut_ptr() as *mut f32,
+            len(output) / self.channels as c_int,
+            fec as c_int
+        );
+        self.stats.record(input, fec);
+        let samples = len as usize * self.channels as usize;
+        // libopus has initialized exactly `samples` values.
+        Ok(unsafe { std::slice::from_raw_parts_mut(output.as_mut_ptr() as *mut f32, samples) })
+    }
+
+    /// Get the counts of frames decoded, concealed, and recovered since the
+    /// decoder was created or `reset_stats` was called.
+    pub fn stats(&self) -> DecoderStats {
+        self.stats
+    }
+
+    /// Reset the counts of `stats` to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = DecoderStats::default();
+    }
+
     /// Get the number of samples of an Opus packet.
     pub fn get_nb_samples(&self, packet: &[u8]) -> Result<usize> {
         let len = ffi!(
@@ -498,6 +1092,59 @@ impl Decoder {
         Ok(len as usize)
     }
 
+    /// Decode a sequence of packets, appending the audio to `out`.
+    ///
+    /// Each packet is sized up front and decoded straight into the end of
+    /// `out`. Packets which fail to decode, including empty ones, are handled
+    /// according to `policy`. Returns the number of samples per channel
+    /// appended.
+    pub fn decode_all<'a, I>(
+        &mut self,
+        packets: I,
+        out: &mut Vec<i16>,
+        policy: ErrorPolicy,
+    ) -> Result<usize>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let channels = self.channels as usize;
+        let mut last = self.get_sample_rate()? as usize / 50;
+        let mut total = 0;
+        for packet in packets {
+            let start = out.len();
+            let result = self.get_nb_samples(packet).and_then(|samples| {
+                out.resize(start + samples * channels, 0);
+                self.decode(packet, &mut out[start..], false)
+            });
+            let samples = match result {
+                Ok(samples) => {
+                    last = samples;
+                    samples
+                }
+                Err(err) => match policy {
+                    ErrorPolicy::Skip => 0,
+                    ErrorPolicy::Plc => {
+                        out.resize(start + last * channels, 0);
+                        match self.decode(&[], &mut out[start..], false) {
+                            Ok(samples) => samples,
+                            Err(err) => {
+                                out.truncate(start);
+                                return Err(err);
+                            }
+                        }
+                    }
+                    ErrorPolicy::Fail => {
+                        out.truncate(start);
+                        return Err(err);
+                    }
+                },
+            };
+            out.truncate(start + samples * channels);
+            total += samples;
+        }
+        Ok(total)
+    }
+
     // ------------
     // Generic CTLs
 
@@ -551,6 +1198,34 @@ impl Decoder {
         Ok(value)
     }
 
+    /// Sets the decoder's computational complexity, from 0 to 10 inclusive,
+    /// which enables the neural network features compiled into libopus 1.5.
+    ///
+    /// Up to 4, the default 0 included, decoding is as in earlier releases.
+    /// From 5 lost packets are concealed with the deep PLC, which only costs
+    /// CPU time while packets are missing. At 6 speech is enhanced with LACE,
+    /// and from 7 with NoLACE, which is better and several times as costly;
+    /// both run on every SILK frame and can take more CPU time than decoding
+    /// itself. Features not compiled into libopus are skipped; see
+    /// `capabilities`.
+    ///
+    /// Only available when libopus 1.5 or later is linked.
+    #[cfg(opus_ge_1_5)]
+    pub fn set_complexity(&mut self, value: i32) -> Result<()> {
+        dec_ctl!(self, OPUS_SET_COMPLEXITY, value);
+        Ok(())
+    }
+
+    /// Gets the decoder's complexity configuration.
+    ///
+    /// Only available when libopus 1.5 or later is linked.
+    #[cfg(opus_ge_1_5)]
+    pub fn get_complexity(&mut self) -> Result<i32> {
+        let mut value: i32 = 0;
+        dec_ctl!(self, OPUS_GET_COMPLEXITY, &mut value);
+        Ok(value)
+    }
+
     /// Gets the duration (in samples) of the last packet successfully decoded
     /// or concealed.
     pub fn get_last_packet_duration(&mut self) -> Result<u32> {
@@ -572,14 +1247,36 @@ impl Decoder {
     }
 }
 
-impl Drop for Decoder {
+impl<S: Storage> Drop for Decoder<S> {
     fn drop(&mut self) {
-        unsafe { ffi::opus_decoder_destroy(self.ptr) }
+        if storage::is_heap(&mut self.storage) {
+            unsafe { ffi::opus_decoder_destroy(self.ptr) }
+        }
     }
 }
 
 // See `unsafe impl Send for Encoder`.
-unsafe impl Send for Decoder {}
+unsafe impl<S: Storage + Send> Send for Decoder<S> {}
+
+// ============================================================================
+// Fixed Channel Counts
+
+mod fixed;
+pub use fixed::{
+    FixedDecoder, FixedEncoder, MonoDecoder, MonoEncoder, StereoDecoder, StereoEncoder,
+};
+
+// ============================================================================
+// Custom Modes
+
+#[cfg(feature = "custom")]
+pub mod custom;
+
+// ============================================================================
+// Ambisonics Projection
+
+#[cfg(feature = "projection")]
+pub mod projection;
 
 // ============================================================================
 // Packet Analysis
@@ -702,18 +1399,47 @@ pub mod packet {
         let result = ffi!(opus_packet_unpad, packet.as_mut_ptr(), len(packet));
         Ok(result as usize)
     }
+
+    /// Pad a given multistream packet of `streams` streams to a larger size.
+    ///
+    /// The padding is added to the last stream's packet, as with `pad`.
+    pub fn multistream_pad(packet: &mut [u8], prev_len: usize, streams: u8) -> Result<usize> {
+        ffi!(
+            opus_multistream_packet_pad,
+            packet.as_mut_ptr(),
+            check_len(prev_len),
+            len(packet),
+            streams as c_int
+        );
+        Ok(packet.len())
+    }
+
+    /// Remove all padding from a given multistream packet of `streams`
+    /// streams, and rewrite the TOC sequence of each stream's packet to
+    /// minimize space usage.
+    pub fn multistream_unpad(packet: &mut [u8], streams: u8) -> Result<usize> {
+        let result = ffi!(
+            opus_multistream_packet_unpad,
+            packet.as_mut_ptr(),
+            len(packet),
+            streams as c_int
+        );
+        Ok(result as usize)
+    }
 }
 
 // ============================================================================
 // Float Soft Clipping
 
 /// Soft-clipping to bring a float signal within the [-1,1] range.
+#[cfg(not(feature = "disable-float-api"))]
 #[derive(Debug)]
 pub struct SoftClip {
     channels: Channels,
     memory: [f32; 2],
 }
 
+#[cfg(not(feature = "disable-float-api"))]
 impl SoftClip {
     /// Initialize a new soft-clipping state.
     pub fn new(channels: Channels) -> SoftClip {
@@ -740,6 +1466,8 @@ impl SoftClip {
 // Repacketizer
 
 /// A repacketizer used to merge together or split apart multiple Opus packets.
+///
+/// Like the codec states, it is `Send` but not `Sync`.
 #[derive(Debug)]
 pub struct Repacketizer {
     ptr: *mut ffi::OpusRepacketizer,
@@ -789,6 +1517,8 @@ impl Drop for Repacketizer {
 // See `unsafe impl Send for Encoder`.
 unsafe impl Send for Repacketizer {}
 
+mod superframe;
+
 // To understand why these lifetime bounds are needed, imagine that the
 // repacketizer keeps an internal Vec<&'buf [u8]>, which is added to by cat()
 // and accessed by get_nb_frames(), out(), and out_range(). To prove that these
@@ -862,47 +1592,854 @@ impl<'rp, 'buf> RepacketizerState<'rp, 'buf> {
 }
 
 // ============================================================================
-// TODO: Multistream API
+// Timestamps
+
+pub mod time;
+pub use time::Timestamp48k;
 
 // ============================================================================
-// Error Handling
+// Deep Redundancy
 
-/// Opus error Result alias.
-pub type Result<T> = std::result::Result<T, Error>;
+#[cfg(opus_ge_1_5)]
+pub mod dred;
 
-/// An error generated by the Opus library.
-#[derive(Debug)]
-pub struct Error {
-    function: &'static str,
-    code: ErrorCode,
-}
+// ============================================================================
+// Jitter Buffer
 
-impl Error {
-    fn bad_arg(what: &'static str) -> Error {
-        Error {
-            function: what,
-            code: ErrorCode::BadArg,
-        }
-    }
+pub mod jitter;
 
-    fn from_code(what: &'static str, code: c_int) -> Error {
-        Error {
-            function: what,
-            code: ErrorCode::from_int(code),
-        }
-    }
+// ============================================================================
+// Discontinuous Transmission
 
-    /// Get the name of the Opus function from which the error originated.
-    #[inline]
-    pub fn function(&self) -> &'static str {
-        self.function
-    }
+pub mod dtx;
 
-    /// Get a textual description of the error provided by Opus.
-    #[inline]
-    pub fn description(&self) -> &'static str {
-        self.code.description()
-    }
+// ============================================================================
+// Loss-Resilient Sending
+
+pub mod sender;
+
+// ============================================================================
+// Bitrate Ladders
+
+pub mod abr;
+
+// ============================================================================
+// Network Simulation
+
+pub mod simulate;
+
+// ============================================================================
+// Packet Pipelines
+
+#[cfg(feature = "std")]
+pub mod pipeline;
+
+// ============================================================================
+// I/O Adapters
+
+#[cfg(feature = "std")]
+pub mod io;
+
+// ============================================================================
+// Async Streams
+
+#[cfg(feature = "tokio")]
+pub mod stream;
+
+// ============================================================================
+// RTP
+
+#[cfg(feature = "std")]
+pub mod rtp;
+
+// ============================================================================
+// Ogg Opus
+
+#[cfg(feature = "std")]
+pub mod ogg;
+
+// ============================================================================
+// Audio Capture
+
+#[cfg(feature = "cpal")]
+pub mod capture;
+
+// ============================================================================
+// Symphonia Integration
+
+#[cfg(feature = "symphonia")]
+pub mod symphonia;
+
+// ============================================================================
+// dasp Frames
+
+#[cfg(feature = "dasp")]
+mod dasp;
+
+// ============================================================================
+// Quality Analysis
+
+#[cfg(feature = "std")]
+pub mod analysis;
+
+// ============================================================================
+// Loudness Normalization
+
+#[cfg(feature = "std")]
+pub mod loudness;
+
+// ============================================================================
+// Channel Mixing
+
+#[cfg(feature = "std")]
+pub mod mix;
+
+// ============================================================================
+// Resampling
+
+#[cfg(feature = "resample")]
+pub mod resample;
+
+// ============================================================================
+// Test Vectors
+
+#[cfg(feature = "testvectors")]
+pub mod testvectors;
+
+// ============================================================================
+// WAV Files
+
+#[cfg(feature = "hound")]
+pub mod wav;
+
+// ============================================================================
+// HTTP Sources
+
+#[cfg(feature = "http")]
+pub mod http;
+
+// ============================================================================
+// Batch Encoding
+
+#[cfg(feature = "rayon")]
+pub mod batch;
+
+// ============================================================================
+// Real-Time Pipelines
+
+#[cfg(feature = "std")]
+pub mod ring;
+
+#[cfg(all(feature = "std", not(target_family = "wasm")))]
+pub mod realtime;
+
+// ============================================================================
+// Multistream API
+
+macro_rules! ms_enc_ctl {
+	($this:ident, $ctl:ident $(, $rest:expr)*) => {
+		ctl!(opus_multistream_encoder_ctl, $this, $ctl, $($rest),*)
+	}
+}
+
+macro_rules! ms_dec_ctl {
+	($this:ident, $ctl:ident $(, $rest:expr)*) => {
+		ctl!(opus_multistream_decoder_ctl, $this, $ctl, $($rest),*)
+	}
+}
+
+/// The stream layout of a multistream encoder or decoder.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct StreamLayout {
+    /// The number of input or output channels.
+    pub channels: u8,
+    /// The total number of streams.
+    pub streams: u8,
+    /// The number of streams which are coupled stereo pairs.
+    pub coupled_streams: u8,
+    /// For each channel, the index of the stream channel it is coded in.
+    ///
+    /// Coupled streams occupy indices `2 * n` and `2 * n + 1`, followed by
+    /// the mono streams. Index 255 marks a silent channel.
+    pub mapping: Vec<u8>,
+}
+
+impl StreamLayout {
+    /// Get the layout of mapping family 255: each channel coded on its own
+    /// in a mono stream, with no meaning given to any of them, such as for
+    /// the separate microphones of a multitrack recording.
+    pub fn discrete(channels: u8) -> StreamLayout {
+        StreamLayout {
+            channels,
+            streams: channels,
+            coupled_streams: 0,
+            mapping: (0..channels).collect(),
+        }
+    }
+
+    /// Determine whether the layout is one libopus accepts: at least one
+    /// stream, no more coupled streams than streams, at most 255 stream
+    /// channels, and a mapping of each channel to one of them or to 255.
+    pub fn is_valid(&self) -> bool {
+        let total = self.streams as usize + self.coupled_streams as usize;
+        self.channels > 0
+            && self.streams > 0
+            && self.coupled_streams <= self.streams
+            && total <= 255
+            && self.mapping.len() == self.channels as usize
+            && self
+                .mapping
+                .iter()
+                .all(|&m| m == 255 || (m as usize) < total)
+    }
+
+    fn check(&self, what: &'static str) -> Result<()> {
+        if !self.is_valid() {
+            return Err(Error::bad_arg(what));
+        }
+        Ok(())
+    }
+}
+
+/// An Opus multistream encoder, coding many channels as several mono and
+/// stereo streams in a single packet.
+///
+/// For high channel counts, `ParallelMultistreamEncoder`, available with the
+/// `rayon` feature, can encode the streams on separate threads instead.
+///
+/// As with `Encoder`, it is `Send` but not `Sync`.
+#[derive(Debug)]
+pub struct MultistreamEncoder<S: Storage = Heap> {
+    ptr: *mut ffi::OpusMSEncoder,
+    layout: StreamLayout,
+    lfe_stream: Option<u8>,
+    storage: S,
+}
+
+impl MultistreamEncoder {
+    /// Create and initialize a multistream encoder with an explicit layout.
+    pub fn new(
+        sample_rate: u32,
+        layout: StreamLayout,
+        mode: Application,
+    ) -> Result<MultistreamEncoder> {
+        layout.check("opus_multistream_encoder_create")?;
+        let mut error = 0;
+        let ptr = unsafe {
+            ffi::opus_multistream_encoder_create(
+                sample_rate as i32,
+                layout.channels as c_int,
+                layout.streams as c_int,
+                layout.coupled_streams as c_int,
+                layout.mapping.as_ptr(),
+                mode as c_int,
+                &mut error,
+            )
+        };
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            Err(Error::from_code("opus_multistream_encoder_create", error))
+        } else {
+            Ok(MultistreamEncoder {
+                ptr,
+                layout,
+                lfe_stream: None,
+                storage: Heap,
+            })
+        }
+    }
+
+    /// Create and initialize a multistream encoder using the standard layout
+    /// for the given channel mapping family, as used by Ogg Opus.
+    ///
+    /// Family 1 is the surround layouts of up to eight channels, and family
+    /// 255 any number of independent channels, as given by
+    /// `StreamLayout::discrete`.
+    pub fn surround(
+        sample_rate: u32,
+        channels: u8,
+        mapping_family: u8,
+        mode: Application,
+    ) -> Result<MultistreamEncoder> {
+        let mut error = 0;
+        let mut streams = 0;
+        let mut coupled_streams = 0;
+        let mut mapping = vec![0u8; channels as usize];
+        let ptr = unsafe {
+            ffi::opus_multistream_surround_encoder_create(
+                sample_rate as i32,
+                channels as c_int,
+                mapping_family as c_int,
+                &mut streams,
+                &mut coupled_streams,
+                mapping.as_mut_ptr(),
+                mode as c_int,
+                &mut error,
+            )
+        };
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            Err(Error::from_code(
+                "opus_multistream_surround_encoder_create",
+                error,
+            ))
+        } else {
+            let layout = StreamLayout {
+                channels,
+                streams: streams as u8,
+                coupled_streams: coupled_streams as u8,
+                mapping,
+            };
+            Ok(MultistreamEncoder {
+                ptr,
+                lfe_stream: allocation::lfe_stream(&layout, mapping_family),
+                layout,
+                storage: Heap,
+            })
+        }
+    }
+
+    /// Get the size in bytes of the state of an encoder with the given
+    /// layout, as required by `new_in`.
+    pub fn state_size(layout: &StreamLayout) -> Result<usize> {
+        let size = unsafe {
+            ffi::opus_multistream_encoder_get_size(
+                layout.streams as c_int,
+                layout.coupled_streams as c_int,
+            )
+        };
+        match size {
+            0 => Err(Error::bad_arg("opus_multistream_encoder_get_size")),
+            size => Ok(size as usize),
+        }
+    }
+
+    /// Get the size in bytes of the state of an encoder using the standard
+    /// layout for the given channel mapping family, as required by
+    /// `surround_in`.
+    pub fn surround_state_size(channels: u8, mapping_family: u8) -> Result<usize> {
+        let size = unsafe {
+            ffi::opus_multistream_surround_encoder_get_size(
+                channels as c_int,
+                mapping_family as c_int,
+            )
+        };
+        match size {
+            0 => Err(Error::bad_arg("opus_multistream_surround_encoder_get_size")),
+            size => Ok(size as usize),
+        }
+    }
+}
+
+impl<S: Storage> MultistreamEncoder<S> {
+    /// Initialize a multistream encoder with an explicit layout in
+    /// caller-provided storage, without allocating.
+    ///
+    /// The storage must hold at least `MultistreamEncoder::state_size` bytes
+    /// aligned to `storage::STATE_ALIGN`.
+    pub fn new_in(
+        mut storage: S,
+        sample_rate: u32,
+        layout: StreamLayout,
+        mode: Application,
+    ) -> Result<MultistreamEncoder<S>> {
+        let what = "opus_multistream_encoder_init";
+        layout.check(what)?;
+        let size = MultistreamEncoder::state_size(&layout)?;
+        let ptr = storage::place(&mut storage, what, size as i32)? as *mut ffi::OpusMSEncoder;
+        let code = unsafe {
+            ffi::opus_multistream_encoder_init(
+                ptr,
+                sample_rate as i32,
+                layout.channels as c_int,
+                layout.streams as c_int,
+                layout.coupled_streams as c_int,
+                layout.mapping.as_ptr(),
+                mode as c_int,
+            )
+        };
+        if code != ffi::OPUS_OK {
+            return Err(Error::from_code(what, code));
+        }
+        Ok(MultistreamEncoder {
+            ptr,
+            layout,
+            lfe_stream: None,
+            storage,
+        })
+    }
+
+    /// Initialize a multistream encoder using the standard layout for the
+    /// given channel mapping family in caller-provided storage, without
+    /// allocating the state.
+    ///
+    /// The storage must hold at least
+    /// `MultistreamEncoder::surround_state_size` bytes aligned to
+    /// `storage::STATE_ALIGN`.
+    pub fn surround_in(
+        mut storage: S,
+        sample_rate: u32,
+        channels: u8,
+        mapping_family: u8,
+        mode: Application,
+    ) -> Result<MultistreamEncoder<S>> {
+        let what = "opus_multistream_surround_encoder_init";
+        let size = MultistreamEncoder::surround_state_size(channels, mapping_family)?;
+        let ptr = storage::place(&mut storage, what, size as i32)? as *mut ffi::OpusMSEncoder;
+        let mut streams = 0;
+        let mut coupled_streams = 0;
+        let mut mapping = vec![0u8; channels as usize];
+        let code = unsafe {
+            ffi::opus_multistream_surround_encoder_init(
+                ptr,
+                sample_rate as i32,
+                channels as c_int,
+                mapping_family as c_int,
+                &mut streams,
+                &mut coupled_streams,
+                mapping.as_mut_ptr(),
+                mode as c_int,
+            )
+        };
+        if code != ffi::OPUS_OK {
+            return Err(Error::from_code(what, code));
+        }
+        let layout = StreamLayout {
+            channels,
+            streams: streams as u8,
+            coupled_streams: coupled_streams as u8,
+            mapping,
+        };
+        Ok(MultistreamEncoder {
+            ptr,
+            lfe_stream: allocation::lfe_stream(&layout, mapping_family),
+            layout,
+            storage,
+        })
+    }
+
+    /// Get the stream layout of the encoder.
+    pub fn layout(&self) -> &StreamLayout {
+        &self.layout
+    }
+
+    /// Encode an Opus frame.
+    pub fn encode(&mut self, input: &[i16], output: &mut [u8]) -> Result<usize> {
+        let len = ffi!(
+            opus_multistream_encode,
+            self.ptr,
+            input.as_ptr(),
+            len(input) / self.layout.channels.max(1) as c_int,
+            output.as_mut_ptr(),
+            len(output)
+        );
+        Ok(len as usize)
+    }
+
+    /// Encode an Opus frame from floating point input.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        let len = ffi!(
+            opus_multistream_encode_float,
+            self.ptr,
+            input.as_ptr(),
+            len(input) / self.layout.channels.max(1) as c_int,
+            output.as_mut_ptr(),
+            len(output)
+        );
+        Ok(len as usize)
+    }
+
+    /// Encode an Opus frame to a new buffer.
+    pub fn encode_vec(&mut self, input: &[i16], max_size: usize) -> Result<Vec<u8>> {
+        let mut output: Vec<u8> = vec![0; max_size];
+        let result = self.encode(input, output.as_mut_slice())?;
+        output.truncate(result);
+        Ok(output)
+    }
+
+    /// Encode an Opus frame from floating point input to a new buffer.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_vec_float(&mut self, input: &[f32], max_size: usize) -> Result<Vec<u8>> {
+        let mut output: Vec<u8> = vec![0; max_size];
+        let result = self.encode_float(input, output.as_mut_slice())?;
+        output.truncate(result);
+        Ok(output)
+    }
+
+    // ------------
+    // Generic CTLs
+
+    /// Reset the codec state to be equivalent to a freshly initialized state.
+    pub fn reset_state(&mut self) -> Result<()> {
+        ms_enc_ctl!(self, OPUS_RESET_STATE);
+        Ok(())
+    }
+
+    /// Get the final range of the codec's entropy coder.
+    pub fn get_final_range(&mut self) -> Result<u32> {
+        let mut value: u32 = 0;
+        ms_enc_ctl!(self, OPUS_GET_FINAL_RANGE, &mut value);
+        Ok(value)
+    }
+
+    /// Get the encoder's configured bandpass.
+    pub fn get_bandwidth(&mut self) -> Result<Bandwidth> {
+        let mut value: i32 = 0;
+        ms_enc_ctl!(self, OPUS_GET_BANDWIDTH, &mut value);
+        Bandwidth::decode(value, "opus_multistream_encoder_ctl(OPUS_GET_BANDWIDTH)")
+    }
+
+    /// Get the samping rate the encoder was intialized with.
+    pub fn get_sample_rate(&mut self) -> Result<u32> {
+        let mut value: i32 = 0;
+        ms_enc_ctl!(self, OPUS_GET_SAMPLE_RATE, &mut value);
+        Ok(value as u32)
+    }
+
+    // ------------
+    // Encoder CTLs
+
+    /// Set the total bitrate of all streams, leaving libopus to divide it
+    /// between them on every frame.
+    pub fn set_bitrate(&mut self, value: Bitrate) -> Result<()> {
+        let value: i32 = match value {
+            Bitrate::Auto => OPUS_AUTO,
+            Bitrate::Max => OPUS_BITRATE_MAX,
+            Bitrate::Bits(b) => b,
+        };
+        ms_enc_ctl!(self, OPUS_SET_BITRATE, value);
+        Ok(())
+    }
+
+    /// Set the bitrate of each stream's encoder, in stream order.
+    ///
+    /// libopus may divide the total bitrate between the streams anew as it
+    /// encodes each frame, replacing these with its own split.
+    pub fn set_stream_bitrates(&mut self, bitrates: &[Bitrate]) -> Result<()> {
+        if bitrates.len() != self.layout.streams as usize {
+            return Err(Error::bad_arg("MultistreamEncoder::set_stream_bitrates"));
+        }
+        allocation::set_bitrates(self.ptr, bitrates)
+    }
+
+    /// Set a total bitrate, and divide it between the streams by the
+    /// importance of their channels, as `StreamLayout::weighted_bitrates`
+    /// does, with `set_stream_bitrates`.
+    ///
+    /// The LFE channel of a 5.1, 6.1, or 7.1 layout from `surround` is given
+    /// the least.
+    pub fn set_weighted_bitrate(&mut self, total: i32) -> Result<()> {
+        self.set_bitrate(Bitrate::Bits(total))?;
+        let bitrates = self.layout.weighted_bitrates(total, self.lfe_stream);
+        self.set_stream_bitrates(&bitrates)
+    }
+
+    /// Get the total bitrate of all streams.
+    pub fn get_bitrate(&mut self) -> Result<Bitrate> {
+        let mut value: i32 = 0;
+        ms_enc_ctl!(self, OPUS_GET_BITRATE, &mut value);
+        Ok(match value {
+            OPUS_AUTO => Bitrate::Auto,
+            OPUS_BITRATE_MAX => Bitrate::Max,
+            _ => Bitrate::Bits(value),
+        })
+    }
+
+    /// Enable or disable variable bitrate.
+    pub fn set_vbr(&mut self, vbr: bool) -> Result<()> {
+        let value: i32 = if vbr { 1 } else { 0 };
+        ms_enc_ctl!(self, OPUS_SET_VBR, value);
+        Ok(())
+    }
+
+    /// Determine if variable bitrate is enabled.
+    pub fn get_vbr(&mut self) -> Result<bool> {
+        let mut value: i32 = 0;
+        ms_enc_ctl!(self, OPUS_GET_VBR, &mut value);
+        Ok(value != 0)
+    }
+
+    /// Configures the encoder's computational complexity, from 0 to 10.
+    pub fn set_complexity(&mut self, value: i32) -> Result<()> {
+        ms_enc_ctl!(self, OPUS_SET_COMPLEXITY, value);
+        Ok(())
+    }
+
+    /// Gets the encoder's computational complexity.
+    pub fn get_complexity(&mut self) -> Result<i32> {
+        let mut value: i32 = 0;
+        ms_enc_ctl!(self, OPUS_GET_COMPLEXITY, &mut value);
+        Ok(value)
+    }
+
+    /// Configures the encoder's use of inband forward error correction.
+    pub fn set_inband_fec(&mut self, value: bool) -> Result<()> {
+        let value: i32 = if value { 1 } else { 0 };
+        ms_enc_ctl!(self, OPUS_SET_INBAND_FEC, value);
+        Ok(())
+    }
+
+    /// Configures the encoder's expected packet loss percentage.
+    pub fn set_packet_loss_perc(&mut self, value: i32) -> Result<()> {
+        ms_enc_ctl!(self, OPUS_SET_PACKET_LOSS_PERC, value);
+        Ok(())
+    }
+
+    /// Gets the total samples of delay added by the entire codec.
+    pub fn get_lookahead(&mut self) -> Result<i32> {
+        let mut value: i32 = 0;
+        ms_enc_ctl!(self, OPUS_GET_LOOKAHEAD, &mut value);
+        Ok(value)
+    }
+
+    /// Configures the duration of each encoded frame.
+    pub fn set_expert_frame_duration(&mut self, value: FrameDuration) -> Result<()> {
+        ms_enc_ctl!(self, OPUS_SET_EXPERT_FRAME_DURATION, value as i32);
+        Ok(())
+    }
+}
+
+impl<S: Storage> Drop for MultistreamEncoder<S> {
+    fn drop(&mut self) {
+        if storage::is_heap(&mut self.storage) {
+            unsafe { ffi::opus_multistream_encoder_destroy(self.ptr) }
+        }
+    }
+}
+
+// See `unsafe impl Send for Encoder`.
+unsafe impl<S: Storage + Send> Send for MultistreamEncoder<S> {}
+
+/// An Opus multistream decoder, `Send` but not `Sync` like `Decoder`.
+#[derive(Debug)]
+pub struct MultistreamDecoder<S: Storage = Heap> {
+    ptr: *mut ffi::OpusMSDecoder,
+    layout: StreamLayout,
+    storage: S,
+}
+
+impl MultistreamDecoder {
+    /// Create and initialize a multistream decoder.
+    pub fn new(sample_rate: u32, layout: StreamLayout) -> Result<MultistreamDecoder> {
+        layout.check("opus_multistream_decoder_create")?;
+        let mut error = 0;
+        let ptr = unsafe {
+            ffi::opus_multistream_decoder_create(
+                sample_rate as i32,
+                layout.channels as c_int,
+                layout.streams as c_int,
+                layout.coupled_streams as c_int,
+                layout.mapping.as_ptr(),
+                &mut error,
+            )
+        };
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            Err(Error::from_code("opus_multistream_decoder_create", error))
+        } else {
+            Ok(MultistreamDecoder {
+                ptr,
+                layout,
+                storage: Heap,
+            })
+        }
+    }
+
+    /// Get the size in bytes of the state of a decoder with the given
+    /// layout, as required by `new_in`.
+    pub fn state_size(layout: &StreamLayout) -> Result<usize> {
+        let size = unsafe {
+            ffi::opus_multistream_decoder_get_size(
+                layout.streams as c_int,
+                layout.coupled_streams as c_int,
+            )
+        };
+        match size {
+            0 => Err(Error::bad_arg("opus_multistream_decoder_get_size")),
+            size => Ok(size as usize),
+        }
+    }
+}
+
+impl<S: Storage> MultistreamDecoder<S> {
+    /// Initialize a multistream decoder in caller-provided storage, without
+    /// allocating.
+    ///
+    /// The storage must hold at least `MultistreamDecoder::state_size` bytes
+    /// aligned to `storage::STATE_ALIGN`.
+    pub fn new_in(
+        mut storage: S,
+        sample_rate: u32,
+        layout: StreamLayout,
+    ) -> Result<MultistreamDecoder<S>> {
+        let what = "opus_multistream_decoder_init";
+        layout.check(what)?;
+        let size = MultistreamDecoder::state_size(&layout)?;
+        let ptr = storage::place(&mut storage, what, size as i32)? as *mut ffi::OpusMSDecoder;
+        let code = unsafe {
+            ffi::opus_multistream_decoder_init(
+                ptr,
+                sample_rate as i32,
+                layout.channels as c_int,
+                layout.streams as c_int,
+                layout.coupled_streams as c_int,
+                layout.mapping.as_ptr(),
+            )
+        };
+        if code != ffi::OPUS_OK {
+            return Err(Error::from_code(what, code));
+        }
+        Ok(MultistreamDecoder {
+            ptr,
+            layout,
+            storage,
+        })
+    }
+
+    /// Get the stream layout of the decoder.
+    pub fn layout(&self) -> &StreamLayout {
+        &self.layout
+    }
+
+    /// Decode an Opus packet.
+    pub fn decode(&mut self, input: &[u8], output: &mut [i16], fec: bool) -> Result<usize> {
+        let ptr = match input.len() {
+            0 => std::ptr::null(),
+            _ => input.as_ptr(),
+        };
+        let len = ffi!(
+            opus_multistream_decode,
+            self.ptr,
+            ptr,
+            len(input),
+            output.as_mut_ptr(),
+            len(output) / self.layout.channels.max(1) as c_int,
+            fec as c_int
+        );
+        Ok(len as usize)
+    }
+
+    /// Decode an Opus packet with floating point output.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn decode_float(&mut self, input: &[u8], output: &mut [f32], fec: bool) -> Result<usize> {
+        let ptr = match input.len() {
+            0 => std::ptr::null(),
+            _ => input.as_ptr(),
+        };
+        let len = ffi!(
+            opus_multistream_decode_float,
+            self.ptr,
+            ptr,
+            len(input),
+            output.as_mut_ptr(),
+            len(output) / self.layout.channels.max(1) as c_int,
+            fec as c_int
+        );
+        Ok(len as usize)
+    }
+
+    // ------------
+    // Generic CTLs
+
+    /// Reset the codec state to be equivalent to a freshly initialized state.
+    pub fn reset_state(&mut self) -> Result<()> {
+        ms_dec_ctl!(self, OPUS_RESET_STATE);
+        Ok(())
+    }
+
+    /// Get the final range of the codec's entropy coder.
+    pub fn get_final_range(&mut self) -> Result<u32> {
+        let mut value: u32 = 0;
+        ms_dec_ctl!(self, OPUS_GET_FINAL_RANGE, &mut value);
+        Ok(value)
+    }
+
+    /// Get the samping rate the decoder was intialized with.
+    pub fn get_sample_rate(&mut self) -> Result<u32> {
+        let mut value: i32 = 0;
+        ms_dec_ctl!(self, OPUS_GET_SAMPLE_RATE, &mut value);
+        Ok(value as u32)
+    }
+
+    // ------------
+    // Decoder CTLs
+
+    /// Configures decoder gain adjustment.
+    ///
+    /// Scales the decoded output by a factor specified in Q8 dB units. This has
+    /// a maximum range of -32768 to 32768 inclusive, and a default value of 0.
+    pub fn set_gain(&mut self, gain: i32) -> Result<()> {
+        ms_dec_ctl!(self, OPUS_SET_GAIN, gain);
+        Ok(())
+    }
+
+    /// Gets the duration (in samples) of the last packet successfully decoded
+    /// or concealed.
+    pub fn get_last_packet_duration(&mut self) -> Result<u32> {
+        let mut value: i32 = 0;
+        ms_dec_ctl!(self, OPUS_GET_LAST_PACKET_DURATION, &mut value);
+        Ok(value as u32)
+    }
+}
+
+impl<S: Storage> Drop for MultistreamDecoder<S> {
+    fn drop(&mut self) {
+        if storage::is_heap(&mut self.storage) {
+            unsafe { ffi::opus_multistream_decoder_destroy(self.ptr) }
+        }
+    }
+}
+
+// See `unsafe impl Send for Encoder`.
+unsafe impl<S: Storage + Send> Send for MultistreamDecoder<S> {}
+
+mod framing;
+pub use framing::MultistreamPacket;
+
+mod allocation;
+
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "rayon")]
+pub use parallel::ParallelMultistreamEncoder;
+
+// ============================================================================
+// Error Handling
+
+/// Opus error Result alias.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An error generated by the Opus library.
+#[derive(Debug)]
+pub struct Error {
+    function: &'static str,
+    code: ErrorCode,
+}
+
+impl Error {
+    fn bad_arg(what: &'static str) -> Error {
+        Error {
+            function: what,
+            code: ErrorCode::BadArg,
+        }
+    }
+
+    fn from_code(what: &'static str, code: c_int) -> Error {
+        Error {
+            function: what,
+            code: ErrorCode::from_int(code),
+        }
+    }
+
+    /// Get the name of the Opus function from which the error originated.
+    #[inline]
+    pub fn function(&self) -> &'static str {
+        self.function
+    }
+
+    /// Get a textual description of the error provided by Opus.
+    #[inline]
+    pub fn description(&self) -> &'static str {
+        self.code.description()
+    }
 
     /// Get the Opus error code of the error.
     #[inline]
@@ -917,18 +2454,35 @@ impl std::fmt::Display for Error {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for Error {
     fn description(&self) -> &str {
         self.code.description()
     }
 }
 
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> std::io::Error {
+        use std::io::ErrorKind;
+        let kind = match err.code {
+            ErrorCode::BadArg => ErrorKind::InvalidInput,
+            ErrorCode::InvalidPacket => ErrorKind::InvalidData,
+            _ => ErrorKind::Other,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
+// Lengths past what libopus can describe are clamped rather than rejected:
+// an output buffer is then only partly used, while an input that long is no
+// valid packet or frame, and libopus reports the error.
 fn check_len(val: usize) -> c_int {
-    let len = val as c_int;
-    if len as usize != val {
-        panic!("length out of range: {}", val);
+    if val > c_int::MAX as usize {
+        c_int::MAX
+    } else {
+        val as c_int
     }
-    len
 }
 
 #[inline]
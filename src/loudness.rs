@@ -0,0 +1,316 @@
+//! Loudness measurement and normalization (ITU-R BS.1770, EBU R128).
+//!
+//! `LoudnessMeter` is a pre-encode analysis pass: push the whole input
+//! through it and `finish` gives the integrated loudness and true peak. The
+//! input can then be normalized with `apply_gain`, and the result recorded
+//! in the `R128_TRACK_GAIN` comment which Ogg Opus players use to reach the
+//! -23 LUFS reference level of EBU R128 (RFC 7845 section 5.2.1).
+
+use super::ogg::OpusTags;
+
+/// The reference loudness of `R128_TRACK_GAIN`, in LUFS.
+pub const R128_REFERENCE: f64 = -23.0;
+
+/// The comment field holding the track gain.
+pub const R128_TRACK_GAIN: &str = "R128_TRACK_GAIN";
+
+// Blocks quieter than this are never counted, in LUFS.
+const ABSOLUTE_GATE: f64 = -70.0;
+
+// Blocks this far below the ungated loudness are not counted, in LU.
+const RELATIVE_GATE: f64 = -10.0;
+
+// Taps of the true peak interpolation filter per oversampled phase.
+const PEAK_TAPS: usize = 12;
+
+/// The loudness of a signal, as measured by `LoudnessMeter`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// The gated integrated loudness in LUFS, or negative infinity if the
+    /// signal was too short or quiet to measure.
+    pub integrated: f64,
+    /// The true peak level in dBTP, or negative infinity for silence.
+    pub true_peak: f64,
+}
+
+impl Loudness {
+    /// Get the loudness of the same signal after a gain of `gain` dB.
+    pub fn with_gain(&self, gain: f64) -> Loudness {
+        Loudness {
+            integrated: self.integrated + gain,
+            true_peak: self.true_peak + gain,
+        }
+    }
+
+    /// Get the gain in dB which brings the signal to `target` LUFS, reduced
+    /// if necessary to keep the true peak at or below `ceiling` dBTP.
+    ///
+    /// Returns zero if the loudness could not be measured.
+    pub fn normalize_gain(&self, target: f64, ceiling: f64) -> f64 {
+        if !self.integrated.is_finite() {
+            return 0.0;
+        }
+        (target - self.integrated).min(ceiling - self.true_peak)
+    }
+
+    /// Get the `R128_TRACK_GAIN` value for the signal, in Q7.8 dB.
+    ///
+    /// The value is relative to the output gain of the `OpusHead`, so if it
+    /// isn't zero, find the value for `with_gain(output_gain as f64 / 256.0)`.
+    pub fn r128_track_gain(&self) -> i16 {
+        if !self.integrated.is_finite() {
+            return 0;
+        }
+        let gain = ((R128_REFERENCE - self.integrated) * 256.0).round();
+        gain.clamp(i16::MIN as f64, i16::MAX as f64) as i16
+    }
+
+    /// Set the `R128_TRACK_GAIN` comment in `tags`, replacing any existing
+    /// one.
+    pub fn write_tags(&self, tags: &mut OpusTags) {
+        tags.comments
+            .retain(|(field, _)| !field.eq_ignore_ascii_case(R128_TRACK_GAIN));
+        tags.comments.push((
+            R128_TRACK_GAIN.to_string(),
+            self.r128_track_gain().to_string(),
+        ));
+    }
+}
+
+/// Measure the loudness of interleaved samples, normalized to [-1, 1].
+pub fn measure(pcm: &[f32], channels: usize, sample_rate: u32) -> Loudness {
+    let mut meter = LoudnessMeter::new(channels, sample_rate);
+    meter.push(pcm);
+    meter.finish()
+}
+
+/// Scale interleaved samples by `gain` dB.
+pub fn apply_gain(pcm: &mut [f32], gain: f64) {
+    let factor = 10f64.powf(gain / 20.0) as f32;
+    for sample in pcm {
+        *sample *= factor;
+    }
+}
+
+/// Scale interleaved 16-bit samples by `gain` dB, saturating at full scale.
+pub fn apply_gain_i16(pcm: &mut [i16], gain: f64) {
+    let factor = 10f64.powf(gain / 20.0);
+    for sample in pcm {
+        *sample = (*sample as f64 * factor)
+            .round()
+            .clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+    }
+}
+
+/// Measures the integrated loudness and true peak of a signal in one pass.
+///
+/// Loudness is measured over 400 ms blocks overlapping by 75%, and gated
+/// as specified by BS.1770-4. Channels are weighted by their position in
+/// the Vorbis channel order used by Ogg Opus, with the LFE channel
+/// ignored.
+pub struct LoudnessMeter {
+    channels: usize,
+    weights: Vec<f64>,
+    filters: Vec<[Biquad; 2]>,
+    // samples per channel in each 100 ms step of a block
+    step: usize,
+    // the position in the current step, and the weighted energy of it and
+    // of the three steps before
+    pos: usize,
+    steps: [f64; 4],
+    seen: usize,
+    blocks: Vec<f64>,
+    factor: usize,
+    interpolator: Vec<f64>,
+    history: Vec<Vec<f64>>,
+    peak: f64,
+}
+
+impl LoudnessMeter {
+    /// Create a meter for `channels` interleaved channels at `sample_rate`.
+    pub fn new(channels: usize, sample_rate: u32) -> LoudnessMeter {
+        let rate = sample_rate as f64;
+        // oversample to at least 192kHz to find the peaks between samples
+        let factor = match sample_rate {
+            0..=95999 => 4,
+            96000..=191999 => 2,
+            _ => 1,
+        };
+        LoudnessMeter {
+            channels,
+            weights: weights(channels),
+            filters: vec![[Biquad::shelf(rate), Biquad::high_pass(rate)]; channels],
+            step: (sample_rate as usize / 10).max(1),
+            pos: 0,
+            steps: [0.0; 4],
+            seen: 0,
+            blocks: Vec::new(),
+            factor,
+            interpolator: interpolator(factor),
+            history: vec![vec![0.0; PEAK_TAPS]; channels],
+            peak: 0.0,
+        }
+    }
+
+    /// Analyze interleaved samples, normalized to [-1, 1].
+    pub fn push(&mut self, pcm: &[f32]) {
+        for frame in pcm.chunks_exact(self.channels) {
+            self.push_frame(frame.iter().map(|&s| s as f64));
+        }
+    }
+
+    /// Analyze interleaved 16-bit samples.
+    pub fn push_i16(&mut self, pcm: &[i16]) {
+        for frame in pcm.chunks_exact(self.channels) {
+            self.push_frame(frame.iter().map(|&s| s as f64 / 32768.0));
+        }
+    }
+
+    fn push_frame<I: Iterator<Item = f64>>(&mut self, frame: I) {
+        let mut energy = 0.0;
+        for (channel, sample) in frame.enumerate() {
+            let [shelf, high_pass] = &mut self.filters[channel];
+            let filtered = high_pass.process(shelf.process(sample));
+            energy += self.weights[channel] * filtered * filtered;
+
+            let history = &mut self.history[channel];
+            history.rotate_right(1);
+            history[0] = sample;
+            let mut peak = sample.abs();
+            for phase in 0..self.factor {
+                let taps = self.interpolator[phase..].iter().step_by(self.factor);
+                let value: f64 = history.iter().zip(taps).map(|(x, tap)| x * tap).sum();
+                peak = peak.max(value.abs());
+            }
+            self.peak = self.peak.max(peak);
+        }
+        self.steps[3] += energy;
+        self.pos += 1;
+        if self.pos == self.step {
+            self.pos = 0;
+            self.seen += 1;
+            if self.seen >= 4 {
+                let block = self.steps.iter().sum::<f64>() / (4 * self.step) as f64;
+                self.blocks.push(block);
+            }
+            self.steps.rotate_left(1);
+            self.steps[3] = 0.0;
+        }
+    }
+
+    /// Get the loudness of everything analyzed so far.
+    ///
+    /// Signals shorter than a 400 ms block have no integrated loudness.
+    pub fn finish(&self) -> Loudness {
+        let gated = |threshold: f64| {
+            let (sum, count) = self
+                .blocks
+                .iter()
+                .filter(|&&block| lufs(block) > threshold)
+                .fold((0.0, 0), |(sum, count), &block| (sum + block, count + 1));
+            if count == 0 {
+                0.0
+            } else {
+                sum / count as f64
+            }
+        };
+        let relative = lufs(gated(ABSOLUTE_GATE)) + RELATIVE_GATE;
+        Loudness {
+            integrated: lufs(gated(relative.max(ABSOLUTE_GATE))),
+            true_peak: 20.0 * self.peak.log10(),
+        }
+    }
+}
+
+fn lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+// BS.1770 channel weights for the Vorbis channel orders of mapping family 1.
+fn weights(channels: usize) -> Vec<f64> {
+    const S: f64 = 1.41;
+    match channels {
+        4 => vec![1.0, 1.0, S, S],
+        5 => vec![1.0, 1.0, 1.0, S, S],
+        6 => vec![1.0, 1.0, 1.0, S, S, 0.0],
+        7 => vec![1.0, 1.0, 1.0, S, S, S, 0.0],
+        8 => vec![1.0, 1.0, 1.0, S, S, S, S, 0.0],
+        _ => vec![1.0; channels],
+    }
+}
+
+// A Hann-windowed sinc lowpass for interpolating by `factor`, laid out so
+// that tap `k * factor + phase` weights the sample `k` periods back for
+// output `phase`. Each phase is normalized to unity gain at DC.
+fn interpolator(factor: usize) -> Vec<f64> {
+    use std::f64::consts::PI;
+    let len = PEAK_TAPS * factor;
+    let center = (len - 1) as f64 / 2.0;
+    let mut taps: Vec<f64> = (0..len)
+        .map(|n| {
+            let x = (n as f64 - center) / factor as f64;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            let window = 0.5 - 0.5 * (2.0 * PI * (n + 1) as f64 / (len + 1) as f64).cos();
+            sinc * window
+        })
+        .collect();
+    for phase in 0..factor {
+        let sum: f64 = taps.iter().skip(phase).step_by(factor).sum();
+        for tap in taps.iter_mut().skip(phase).step_by(factor) {
+            *tap /= sum;
+        }
+    }
+    taps
+}
+
+// A second order IIR filter in transposed direct form II.
+#[derive(Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    // The first stage of K-weighting, a high shelf modelling the head, with
+    // the coefficients of BS.1770 at 48kHz generalized to any rate.
+    fn shelf(rate: f64) -> Biquad {
+        let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        }
+    }
+
+    // The second stage of K-weighting, the RLB high-pass.
+    fn high_pass(rate: f64) -> Biquad {
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
@@ -0,0 +1,315 @@
+//! Channel layouts, and mixing between them.
+//!
+//! Ogg Opus orders the channels of mapping family 1 as Vorbis does, while WAV
+//! files use the order of `WAVEFORMATEXTENSIBLE`; `vorbis_layout` and
+//! `wave_layout` name the speaker of each channel in either. A `Mixer`
+//! applies a matrix to interleaved frames to reorder, downmix, or upmix
+//! them, and `Mixer::downmix` builds the matrix between any two layouts
+//! with the coefficients of ITU-R BS.775. Downmixes can sum past full
+//! scale, so a `Limiter` can be attached to catch the peaks.
+
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use super::convert;
+
+/// The position of the speaker a channel is meant for.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Speaker {
+    /// Front left.
+    FrontLeft,
+    /// Front right.
+    FrontRight,
+    /// Front center, which mono audio is played on.
+    FrontCenter,
+    /// Low-frequency effects.
+    Lfe,
+    /// Rear left surround.
+    RearLeft,
+    /// Rear right surround.
+    RearRight,
+    /// Rear center surround.
+    RearCenter,
+    /// Side left surround.
+    SideLeft,
+    /// Side right surround.
+    SideRight,
+}
+
+use self::Speaker::*;
+
+/// Get the speakers of 1 to 8 channels in the Vorbis order used by Ogg Opus
+/// mapping families 0 and 1 (RFC 7845 section 5.1.1.2).
+pub fn vorbis_layout(channels: usize) -> Option<&'static [Speaker]> {
+    let layout: &'static [Speaker] = match channels {
+        1 => &[FrontCenter],
+        2 => &[FrontLeft, FrontRight],
+        3 => &[FrontLeft, FrontCenter, FrontRight],
+        4 => &[FrontLeft, FrontRight, RearLeft, RearRight],
+        5 => &[FrontLeft, FrontCenter, FrontRight, RearLeft, RearRight],
+        6 => &[FrontLeft, FrontCenter, FrontRight, RearLeft, RearRight, Lfe],
+        7 => &[
+            FrontLeft,
+            FrontCenter,
+            FrontRight,
+            SideLeft,
+            SideRight,
+            RearCenter,
+            Lfe,
+        ],
+        8 => &[
+            FrontLeft,
+            FrontCenter,
+            FrontRight,
+            SideLeft,
+            SideRight,
+            RearLeft,
+            RearRight,
+            Lfe,
+        ],
+        _ => return None,
+    };
+    Some(layout)
+}
+
+/// Get the speakers of 1 to 8 channels in the default order of WAV files,
+/// and of most audio device APIs.
+pub fn wave_layout(channels: usize) -> Option<&'static [Speaker]> {
+    let layout: &'static [Speaker] = match channels {
+        1 => &[FrontCenter],
+        2 => &[FrontLeft, FrontRight],
+        3 => &[FrontLeft, FrontRight, FrontCenter],
+        4 => &[FrontLeft, FrontRight, RearLeft, RearRight],
+        5 => &[FrontLeft, FrontRight, FrontCenter, RearLeft, RearRight],
+        6 => &[FrontLeft, FrontRight, FrontCenter, Lfe, RearLeft, RearRight],
+        7 => &[
+            FrontLeft,
+            FrontRight,
+            FrontCenter,
+            Lfe,
+            RearCenter,
+            SideLeft,
+            SideRight,
+        ],
+        8 => &[
+            FrontLeft,
+            FrontRight,
+            FrontCenter,
+            Lfe,
+            RearLeft,
+            RearRight,
+            SideLeft,
+            SideRight,
+        ],
+        _ => return None,
+    };
+    Some(layout)
+}
+
+/// Mixes interleaved frames of one channel count into another through a
+/// matrix.
+#[derive(Debug, Clone)]
+pub struct Mixer {
+    inputs: usize,
+    outputs: usize,
+    matrix: Vec<f32>,
+    limiter: Option<Limiter>,
+    // conversion buffers for `process_i16`
+    float_in: Vec<f32>,
+    float_out: Vec<f32>,
+}
+
+impl Mixer {
+    /// Create a mixer from `inputs` to `outputs` channels, with `matrix`
+    /// holding the gain of each input in each output, one output at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `matrix` doesn't have `inputs * outputs` entries, or either
+    /// count is zero.
+    pub fn new(inputs: usize, outputs: usize, matrix: Vec<f32>) -> Mixer {
+        assert!(inputs > 0 && outputs > 0 && matrix.len() == inputs * outputs);
+        Mixer {
+            inputs,
+            outputs,
+            matrix,
+            limiter: None,
+            float_in: Vec::new(),
+            float_out: Vec::new(),
+        }
+    }
+
+    /// Create a mixer reordering the channels of layout `from` into layout
+    /// `to`. Speakers missing from `from` are silent.
+    pub fn remap(from: &[Speaker], to: &[Speaker]) -> Mixer {
+        let mut matrix = vec![0.0; from.len() * to.len()];
+        for (row, speaker) in matrix.chunks_mut(from.len()).zip(to) {
+            if let Some(input) = from.iter().position(|s| s == speaker) {
+                row[input] = 1.0;
+            }
+        }
+        Mixer::new(from.len(), to.len(), matrix)
+    }
+
+    /// Create a mixer between layouts `from` and `to`.
+    ///
+    /// Each speaker of `from` goes to the same speaker of `to` if it has
+    /// one. Otherwise surrounds fold into the other surrounds, then into the
+    /// front; the center splits between the front left and right, and they
+    /// join into the center; each step 3 dB down as in ITU-R BS.775. The LFE
+    /// channel is dropped unless `to` has one. Mono is played on both front
+    /// speakers at full level when `to` has no center.
+    pub fn downmix(from: &[Speaker], to: &[Speaker]) -> Mixer {
+        let mut matrix = vec![0.0; from.len() * to.len()];
+        for (input, &speaker) in from.iter().enumerate() {
+            let mut add = |output, gain| matrix[output * from.len() + input] += gain;
+            if from.len() == 1 && !to.contains(&FrontCenter) {
+                // mono stays at full level rather than being panned center
+                route(FrontLeft, to, 1.0, &mut add);
+                route(FrontRight, to, 1.0, &mut add);
+            } else {
+                route(speaker, to, 1.0, &mut add);
+            }
+        }
+        Mixer::new(from.len(), to.len(), matrix)
+    }
+
+    /// Limit the output to full scale with a `Limiter` releasing over 50 ms
+    /// at `sample_rate`.
+    pub fn with_limiter(mut self, sample_rate: u32) -> Mixer {
+        self.limiter = Some(Limiter::new(self.outputs, sample_rate, 50));
+        self
+    }
+
+    /// Get the number of input channels.
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    /// Get the number of output channels.
+    pub fn outputs(&self) -> usize {
+        self.outputs
+    }
+
+    /// Get the matrix, holding the gain of each input in each output, one
+    /// output at a time.
+    pub fn matrix(&self) -> &[f32] {
+        &self.matrix
+    }
+
+    /// Mix interleaved frames from `input` into `output`.
+    ///
+    /// Mixes as many whole frames as both buffers hold, and returns that
+    /// number.
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> usize {
+        let frames = (input.len() / self.inputs).min(output.len() / self.outputs);
+        let output = &mut output[..frames * self.outputs];
+        for (input, output) in input
+            .chunks_exact(self.inputs)
+            .zip(output.chunks_exact_mut(self.outputs))
+        {
+            for (out, row) in output.iter_mut().zip(self.matrix.chunks_exact(self.inputs)) {
+                *out = row
+                    .iter()
+                    .zip(input)
+                    .map(|(gain, sample)| gain * sample)
+                    .sum();
+            }
+        }
+        if let Some(ref mut limiter) = self.limiter {
+            limiter.apply(output);
+        }
+        frames
+    }
+
+    /// Mix interleaved 16-bit frames from `input` into `output`, as
+    /// `process` does.
+    pub fn process_i16(&mut self, input: &[i16], output: &mut [i16]) -> usize {
+        let frames = (input.len() / self.inputs).min(output.len() / self.outputs);
+        let mut float_in = std::mem::take(&mut self.float_in);
+        let mut float_out = std::mem::take(&mut self.float_out);
+        float_in.resize(frames * self.inputs, 0.0);
+        float_out.resize(frames * self.outputs, 0.0);
+        convert::i16_to_f32(&input[..float_in.len()], &mut float_in);
+        self.process(&float_in, &mut float_out);
+        convert::f32_to_i16(&float_out, &mut output[..float_out.len()]);
+        self.float_in = float_in;
+        self.float_out = float_out;
+        frames
+    }
+}
+
+// Call `out` with each channel of `to` which `speaker` is mixed into, and its
+// gain.
+fn route<F: FnMut(usize, f32)>(speaker: Speaker, to: &[Speaker], gain: f32, out: &mut F) {
+    if let Some(output) = to.iter().position(|&s| s == speaker) {
+        return out(output, gain);
+    }
+    let has = |s: Speaker| to.contains(&s);
+    let half = gain * FRAC_1_SQRT_2;
+    match speaker {
+        FrontCenter if has(FrontLeft) && has(FrontRight) => {
+            route(FrontLeft, to, half, out);
+            route(FrontRight, to, half, out);
+        }
+        FrontLeft | FrontRight if has(FrontCenter) => route(FrontCenter, to, half, out),
+        SideLeft if has(RearLeft) => route(RearLeft, to, gain, out),
+        SideRight if has(RearRight) => route(RearRight, to, gain, out),
+        RearLeft if has(SideLeft) => route(SideLeft, to, gain, out),
+        RearRight if has(SideRight) => route(SideRight, to, gain, out),
+        RearCenter if has(RearLeft) || has(SideLeft) => {
+            route(RearLeft, to, half, out);
+            route(RearRight, to, half, out);
+        }
+        SideLeft | RearLeft => route(FrontLeft, to, half, out),
+        SideRight | RearRight => route(FrontRight, to, half, out),
+        RearCenter => route(FrontCenter, to, half, out),
+        _ => {}
+    }
+}
+
+/// A peak limiter holding interleaved samples within [-1, 1].
+///
+/// The gain drops at once to bring any frame over full scale down to it,
+/// then recovers exponentially, so the signal is never clipped and quiet
+/// passages are left untouched.
+#[derive(Debug, Clone)]
+pub struct Limiter {
+    channels: usize,
+    release: f32,
+    gain: f32,
+}
+
+impl Limiter {
+    /// Create a limiter for `channels` interleaved channels at
+    /// `sample_rate`, recovering from a reduction with a time constant of
+    /// `release_ms`.
+    pub fn new(channels: usize, sample_rate: u32, release_ms: u32) -> Limiter {
+        let samples = sample_rate as f32 * release_ms as f32 / 1000.0;
+        Limiter {
+            channels: channels.max(1),
+            release: (-1.0 / samples.max(1.0)).exp(),
+            gain: 1.0,
+        }
+    }
+
+    /// Get the current gain.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Limit interleaved samples in place.
+    pub fn apply(&mut self, pcm: &mut [f32]) {
+        for frame in pcm.chunks_mut(self.channels) {
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            let target = if peak > 1.0 { 1.0 / peak } else { 1.0 };
+            self.gain = if target < self.gain {
+                target
+            } else {
+                target - (target - self.gain) * self.release
+            };
+            for sample in frame {
+                *sample *= self.gain;
+            }
+        }
+    }
+}
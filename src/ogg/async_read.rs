@@ -0,0 +1,142 @@
+//! Demuxing and decoding Ogg Opus from a `tokio::io::AsyncRead`.
+
+use std::collections::VecDeque;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use super::super::Decoder;
+use super::demux::{Event, OggDemuxer};
+use super::head::{OpusHead, OpusTags};
+
+/// Size of each read from the underlying reader.
+const READ_CHUNK: usize = 4096;
+
+/// Reads an Ogg Opus stream from an `AsyncRead` and yields the decoded audio
+/// as a `Stream` of interleaved 48kHz frames.
+///
+/// Pre-skip and end trimming are applied, as is the header's output gain.
+/// Chained streams are decoded one after another. Only mapping family 0
+/// (mono and stereo) is supported.
+#[derive(Debug)]
+pub struct AsyncOggReader<R> {
+    reader: R,
+    demuxer: OggDemuxer,
+    head: Option<OpusHead>,
+    tags: Option<OpusTags>,
+    decoder: Option<Decoder>,
+    pcm: Vec<i16>,
+    ready: VecDeque<Vec<i16>>,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncOggReader<R> {
+    /// Create a reader over the given byte stream.
+    pub fn new(reader: R) -> AsyncOggReader<R> {
+        AsyncOggReader {
+            reader,
+            demuxer: OggDemuxer::new(),
+            head: None,
+            tags: None,
+            decoder: None,
+            pcm: Vec::new(),
+            ready: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Get the identification header of the current stream, once read.
+    pub fn head(&self) -> Option<&OpusHead> {
+        self.head.as_ref()
+    }
+
+    /// Get the comment header of the current stream, once read.
+    pub fn tags(&self) -> Option<&OpusTags> {
+        self.tags.as_ref()
+    }
+
+    /// Get the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Unwrap the underlying reader, discarding any buffered data.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn handle(&mut self, event: Event) -> io::Result<()> {
+        match event {
+            Event::Header(head) => {
+                self.decoder = Some(head.decoder()?);
+                self.head = Some(head);
+                self.tags = None;
+            }
+            Event::Tags(tags) => self.tags = Some(tags),
+            Event::Audio(audio) => {
+                let decoder = match self.decoder {
+                    Some(ref mut decoder) => decoder,
+                    None => return Ok(()),
+                };
+                let channels = decoder.channels as usize;
+                self.pcm.resize(audio.info.duration as usize * channels, 0);
+                let len = decoder.decode(&audio.data, &mut self.pcm, false);
+                self.demuxer.recycle(audio.data);
+                let len = len?;
+                let start = (audio.trim_start as usize).min(len);
+                let end = len.saturating_sub(audio.trim_end as usize).max(start);
+                if start < end {
+                    self.ready
+                        .push_back(self.pcm[start * channels..end * channels].to_vec());
+                }
+            }
+            Event::Eos | Event::DataLoss { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncOggReader<R> {
+    type Item = io::Result<Vec<i16>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(pcm) = this.ready.pop_front() {
+                return Poll::Ready(Some(Ok(pcm)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            let mut chunk = [0; READ_CHUNK];
+            let mut read = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.reader).poll_read(cx, &mut read) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(Ok(())) => {}
+            }
+            if read.filled().is_empty() {
+                this.done = true;
+                if this.demuxer.buffered() > 0 {
+                    return Poll::Ready(Some(Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "truncated Ogg page",
+                    ))));
+                }
+                continue;
+            }
+            let result = this
+                .demuxer
+                .push_bytes(read.filled())
+                .and_then(|events| events.into_iter().try_for_each(|event| this.handle(event)));
+            if let Err(err) = result {
+                this.done = true;
+                return Poll::Ready(Some(Err(err)));
+            }
+        }
+    }
+}
@@ -0,0 +1,521 @@
+//! A push-based Ogg Opus demuxer with no I/O of its own.
+
+use std::io;
+
+use super::super::pipeline::PacketInfo;
+use super::super::pool::{BufferPool, VecPool};
+use super::super::time::Timestamp48k;
+use super::head::{OpusHead, OpusTags};
+use super::page::{invalid, PacketAssembler, Page, CAPTURE};
+
+/// An audio packet produced by the demuxer.
+///
+/// The data is owned by default; `OggSliceReader` borrows it from the input
+/// where it can.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct AudioPacket<D = Vec<u8>> {
+    /// The Opus packet.
+    pub data: D,
+    /// The packet's position and duration in the stream. The timestamp is
+    /// the granule position of the first sample, before any pre-skip.
+    pub info: PacketInfo,
+    /// The number of decoded samples to discard from the start of the packet,
+    /// due to the stream's pre-skip.
+    pub trim_start: u32,
+    /// The number of decoded samples to discard from the end of the packet,
+    /// as signalled by the final granule position.
+    pub trim_end: u32,
+}
+
+impl<D> AudioPacket<D> {
+    /// Get the number of samples per channel to keep after trimming.
+    pub fn playable(&self) -> u32 {
+        self.info
+            .duration
+            .saturating_sub(self.trim_start + self.trim_end)
+    }
+}
+
+/// An event produced by `OggDemuxer::push_bytes`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Event<D = Vec<u8>> {
+    /// The identification header of a new stream.
+    Header(OpusHead),
+    /// The comment header of the current stream.
+    Tags(OpusTags),
+    /// An audio packet of the current stream.
+    Audio(AudioPacket<D>),
+    /// The end of the current stream. A chained stream may follow.
+    Eos,
+    /// A gap in the current stream, found by a lenient demuxer before the
+    /// audio packet which follows it.
+    DataLoss {
+        /// The number of samples per channel lost, at 48kHz, or zero if it
+        /// can't be told, as when the input ends in the middle of a page.
+        samples: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum State {
+    Header,
+    Tags,
+    Audio,
+}
+
+/// The header and timeline state of the logical stream being followed.
+#[derive(Debug, Clone)]
+pub(crate) struct StreamState<D> {
+    serial: Option<u32>,
+    // the serial number of the last stream followed, kept once it ends
+    last_serial: Option<u32>,
+    state: State,
+    pre_skip: u64,
+    position: Option<u64>,
+    sequence: u64,
+    // whether pages were lost since the position was last known
+    lost: bool,
+    // data, duration, and trim_end of audio packets awaiting a granule
+    pending: Vec<(D, u32, u32)>,
+}
+
+impl<D: AsRef<[u8]>> StreamState<D> {
+    pub(crate) fn new() -> StreamState<D> {
+        StreamState {
+            serial: None,
+            last_serial: None,
+            state: State::Header,
+            pre_skip: 0,
+            position: None,
+            sequence: 0,
+            lost: false,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Determine whether a page belongs to the followed stream, latching on to
+    /// the first stream to begin.
+    pub(crate) fn accept(&mut self, bos: bool, serial: u32) -> bool {
+        if self.serial.is_none() && bos {
+            self.serial = Some(serial);
+            self.last_serial = Some(serial);
+        }
+        self.serial == Some(serial)
+    }
+
+    /// Handle a completed packet. Header packets are handed to `discard` once
+    /// parsed; audio packets wait in `pending` for `flush`.
+    pub(crate) fn packet<F: FnOnce(D)>(
+        &mut self,
+        packet: D,
+        events: &mut Vec<Event<D>>,
+        discard: F,
+    ) -> io::Result<()> {
+        match self.state {
+            State::Header => {
+                let head = OpusHead::parse(packet.as_ref())?;
+                discard(packet);
+                self.pre_skip = head.pre_skip as u64;
+                self.state = State::Tags;
+                events.push(Event::Header(head));
+            }
+            State::Tags => {
+                let tags = OpusTags::parse(packet.as_ref())?;
+                discard(packet);
+                events.push(Event::Tags(tags));
+                self.state = State::Audio;
+            }
+            State::Audio => {
+                let duration = match Timestamp48k::of_packet(packet.as_ref()) {
+                    Ok(duration) => duration.samples() as u32,
+                    Err(_) => return Err(invalid("invalid Opus packet in Ogg stream")),
+                };
+                self.pending.push((packet, duration, 0));
+            }
+        }
+        Ok(())
+    }
+
+    /// Note that pages of the stream were lost, so that the timeline is
+    /// anchored anew by the next granule position.
+    pub(crate) fn lose(&mut self) {
+        self.lost = true;
+    }
+
+    /// Report the samples from the current position up to `granule` as
+    /// lost.
+    fn lose_until(&mut self, granule: u64, events: &mut Vec<Event<D>>) {
+        if let Some(position) = self.position {
+            if granule > position {
+                events.push(Event::DataLoss {
+                    samples: granule - position,
+                });
+                self.position = Some(granule);
+            }
+        }
+    }
+
+    /// Get the serial number of the stream being followed, or last followed.
+    pub(crate) fn last_serial(&self) -> Option<u32> {
+        self.last_serial
+    }
+
+    /// Forget the position in the stream, keeping its headers, handing
+    /// pending packets to `discard`. A stream which has ended is resumed.
+    pub(crate) fn resync<F: FnMut(D)>(&mut self, discard: F) {
+        if self.serial.is_none() && self.last_serial.is_some() {
+            self.serial = self.last_serial;
+            self.state = State::Audio;
+        }
+        self.position = None;
+        self.lost = false;
+        self.pending
+            .drain(..)
+            .map(|(data, _, _)| data)
+            .for_each(discard);
+    }
+
+    /// Forget the state of the stream, other than its serial number and
+    /// pre-skip, handing pending packets to `discard`.
+    pub(crate) fn restart<F: FnMut(D)>(&mut self, discard: F) {
+        self.serial = None;
+        self.state = State::Header;
+        self.position = None;
+        self.sequence = 0;
+        self.lost = false;
+        self.pending
+            .drain(..)
+            .map(|(data, _, _)| data)
+            .for_each(discard);
+    }
+
+    /// Emit the pending audio packets of a page, once their position is
+    /// known.
+    pub(crate) fn flush(&mut self, granule: Option<u64>, eos: bool, events: &mut Vec<Event<D>>) {
+        let granule = match granule {
+            Some(granule) if !self.pending.is_empty() => granule,
+            _ => return,
+        };
+        let total: u64 = self.pending.iter().map(|&(_, d, _)| d as u64).sum();
+        // the first audio page anchors the timeline to its granule position,
+        // as does the first after a gap
+        let start = match self.position {
+            Some(position) if self.lost => {
+                let start = granule.saturating_sub(total).max(position);
+                self.lose_until(start, events);
+                start
+            }
+            Some(position) => position,
+            None => granule.saturating_sub(total),
+        };
+        self.lost = false;
+        let mut excess = if eos {
+            start.saturating_add(total).saturating_sub(granule)
+        } else {
+            0
+        };
+
+        for &mut (_, duration, ref mut trim_end) in self.pending.iter_mut().rev() {
+            let cut = excess.min(duration as u64);
+            *trim_end = cut as u32;
+            excess -= cut;
+        }
+
+        let mut timestamp = start;
+        for (data, duration, trim_end) in self.pending.drain(..) {
+            let trim_start = self
+                .pre_skip
+                .saturating_sub(timestamp)
+                .min((duration - trim_end) as u64) as u32;
+            events.push(Event::Audio(AudioPacket {
+                data,
+                info: PacketInfo {
+                    sequence: self.sequence,
+                    timestamp,
+                    duration,
+                },
+                trim_start,
+                trim_end,
+            }));
+            self.sequence += 1;
+            timestamp = timestamp.saturating_add(duration as u64);
+        }
+        self.position = Some(timestamp);
+    }
+}
+
+/// Turns Ogg Opus bytes into header, tag, and audio events.
+///
+/// Bytes may be pushed in chunks of any size; incomplete pages are kept until
+/// the rest arrives. Only the first logical stream in each link of a chain is
+/// followed, other multiplexed streams are skipped.
+///
+/// Packet buffers are taken from a `BufferPool`. Handing the data of each
+/// audio packet back with `recycle` once it has been decoded, and pushing
+/// with `push_bytes_into`, makes steady-state demuxing allocation-free.
+///
+/// A damaged page is an error by default. In lenient mode it is skipped
+/// instead, the demuxer resynchronizing on the next capture pattern, and the
+/// audio missing from the stream is reported as `Event::DataLoss`. This
+/// salvages what remains of files which were corrupted or cut short, such as
+/// recordings of a session which crashed.
+#[derive(Debug, Clone)]
+pub struct OggDemuxer<P = VecPool> {
+    buf: Vec<u8>,
+    page: Page,
+    packets: Vec<Vec<u8>>,
+    assembler: PacketAssembler,
+    stream: StreamState<Vec<u8>>,
+    pool: P,
+    lenient: bool,
+    // the sequence number expected of the next page of the stream
+    next_page: Option<u32>,
+    // whether to look for the first page after a resync
+    seeking: bool,
+}
+
+impl Default for OggDemuxer {
+    fn default() -> OggDemuxer {
+        OggDemuxer::new()
+    }
+}
+
+impl OggDemuxer {
+    /// Create a demuxer expecting the start of a stream.
+    pub fn new() -> OggDemuxer {
+        OggDemuxer::with_pool(VecPool::new())
+    }
+}
+
+impl<P: BufferPool> OggDemuxer<P> {
+    /// Create a demuxer taking its packet buffers from `pool`.
+    pub fn with_pool(pool: P) -> OggDemuxer<P> {
+        OggDemuxer {
+            buf: Vec::new(),
+            page: Page::default(),
+            packets: Vec::new(),
+            assembler: PacketAssembler::new(),
+            stream: StreamState::new(),
+            pool,
+            lenient: false,
+            next_page: None,
+            seeking: false,
+        }
+    }
+
+    /// Set whether damaged pages are skipped rather than being an error.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    /// Determine whether damaged pages are skipped.
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// Get the buffer pool.
+    pub fn pool(&mut self) -> &mut P {
+        &mut self.pool
+    }
+
+    /// Return the data of a decoded audio packet to the buffer pool.
+    pub fn recycle(&mut self, data: Vec<u8>) {
+        self.pool.recycle(data);
+    }
+
+    /// Get the number of bytes buffered waiting for a complete page.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Get the serial number of the logical stream being followed, or last
+    /// followed if it has ended.
+    pub fn serial(&self) -> Option<u32> {
+        self.stream.last_serial()
+    }
+
+    /// Forget all stream state and buffered bytes.
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.seeking = false;
+        self.restart();
+        self.stream = StreamState::new();
+    }
+
+    /// Forget buffered bytes and the position in the current stream, keeping
+    /// its headers, so that bytes from another page of the stream can be
+    /// pushed, as after seeking the input. Bytes up to the first page are
+    /// skipped, and a stream which has ended is resumed.
+    ///
+    /// The timeline is anchored anew by the next granule position, and a
+    /// packet continued from an earlier page is dropped.
+    pub fn resync(&mut self) {
+        self.buf.clear();
+        self.seeking = true;
+        self.next_page = None;
+        self.assembler.reset();
+        let pool = &mut self.pool;
+        self.stream.resync(|data| pool.recycle(data));
+    }
+
+    /// Feed bytes of the stream, returning the events they complete.
+    pub fn push_bytes(&mut self, data: &[u8]) -> io::Result<Vec<Event>> {
+        let mut events = Vec::new();
+        self.push_bytes_into(data, &mut events)?;
+        Ok(events)
+    }
+
+    /// Feed bytes of the stream, appending the events they complete.
+    pub fn push_bytes_into(&mut self, data: &[u8], events: &mut Vec<Event>) -> io::Result<()> {
+        self.buf.extend_from_slice(data);
+        let mut page = std::mem::take(&mut self.page);
+        let mut pos = 0;
+        let result = loop {
+            match Page::parse_into(&self.buf[pos..], &mut page) {
+                Ok(Some(len)) => {
+                    pos += len;
+                    self.seeking = false;
+                    if let Err(err) = self.push_page(&page, events) {
+                        break Err(err);
+                    }
+                }
+                Ok(None) => break Ok(()),
+                // a gap this leaves in the stream is found from the page
+                // sequence numbers
+                Err(_) if self.lenient || self.seeking => {
+                    pos += 1 + find_capture(&self.buf[pos + 1..])
+                }
+                Err(err) => break Err(err),
+            }
+        };
+        self.page = page;
+        self.buf.drain(..pos);
+        result
+    }
+
+    /// Signal the end of the input, appending any events this completes.
+    ///
+    /// Input ending in the middle of a page is an error, unless the demuxer
+    /// is lenient. Then any whole pages after the partial one, whose header
+    /// may be damaged rather than cut off, are still demuxed, and the rest is
+    /// dropped and reported as `Event::DataLoss` of unknown length.
+    pub fn finish_into(&mut self, events: &mut Vec<Event>) -> io::Result<()> {
+        if self.seeking {
+            // no page was found after a resync
+            self.buf.clear();
+        }
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        if !self.lenient {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated Ogg page",
+            ));
+        }
+        let mut page = std::mem::take(&mut self.page);
+        let mut pos = 0;
+        let mut dropped = false;
+        let mut result = Ok(());
+        while pos < self.buf.len() {
+            match Page::parse_into(&self.buf[pos..], &mut page) {
+                Ok(Some(len)) => {
+                    pos += len;
+                    // a page of the stream lost with the dropped bytes is
+                    // found from the page sequence numbers
+                    dropped = false;
+                    result = self.push_page(&page, events);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                _ => {
+                    pos += 1 + find_capture(&self.buf[pos + 1..]);
+                    dropped = true;
+                }
+            }
+        }
+        if dropped {
+            events.push(Event::DataLoss { samples: 0 });
+        }
+        self.page = page;
+        self.buf.clear();
+        result
+    }
+
+    /// Feed a single parsed page, appending the events it completes.
+    pub fn push_page(&mut self, page: &Page, events: &mut Vec<Event>) -> io::Result<()> {
+        if !self.stream.accept(page.is_bos(), page.serial) {
+            return Ok(());
+        }
+        event!(
+            trace,
+            serial = page.serial,
+            sequence = page.sequence,
+            granule = ?page.granule_position,
+            bytes = page.len(),
+            "read Ogg page"
+        );
+        if self.lenient {
+            if self.next_page.is_some_and(|next| next != page.sequence) {
+                event!(
+                    debug,
+                    expected = ?self.next_page,
+                    sequence = page.sequence,
+                    "lost Ogg pages"
+                );
+                // drop any packet left unfinished by the lost pages
+                self.assembler.reset();
+                self.stream.lose();
+            }
+            self.next_page = Some(page.sequence.wrapping_add(1));
+        }
+
+        let mut packets = std::mem::take(&mut self.packets);
+        {
+            let pool = &mut self.pool;
+            self.assembler.push(page, |packet| {
+                let mut buf = pool.take();
+                buf.extend_from_slice(packet);
+                packets.push(buf);
+            });
+        }
+        let mut result = Ok(());
+        for packet in packets.drain(..) {
+            if result.is_ok() {
+                let pool = &mut self.pool;
+                result = self
+                    .stream
+                    .packet(packet, events, |packet| pool.recycle(packet));
+            } else {
+                self.pool.recycle(packet);
+            }
+        }
+        self.packets = packets;
+        result?;
+        self.stream
+            .flush(page.granule_position, page.is_eos(), events);
+
+        if page.is_eos() {
+            events.push(Event::Eos);
+            self.restart();
+        }
+        Ok(())
+    }
+
+    /// Forget the state of the current logical stream.
+    fn restart(&mut self) {
+        self.next_page = None;
+        self.assembler.reset();
+        let pool = &mut self.pool;
+        self.stream.restart(|data| pool.recycle(data));
+    }
+}
+
+// Get the offset in `buf` of the next capture pattern, or of the start of one
+// which may be cut off at its end.
+fn find_capture(buf: &[u8]) -> usize {
+    (0..buf.len())
+        .find(|&i| CAPTURE.starts_with(&buf[i..buf.len().min(i + CAPTURE.len())]))
+        .unwrap_or(buf.len())
+}
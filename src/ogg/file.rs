@@ -0,0 +1,490 @@
+//! Decoding whole Ogg Opus files.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+#[cfg(feature = "http")]
+use super::super::http::HttpReader;
+use super::super::mix::{vorbis_layout, Mixer};
+use super::super::time::Timestamp48k;
+use super::super::{Decoder, MultistreamDecoder};
+use super::gain::GainPolicy;
+use super::head::{Chapter, OpusHead, OpusTags};
+use super::page::{PageRef, CAPTURE, MAX_PAGE_LEN};
+use super::read::OggOpusReader;
+
+/// Audio decoded ahead of a seek target so that the decoder has converged by
+/// the time it is reached, as RFC 7845 section 4.6 recommends.
+const PREROLL: u64 = 3840;
+
+/// An Ogg Opus file decoded to interleaved 48kHz samples.
+///
+/// Surround files of mapping family 1 are downmixed to stereo, and files of
+/// family 255 decoded with all their channels as they are.
+///
+/// Samples can be read a frame at a time with `read_frame` or `frames`, all
+/// at once with `decode_all`, or one by one through `Iterator`, which ends at
+/// the first error; the error can then be retrieved with `take_error`. Over
+/// a seekable reader, such as a file from `open`, the duration can be found
+/// and playback moved with `seek`.
+///
+/// The output gain of the header is applied as the file is decoded, and
+/// `set_gain_policy` can add the R128 normalization gains and a gain of
+/// one's own.
+#[derive(Debug)]
+pub struct OpusFile<R> {
+    reader: OggOpusReader<R>,
+    head: OpusHead,
+    decoder: FileDecoder,
+    channels: usize,
+    pcm: Vec<i16>,
+    pos: usize,
+    error: Option<io::Error>,
+    duration: Option<Duration>,
+    // the granule position before which decoded samples are dropped
+    skip_to: u64,
+    gain_policy: GainPolicy,
+    // the gain beyond the range of the decoder's
+    scale: f32,
+}
+
+impl OpusFile<BufReader<File>> {
+    /// Open an Ogg Opus file for decoding.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<OpusFile<BufReader<File>>> {
+        OpusFile::new(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(feature = "http")]
+impl OpusFile<HttpReader> {
+    /// Open an Ogg Opus file over HTTP, fetching it as it is decoded.
+    ///
+    /// If the server accepts range requests, finding the duration and
+    /// seeking fetch only the pages they need.
+    pub fn open_url(url: &str) -> io::Result<OpusFile<HttpReader>> {
+        OpusFile::new(HttpReader::open(url)?)
+    }
+}
+
+impl<R: Read> OpusFile<R> {
+    /// Read the headers of an Ogg Opus stream and prepare to decode it.
+    pub fn new(reader: R) -> io::Result<OpusFile<R>> {
+        let mut reader = OggOpusReader::new(reader);
+        let head = reader.read_headers()?.clone();
+        let mut decoder = FileDecoder::new(&head)?;
+        let gain_policy = GainPolicy::default();
+        let scale = decoder.set_gain(&gain_policy, &head, reader.tags())?;
+        let channels = decoder.channels();
+        Ok(OpusFile {
+            reader,
+            head,
+            decoder,
+            channels,
+            pcm: Vec::new(),
+            pos: 0,
+            error: None,
+            duration: None,
+            skip_to: 0,
+            gain_policy,
+            scale,
+        })
+    }
+
+    /// Get the identification header.
+    pub fn head(&self) -> &OpusHead {
+        &self.head
+    }
+
+    /// Get the comment header.
+    pub fn tags(&self) -> Option<&OpusTags> {
+        self.reader.tags()
+    }
+
+    /// Get the chapter markers of the comment header.
+    pub fn chapters(&self) -> Vec<Chapter> {
+        self.tags().map_or_else(Vec::new, OpusTags::chapters)
+    }
+
+    /// Get the number of interleaved channels.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Set how loud to play the file, from the next frame decoded.
+    pub fn set_gain_policy(&mut self, policy: GainPolicy) -> io::Result<()> {
+        self.scale = self
+            .decoder
+            .set_gain(&policy, &self.head, self.reader.tags())?;
+        self.gain_policy = policy;
+        Ok(())
+    }
+
+    /// Get the gain policy.
+    pub fn gain_policy(&self) -> GainPolicy {
+        self.gain_policy
+    }
+
+    /// Decode the next packet, returning its trimmed interleaved samples, or
+    /// `None` at the end of the stream.
+    ///
+    /// Any samples not yet consumed through `Iterator` are discarded.
+    pub fn read_frame(&mut self) -> io::Result<Option<&[i16]>> {
+        loop {
+            let audio = match self.reader.next_audio()? {
+                Some(audio) => audio,
+                None => return Ok(None),
+            };
+            self.pcm
+                .resize(audio.info.duration as usize * self.channels, 0);
+            let len = self.decoder.decode(&audio.data, &mut self.pcm);
+            self.reader.recycle(audio.data);
+            let len = len?;
+            let mut start = (audio.trim_start as usize).min(len);
+            let end = len.saturating_sub(audio.trim_end as usize).max(start);
+            let first = audio.info.timestamp + start as u64;
+            if self.skip_to > first {
+                start += (self.skip_to - first).min((end - start) as u64) as usize;
+            }
+            if start < end {
+                self.pcm.truncate(end * self.channels);
+                if self.scale != 1.0 {
+                    for sample in &mut self.pcm[start * self.channels..] {
+                        *sample = (*sample as f32 * self.scale).round() as i16;
+                    }
+                }
+                self.pos = self.pcm.len();
+                return Ok(Some(&self.pcm[start * self.channels..]));
+            }
+        }
+    }
+
+    /// Get an iterator over the trimmed interleaved samples of each remaining
+    /// packet, as `read_frame` returns them.
+    pub fn frames(&mut self) -> Frames<'_, R> {
+        Frames { file: self }
+    }
+
+    /// Decode the rest of the stream into interleaved samples, including any
+    /// not yet consumed through `Iterator`.
+    pub fn decode_all(&mut self) -> io::Result<Vec<i16>> {
+        let mut pcm = self.pcm[self.pos..].to_vec();
+        self.pos = self.pcm.len();
+        while let Some(frame) = self.read_frame()? {
+            pcm.extend_from_slice(frame);
+        }
+        Ok(pcm)
+    }
+
+    /// Take the error which ended iteration, if any.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    /// Unwrap the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+impl<R: Read + Seek> OpusFile<R> {
+    /// Get the playback duration of the stream, from the granule position of
+    /// its last page.
+    ///
+    /// The end of the input is read once, after which the duration is
+    /// remembered.
+    pub fn duration(&mut self) -> io::Result<Duration> {
+        if let Some(duration) = self.duration {
+            return Ok(duration);
+        }
+        let serial = self.serial()?;
+        let reader = self.reader.get_mut();
+        let pos = reader.stream_position()?;
+        let granule = last_granule(reader, serial);
+        reader.seek(SeekFrom::Start(pos))?;
+        let duration = Timestamp48k::from_granule(granule?, self.head.pre_skip).to_duration();
+        self.duration = Some(duration);
+        Ok(duration)
+    }
+
+    /// Move playback to `time` from the start of the stream.
+    ///
+    /// The page to resume from is found by bisection, and decoding starts
+    /// 80 ms ahead of `time` so that the output is exact from there on.
+    /// Seeking is within the logical stream being decoded, so in a chained
+    /// file only the first link is reachable.
+    pub fn seek(&mut self, time: Duration) -> io::Result<()> {
+        let serial = self.serial()?;
+        let target = Timestamp48k::from(time).to_granule(self.head.pre_skip);
+        let start = target.saturating_sub(PREROLL);
+        let resume = {
+            let reader = self.reader.get_mut();
+            let (mut lo, mut hi) = (0, reader.seek(SeekFrom::End(0))?);
+            let mut resume = None;
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                match find_page(reader, serial, mid, hi)? {
+                    Some((offset, granule)) if granule <= start => {
+                        resume = Some(offset);
+                        lo = offset + 1;
+                    }
+                    _ => hi = mid,
+                }
+            }
+            reader.seek(SeekFrom::Start(resume.unwrap_or(0)))?;
+            resume
+        };
+        if resume.is_some() {
+            self.reader.resync();
+        } else {
+            // the target is within the first audio page
+            self.reader.reset();
+            self.reader.read_headers()?;
+        }
+        self.decoder.reset()?;
+        self.pcm.clear();
+        self.pos = 0;
+        self.error = None;
+        self.skip_to = target;
+        Ok(())
+    }
+
+    fn serial(&self) -> io::Result<u32> {
+        self.reader
+            .serial()
+            .ok_or_else(|| io::Error::other("Ogg Opus stream has no serial number"))
+    }
+}
+
+/// An iterator over the decoded frames of an `OpusFile`, returned from
+/// `OpusFile::frames`.
+#[derive(Debug)]
+pub struct Frames<'a, R: 'a> {
+    file: &'a mut OpusFile<R>,
+}
+
+impl<'a, R: Read> Iterator for Frames<'a, R> {
+    type Item = io::Result<Vec<i16>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<i16>>> {
+        match self.file.read_frame() {
+            Ok(Some(frame)) => Some(Ok(frame.to_vec())),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+// Find the first audio page of stream `serial` with a granule position which
+// starts from `offset` up to `end`, returning its offset and granule
+// position.
+fn find_page<R: Read + Seek>(
+    reader: &mut R,
+    serial: u32,
+    mut offset: u64,
+    end: u64,
+) -> io::Result<Option<(u64, u64)>> {
+    let mut buf = Vec::new();
+    while offset < end {
+        let limit = (end - offset).min(MAX_PAGE_LEN as u64) as usize;
+        read_at(reader, offset, limit + MAX_PAGE_LEN, &mut buf)?;
+        if let Some(&(pos, granule)) = audio_pages(&buf, limit, serial).first() {
+            return Ok(Some((offset + pos as u64, granule)));
+        }
+        offset += limit as u64;
+    }
+    Ok(None)
+}
+
+// Find the granule position of the last page of stream `serial`.
+fn last_granule<R: Read + Seek>(reader: &mut R, serial: u32) -> io::Result<u64> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let mut buf = Vec::new();
+    let mut end = len;
+    while end > 0 {
+        let offset = end.saturating_sub(MAX_PAGE_LEN as u64);
+        let limit = (end - offset) as usize;
+        read_at(reader, offset, limit + MAX_PAGE_LEN, &mut buf)?;
+        if let Some(&(_, granule)) = audio_pages(&buf, limit, serial).last() {
+            return Ok(granule);
+        }
+        end = offset;
+    }
+    Ok(0)
+}
+
+// Get the offset and granule position of every valid audio page of stream
+// `serial` starting before `limit` in `buf`. Header pages, with granule
+// position zero, are left out.
+fn audio_pages(buf: &[u8], limit: usize, serial: u32) -> Vec<(usize, u64)> {
+    let mut pages = Vec::new();
+    let mut pos = 0;
+    while pos < limit.min(buf.len()) {
+        if !buf[pos..].starts_with(CAPTURE) {
+            pos += 1;
+            continue;
+        }
+        match PageRef::parse(&buf[pos..]) {
+            Ok(Some((page, len))) => {
+                if let (true, Some(granule)) = (page.serial == serial, page.granule_position) {
+                    if granule > 0 {
+                        pages.push((pos, granule));
+                    }
+                }
+                pos += len;
+            }
+            _ => pos += 1,
+        }
+    }
+    pages
+}
+
+// Read up to `len` bytes from `offset` into `buf`.
+fn read_at<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    len: usize,
+    buf: &mut Vec<u8>,
+) -> io::Result<()> {
+    reader.seek(SeekFrom::Start(offset))?;
+    buf.clear();
+    reader.take(len as u64).read_to_end(buf)?;
+    Ok(())
+}
+
+// A decoder for the channel mapping of a file.
+#[derive(Debug)]
+enum FileDecoder {
+    Single(Decoder),
+    Surround {
+        decoder: MultistreamDecoder,
+        mixer: Mixer,
+        pcm: Vec<i16>,
+    },
+    Discrete(MultistreamDecoder),
+}
+
+impl FileDecoder {
+    fn new(head: &OpusHead) -> io::Result<FileDecoder> {
+        match head.mapping_family {
+            0 => return Ok(FileDecoder::Single(head.decoder()?)),
+            255 => {
+                let decoder = MultistreamDecoder::new(48000, head.layout())?;
+                return Ok(FileDecoder::Discrete(decoder));
+            }
+            _ => (),
+        }
+        let channels = head.channels as usize;
+        let (layout, output) = match (
+            head.mapping_family,
+            vorbis_layout(channels),
+            vorbis_layout(channels.min(2)),
+        ) {
+            (1, Some(layout), Some(output)) => (layout, output),
+            _ => return Err(io::Error::other("unsupported Ogg Opus channel mapping")),
+        };
+        let decoder = MultistreamDecoder::new(48000, head.layout())?;
+        Ok(FileDecoder::Surround {
+            decoder,
+            mixer: Mixer::downmix(layout, output).with_limiter(48000),
+            pcm: Vec::new(),
+        })
+    }
+
+    fn set_gain(
+        &mut self,
+        policy: &GainPolicy,
+        head: &OpusHead,
+        tags: Option<&OpusTags>,
+    ) -> io::Result<f32> {
+        match *self {
+            FileDecoder::Single(ref mut decoder) => policy.apply(decoder, head, tags),
+            FileDecoder::Surround {
+                ref mut decoder, ..
+            }
+            | FileDecoder::Discrete(ref mut decoder) => {
+                policy.apply_multistream(decoder, head, tags)
+            }
+        }
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        match *self {
+            FileDecoder::Single(ref mut decoder) => decoder.reset_state()?,
+            FileDecoder::Surround {
+                ref mut decoder, ..
+            }
+            | FileDecoder::Discrete(ref mut decoder) => decoder.reset_state()?,
+        }
+        Ok(())
+    }
+
+    fn channels(&self) -> usize {
+        match *self {
+            FileDecoder::Single(ref decoder) => decoder.channels as usize,
+            FileDecoder::Surround { ref mixer, .. } => mixer.outputs(),
+            FileDecoder::Discrete(ref decoder) => decoder.layout().channels as usize,
+        }
+    }
+
+    fn decode(&mut self, packet: &[u8], output: &mut [i16]) -> io::Result<usize> {
+        match *self {
+            FileDecoder::Single(ref mut decoder) => Ok(decoder.decode(packet, output, false)?),
+            FileDecoder::Surround {
+                ref mut decoder,
+                ref mut mixer,
+                ref mut pcm,
+            } => {
+                pcm.resize(output.len() / mixer.outputs() * mixer.inputs(), 0);
+                let len = decoder.decode(packet, pcm, false)?;
+                mixer.process_i16(&pcm[..len * mixer.inputs()], output);
+                Ok(len)
+            }
+            FileDecoder::Discrete(ref mut decoder) => Ok(decoder.decode(packet, output, false)?),
+        }
+    }
+}
+
+impl<R: Read> Iterator for OpusFile<R> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.pos == self.pcm.len() {
+            if self.error.is_some() {
+                return None;
+            }
+            let start = match self.read_frame() {
+                Ok(Some(frame)) => frame.len(),
+                Ok(None) => return None,
+                Err(err) => {
+                    self.error = Some(err);
+                    return None;
+                }
+            };
+            self.pos = self.pcm.len() - start;
+        }
+        let sample = self.pcm[self.pos];
+        self.pos += 1;
+        Some(sample)
+    }
+}
+
+#[cfg(feature = "rodio")]
+impl<R: Read> ::rodio::Source for OpusFile<R> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        48000
+    }
+
+    fn total_duration(&self) -> Option<::std::time::Duration> {
+        None
+    }
+}
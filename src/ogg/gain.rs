@@ -0,0 +1,116 @@
+//! Choosing the playback gain of an Ogg Opus stream.
+
+use std::io;
+
+use super::super::{Decoder, MultistreamDecoder};
+use super::head::{OpusHead, OpusTags};
+
+/// Which of the R128 normalization gains of the comment header to apply.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ReplayGain {
+    /// Neither.
+    Off,
+    /// `R128_TRACK_GAIN`, normalizing each track on its own.
+    Track,
+    /// `R128_ALBUM_GAIN`, keeping the levels of an album's tracks relative
+    /// to each other, or the track gain if there is no album gain.
+    Album,
+}
+
+/// How loud to play a stream: the output gain of its header, a normalization
+/// gain from its tags, and a gain of the player's own, combined into one.
+///
+/// Both R128 gains are relative to the output gain and bring the stream to
+/// -23 LUFS (RFC 7845 section 5.2.1). The total is set on the decoder with
+/// `OPUS_SET_GAIN` where it lies within the ±128 dB that accepts, with the
+/// rest left as a linear scale for the caller to apply.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GainPolicy {
+    /// Whether to apply the output gain of the identification header, which
+    /// RFC 7845 requires of players by default.
+    pub header_gain: bool,
+    /// The normalization gain to apply.
+    pub replay_gain: ReplayGain,
+    /// A further gain in dB, such as a volume setting or a preamp raising
+    /// R128 levels towards the -18 LUFS of ReplayGain 2.0.
+    pub user_gain: f64,
+}
+
+impl Default for GainPolicy {
+    /// Only the header's output gain, as RFC 7845 playback requires.
+    fn default() -> GainPolicy {
+        GainPolicy {
+            header_gain: true,
+            replay_gain: ReplayGain::Off,
+            user_gain: 0.0,
+        }
+    }
+}
+
+impl GainPolicy {
+    /// Get the policy applying the header gain and the track gain.
+    pub fn track() -> GainPolicy {
+        GainPolicy {
+            replay_gain: ReplayGain::Track,
+            ..GainPolicy::default()
+        }
+    }
+
+    /// Get the policy applying the header gain and the album gain.
+    pub fn album() -> GainPolicy {
+        GainPolicy {
+            replay_gain: ReplayGain::Album,
+            ..GainPolicy::default()
+        }
+    }
+
+    /// Get the total gain in dB for a stream.
+    pub fn gain_db(&self, head: &OpusHead, tags: Option<&OpusTags>) -> f64 {
+        let mut q8 = 0;
+        if self.header_gain {
+            q8 += head.output_gain as i32;
+        }
+        let normalization = tags.and_then(|tags| match self.replay_gain {
+            ReplayGain::Off => None,
+            ReplayGain::Track => tags.r128_track_gain(),
+            ReplayGain::Album => tags.r128_album_gain().or_else(|| tags.r128_track_gain()),
+        });
+        q8 += normalization.unwrap_or(0) as i32;
+        q8 as f64 / 256.0 + self.user_gain
+    }
+
+    /// Split the total gain for a stream into a Q7.8 dB gain for
+    /// `OPUS_SET_GAIN`, and a linear scale for whatever lies beyond its
+    /// range.
+    pub fn split(&self, head: &OpusHead, tags: Option<&OpusTags>) -> (i32, f32) {
+        let q8 = (self.gain_db(head, tags) * 256.0).round();
+        let set = q8.clamp(i16::MIN as f64, i16::MAX as f64);
+        let scale = 10f64.powf((q8 - set) / (20.0 * 256.0));
+        (set as i32, scale as f32)
+    }
+
+    /// Set the gain for a stream on `decoder`, returning the linear scale
+    /// still to be applied to its output.
+    pub fn apply(
+        &self,
+        decoder: &mut Decoder,
+        head: &OpusHead,
+        tags: Option<&OpusTags>,
+    ) -> io::Result<f32> {
+        let (gain, scale) = self.split(head, tags);
+        decoder.set_gain(gain)?;
+        Ok(scale)
+    }
+
+    /// Set the gain for a stream on a multistream decoder, as `apply` does.
+    pub fn apply_multistream(
+        &self,
+        decoder: &mut MultistreamDecoder,
+        head: &OpusHead,
+        tags: Option<&OpusTags>,
+    ) -> io::Result<f32> {
+        let (gain, scale) = self.split(head, tags);
+        decoder.set_gain(gain)?;
+        Ok(scale)
+    }
+}
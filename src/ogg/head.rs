@@ -0,0 +1,629 @@
+//! The Ogg Opus identification and comment headers (RFC 7845 section 5).
+
+use std::io;
+use std::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "projection")]
+use super::super::projection::{DemixingMatrix, ProjectionDecoder};
+use super::super::{Channels, Decoder, StreamLayout};
+use super::page::invalid;
+use super::picture::{Picture, DEFAULT_MAX_PICTURE_LEN, PICTURE_FIELD};
+
+/// Magic signature starting the identification header.
+pub const HEAD_MAGIC: &[u8; 8] = b"OpusHead";
+
+/// Magic signature starting the comment header.
+pub const TAGS_MAGIC: &[u8; 8] = b"OpusTags";
+
+/// The identification header, the first packet of an Ogg Opus stream.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OpusHead {
+    /// The encapsulation version; only major version 0 is understood.
+    pub version: u8,
+    /// The number of output channels.
+    pub channels: u8,
+    /// The number of 48kHz samples to discard from the start of the stream.
+    pub pre_skip: u16,
+    /// The sample rate of the original input, for information only.
+    pub input_sample_rate: u32,
+    /// The gain to apply to the decoded output, in Q7.8 dB.
+    pub output_gain: i16,
+    /// The channel mapping family.
+    pub mapping_family: u8,
+    /// The number of Opus streams in each packet; 1 for family 0.
+    pub stream_count: u8,
+    /// The number of coupled stereo streams; `channels - 1` for family 0.
+    pub coupled_count: u8,
+    /// The stream index of each output channel; empty for family 0. For
+    /// family 3 this is instead the demixing matrix, as written by
+    /// `DemixingMatrix::to_bytes`.
+    pub mapping: Vec<u8>,
+}
+
+impl OpusHead {
+    /// Create a header for a family 0 (mono or stereo) stream.
+    pub fn new(channels: u8, pre_skip: u16, input_sample_rate: u32) -> OpusHead {
+        OpusHead {
+            version: 1,
+            channels,
+            pre_skip,
+            input_sample_rate,
+            output_gain: 0,
+            mapping_family: 0,
+            stream_count: 1,
+            coupled_count: channels.saturating_sub(1),
+            mapping: Vec::new(),
+        }
+    }
+
+    /// Parse an identification header packet.
+    pub fn parse(packet: &[u8]) -> io::Result<OpusHead> {
+        if packet.len() < 19 || &packet[..8] != HEAD_MAGIC {
+            return Err(invalid("invalid OpusHead packet"));
+        }
+        let version = packet[8];
+        if version >> 4 != 0 {
+            return Err(invalid("unsupported OpusHead version"));
+        }
+        let channels = packet[9];
+        if channels == 0 {
+            return Err(invalid("OpusHead has no channels"));
+        }
+        let mut head = OpusHead {
+            version,
+            channels,
+            pre_skip: u16::from_le_bytes([packet[10], packet[11]]),
+            input_sample_rate: u32::from_le_bytes([packet[12], packet[13], packet[14], packet[15]]),
+            output_gain: i16::from_le_bytes([packet[16], packet[17]]),
+            mapping_family: packet[18],
+            stream_count: 1,
+            coupled_count: channels - 1,
+            mapping: Vec::new(),
+        };
+        if head.mapping_family == 0 {
+            if channels > 2 {
+                return Err(invalid("OpusHead family 0 has more than two channels"));
+            }
+        } else {
+            let table = &packet[19..];
+            if table.len() < 2 + channels as usize {
+                return Err(invalid("truncated OpusHead channel mapping"));
+            }
+            head.stream_count = table[0];
+            head.coupled_count = table[1];
+            if head.mapping_family == 3 {
+                let stream_channels = head.stream_count as usize + head.coupled_count as usize;
+                let len = 2 * channels as usize * stream_channels;
+                if table.len() < 2 + len {
+                    return Err(invalid("truncated OpusHead demixing matrix"));
+                }
+                head.mapping = table[2..2 + len].to_vec();
+                if head.stream_count == 0
+                    || head.coupled_count > head.stream_count
+                    || stream_channels > 255
+                {
+                    return Err(invalid("invalid OpusHead stream counts"));
+                }
+                return Ok(head);
+            }
+            head.mapping = table[2..2 + channels as usize].to_vec();
+            if !head.layout().is_valid() {
+                return Err(invalid("invalid OpusHead channel mapping"));
+            }
+            if head.mapping_family == 1 && channels > 8 {
+                return Err(invalid("OpusHead family 1 has more than eight channels"));
+            }
+        }
+        Ok(head)
+    }
+
+    /// Create a header for a multistream stream of the given mapping family
+    /// and layout, such as those of a `MultistreamEncoder`.
+    ///
+    /// Family 255 takes any valid layout, and is meant for channels with no
+    /// defined meaning, such as `StreamLayout::discrete`.
+    pub fn multistream(
+        mapping_family: u8,
+        layout: &StreamLayout,
+        pre_skip: u16,
+        input_sample_rate: u32,
+    ) -> io::Result<OpusHead> {
+        if mapping_family == 0 || !layout.is_valid() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid channel mapping for OpusHead",
+            ));
+        }
+        Ok(OpusHead {
+            version: 1,
+            channels: layout.channels,
+            pre_skip,
+            input_sample_rate,
+            output_gain: 0,
+            mapping_family,
+            stream_count: layout.streams,
+            coupled_count: layout.coupled_streams,
+            mapping: layout.mapping.clone(),
+        })
+    }
+
+    /// Create a header for an ambisonics stream of mapping family 3, from the
+    /// stream counts and demixing matrix of a `ProjectionEncoder`.
+    ///
+    /// The gain of the matrix becomes the output gain.
+    #[cfg(feature = "projection")]
+    pub fn ambisonics(
+        streams: u8,
+        coupled_streams: u8,
+        matrix: &DemixingMatrix,
+        pre_skip: u16,
+        input_sample_rate: u32,
+    ) -> io::Result<OpusHead> {
+        let stream_channels = streams as usize + coupled_streams as usize;
+        if streams == 0
+            || coupled_streams > streams
+            || matrix.stream_channels() as usize != stream_channels
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "demixing matrix does not match the streams",
+            ));
+        }
+        Ok(OpusHead {
+            version: 1,
+            channels: matrix.channels(),
+            pre_skip,
+            input_sample_rate,
+            output_gain: matrix.gain.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+            mapping_family: 3,
+            stream_count: streams,
+            coupled_count: coupled_streams,
+            mapping: matrix.to_bytes(),
+        })
+    }
+
+    /// Get the demixing matrix of a family 3 stream.
+    #[cfg(feature = "projection")]
+    pub fn demixing_matrix(&self) -> Option<DemixingMatrix> {
+        if self.mapping_family != 3 {
+            return None;
+        }
+        let stream_channels = self.stream_count.checked_add(self.coupled_count)?;
+        DemixingMatrix::from_bytes(self.channels, stream_channels, &self.mapping).ok()
+    }
+
+    /// Create a 48kHz decoder for a family 3 stream, with the output gain
+    /// applied.
+    #[cfg(feature = "projection")]
+    pub fn projection_decoder(&self) -> io::Result<ProjectionDecoder> {
+        let matrix = self
+            .demixing_matrix()
+            .ok_or_else(|| io::Error::other("not an Ogg Opus ambisonics stream"))?;
+        let mut decoder =
+            ProjectionDecoder::new(48000, self.stream_count, self.coupled_count, &matrix)?;
+        decoder.set_gain(self.output_gain as i32)?;
+        Ok(decoder)
+    }
+
+    /// Create a 48kHz decoder for this stream, with the output gain applied.
+    ///
+    /// Only mapping family 0 (mono and stereo) is supported.
+    pub fn decoder(&self) -> io::Result<Decoder> {
+        let channels = match (self.mapping_family, self.channels) {
+            (0, 1) => Channels::Mono,
+            (0, 2) => Channels::Stereo,
+            _ => return Err(io::Error::other("unsupported Ogg Opus channel mapping")),
+        };
+        let mut decoder = Decoder::new(48000, channels)?;
+        decoder.set_gain(self.output_gain as i32)?;
+        Ok(decoder)
+    }
+
+    /// Get the stream layout of a multistream decoder for this stream.
+    pub fn layout(&self) -> StreamLayout {
+        let mapping = if self.mapping_family == 0 {
+            (0..self.channels).collect()
+        } else {
+            self.mapping.clone()
+        };
+        StreamLayout {
+            channels: self.channels,
+            streams: self.stream_count,
+            coupled_streams: self.coupled_count,
+            mapping,
+        }
+    }
+
+    /// Append the serialized header packet to `out`.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(HEAD_MAGIC);
+        out.push(self.version);
+        out.push(self.channels);
+        out.extend_from_slice(&self.pre_skip.to_le_bytes());
+        out.extend_from_slice(&self.input_sample_rate.to_le_bytes());
+        out.extend_from_slice(&self.output_gain.to_le_bytes());
+        out.push(self.mapping_family);
+        if self.mapping_family != 0 {
+            out.push(self.stream_count);
+            out.push(self.coupled_count);
+            out.extend_from_slice(&self.mapping);
+        }
+    }
+}
+
+/// A chapter marker of a stream, as stored in its comment header.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Chapter {
+    /// The time the chapter starts from the start of the stream.
+    pub start: Duration,
+    /// The chapter's name, which may be empty.
+    pub title: String,
+}
+
+/// The comment header, the second packet of an Ogg Opus stream.
+///
+/// Comments are Vorbis comments: field names are ASCII and compare without
+/// regard to case, and a field may appear any number of times, as for a
+/// track with several artists. `get` and `set` and the accessors of the
+/// standard fields see only the first value of a field, and `get_all` and
+/// `add` all of them.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OpusTags {
+    /// The name of the encoder which produced the stream.
+    pub vendor: String,
+    /// The user comments as `(field, value)` pairs, in stream order.
+    pub comments: Vec<(String, String)>,
+}
+
+impl OpusTags {
+    /// Create a comment header with the given vendor and no comments.
+    pub fn new<S: Into<String>>(vendor: S) -> OpusTags {
+        OpusTags {
+            vendor: vendor.into(),
+            comments: Vec::new(),
+        }
+    }
+
+    /// Parse a comment header packet.
+    ///
+    /// Comments without a `=` separator are ignored, as are any binary data
+    /// following the comment list.
+    pub fn parse(packet: &[u8]) -> io::Result<OpusTags> {
+        if packet.len() < 16 || &packet[..8] != TAGS_MAGIC {
+            return Err(invalid("invalid OpusTags packet"));
+        }
+        let mut pos = 8;
+        let vendor = read_string(packet, &mut pos)?;
+        let count = read_u32(packet, &mut pos)?;
+        let mut comments = Vec::new();
+        for _ in 0..count {
+            let comment = read_string(packet, &mut pos)?;
+            if let Some(eq) = comment.find('=') {
+                comments.push((comment[..eq].to_string(), comment[eq + 1..].to_string()));
+            }
+        }
+        Ok(OpusTags { vendor, comments })
+    }
+
+    /// Get the first value of a field.
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.comments
+            .iter()
+            .find(|&(name, _)| name.eq_ignore_ascii_case(field))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Get every value of a field, in stream order.
+    pub fn get_all(&self, field: &str) -> Vec<&str> {
+        self.comments
+            .iter()
+            .filter(|&(name, _)| name.eq_ignore_ascii_case(field))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Set a field to a single value, replacing the first of any values it
+    /// had in place and removing the rest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `field` is not a valid field name: empty, or with
+    /// characters other than printable ASCII besides `=`.
+    pub fn set<V: Into<String>>(&mut self, field: &str, value: V) {
+        check_field(field);
+        let value = value.into();
+        match self
+            .comments
+            .iter()
+            .position(|(name, _)| name.eq_ignore_ascii_case(field))
+        {
+            Some(first) => {
+                self.comments[first].1 = value;
+                let rest = self.comments.split_off(first + 1);
+                self.comments.extend(
+                    rest.into_iter()
+                        .filter(|(name, _)| !name.eq_ignore_ascii_case(field)),
+                );
+            }
+            None => self.comments.push((field.to_string(), value)),
+        }
+    }
+
+    /// Add a value to a field, after any it has.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `field` is not a valid field name, as `set` does.
+    pub fn add<V: Into<String>>(&mut self, field: &str, value: V) {
+        check_field(field);
+        self.comments.push((field.to_string(), value.into()));
+    }
+
+    /// Remove every value of a field, returning how many there were.
+    pub fn remove(&mut self, field: &str) -> usize {
+        let len = self.comments.len();
+        self.comments
+            .retain(|(name, _)| !name.eq_ignore_ascii_case(field));
+        len - self.comments.len()
+    }
+
+    /// Get the `TITLE`.
+    pub fn title(&self) -> Option<&str> {
+        self.get("TITLE")
+    }
+
+    /// Set the `TITLE`.
+    pub fn set_title<V: Into<String>>(&mut self, title: V) {
+        self.set("TITLE", title);
+    }
+
+    /// Get the first `ARTIST`; `get_all("ARTIST")` returns them all.
+    pub fn artist(&self) -> Option<&str> {
+        self.get("ARTIST")
+    }
+
+    /// Set the `ARTIST`, replacing any others.
+    pub fn set_artist<V: Into<String>>(&mut self, artist: V) {
+        self.set("ARTIST", artist);
+    }
+
+    /// Get the `ALBUM`.
+    pub fn album(&self) -> Option<&str> {
+        self.get("ALBUM")
+    }
+
+    /// Set the `ALBUM`.
+    pub fn set_album<V: Into<String>>(&mut self, album: V) {
+        self.set("ALBUM", album);
+    }
+
+    /// Get the `DATE`, conventionally as in ISO 8601, such as `2024-05-01`.
+    pub fn date(&self) -> Option<&str> {
+        self.get("DATE")
+    }
+
+    /// Set the `DATE`.
+    pub fn set_date<V: Into<String>>(&mut self, date: V) {
+        self.set("DATE", date);
+    }
+
+    /// Get the `TRACKNUMBER`, which may also be written as, say, `3/12`.
+    pub fn track_number(&self) -> Option<u32> {
+        let value = self.get("TRACKNUMBER")?;
+        let number = value.split('/').next().unwrap_or(value);
+        number.trim().parse().ok()
+    }
+
+    /// Set the `TRACKNUMBER`.
+    pub fn set_track_number(&mut self, number: u32) {
+        self.set("TRACKNUMBER", number.to_string());
+    }
+
+    /// Get the start time of chapter `index`, from its `CHAPTERxxx` field,
+    /// as written, such as `00:01:30.000`.
+    pub fn chapter_time(&self, index: u32) -> Option<&str> {
+        self.get(&chapter_field(index, ""))
+    }
+
+    /// Get the name of chapter `index`, from its `CHAPTERxxxNAME` field.
+    pub fn chapter_name(&self, index: u32) -> Option<&str> {
+        self.get(&chapter_field(index, "NAME"))
+    }
+
+    /// Set the start time and name of chapter `index`.
+    pub fn set_chapter<T: Into<String>, N: Into<String>>(&mut self, index: u32, time: T, name: N) {
+        self.set(&chapter_field(index, ""), time);
+        self.set(&chapter_field(index, "NAME"), name);
+    }
+
+    /// Get the chapter markers, in order of their numbers.
+    ///
+    /// Markers follow the Vorbis comment convention of podcasting tools: a
+    /// `CHAPTER001` field holding the start time as `HH:MM:SS.mmm`, and a
+    /// `CHAPTER001NAME` field holding the name. Markers whose time can't be
+    /// parsed are skipped.
+    pub fn chapters(&self) -> Vec<Chapter> {
+        let mut indices: Vec<u32> = self
+            .comments
+            .iter()
+            .filter_map(|(name, _)| match chapter_index(name) {
+                Some((index, false)) => Some(index),
+                _ => None,
+            })
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+            .into_iter()
+            .filter_map(|index| {
+                Some(Chapter {
+                    start: parse_chapter_time(self.chapter_time(index)?)?,
+                    title: self.chapter_name(index).unwrap_or("").to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Replace the chapter markers, numbering them from `CHAPTER001`.
+    pub fn set_chapters(&mut self, chapters: &[Chapter]) {
+        self.comments
+            .retain(|(name, _)| chapter_index(name).is_none());
+        for (index, chapter) in (1..).zip(chapters) {
+            self.add(
+                &chapter_field(index, ""),
+                format_chapter_time(chapter.start),
+            );
+            self.add(&chapter_field(index, "NAME"), chapter.title.clone());
+        }
+    }
+
+    /// Get the embedded pictures, such as cover art, skipping any which are
+    /// malformed or larger than `DEFAULT_MAX_PICTURE_LEN`.
+    pub fn pictures(&self) -> Vec<Picture> {
+        self.pictures_with_limit(DEFAULT_MAX_PICTURE_LEN)
+    }
+
+    /// Get the embedded pictures, skipping any which are malformed or have
+    /// more than `max_len` bytes of data.
+    pub fn pictures_with_limit(&self, max_len: usize) -> Vec<Picture> {
+        self.get_all(PICTURE_FIELD)
+            .into_iter()
+            .filter_map(|value| Picture::parse(value, max_len))
+            .collect()
+    }
+
+    /// Get the `R128_TRACK_GAIN` of the stream, in Q7.8 dB.
+    pub fn r128_track_gain(&self) -> Option<i16> {
+        self.r128_gain("R128_TRACK_GAIN")
+    }
+
+    /// Get the `R128_ALBUM_GAIN` of the stream, in Q7.8 dB.
+    pub fn r128_album_gain(&self) -> Option<i16> {
+        self.r128_gain("R128_ALBUM_GAIN")
+    }
+
+    // An R128 gain must be a plain decimal integer.
+    fn r128_gain(&self, field: &str) -> Option<i16> {
+        let value = self.get(field)?;
+        let digits = value.strip_prefix('-').unwrap_or(value);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        value.parse().ok()
+    }
+
+    /// Append the serialized header packet to `out`.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(TAGS_MAGIC);
+        out.extend_from_slice(&(self.vendor.len() as u32).to_le_bytes());
+        out.extend_from_slice(self.vendor.as_bytes());
+        out.extend_from_slice(&(self.comments.len() as u32).to_le_bytes());
+        for (field, value) in &self.comments {
+            out.extend_from_slice(&((field.len() + 1 + value.len()) as u32).to_le_bytes());
+            out.extend_from_slice(field.as_bytes());
+            out.push(b'=');
+            out.extend_from_slice(value.as_bytes());
+        }
+    }
+}
+
+// Panic unless `field` is a valid comment field name.
+fn check_field(field: &str) {
+    assert!(
+        !field.is_empty()
+            && field
+                .bytes()
+                .all(|b| (0x20..=0x7d).contains(&b) && b != b'='),
+        "invalid comment field name {:?}",
+        field
+    );
+}
+
+// Get the name of a field of chapter `index`, numbered with at least three
+// digits.
+fn chapter_field(index: u32, suffix: &str) -> String {
+    format!("CHAPTER{:03}{}", index, suffix)
+}
+
+// Get the chapter number of a `CHAPTERxxx` or `CHAPTERxxxNAME` field name,
+// and whether it is the latter.
+fn chapter_index(field: &str) -> Option<(u32, bool)> {
+    let prefix = field.get(..7)?;
+    if !prefix.eq_ignore_ascii_case("CHAPTER") {
+        return None;
+    }
+    let rest = &field[7..];
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    let suffix = &rest[digits..];
+    let name = match suffix {
+        "" => false,
+        _ if suffix.eq_ignore_ascii_case("NAME") => true,
+        _ => return None,
+    };
+    Some((rest[..digits].parse().ok()?, name))
+}
+
+// Parse a chapter time of the form `HH:MM:SS.mmm`, where the hours, or hours
+// and minutes, may be left out, and the fraction may have any precision.
+fn parse_chapter_time(time: &str) -> Option<Duration> {
+    let (whole, fraction) = match time.trim().split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (time.trim(), ""),
+    };
+    let mut secs = 0u64;
+    let mut parts = 0;
+    for part in whole.split(':') {
+        parts += 1;
+        if parts > 3 || part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        secs = secs.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+    }
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = fraction
+        .bytes()
+        .chain(std::iter::repeat(b'0'))
+        .take(9)
+        .fold(0, |nanos, digit| nanos * 10 + (digit - b'0') as u32);
+    Some(Duration::new(secs, nanos))
+}
+
+// Format a chapter time as `HH:MM:SS.mmm`.
+fn format_chapter_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        time.subsec_millis()
+    )
+}
+
+fn read_u32(packet: &[u8], pos: &mut usize) -> io::Result<u32> {
+    match packet.get(*pos..).and_then(|rest| rest.get(..4)) {
+        Some(b) => {
+            *pos += 4;
+            Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        }
+        None => Err(invalid("truncated OpusTags packet")),
+    }
+}
+
+fn read_string(packet: &[u8], pos: &mut usize) -> io::Result<String> {
+    let len = read_u32(packet, pos)? as usize;
+    match packet.get(*pos..).and_then(|rest| rest.get(..len)) {
+        Some(bytes) => {
+            *pos += len;
+            Ok(String::from_utf8_lossy(bytes).into_owned())
+        }
+        None => Err(invalid("truncated OpusTags packet")),
+    }
+}
@@ -0,0 +1,43 @@
+//! Ogg Opus encapsulation (RFC 7845).
+//!
+//! The `page` module handles Ogg framing, and `OpusHead` and `OpusTags` the
+//! two header packets which start every Ogg Opus stream. `OggDemuxer` turns
+//! raw bytes into header and audio events without performing any I/O, and
+//! the readers drive it from synchronous or asynchronous byte streams.
+//! `OggSliceReader` instead works over bytes already in memory, such as a
+//! memory-mapped file, borrowing packets from them rather than copying.
+//! `encode_voice_message` goes the other way, for short voice recordings.
+
+pub mod page;
+pub use self::page::{PacketAssembler, Page, PageRef};
+
+mod head;
+pub use self::head::{Chapter, OpusHead, OpusTags};
+
+pub mod picture;
+pub use self::picture::Picture;
+
+mod gain;
+pub use self::gain::{GainPolicy, ReplayGain};
+
+mod demux;
+pub use self::demux::{AudioPacket, Event, OggDemuxer};
+
+mod slice;
+#[cfg(feature = "memmap2")]
+pub use self::slice::map_file;
+pub use self::slice::OggSliceReader;
+
+mod read;
+pub use self::read::OggOpusReader;
+
+mod file;
+pub use self::file::{Frames, OpusFile};
+
+mod message;
+pub use self::message::{encode_voice_message, VoiceQuality, VOICE_MESSAGE_RATE};
+
+#[cfg(feature = "tokio")]
+mod async_read;
+#[cfg(feature = "tokio")]
+pub use self::async_read::AsyncOggReader;
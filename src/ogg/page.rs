@@ -0,0 +1,440 @@
+//! Ogg page framing (RFC 3533).
+
+use std::borrow::Cow;
+use std::io;
+
+/// The capture pattern starting every page.
+pub const CAPTURE: &[u8; 4] = b"OggS";
+
+/// Length of a page header without the segment table.
+pub const HEADER_LEN: usize = 27;
+
+/// Largest possible page, including the header.
+pub const MAX_PAGE_LEN: usize = HEADER_LEN + 255 + 255 * 255;
+
+/// Flag set on pages whose first packet continues from the previous page.
+pub const CONTINUED: u8 = 0x01;
+/// Flag set on the first page of a logical stream.
+pub const BOS: u8 = 0x02;
+/// Flag set on the last page of a logical stream.
+pub const EOS: u8 = 0x04;
+
+/// A single Ogg page.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Page {
+    /// The header type flags (`CONTINUED`, `BOS`, `EOS`).
+    pub flags: u8,
+    /// The granule position after the last packet completed on this page,
+    /// or `None` if no packet completes on this page.
+    pub granule_position: Option<u64>,
+    /// The serial number of the logical stream.
+    pub serial: u32,
+    /// The sequence number of the page within the logical stream.
+    pub sequence: u32,
+    /// The lacing values of the segment table.
+    pub lacing: Vec<u8>,
+    /// The page body.
+    pub body: Vec<u8>,
+}
+
+impl Page {
+    /// Parse a page from the start of `buf`.
+    ///
+    /// Returns the page and the number of bytes it occupied, or `None` if
+    /// `buf` does not yet hold a complete page.
+    pub fn parse(buf: &[u8]) -> io::Result<Option<(Page, usize)>> {
+        let mut page = Page::default();
+        Ok(Page::parse_into(buf, &mut page)?.map(|len| (page, len)))
+    }
+
+    /// Parse a page from the start of `buf` into `page`, reusing its
+    /// buffers.
+    ///
+    /// Returns the number of bytes the page occupied, or `None` if `buf` does
+    /// not yet hold a complete page, in which case `page` is left untouched.
+    pub fn parse_into(buf: &[u8], page: &mut Page) -> io::Result<Option<usize>> {
+        let (parsed, len) = match PageRef::parse(buf)? {
+            Some(parsed) => parsed,
+            None => return Ok(None),
+        };
+        page.flags = parsed.flags;
+        page.granule_position = parsed.granule_position;
+        page.serial = parsed.serial;
+        page.sequence = parsed.sequence;
+        page.lacing.clear();
+        page.lacing.extend_from_slice(parsed.lacing);
+        page.body.clear();
+        page.body.extend_from_slice(parsed.body);
+        Ok(Some(len))
+    }
+
+    /// Determine whether the first packet continues from the previous page.
+    pub fn is_continued(&self) -> bool {
+        self.flags & CONTINUED != 0
+    }
+
+    /// Determine whether this is the first page of a logical stream.
+    pub fn is_bos(&self) -> bool {
+        self.flags & BOS != 0
+    }
+
+    /// Determine whether this is the last page of a logical stream.
+    pub fn is_eos(&self) -> bool {
+        self.flags & EOS != 0
+    }
+
+    /// Get the total length of the page when written.
+    pub fn len(&self) -> usize {
+        HEADER_LEN + self.lacing.len() + self.body.len()
+    }
+
+    /// Determine whether the page carries no segments.
+    pub fn is_empty(&self) -> bool {
+        self.lacing.is_empty()
+    }
+
+    /// Append a whole packet to the page.
+    ///
+    /// Returns `false`, leaving the page untouched, if the segment table
+    /// does not have room for the whole packet.
+    pub fn push_packet(&mut self, packet: &[u8]) -> bool {
+        let segments = packet.len() / 255 + 1;
+        if self.lacing.len() + segments > 255 {
+            return false;
+        }
+        let full = self.lacing.len() + segments - 1;
+        self.lacing.resize(full, 255);
+        self.lacing.push((packet.len() % 255) as u8);
+        self.body.extend_from_slice(packet);
+        true
+    }
+
+    /// Append the serialized page, with its checksum, to `out`.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let start = out.len();
+        out.extend_from_slice(CAPTURE);
+        out.push(0);
+        out.push(self.flags);
+        out.extend_from_slice(&self.granule_position.unwrap_or(u64::MAX).to_le_bytes());
+        out.extend_from_slice(&self.serial.to_le_bytes());
+        out.extend_from_slice(&self.sequence.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.push(self.lacing.len() as u8);
+        out.extend_from_slice(&self.lacing);
+        out.extend_from_slice(&self.body);
+
+        let mut crc = Crc::new();
+        crc.update(&out[start..]);
+        out[start + 22..start + 26].copy_from_slice(&crc.finish().to_le_bytes());
+        event!(
+            trace,
+            serial = self.serial,
+            sequence = self.sequence,
+            granule = ?self.granule_position,
+            bytes = out.len() - start,
+            "wrote Ogg page"
+        );
+    }
+
+    /// Split the page into its packet fragments.
+    ///
+    /// Each item is a fragment and whether it completes a packet. Only the
+    /// first fragment may continue a packet from the previous page, and only
+    /// the last may be unfinished.
+    pub fn fragments(&self) -> Fragments<'_> {
+        Fragments::new(&self.lacing, &self.body)
+    }
+}
+
+/// A single Ogg page borrowed from a byte buffer.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct PageRef<'a> {
+    /// The header type flags (`CONTINUED`, `BOS`, `EOS`).
+    pub flags: u8,
+    /// The granule position after the last packet completed on this page,
+    /// or `None` if no packet completes on this page.
+    pub granule_position: Option<u64>,
+    /// The serial number of the logical stream.
+    pub serial: u32,
+    /// The sequence number of the page within the logical stream.
+    pub sequence: u32,
+    /// The lacing values of the segment table.
+    pub lacing: &'a [u8],
+    /// The page body.
+    pub body: &'a [u8],
+}
+
+impl<'a> PageRef<'a> {
+    /// Parse a page from the start of `buf` without copying it.
+    ///
+    /// Returns the page and the number of bytes it occupied, or `None` if
+    /// `buf` does not yet hold a complete page.
+    pub fn parse(buf: &'a [u8]) -> io::Result<Option<(PageRef<'a>, usize)>> {
+        if buf.len() < HEADER_LEN {
+            if !CAPTURE.starts_with(&buf[..buf.len().min(4)]) {
+                return Err(invalid("missing Ogg capture pattern"));
+            }
+            return Ok(None);
+        }
+        if &buf[..4] != CAPTURE {
+            return Err(invalid("missing Ogg capture pattern"));
+        }
+        if buf[4] != 0 {
+            return Err(invalid("unsupported Ogg version"));
+        }
+        let segments = buf[26] as usize;
+        if buf.len() < HEADER_LEN + segments {
+            return Ok(None);
+        }
+        let lacing = &buf[HEADER_LEN..HEADER_LEN + segments];
+        let body_len: usize = lacing.iter().map(|&l| l as usize).sum();
+        let total = HEADER_LEN + segments + body_len;
+        if buf.len() < total {
+            return Ok(None);
+        }
+
+        let stored = u32::from_le_bytes([buf[22], buf[23], buf[24], buf[25]]);
+        let mut crc = Crc::new();
+        crc.update(&buf[..22]);
+        crc.update(&[0; 4]);
+        crc.update(&buf[26..total]);
+        if crc.finish() != stored {
+            return Err(invalid("Ogg page checksum mismatch"));
+        }
+
+        let mut granule = [0; 8];
+        granule.copy_from_slice(&buf[6..14]);
+        let granule = u64::from_le_bytes(granule);
+        let page = PageRef {
+            flags: buf[5],
+            granule_position: if granule == u64::MAX {
+                None
+            } else {
+                Some(granule)
+            },
+            serial: u32::from_le_bytes([buf[14], buf[15], buf[16], buf[17]]),
+            sequence: u32::from_le_bytes([buf[18], buf[19], buf[20], buf[21]]),
+            lacing,
+            body: &buf[HEADER_LEN + segments..total],
+        };
+        Ok(Some((page, total)))
+    }
+
+    /// Determine whether the first packet continues from the previous page.
+    pub fn is_continued(&self) -> bool {
+        self.flags & CONTINUED != 0
+    }
+
+    /// Determine whether this is the first page of a logical stream.
+    pub fn is_bos(&self) -> bool {
+        self.flags & BOS != 0
+    }
+
+    /// Determine whether this is the last page of a logical stream.
+    pub fn is_eos(&self) -> bool {
+        self.flags & EOS != 0
+    }
+
+    /// Copy the page into an owned `Page`.
+    pub fn to_page(&self) -> Page {
+        Page {
+            flags: self.flags,
+            granule_position: self.granule_position,
+            serial: self.serial,
+            sequence: self.sequence,
+            lacing: self.lacing.to_vec(),
+            body: self.body.to_vec(),
+        }
+    }
+
+    /// Split the page into its packet fragments, as `Page::fragments` does.
+    pub fn fragments(&self) -> Fragments<'a> {
+        Fragments::new(self.lacing, self.body)
+    }
+}
+
+/// An iterator over the packet fragments of a page.
+#[derive(Debug)]
+pub struct Fragments<'a> {
+    lacing: &'a [u8],
+    body: &'a [u8],
+    segment: usize,
+    offset: usize,
+}
+
+impl<'a> Fragments<'a> {
+    fn new(lacing: &'a [u8], body: &'a [u8]) -> Fragments<'a> {
+        Fragments {
+            lacing,
+            body,
+            segment: 0,
+            offset: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for Fragments<'a> {
+    type Item = (&'a [u8], bool);
+
+    fn next(&mut self) -> Option<(&'a [u8], bool)> {
+        if self.segment >= self.lacing.len() {
+            return None;
+        }
+        let start = self.offset;
+        let mut complete = false;
+        while self.segment < self.lacing.len() {
+            let lacing = self.lacing[self.segment] as usize;
+            self.segment += 1;
+            self.offset += lacing;
+            if lacing < 255 {
+                complete = true;
+                break;
+            }
+        }
+        // a hand-built page may have a shorter body than its lacing values
+        let end = self.offset.min(self.body.len());
+        Some((&self.body[start.min(end)..end], complete))
+    }
+}
+
+/// Reassembles packets spanning several pages.
+#[derive(Debug, Clone, Default)]
+pub struct PacketAssembler {
+    partial: Vec<u8>,
+    in_packet: bool,
+    skipping: bool,
+}
+
+impl PacketAssembler {
+    /// Create an empty assembler.
+    pub fn new() -> PacketAssembler {
+        PacketAssembler::default()
+    }
+
+    /// Discard any partially assembled packet.
+    pub fn reset(&mut self) {
+        self.partial.clear();
+        self.in_packet = false;
+        self.skipping = false;
+    }
+
+    /// Feed a page, calling `f` with every packet completed on it.
+    ///
+    /// A continued fragment with no packet in progress, as happens after a
+    /// seek or at the start of a capture, is discarded.
+    pub fn push<F: FnMut(&[u8])>(&mut self, page: &Page, mut f: F) {
+        self.push_fragments(
+            page.is_continued(),
+            page.fragments(),
+            |packet| match packet {
+                Assembled::Whole(packet) => f(packet),
+                Assembled::Joined(packet) => {
+                    f(packet);
+                    packet.clear();
+                }
+            },
+        );
+    }
+
+    /// Feed a borrowed page, calling `f` with every packet completed on it.
+    ///
+    /// Packets lying within the page are borrowed from it, and only those
+    /// spanning pages are copied.
+    pub fn push_ref<'a, F: FnMut(Cow<'a, [u8]>)>(&mut self, page: &PageRef<'a>, mut f: F) {
+        self.push_fragments(
+            page.is_continued(),
+            page.fragments(),
+            |packet| match packet {
+                Assembled::Whole(packet) => f(Cow::Borrowed(packet)),
+                Assembled::Joined(packet) => f(Cow::Owned(std::mem::take(packet))),
+            },
+        );
+    }
+
+    fn push_fragments<'a, F>(&mut self, continued: bool, fragments: Fragments<'a>, mut f: F)
+    where
+        F: FnMut(Assembled<'a, '_>),
+    {
+        for (i, (fragment, complete)) in fragments.enumerate() {
+            if i == 0 && continued != self.in_packet {
+                // Either a continuation with nothing to continue, or a
+                // packet left unfinished by a lost page.
+                self.partial.clear();
+                self.in_packet = false;
+                self.skipping = continued;
+            }
+            if self.skipping {
+                self.skipping = !complete;
+                self.in_packet = !complete;
+                continue;
+            }
+            if complete {
+                if self.partial.is_empty() {
+                    f(Assembled::Whole(fragment));
+                } else {
+                    self.partial.extend_from_slice(fragment);
+                    f(Assembled::Joined(&mut self.partial));
+                    self.partial.clear();
+                }
+                self.in_packet = false;
+            } else {
+                self.partial.extend_from_slice(fragment);
+                self.in_packet = true;
+            }
+        }
+    }
+}
+
+/// A packet completed by `PacketAssembler`.
+enum Assembled<'a, 'b> {
+    /// A packet lying entirely within the current page.
+    Whole(&'a [u8]),
+    /// A packet joined from the fragments of several pages.
+    Joined(&'b mut Vec<u8>),
+}
+
+/// The CRC-32 variant used by Ogg: polynomial 0x04c11db7, no reflection, no
+/// final inversion.
+struct Crc(u32);
+
+impl Crc {
+    fn new() -> Crc {
+        Crc(0)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.0 = (self.0 << 8) ^ CRC_TABLE[((self.0 >> 24) as u8 ^ byte) as usize];
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        self.0
+    }
+}
+
+static CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut r = (i as u32) << 24;
+        let mut j = 0;
+        while j < 8 {
+            r = if r & 0x8000_0000 != 0 {
+                (r << 1) ^ 0x04c1_1db7
+            } else {
+                r << 1
+            };
+            j += 1;
+        }
+        table[i] = r;
+        i += 1;
+    }
+    table
+}
+
+pub(crate) fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
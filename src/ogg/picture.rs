@@ -0,0 +1,148 @@
+//! Pictures embedded in the comment header, such as cover art.
+
+/// The field holding a picture, as a base64 FLAC picture block.
+pub const PICTURE_FIELD: &str = "METADATA_BLOCK_PICTURE";
+
+/// The default limit on the size of a picture's data.
+pub const DEFAULT_MAX_PICTURE_LEN: usize = 16 * 1024 * 1024;
+
+/// A picture from a `METADATA_BLOCK_PICTURE` comment.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Picture {
+    /// The picture type of ID3v2 APIC frames; 3 is the front cover.
+    pub picture_type: u32,
+    /// The MIME type of the data. The type is found from the data itself for
+    /// PNG, JPEG, GIF, WebP, and BMP images, as files often declare these
+    /// wrongly; the declared type is kept for anything else. A type of `-->`
+    /// means the data is the URL of the picture.
+    pub mime: String,
+    /// A description of the picture.
+    pub description: String,
+    /// The width in pixels, or zero if not given.
+    pub width: u32,
+    /// The height in pixels, or zero if not given.
+    pub height: u32,
+    /// The color depth in bits per pixel, or zero if not given.
+    pub depth: u32,
+    /// The number of colors of an indexed image, or zero for others.
+    pub colors: u32,
+    /// The picture file.
+    pub data: Vec<u8>,
+}
+
+impl Picture {
+    /// The picture type of a front cover.
+    pub const FRONT_COVER: u32 = 3;
+
+    /// Determine whether this is the front cover.
+    pub fn is_front_cover(&self) -> bool {
+        self.picture_type == Picture::FRONT_COVER
+    }
+
+    /// Decode a `METADATA_BLOCK_PICTURE` comment value, or return `None` if
+    /// it is malformed or its data is longer than `max_len` bytes.
+    ///
+    /// The length is checked before anything is decoded, so a huge comment
+    /// costs no more than looking at its size.
+    pub fn parse(value: &str, max_len: usize) -> Option<Picture> {
+        // the fixed fields of the block take 32 bytes, and the strings are
+        // short, so only the data can make a block much larger than this
+        let limit = max_len.saturating_add(64 * 1024);
+        if value.len() / 4 * 3 > limit {
+            return None;
+        }
+        let block = base64_decode(value.as_bytes())?;
+        let mut pos = 0;
+        let picture_type = read_u32(&block, &mut pos)?;
+        let mime = read_string(&block, &mut pos)?;
+        let description = read_string(&block, &mut pos)?;
+        let width = read_u32(&block, &mut pos)?;
+        let height = read_u32(&block, &mut pos)?;
+        let depth = read_u32(&block, &mut pos)?;
+        let colors = read_u32(&block, &mut pos)?;
+        let len = read_u32(&block, &mut pos)? as usize;
+        if len > max_len {
+            return None;
+        }
+        let data = block.get(pos..)?.get(..len)?.to_vec();
+        let mime = match sniff(&data) {
+            Some(sniffed) if mime != "-->" => sniffed.to_string(),
+            _ => mime,
+        };
+        Some(Picture {
+            picture_type,
+            mime,
+            description,
+            width,
+            height,
+            depth,
+            colors,
+            data,
+        })
+    }
+}
+
+// Find the MIME type of a common image format from its signature.
+fn sniff(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(b"\xff\xd8\xff") {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if data.starts_with(b"BM") && data.len() >= 14 {
+        Some("image/bmp")
+    } else {
+        None
+    }
+}
+
+fn read_u32(block: &[u8], pos: &mut usize) -> Option<u32> {
+    let b = block.get(*pos..)?.get(..4)?;
+    *pos += 4;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_string(block: &[u8], pos: &mut usize) -> Option<String> {
+    let len = read_u32(block, pos)? as usize;
+    let bytes = block.get(*pos..)?.get(..len)?;
+    *pos += len;
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+// Decode standard base64, with or without padding, ignoring whitespace.
+fn base64_decode(input: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() / 4 * 3);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    let mut padding = 0;
+    for &c in input {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => {
+                padding += 1;
+                continue;
+            }
+            b' ' | b'\t' | b'\r' | b'\n' => continue,
+            _ => return None,
+        };
+        if padding > 0 {
+            // data after padding
+            return None;
+        }
+        acc = acc << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
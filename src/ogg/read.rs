@@ -0,0 +1,197 @@
+//! Demuxing Ogg Opus from a `std::io::Read`.
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+use super::super::pipeline::{PacketInfo, PacketSource};
+use super::super::pool::{BufferPool, VecPool};
+use super::demux::{AudioPacket, Event, OggDemuxer};
+use super::head::{OpusHead, OpusTags};
+
+/// Size of each read from the underlying reader.
+const READ_CHUNK: usize = 4096;
+
+/// Reads Opus packets from an Ogg Opus stream.
+///
+/// Packets are yielded undecoded; through `PacketSource` their trimming
+/// information is dropped, while `next_audio` keeps it.
+///
+/// Packet buffers come from a `BufferPool`; return them with `recycle` once
+/// decoded to avoid allocating for every packet.
+///
+/// In lenient mode damaged pages are skipped, and the audio lost with them
+/// is counted for `take_data_loss`.
+#[derive(Debug)]
+pub struct OggOpusReader<R, P = VecPool> {
+    reader: R,
+    demuxer: OggDemuxer<P>,
+    head: Option<OpusHead>,
+    tags: Option<OpusTags>,
+    events: Vec<Event>,
+    // audio packets, and the data lost before them
+    audio: VecDeque<Event>,
+    lost: u64,
+    eof: bool,
+}
+
+impl<R: Read> OggOpusReader<R> {
+    /// Create a reader over the given byte stream.
+    pub fn new(reader: R) -> OggOpusReader<R> {
+        OggOpusReader::with_pool(reader, VecPool::new())
+    }
+}
+
+impl<R: Read, P: BufferPool> OggOpusReader<R, P> {
+    /// Create a reader over the given byte stream, taking its packet buffers
+    /// from `pool`.
+    pub fn with_pool(reader: R, pool: P) -> OggOpusReader<R, P> {
+        OggOpusReader {
+            reader,
+            demuxer: OggDemuxer::with_pool(pool),
+            head: None,
+            tags: None,
+            events: Vec::new(),
+            audio: VecDeque::new(),
+            lost: 0,
+            eof: false,
+        }
+    }
+
+    /// Set whether damaged pages are skipped rather than being an error, as
+    /// `OggDemuxer::set_lenient` does.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.demuxer.set_lenient(lenient);
+    }
+
+    /// Read up to and including the comment header, returning the
+    /// identification header.
+    pub fn read_headers(&mut self) -> io::Result<&OpusHead> {
+        while self.tags.is_none() && !self.eof {
+            self.fill()?;
+        }
+        match self.head {
+            Some(ref head) => Ok(head),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "missing Ogg Opus headers",
+            )),
+        }
+    }
+
+    /// Get the serial number of the current stream, once its first page has
+    /// been read, as `OggDemuxer::serial` does.
+    pub fn serial(&self) -> Option<u32> {
+        self.demuxer.serial()
+    }
+
+    /// Get the identification header of the current stream, once read.
+    pub fn head(&self) -> Option<&OpusHead> {
+        self.head.as_ref()
+    }
+
+    /// Get the comment header of the current stream, once read.
+    pub fn tags(&self) -> Option<&OpusTags> {
+        self.tags.as_ref()
+    }
+
+    /// Get the next audio packet, or `None` at the end of the input.
+    pub fn next_audio(&mut self) -> io::Result<Option<AudioPacket>> {
+        loop {
+            while self.audio.is_empty() && !self.eof {
+                self.fill()?;
+            }
+            match self.audio.pop_front() {
+                Some(Event::Audio(audio)) => return Ok(Some(audio)),
+                Some(Event::DataLoss { samples }) => self.lost += samples,
+                Some(_) => {}
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Take the number of samples per channel, at 48kHz, found lost by a
+    /// lenient reader since the last call.
+    ///
+    /// A loss is counted as `next_audio` returns the packet following it, or
+    /// the end of the input, so that a decoder can conceal it in place.
+    pub fn take_data_loss(&mut self) -> u64 {
+        std::mem::take(&mut self.lost)
+    }
+
+    /// Return the data of a decoded audio packet to the buffer pool.
+    pub fn recycle(&mut self, data: Vec<u8>) {
+        self.demuxer.recycle(data);
+    }
+
+    /// Forget all stream state and buffered input, as when the underlying
+    /// reader has been rewound to the start.
+    pub fn reset(&mut self) {
+        self.demuxer.reset();
+        self.discard();
+        self.head = None;
+        self.tags = None;
+    }
+
+    /// Forget buffered input and the position in the current stream, keeping
+    /// its headers, as when the underlying reader has been moved to another
+    /// page of the stream. See `OggDemuxer::resync`.
+    pub fn resync(&mut self) {
+        self.demuxer.resync();
+        self.discard();
+    }
+
+    /// Get the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Unwrap the underlying reader, discarding any buffered data.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn discard(&mut self) {
+        for event in self.audio.drain(..) {
+            if let Event::Audio(audio) = event {
+                self.demuxer.recycle(audio.data);
+            }
+        }
+        self.lost = 0;
+        self.eof = false;
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let mut chunk = [0; READ_CHUNK];
+        let len = match self.reader.read(&mut chunk) {
+            Ok(len) => len,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let mut events = std::mem::take(&mut self.events);
+        let result = if len == 0 {
+            self.eof = true;
+            self.demuxer.finish_into(&mut events)
+        } else {
+            self.demuxer.push_bytes_into(&chunk[..len], &mut events)
+        };
+        for event in events.drain(..) {
+            match event {
+                Event::Header(head) => {
+                    self.head = Some(head);
+                    self.tags = None;
+                }
+                Event::Tags(tags) => self.tags = Some(tags),
+                Event::Audio(_) | Event::DataLoss { .. } => self.audio.push_back(event),
+                Event::Eos => {}
+            }
+        }
+        self.events = events;
+        result
+    }
+}
+
+impl<R: Read, P: BufferPool> PacketSource for OggOpusReader<R, P> {
+    fn next_packet(&mut self) -> io::Result<Option<(Vec<u8>, PacketInfo)>> {
+        Ok(self.next_audio()?.map(|audio| (audio.data, audio.info)))
+    }
+}
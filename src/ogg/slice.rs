@@ -0,0 +1,160 @@
+//! Zero-copy demuxing of Ogg Opus held entirely in memory.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io;
+
+use super::super::pipeline::{PacketInfo, PacketSource};
+use super::demux::{AudioPacket, Event, StreamState};
+use super::head::{OpusHead, OpusTags};
+use super::page::{PacketAssembler, PageRef};
+
+/// Reads Opus packets from an Ogg Opus stream held in a byte slice.
+///
+/// Pages are parsed in place and packets lying within a single page are
+/// borrowed from the input, so only packets spanning pages are copied. This
+/// suits batch processing of large files mapped into memory with `map_file`.
+///
+/// Like `OggDemuxer`, only the first logical stream in each link of a chain
+/// is followed.
+#[derive(Debug, Clone)]
+pub struct OggSliceReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    assembler: PacketAssembler,
+    stream: StreamState<Cow<'a, [u8]>>,
+    head: Option<OpusHead>,
+    tags: Option<OpusTags>,
+    events: Vec<Event<Cow<'a, [u8]>>>,
+    audio: VecDeque<AudioPacket<Cow<'a, [u8]>>>,
+}
+
+impl<'a> OggSliceReader<'a> {
+    /// Create a reader over the given bytes.
+    pub fn new(data: &'a [u8]) -> OggSliceReader<'a> {
+        OggSliceReader {
+            data,
+            pos: 0,
+            assembler: PacketAssembler::new(),
+            stream: StreamState::new(),
+            head: None,
+            tags: None,
+            events: Vec::new(),
+            audio: VecDeque::new(),
+        }
+    }
+
+    /// Read up to and including the comment header, returning the
+    /// identification header.
+    pub fn read_headers(&mut self) -> io::Result<&OpusHead> {
+        while self.tags.is_none() && self.fill()? {}
+        match self.head {
+            Some(ref head) => Ok(head),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "missing Ogg Opus headers",
+            )),
+        }
+    }
+
+    /// Get the identification header of the current stream, once read.
+    pub fn head(&self) -> Option<&OpusHead> {
+        self.head.as_ref()
+    }
+
+    /// Get the comment header of the current stream, once read.
+    pub fn tags(&self) -> Option<&OpusTags> {
+        self.tags.as_ref()
+    }
+
+    /// Get the next audio packet, or `None` at the end of the input.
+    pub fn next_audio(&mut self) -> io::Result<Option<AudioPacket<Cow<'a, [u8]>>>> {
+        while self.audio.is_empty() && self.fill()? {}
+        Ok(self.audio.pop_front())
+    }
+
+    /// Get the byte offset of the next page to be parsed.
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// Get the bytes not yet parsed.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+    /// Parse the next page, returning `false` at the end of the input.
+    fn fill(&mut self) -> io::Result<bool> {
+        let rest = &self.data[self.pos..];
+        if rest.is_empty() {
+            return Ok(false);
+        }
+        let (page, len) = match PageRef::parse(rest)? {
+            Some(parsed) => parsed,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "truncated Ogg page",
+                ))
+            }
+        };
+        self.pos += len;
+        if !self.stream.accept(page.is_bos(), page.serial) {
+            return Ok(true);
+        }
+
+        let mut events = std::mem::take(&mut self.events);
+        let mut result = Ok(());
+        {
+            let stream = &mut self.stream;
+            let events = &mut events;
+            self.assembler.push_ref(&page, |packet| {
+                if result.is_ok() {
+                    result = stream.packet(packet, events, drop);
+                }
+            });
+        }
+        if result.is_ok() {
+            self.stream
+                .flush(page.granule_position, page.is_eos(), &mut events);
+            if page.is_eos() {
+                events.push(Event::Eos);
+                self.assembler.reset();
+                self.stream.restart(drop);
+            }
+        }
+        for event in events.drain(..) {
+            match event {
+                Event::Header(head) => {
+                    self.head = Some(head);
+                    self.tags = None;
+                }
+                Event::Tags(tags) => self.tags = Some(tags),
+                Event::Audio(audio) => self.audio.push_back(audio),
+                Event::Eos | Event::DataLoss { .. } => {}
+            }
+        }
+        self.events = events;
+        result.map(|()| true)
+    }
+}
+
+impl<'a> PacketSource for OggSliceReader<'a> {
+    fn next_packet(&mut self) -> io::Result<Option<(Vec<u8>, PacketInfo)>> {
+        Ok(self
+            .next_audio()?
+            .map(|audio| (audio.data.into_owned(), audio.info)))
+    }
+}
+
+/// Map a file into memory for use with `OggSliceReader`.
+///
+/// The mapping is read-only, but its contents are undefined if the file is
+/// modified or truncated by another process while it is mapped.
+#[cfg(feature = "memmap2")]
+pub fn map_file<P: AsRef<std::path::Path>>(path: P) -> io::Result<memmap2::Mmap> {
+    let file = std::fs::File::open(path)?;
+    // the mapping is only ever read; concurrent modification is the caller's
+    // responsibility, as documented above
+    unsafe { memmap2::Mmap::map(&file) }
+}
@@ -0,0 +1,167 @@
+//! Bundled encoder settings and interop presets.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Application, Bandwidth, Bitrate, Channels, Encoder, FrameDuration, Result, Signal};
+
+/// Common interop targets for which `EncoderOptions` can be preconfigured.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Preset {
+    /// Conversational voice as negotiated by WebRTC browsers: 20 ms frames,
+    /// in-band FEC, and a 32 kbit/s VBR target.
+    WebRtcVoice,
+    /// Music over WebRTC: 20 ms frames, no FEC, and a 64 kbit/s VBR target.
+    WebRtcMusic,
+    /// Narrowband telephony-grade voice at 12 kbit/s with FEC and DTX.
+    Voip8k,
+    /// High-fidelity unconstrained VBR at 128 kbit/s for broadcast or
+    /// archival.
+    Broadcast,
+    /// CELT-only voice chat with 10 ms frames for latency-sensitive games.
+    LowDelayGame,
+    /// Restricted low-delay voice chat with 5 ms frames, for games where
+    /// every millisecond of mouth-to-ear latency counts. The frames can be
+    /// shortened further to 2.5 ms; pair the receiver with
+    /// `AdaptiveDelay::for_frames` so that it adapts in steps of a frame.
+    GameVoice,
+}
+
+/// A complete set of encoder settings which can be applied in one call.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EncoderOptions {
+    /// The coding mode the encoder is created with.
+    pub application: Application,
+    /// The duration of each encoded frame.
+    pub frame_duration: FrameDuration,
+    /// The target bitrate.
+    pub bitrate: Bitrate,
+    /// The maximum bandpass the encoder may select.
+    pub max_bandwidth: Bandwidth,
+    /// The computational complexity, from 0 to 10 inclusive.
+    pub complexity: i32,
+    /// The type of signal being encoded.
+    pub signal: Signal,
+    /// Whether variable bitrate is enabled.
+    pub vbr: bool,
+    /// Whether variable bitrate is constrained to the target.
+    pub vbr_constraint: bool,
+    /// Whether in-band forward error correction is enabled.
+    pub inband_fec: bool,
+    /// The expected packet loss percentage.
+    pub packet_loss_perc: i32,
+    /// Whether discontinuous transmission is enabled.
+    pub dtx: bool,
+}
+
+impl Default for EncoderOptions {
+    /// The settings of a freshly created `Audio` encoder, which takes its
+    /// frame duration from the size of each input.
+    fn default() -> EncoderOptions {
+        EncoderOptions {
+            application: Application::Audio,
+            frame_duration: FrameDuration::Arg,
+            bitrate: Bitrate::Auto,
+            max_bandwidth: Bandwidth::Fullband,
+            complexity: 10,
+            signal: Signal::Auto,
+            vbr: true,
+            vbr_constraint: true,
+            inband_fec: false,
+            packet_loss_perc: 0,
+            dtx: false,
+        }
+    }
+}
+
+impl EncoderOptions {
+    /// Get the settings for a common interop target.
+    pub fn preset(preset: Preset) -> EncoderOptions {
+        let defaults = EncoderOptions::default();
+        match preset {
+            Preset::WebRtcVoice => EncoderOptions {
+                application: Application::Voip,
+                frame_duration: FrameDuration::Ms20,
+                bitrate: Bitrate::Bits(32000),
+                signal: Signal::Voice,
+                vbr_constraint: false,
+                inband_fec: true,
+                packet_loss_perc: 5,
+                ..defaults
+            },
+            Preset::WebRtcMusic => EncoderOptions {
+                frame_duration: FrameDuration::Ms20,
+                bitrate: Bitrate::Bits(64000),
+                signal: Signal::Music,
+                vbr_constraint: false,
+                ..defaults
+            },
+            Preset::Voip8k => EncoderOptions {
+                application: Application::Voip,
+                frame_duration: FrameDuration::Ms20,
+                bitrate: Bitrate::Bits(12000),
+                max_bandwidth: Bandwidth::Narrowband,
+                signal: Signal::Voice,
+                inband_fec: true,
+                packet_loss_perc: 10,
+                dtx: true,
+                ..defaults
+            },
+            Preset::Broadcast => EncoderOptions {
+                frame_duration: FrameDuration::Ms20,
+                bitrate: Bitrate::Bits(128000),
+                signal: Signal::Music,
+                vbr_constraint: false,
+                ..defaults
+            },
+            Preset::LowDelayGame => EncoderOptions {
+                application: Application::LowDelay,
+                frame_duration: FrameDuration::Ms10,
+                bitrate: Bitrate::Bits(48000),
+                complexity: 5,
+                vbr_constraint: false,
+                ..defaults
+            },
+            Preset::GameVoice => EncoderOptions {
+                application: Application::LowDelay,
+                frame_duration: FrameDuration::Ms5,
+                bitrate: Bitrate::Bits(40000),
+                complexity: 5,
+                signal: Signal::Voice,
+                vbr_constraint: false,
+                ..defaults
+            },
+        }
+    }
+
+    /// Get the number of samples per channel in each frame at `sample_rate`,
+    /// or `None` for `FrameDuration::Arg`.
+    pub fn frame_size(&self, sample_rate: u32) -> Option<usize> {
+        self.frame_duration.samples(sample_rate)
+    }
+
+    /// Create an encoder configured with these settings.
+    pub fn build(&self, sample_rate: u32, channels: Channels) -> Result<Encoder> {
+        let mut encoder = Encoder::new(sample_rate, channels, self.application)?;
+        self.apply(&mut encoder)?;
+        Ok(encoder)
+    }
+
+    /// Apply these settings to an existing encoder.
+    ///
+    /// The application cannot be changed after creation and is ignored.
+    pub fn apply(&self, encoder: &mut Encoder) -> Result<()> {
+        encoder.set_expert_frame_duration(self.frame_duration)?;
+        encoder.set_bitrate(self.bitrate)?;
+        encoder.set_max_bandwidth(self.max_bandwidth)?;
+        encoder.set_complexity(self.complexity)?;
+        encoder.set_signal(self.signal)?;
+        encoder.set_vbr(self.vbr)?;
+        encoder.set_vbr_constraint(self.vbr_constraint)?;
+        encoder.set_inband_fec(self.inband_fec)?;
+        encoder.set_packet_loss_perc(self.packet_loss_perc)?;
+        encoder.set_dtx(self.dtx)
+    }
+}
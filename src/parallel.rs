@@ -0,0 +1,242 @@
+//! Encoding the streams of a multistream packet in parallel with `rayon`.
+
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+
+use super::chunk::{Sample, MAX_PACKET};
+use super::ffi;
+use super::framing;
+use super::{
+    Application, Bitrate, Channels, Encoder, Error, MultistreamEncoder, Result, StreamLayout,
+};
+
+#[derive(Debug, Default)]
+struct Buffers {
+    i16: Vec<i16>,
+    #[cfg(not(feature = "disable-float-api"))]
+    f32: Vec<f32>,
+}
+
+trait StreamSample: Sample + Send + Sync {
+    fn buffer(buffers: &mut Buffers) -> &mut Vec<Self>;
+}
+
+impl StreamSample for i16 {
+    fn buffer(buffers: &mut Buffers) -> &mut Vec<i16> {
+        &mut buffers.i16
+    }
+}
+
+#[cfg(not(feature = "disable-float-api"))]
+impl StreamSample for f32 {
+    fn buffer(buffers: &mut Buffers) -> &mut Vec<f32> {
+        &mut buffers.f32
+    }
+}
+
+#[derive(Debug)]
+struct Stream {
+    encoder: Encoder,
+    channels: usize,
+    pcm: Buffers,
+    packet: Vec<u8>,
+    len: usize,
+}
+
+/// A multistream encoder which encodes each of its streams on the `rayon`
+/// thread pool.
+///
+/// The streams of a multistream packet are coded independently, so for high
+/// channel counts such as ambisonics or 7.1 this can cut the wall-clock time
+/// of encoding considerably. The packets produced are identical in format to
+/// those of `MultistreamEncoder` and decode with a `MultistreamDecoder` of the
+/// same layout.
+///
+/// Unlike `MultistreamEncoder::surround`, no analysis is shared between the
+/// streams: the bitrate is split in proportion to the channels of each stream
+/// and LFE channels receive no special treatment.
+#[derive(Debug)]
+pub struct ParallelMultistreamEncoder {
+    layout: StreamLayout,
+    streams: Vec<Stream>,
+}
+
+impl ParallelMultistreamEncoder {
+    /// Create and initialize an encoder with an explicit layout.
+    pub fn new(
+        sample_rate: u32,
+        layout: StreamLayout,
+        mode: Application,
+    ) -> Result<ParallelMultistreamEncoder> {
+        let what = "ParallelMultistreamEncoder::new";
+        layout.check(what)?;
+
+        let mut streams = Vec::with_capacity(layout.streams as usize);
+        for s in 0..layout.streams {
+            let channels = if s < layout.coupled_streams {
+                Channels::Stereo
+            } else {
+                Channels::Mono
+            };
+            streams.push(Stream {
+                encoder: Encoder::new(sample_rate, channels, mode)?,
+                channels: channels as usize,
+                pcm: Buffers::default(),
+                packet: vec![0; MAX_PACKET],
+                len: 0,
+            });
+        }
+        Ok(ParallelMultistreamEncoder { layout, streams })
+    }
+
+    /// Create and initialize an encoder using the standard layout for the
+    /// given channel mapping family, as chosen by `MultistreamEncoder::surround`.
+    pub fn surround(
+        sample_rate: u32,
+        channels: u8,
+        mapping_family: u8,
+        mode: Application,
+    ) -> Result<ParallelMultistreamEncoder> {
+        let layout = MultistreamEncoder::surround(sample_rate, channels, mapping_family, mode)?
+            .layout()
+            .clone();
+        ParallelMultistreamEncoder::new(sample_rate, layout, mode)
+    }
+
+    /// Get the stream layout of the encoder.
+    pub fn layout(&self) -> &StreamLayout {
+        &self.layout
+    }
+
+    /// Get the encoder of a single stream, for configuration beyond the
+    /// settings applied to all streams.
+    pub fn encoder_mut(&mut self, stream: usize) -> Option<&mut Encoder> {
+        self.streams.get_mut(stream).map(|s| &mut s.encoder)
+    }
+
+    /// Reset the state of every stream's encoder.
+    pub fn reset_state(&mut self) -> Result<()> {
+        for stream in &mut self.streams {
+            stream.encoder.reset_state()?;
+        }
+        Ok(())
+    }
+
+    /// Set the total bitrate of all streams.
+    ///
+    /// An explicit bitrate is divided between the streams in proportion to
+    /// their channel count.
+    pub fn set_bitrate(&mut self, value: Bitrate) -> Result<()> {
+        let total: usize = self.streams.iter().map(|s| s.channels).sum();
+        for stream in &mut self.streams {
+            let value = match value {
+                Bitrate::Bits(b) => Bitrate::Bits(b * stream.channels as i32 / total as i32),
+                other => other,
+            };
+            stream.encoder.set_bitrate(value)?;
+        }
+        Ok(())
+    }
+
+    /// Enable or disable variable bitrate on every stream.
+    pub fn set_vbr(&mut self, vbr: bool) -> Result<()> {
+        for stream in &mut self.streams {
+            stream.encoder.set_vbr(vbr)?;
+        }
+        Ok(())
+    }
+
+    /// Configure the computational complexity of every stream, from 0 to 10.
+    pub fn set_complexity(&mut self, value: i32) -> Result<()> {
+        for stream in &mut self.streams {
+            stream.encoder.set_complexity(value)?;
+        }
+        Ok(())
+    }
+
+    /// Encode an Opus frame.
+    pub fn encode(&mut self, input: &[i16], output: &mut [u8]) -> Result<usize> {
+        self.encode_samples(input, output)
+    }
+
+    /// Encode an Opus frame from floating point input.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        self.encode_samples(input, output)
+    }
+
+    /// Encode an Opus frame to a new buffer.
+    pub fn encode_vec(&mut self, input: &[i16], max_size: usize) -> Result<Vec<u8>> {
+        let mut output: Vec<u8> = vec![0; max_size];
+        let result = self.encode(input, output.as_mut_slice())?;
+        output.truncate(result);
+        Ok(output)
+    }
+
+    /// Encode an Opus frame from floating point input to a new buffer.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_vec_float(&mut self, input: &[f32], max_size: usize) -> Result<Vec<u8>> {
+        let mut output: Vec<u8> = vec![0; max_size];
+        let result = self.encode_float(input, output.as_mut_slice())?;
+        output.truncate(result);
+        Ok(output)
+    }
+
+    fn encode_samples<T: StreamSample>(&mut self, input: &[T], output: &mut [u8]) -> Result<usize> {
+        let channels = self.layout.channels as usize;
+        let frame_size = input.len() / channels;
+        if frame_size * channels != input.len() {
+            return Err(Error::bad_arg("ParallelMultistreamEncoder::encode"));
+        }
+
+        // Route each input channel to its slot within a stream, leaving any
+        // stream channel without an input silent.
+        for stream in &mut self.streams {
+            let pcm = T::buffer(&mut stream.pcm);
+            pcm.clear();
+            pcm.resize(frame_size * stream.channels, T::default());
+        }
+        let coupled = self.layout.coupled_streams as usize;
+        for (c, &m) in self.layout.mapping.iter().enumerate() {
+            let m = m as usize;
+            let (s, slot) = match m {
+                255 => continue,
+                m if m < 2 * coupled => (m / 2, m % 2),
+                m => (m - coupled, 0),
+            };
+            let stream = &mut self.streams[s];
+            let width = stream.channels;
+            let pcm = T::buffer(&mut stream.pcm);
+            for (i, frame) in input.chunks_exact(channels).enumerate() {
+                pcm[i * width + slot] = frame[c];
+            }
+        }
+
+        self.streams
+            .par_iter_mut()
+            .try_for_each(|stream| -> Result<()> {
+                let pcm = T::buffer(&mut stream.pcm);
+                stream.len = T::encode(&mut stream.encoder, pcm, &mut stream.packet)?;
+                Ok(())
+            })?;
+
+        // All streams but the last use self-delimiting framing.
+        let last = self.streams.len() - 1;
+        let mut pos = 0;
+        for stream in &self.streams[..last] {
+            pos += framing::reframe(&stream.packet[..stream.len], &mut output[pos..])?;
+        }
+        let packet = &self.streams[last].packet[..self.streams[last].len];
+        if output.len() - pos < packet.len() {
+            return Err(buffer_too_small());
+        }
+        output[pos..pos + packet.len()].copy_from_slice(packet);
+        Ok(pos + packet.len())
+    }
+}
+
+fn buffer_too_small() -> Error {
+    Error::from_code(
+        "ParallelMultistreamEncoder::encode",
+        ffi::OPUS_BUFFER_TOO_SMALL,
+    )
+}
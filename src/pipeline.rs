@@ -0,0 +1,512 @@
+//! Transport-agnostic plumbing for moving Opus packets around.
+//!
+//! Encoders push timestamped packets into a `PacketSink`, and decoders pull
+//! them from a `PacketSource`. Containers and transports only need to
+//! implement these traits to share the same encoding and timestamping code.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+
+use super::chunk::{FrameChunker, Sample, MAX_PACKET};
+use super::pool::{BufferPool, VecPool};
+use super::time::Timestamp48k;
+use super::{Channels, Encoder, Result};
+
+/// Sample rate in which all packet timestamps and durations are expressed.
+pub use super::time::CLOCK_RATE;
+
+/// Sequencing and timing information accompanying a packet.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct PacketInfo {
+    /// Index of the packet within the stream, starting from zero.
+    pub sequence: u64,
+    /// Position of the first sample of the packet in 48kHz samples.
+    pub timestamp: u64,
+    /// Duration of the packet in 48kHz samples.
+    pub duration: u32,
+}
+
+impl PacketInfo {
+    /// Get the time of the first sample of the packet.
+    pub fn start(&self) -> Timestamp48k {
+        Timestamp48k(self.timestamp)
+    }
+
+    /// Get the time just after the last sample of the packet.
+    pub fn end(&self) -> Timestamp48k {
+        Timestamp48k(self.timestamp.saturating_add(self.duration as u64))
+    }
+}
+
+/// A block of decoded audio together with its timing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodedFrame {
+    /// The interleaved samples.
+    pub pcm: Vec<i16>,
+    /// The number of samples per channel in `pcm`.
+    pub samples: usize,
+    /// Position of the first sample in 48kHz samples.
+    pub timestamp_48k: u64,
+    /// Whether the audio was synthesized by packet loss concealment.
+    pub is_plc: bool,
+}
+
+/// A destination for encoded packets.
+pub trait PacketSink {
+    /// Accept a single packet.
+    fn send_packet(&mut self, packet: &[u8], info: PacketInfo) -> io::Result<()>;
+
+    /// Flush any packets buffered by the sink.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A supplier of encoded packets.
+pub trait PacketSource {
+    /// Get the next packet, or `None` at the end of the stream.
+    fn next_packet(&mut self) -> io::Result<Option<(Vec<u8>, PacketInfo)>>;
+}
+
+impl<S: PacketSink + ?Sized> PacketSink for &mut S {
+    fn send_packet(&mut self, packet: &[u8], info: PacketInfo) -> io::Result<()> {
+        (**self).send_packet(packet, info)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+}
+
+impl<S: PacketSource + ?Sized> PacketSource for &mut S {
+    fn next_packet(&mut self) -> io::Result<Option<(Vec<u8>, PacketInfo)>> {
+        (**self).next_packet()
+    }
+}
+
+impl<S: PacketSink + ?Sized> PacketSink for Box<S> {
+    fn send_packet(&mut self, packet: &[u8], info: PacketInfo) -> io::Result<()> {
+        (**self).send_packet(packet, info)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+}
+
+impl<S: PacketSource + ?Sized> PacketSource for Box<S> {
+    fn next_packet(&mut self) -> io::Result<Option<(Vec<u8>, PacketInfo)>> {
+        (**self).next_packet()
+    }
+}
+
+impl PacketSink for Vec<(Vec<u8>, PacketInfo)> {
+    fn send_packet(&mut self, packet: &[u8], info: PacketInfo) -> io::Result<()> {
+        self.push((packet.to_vec(), info));
+        Ok(())
+    }
+}
+
+impl PacketSink for VecDeque<(Vec<u8>, PacketInfo)> {
+    fn send_packet(&mut self, packet: &[u8], info: PacketInfo) -> io::Result<()> {
+        self.push_back((packet.to_vec(), info));
+        Ok(())
+    }
+}
+
+impl PacketSource for VecDeque<(Vec<u8>, PacketInfo)> {
+    fn next_packet(&mut self) -> io::Result<Option<(Vec<u8>, PacketInfo)>> {
+        Ok(self.pop_front())
+    }
+}
+
+/// Packets which the buffer drops as late, duplicate, or malformed are
+/// counted in its `stats` rather than reported as errors, as a drop is not a
+/// failure of the transport.
+impl PacketSink for super::jitter::JitterBuffer {
+    fn send_packet(&mut self, packet: &[u8], info: PacketInfo) -> io::Result<()> {
+        self.push(
+            info.sequence as u16,
+            info.start().to_rtp(0),
+            packet.to_vec(),
+        );
+        Ok(())
+    }
+}
+
+/// A stage transforming PCM in place before it is encoded, such as noise
+/// suppression, automatic gain control, or a high-pass filter.
+///
+/// `PushEncoder` runs its processors on every frame it encodes, each frame
+/// being exactly the encoder's frame size of interleaved samples.
+pub trait PcmProcessor<T> {
+    /// Process one interleaved frame in place.
+    fn process(&mut self, frame: &mut [T]);
+}
+
+/// A boxed processor, as added to a `PushEncoder`.
+pub type BoxedProcessor<T> = Box<dyn PcmProcessor<T> + Send>;
+
+impl<T, F: FnMut(&mut [T])> PcmProcessor<T> for F {
+    fn process(&mut self, frame: &mut [T]) {
+        self(frame)
+    }
+}
+
+/// A first-order high-pass filter removing DC offset and rumble, the
+/// simplest of pre-processing stages.
+#[derive(Debug, Clone)]
+pub struct HighPass {
+    coeff: f32,
+    // the last input and output of each channel
+    state: Vec<(f32, f32)>,
+}
+
+impl HighPass {
+    /// Create a filter for `channels` interleaved channels at `sample_rate`,
+    /// cutting below `cutoff` Hz.
+    pub fn new(channels: Channels, sample_rate: u32, cutoff: f32) -> HighPass {
+        HighPass {
+            coeff: (-2.0 * std::f32::consts::PI * cutoff / sample_rate as f32).exp(),
+            state: vec![(0.0, 0.0); channels as usize],
+        }
+    }
+
+    fn filter(&mut self, channel: usize, x: f32) -> f32 {
+        let (x1, y1) = self.state[channel];
+        let y = x - x1 + self.coeff * y1;
+        self.state[channel] = (x, y);
+        y
+    }
+}
+
+impl PcmProcessor<f32> for HighPass {
+    fn process(&mut self, frame: &mut [f32]) {
+        let channels = self.state.len();
+        for (i, sample) in frame.iter_mut().enumerate() {
+            *sample = self.filter(i % channels, *sample);
+        }
+    }
+}
+
+impl PcmProcessor<i16> for HighPass {
+    fn process(&mut self, frame: &mut [i16]) {
+        let channels = self.state.len();
+        for (i, sample) in frame.iter_mut().enumerate() {
+            let y = self.filter(i % channels, *sample as f32);
+            *sample = y.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+    }
+}
+
+/// Assigns sequence numbers and timestamps to consecutive packets.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct Timestamper {
+    sequence: u64,
+    timestamp: u64,
+}
+
+impl Timestamper {
+    /// Start stamping packets from sequence number and timestamp zero.
+    pub fn new() -> Timestamper {
+        Timestamper::default()
+    }
+
+    /// Start stamping packets from the given 48kHz timestamp.
+    pub fn starting_at(timestamp: u64) -> Timestamper {
+        Timestamper {
+            sequence: 0,
+            timestamp,
+        }
+    }
+
+    /// Get the information for the next packet without advancing.
+    pub fn peek(&self, duration: u32) -> PacketInfo {
+        PacketInfo {
+            sequence: self.sequence,
+            timestamp: self.timestamp,
+            duration,
+        }
+    }
+
+    /// Stamp a packet of the given duration and advance past it.
+    pub fn advance(&mut self, duration: u32) -> PacketInfo {
+        let info = self.peek(duration);
+        self.sequence += 1;
+        self.timestamp += duration as u64;
+        info
+    }
+
+    /// Stamp an encoded packet, measuring its duration, and advance past it.
+    pub fn stamp(&mut self, packet: &[u8]) -> io::Result<PacketInfo> {
+        let duration = Timestamp48k::of_packet(packet)?;
+        Ok(self.advance(duration.samples() as u32))
+    }
+
+    /// Skip the given number of 48kHz samples without emitting a packet, as
+    /// happens when a sender stops transmitting during silence.
+    pub fn skip(&mut self, duration: u32) {
+        self.timestamp += duration as u64;
+    }
+}
+
+/// Encodes complete frames and forwards the timestamped packets to a sink.
+#[derive(Debug)]
+pub struct Packetizer<S> {
+    encoder: Encoder,
+    sink: S,
+    timestamper: Timestamper,
+    buffer: Vec<u8>,
+}
+
+impl<S: PacketSink> Packetizer<S> {
+    /// Create a packetizer producing packets of at most `max_size` bytes.
+    pub fn new(encoder: Encoder, sink: S, max_size: usize) -> Packetizer<S> {
+        Packetizer {
+            encoder,
+            sink,
+            timestamper: Timestamper::new(),
+            buffer: vec![0; max_size],
+        }
+    }
+
+    /// Encode a single frame and send it to the sink.
+    pub fn write_frame(&mut self, frame: &[i16]) -> io::Result<PacketInfo> {
+        let len = self.encoder.encode(frame, &mut self.buffer)?;
+        self.send(len)
+    }
+
+    /// Encode a single frame of floating point input and send it to the sink.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn write_frame_float(&mut self, frame: &[f32]) -> io::Result<PacketInfo> {
+        let len = self.encoder.encode_float(frame, &mut self.buffer)?;
+        self.send(len)
+    }
+
+    fn send(&mut self, len: usize) -> io::Result<PacketInfo> {
+        let packet = &self.buffer[..len];
+        let info = self.timestamper.stamp(packet)?;
+        self.sink.send_packet(packet, info)?;
+        Ok(info)
+    }
+
+    /// Flush the sink.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    /// Get the encoder.
+    pub fn encoder(&mut self) -> &mut Encoder {
+        &mut self.encoder
+    }
+
+    /// Get the sink.
+    pub fn sink(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Get the timestamper used to stamp outgoing packets.
+    pub fn timestamper(&mut self) -> &mut Timestamper {
+        &mut self.timestamper
+    }
+
+    /// Unwrap the encoder and sink.
+    pub fn into_inner(self) -> (Encoder, S) {
+        (self.encoder, self.sink)
+    }
+}
+
+/// A push-based encoder performing no I/O of its own.
+///
+/// Samples of any length are fed with `push`, split into frames of the
+/// encoder's configured frame duration (or 20 ms if none was set), and the
+/// resulting timestamped packets are collected with `poll_packet` whenever
+/// convenient, for instance once per tick of a game loop.
+///
+/// Packet buffers are taken from a `BufferPool`; handing them back with
+/// `recycle` once sent keeps steady-state encoding allocation-free.
+///
+/// Frames pass through any `PcmProcessor`s added with `add_processor`, in
+/// order, before they are encoded.
+pub struct PushEncoder<T, P = VecPool> {
+    encoder: Encoder,
+    chunker: FrameChunker<T>,
+    timestamper: Timestamper,
+    buffer: Vec<u8>,
+    ready: VecDeque<(Vec<u8>, PacketInfo)>,
+    pool: P,
+    processors: Vec<BoxedProcessor<T>>,
+    frame: Vec<T>,
+}
+
+impl<T: fmt::Debug, P: fmt::Debug> fmt::Debug for PushEncoder<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PushEncoder")
+            .field("encoder", &self.encoder)
+            .field("chunker", &self.chunker)
+            .field("timestamper", &self.timestamper)
+            .field("ready", &self.ready.len())
+            .field("pool", &self.pool)
+            .field("processors", &self.processors.len())
+            .finish()
+    }
+}
+
+impl<T: Sample> PushEncoder<T> {
+    /// Create a push encoder wrapping `encoder`.
+    pub fn new(encoder: Encoder) -> Result<PushEncoder<T>> {
+        PushEncoder::with_pool(encoder, VecPool::new())
+    }
+}
+
+impl<T: Sample, P: BufferPool> PushEncoder<T, P> {
+    /// Create a push encoder wrapping `encoder`, taking its packet buffers
+    /// from `pool`.
+    pub fn with_pool(mut encoder: Encoder, pool: P) -> Result<PushEncoder<T, P>> {
+        let frame_size = encoder.frame_size()?;
+        let channels = encoder.channels;
+        Ok(PushEncoder {
+            encoder,
+            chunker: FrameChunker::new(frame_size, channels),
+            timestamper: Timestamper::new(),
+            buffer: vec![0; MAX_PACKET],
+            ready: VecDeque::new(),
+            pool,
+            processors: Vec::new(),
+            frame: Vec::new(),
+        })
+    }
+
+    /// Add a processor to run on each frame before it is encoded, after any
+    /// added before it.
+    pub fn add_processor(&mut self, processor: BoxedProcessor<T>) {
+        self.processors.push(processor);
+    }
+
+    /// Feed interleaved samples, encoding every frame they complete.
+    ///
+    /// Returns the number of packets which became ready.
+    pub fn push(&mut self, pcm: &[T]) -> Result<usize> {
+        let before = self.ready.len();
+        let PushEncoder {
+            ref mut encoder,
+            ref mut chunker,
+            ref mut timestamper,
+            ref mut buffer,
+            ref mut ready,
+            ref mut pool,
+            ref mut processors,
+            frame: ref mut scratch,
+        } = *self;
+        let mut stages = Stages {
+            processors,
+            scratch,
+        };
+        chunker.push(pcm, |frame| {
+            let frame = stages.run(frame);
+            encode_frame(encoder, timestamper, buffer, ready, pool, frame)
+        })?;
+        Ok(self.ready.len() - before)
+    }
+
+    /// Encode any buffered samples as a final frame padded with silence.
+    ///
+    /// Returns the number of packets which became ready.
+    pub fn finish(&mut self) -> Result<usize> {
+        let before = self.ready.len();
+        let PushEncoder {
+            ref mut encoder,
+            ref mut chunker,
+            ref mut timestamper,
+            ref mut buffer,
+            ref mut ready,
+            ref mut pool,
+            ref mut processors,
+            frame: ref mut scratch,
+        } = *self;
+        let mut stages = Stages {
+            processors,
+            scratch,
+        };
+        chunker.flush(|frame| {
+            let frame = stages.run(frame);
+            encode_frame(encoder, timestamper, buffer, ready, pool, frame)
+        })?;
+        Ok(self.ready.len() - before)
+    }
+
+    /// Take the oldest ready packet, if any.
+    pub fn poll_packet(&mut self) -> Option<(Vec<u8>, PacketInfo)> {
+        self.ready.pop_front()
+    }
+
+    /// Return a packet's buffer to the pool once it has been sent.
+    pub fn recycle(&mut self, packet: Vec<u8>) {
+        self.pool.recycle(packet);
+    }
+
+    /// Get the number of packets ready to be taken.
+    pub fn ready(&self) -> usize {
+        self.ready.len()
+    }
+
+    /// Get the number of interleaved samples waiting for a complete frame.
+    pub fn pending(&self) -> usize {
+        self.chunker.pending()
+    }
+
+    /// Get the encoder.
+    pub fn encoder(&mut self) -> &mut Encoder {
+        &mut self.encoder
+    }
+
+    /// Get the timestamper used to stamp packets.
+    pub fn timestamper(&mut self) -> &mut Timestamper {
+        &mut self.timestamper
+    }
+
+    /// Unwrap the encoder, discarding any buffered samples and packets.
+    pub fn into_inner(self) -> Encoder {
+        self.encoder
+    }
+}
+
+// The processors of a `PushEncoder`, with a buffer to run them in.
+struct Stages<'a, T> {
+    processors: &'a mut [BoxedProcessor<T>],
+    scratch: &'a mut Vec<T>,
+}
+
+impl<'a, T: Copy> Stages<'a, T> {
+    // Run the processors on a copy of `frame`, or pass it through untouched
+    // if there are none.
+    fn run<'b>(&'b mut self, frame: &'b [T]) -> &'b [T] {
+        if self.processors.is_empty() {
+            return frame;
+        }
+        self.scratch.clear();
+        self.scratch.extend_from_slice(frame);
+        for processor in self.processors.iter_mut() {
+            processor.process(self.scratch);
+        }
+        self.scratch
+    }
+}
+
+fn encode_frame<T: Sample, P: BufferPool>(
+    encoder: &mut Encoder,
+    timestamper: &mut Timestamper,
+    buffer: &mut [u8],
+    ready: &mut VecDeque<(Vec<u8>, PacketInfo)>,
+    pool: &mut P,
+    frame: &[T],
+) -> Result<()> {
+    let len = T::encode(encoder, frame, buffer)?;
+    let packet = &buffer[..len];
+    let duration = Timestamp48k::of_packet(packet)?;
+    let mut data = pool.take();
+    data.extend_from_slice(packet);
+    ready.push_back((data, timestamper.advance(duration.samples() as u32)));
+    Ok(())
+}
@@ -0,0 +1,73 @@
+//! Reusable byte buffers for allocation-free steady-state operation.
+//!
+//! Types which hand out packets, such as `ogg::OggDemuxer` and
+//! `pipeline::PushEncoder`, take the packet buffers from a `BufferPool` and
+//! accept them back through their `recycle` methods. Once enough buffers are
+//! circulating, no further allocation is needed.
+
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+/// Default number of idle buffers kept by a `VecPool`.
+const DEFAULT_LIMIT: usize = 64;
+
+/// A source of reusable byte buffers.
+pub trait BufferPool {
+    /// Take an empty buffer from the pool, allocating one if none is idle.
+    fn take(&mut self) -> Vec<u8>;
+
+    /// Return a buffer whose contents are no longer needed.
+    fn recycle(&mut self, buf: Vec<u8>);
+}
+
+/// A pool keeping returned buffers in a free list of bounded length.
+#[derive(Debug, Clone)]
+pub struct VecPool {
+    free: Vec<Vec<u8>>,
+    limit: usize,
+}
+
+impl Default for VecPool {
+    fn default() -> VecPool {
+        VecPool::new()
+    }
+}
+
+impl VecPool {
+    /// Create a pool keeping up to 64 idle buffers.
+    pub fn new() -> VecPool {
+        VecPool::with_limit(DEFAULT_LIMIT)
+    }
+
+    /// Create a pool keeping up to `limit` idle buffers. Buffers returned
+    /// beyond that are dropped.
+    pub fn with_limit(limit: usize) -> VecPool {
+        VecPool {
+            free: Vec::with_capacity(limit),
+            limit,
+        }
+    }
+
+    /// Get the number of idle buffers.
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Determine whether no buffers are idle.
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+}
+
+impl BufferPool for VecPool {
+    fn take(&mut self) -> Vec<u8> {
+        self.free.pop().unwrap_or_default()
+    }
+
+    fn recycle(&mut self, mut buf: Vec<u8>) {
+        if self.free.len() < self.limit {
+            buf.clear();
+            self.free.push(buf);
+        }
+    }
+}
@@ -0,0 +1,381 @@
+//! Ambisonics coding with the projection API of libopus.
+//!
+//! A projection encoder mixes the ambisonic channels into streams through a
+//! mixing matrix chosen for the order, and the decoder undoes it with the
+//! matching demixing matrix. Ogg Opus carries that matrix in the header of
+//! channel mapping family 3 (RFC 8486 section 3.2), in the byte format of
+//! `DemixingMatrix::to_bytes`, so that other tools can decode the streams.
+
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use libc::c_int;
+use std::ptr;
+
+use super::{
+    ffi, len, Application, Bitrate, Error, Result, OPUS_AUTO, OPUS_BITRATE_MAX, OPUS_GET_LOOKAHEAD,
+    OPUS_RESET_STATE, OPUS_SET_BITRATE, OPUS_SET_GAIN,
+};
+
+const OPUS_PROJECTION_GET_DEMIXING_MATRIX_GAIN: c_int =
+    ffi::CtlRequest::ProjectionGetDemixingMatrixGain as c_int; // out *i32
+const OPUS_PROJECTION_GET_DEMIXING_MATRIX_SIZE: c_int =
+    ffi::CtlRequest::ProjectionGetDemixingMatrixSize as c_int; // out *i32
+const OPUS_PROJECTION_GET_DEMIXING_MATRIX: c_int =
+    ffi::CtlRequest::ProjectionGetDemixingMatrix as c_int; // out *u8, in i32
+
+macro_rules! proj_enc_ctl {
+	($this:ident, $ctl:ident $(, $rest:expr)*) => {
+		ctl!(opus_projection_encoder_ctl, $this, $ctl, $($rest),*)
+	}
+}
+
+macro_rules! proj_dec_ctl {
+	($this:ident, $ctl:ident $(, $rest:expr)*) => {
+		ctl!(opus_projection_decoder_ctl, $this, $ctl, $($rest),*)
+	}
+}
+
+/// The matrix turning the decoded streams back into ambisonic channels.
+///
+/// It has a row for each output channel and a column for each stream
+/// channel, the coupled streams' two channels first, and its coefficients
+/// are Q15 fixed point.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DemixingMatrix {
+    channels: u8,
+    stream_channels: u8,
+    coefficients: Vec<i16>,
+    /// The gain in Q7.8 dB to apply to the output along with the matrix,
+    /// which Ogg Opus adds to the output gain of the header. It is zero for
+    /// a matrix read from a header.
+    pub gain: i32,
+}
+
+impl DemixingMatrix {
+    /// Create a matrix from its coefficients in column-major order, so that
+    /// the coefficient mixing stream channel `col` into output channel `row`
+    /// is at `col * channels + row`.
+    pub fn new(
+        channels: u8,
+        stream_channels: u8,
+        coefficients: Vec<i16>,
+    ) -> Result<DemixingMatrix> {
+        if channels == 0
+            || stream_channels == 0
+            || coefficients.len() != channels as usize * stream_channels as usize
+        {
+            return Err(Error::bad_arg("DemixingMatrix::new"));
+        }
+        Ok(DemixingMatrix {
+            channels,
+            stream_channels,
+            coefficients,
+            gain: 0,
+        })
+    }
+
+    /// Read a matrix in the format of the Ogg Opus family 3 header: 16-bit
+    /// little-endian coefficients in column-major order.
+    pub fn from_bytes(channels: u8, stream_channels: u8, bytes: &[u8]) -> Result<DemixingMatrix> {
+        if bytes.len() != 2 * channels as usize * stream_channels as usize {
+            return Err(Error::bad_arg("DemixingMatrix::from_bytes"));
+        }
+        let coefficients = bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        DemixingMatrix::new(channels, stream_channels, coefficients)
+    }
+
+    /// Write the matrix in the format of the Ogg Opus family 3 header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.coefficients
+            .iter()
+            .flat_map(|c| c.to_le_bytes())
+            .collect()
+    }
+
+    /// Get the number of output channels, the rows of the matrix.
+    pub fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    /// Get the number of stream channels, the columns of the matrix.
+    pub fn stream_channels(&self) -> u8 {
+        self.stream_channels
+    }
+
+    /// Get the coefficients in column-major order.
+    pub fn coefficients(&self) -> &[i16] {
+        &self.coefficients
+    }
+
+    /// Get the coefficient mixing stream channel `col` into output channel
+    /// `row`.
+    pub fn get(&self, row: u8, col: u8) -> Option<i16> {
+        if row >= self.channels || col >= self.stream_channels {
+            return None;
+        }
+        Some(self.coefficients[col as usize * self.channels as usize + row as usize])
+    }
+}
+
+/// An ambisonics encoder using the projection API.
+#[derive(Debug)]
+pub struct ProjectionEncoder {
+    ptr: *mut ffi::OpusProjectionEncoder,
+    channels: u8,
+    streams: u8,
+    coupled_streams: u8,
+}
+
+impl ProjectionEncoder {
+    /// Create and initialize an encoder of ambisonics of the given order with
+    /// mapping family 3: `(order + 1)²` channels, optionally followed by a
+    /// non-diegetic stereo pair.
+    pub fn ambisonics(
+        sample_rate: u32,
+        channels: u8,
+        mapping_family: u8,
+        mode: Application,
+    ) -> Result<ProjectionEncoder> {
+        let mut error = 0;
+        let mut streams = 0;
+        let mut coupled_streams = 0;
+        let ptr = unsafe {
+            ffi::opus_projection_ambisonics_encoder_create(
+                sample_rate as i32,
+                channels as c_int,
+                mapping_family as c_int,
+                &mut streams,
+                &mut coupled_streams,
+                mode as c_int,
+                &mut error,
+            )
+        };
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            Err(Error::from_code(
+                "opus_projection_ambisonics_encoder_create",
+                error,
+            ))
+        } else {
+            Ok(ProjectionEncoder {
+                ptr,
+                channels,
+                streams: streams as u8,
+                coupled_streams: coupled_streams as u8,
+            })
+        }
+    }
+
+    /// Get the number of input channels.
+    pub fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    /// Get the total number of streams.
+    pub fn streams(&self) -> u8 {
+        self.streams
+    }
+
+    /// Get the number of streams which are coupled stereo pairs.
+    pub fn coupled_streams(&self) -> u8 {
+        self.coupled_streams
+    }
+
+    /// Encode an Opus frame.
+    pub fn encode(&mut self, input: &[i16], output: &mut [u8]) -> Result<usize> {
+        let len = ffi!(
+            opus_projection_encode,
+            self.ptr,
+            input.as_ptr(),
+            len(input) / self.channels.max(1) as c_int,
+            output.as_mut_ptr(),
+            len(output)
+        );
+        Ok(len as usize)
+    }
+
+    /// Encode an Opus frame from floating point input.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        let len = ffi!(
+            opus_projection_encode_float,
+            self.ptr,
+            input.as_ptr(),
+            len(input) / self.channels.max(1) as c_int,
+            output.as_mut_ptr(),
+            len(output)
+        );
+        Ok(len as usize)
+    }
+
+    /// Encode an Opus frame to a new buffer.
+    pub fn encode_vec(&mut self, input: &[i16], max_size: usize) -> Result<Vec<u8>> {
+        let mut output: Vec<u8> = vec![0; max_size];
+        let result = self.encode(input, output.as_mut_slice())?;
+        output.truncate(result);
+        Ok(output)
+    }
+
+    /// Reset the codec state to be equivalent to a freshly initialized state.
+    pub fn reset_state(&mut self) -> Result<()> {
+        proj_enc_ctl!(self, OPUS_RESET_STATE);
+        Ok(())
+    }
+
+    /// Set the total bitrate of all streams.
+    pub fn set_bitrate(&mut self, value: Bitrate) -> Result<()> {
+        let value: i32 = match value {
+            Bitrate::Auto => OPUS_AUTO,
+            Bitrate::Max => OPUS_BITRATE_MAX,
+            Bitrate::Bits(b) => b,
+        };
+        proj_enc_ctl!(self, OPUS_SET_BITRATE, value);
+        Ok(())
+    }
+
+    /// Gets the total samples of delay added by the entire codec.
+    pub fn get_lookahead(&mut self) -> Result<i32> {
+        let mut value: i32 = 0;
+        proj_enc_ctl!(self, OPUS_GET_LOOKAHEAD, &mut value);
+        Ok(value)
+    }
+
+    /// Get the demixing matrix a decoder needs to undo the encoder's mixing,
+    /// with its gain, to be written to the Ogg Opus header.
+    pub fn demixing_matrix(&mut self) -> Result<DemixingMatrix> {
+        let mut gain: i32 = 0;
+        proj_enc_ctl!(self, OPUS_PROJECTION_GET_DEMIXING_MATRIX_GAIN, &mut gain);
+        let mut size: i32 = 0;
+        proj_enc_ctl!(self, OPUS_PROJECTION_GET_DEMIXING_MATRIX_SIZE, &mut size);
+        let mut bytes = vec![0u8; size.max(0) as usize];
+        proj_enc_ctl!(
+            self,
+            OPUS_PROJECTION_GET_DEMIXING_MATRIX,
+            bytes.as_mut_ptr(),
+            size
+        );
+        let stream_channels = self.streams + self.coupled_streams;
+        let mut matrix = DemixingMatrix::from_bytes(self.channels, stream_channels, &bytes)?;
+        matrix.gain = gain;
+        Ok(matrix)
+    }
+}
+
+impl Drop for ProjectionEncoder {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_projection_encoder_destroy(self.ptr) }
+    }
+}
+
+// See `unsafe impl Send for Encoder`.
+unsafe impl Send for ProjectionEncoder {}
+
+/// An ambisonics decoder using the projection API.
+#[derive(Debug)]
+pub struct ProjectionDecoder {
+    ptr: *mut ffi::OpusProjectionDecoder,
+    channels: u8,
+}
+
+impl ProjectionDecoder {
+    /// Create and initialize a decoder with the stream counts and demixing
+    /// matrix of an encoder, as read from an Ogg Opus family 3 header.
+    pub fn new(
+        sample_rate: u32,
+        streams: u8,
+        coupled_streams: u8,
+        matrix: &DemixingMatrix,
+    ) -> Result<ProjectionDecoder> {
+        let what = "opus_projection_decoder_create";
+        if matrix.stream_channels as usize != streams as usize + coupled_streams as usize {
+            return Err(Error::bad_arg(what));
+        }
+        let mut bytes = matrix.to_bytes();
+        let mut error = 0;
+        let ptr = unsafe {
+            ffi::opus_projection_decoder_create(
+                sample_rate as i32,
+                matrix.channels as c_int,
+                streams as c_int,
+                coupled_streams as c_int,
+                bytes.as_mut_ptr(),
+                len(&bytes),
+                &mut error,
+            )
+        };
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            Err(Error::from_code(what, error))
+        } else {
+            Ok(ProjectionDecoder {
+                ptr,
+                channels: matrix.channels,
+            })
+        }
+    }
+
+    /// Get the number of output channels.
+    pub fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    /// Decode an Opus packet.
+    pub fn decode(&mut self, input: &[u8], output: &mut [i16], fec: bool) -> Result<usize> {
+        let ptr = match input.len() {
+            0 => ptr::null(),
+            _ => input.as_ptr(),
+        };
+        let len = ffi!(
+            opus_projection_decode,
+            self.ptr,
+            ptr,
+            len(input),
+            output.as_mut_ptr(),
+            len(output) / self.channels.max(1) as c_int,
+            fec as c_int
+        );
+        Ok(len as usize)
+    }
+
+    /// Decode an Opus packet with floating point output.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn decode_float(&mut self, input: &[u8], output: &mut [f32], fec: bool) -> Result<usize> {
+        let ptr = match input.len() {
+            0 => ptr::null(),
+            _ => input.as_ptr(),
+        };
+        let len = ffi!(
+            opus_projection_decode_float,
+            self.ptr,
+            ptr,
+            len(input),
+            output.as_mut_ptr(),
+            len(output) / self.channels.max(1) as c_int,
+            fec as c_int
+        );
+        Ok(len as usize)
+    }
+
+    /// Reset the codec state to be equivalent to a freshly initialized state.
+    pub fn reset_state(&mut self) -> Result<()> {
+        proj_dec_ctl!(self, OPUS_RESET_STATE);
+        Ok(())
+    }
+
+    /// Configures decoder gain adjustment, in Q7.8 dB.
+    pub fn set_gain(&mut self, gain: i32) -> Result<()> {
+        proj_dec_ctl!(self, OPUS_SET_GAIN, gain);
+        Ok(())
+    }
+}
+
+impl Drop for ProjectionDecoder {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_projection_decoder_destroy(self.ptr) }
+    }
+}
+
+// See `unsafe impl Send for Encoder`.
+unsafe impl Send for ProjectionDecoder {}
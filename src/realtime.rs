@@ -0,0 +1,368 @@
+//! Encoding from and decoding to hard real-time audio callbacks.
+//!
+//! On the encoding side, the callback pushes PCM through a `RealtimeInput`,
+//! which only copies the samples into a lock-free ring buffer and wakes the
+//! worker thread; it never locks or allocates. The worker splits the samples
+//! into frames, encodes them, and hands each packet to a callback of its own.
+//!
+//! On the decoding side, a network thread pushes packets through a
+//! `PacketInput` into a `PlayoutBuffer`, from which the audio callback reads
+//! fixed-size blocks of PCM. Decoding happens on the audio thread, again
+//! without locks or allocations, and packet loss concealment fills in when
+//! packets fail to arrive in time.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle, Thread};
+use std::time::Duration;
+
+use super::chunk::{Sample, MAX_PACKET};
+use super::pipeline::{PacketInfo, PushEncoder};
+use super::ring::{self, Consumer, Producer};
+use super::{ffi, Decoder, Encoder, Error, Result};
+
+/// Number of samples the worker takes from the ring buffer at once.
+const WORKER_CHUNK: usize = 4096;
+
+/// Longest the worker sleeps without being woken, as a safety net.
+const WORKER_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// The audio thread's end of a `RealtimeEncoder`.
+#[derive(Debug)]
+pub struct RealtimeInput<T> {
+    producer: Producer<T>,
+    worker: Thread,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<T: Copy> RealtimeInput<T> {
+    /// Push interleaved samples without blocking or allocating.
+    ///
+    /// Returns the number of samples accepted. Samples which do not fit in
+    /// the ring buffer, because the worker has fallen behind, are dropped
+    /// and counted.
+    pub fn push(&mut self, pcm: &[T]) -> usize {
+        let len = self.producer.push_slice(pcm);
+        if len < pcm.len() {
+            self.dropped
+                .fetch_add((pcm.len() - len) as u64, Ordering::Relaxed);
+        }
+        self.worker.unpark();
+        len
+    }
+
+    /// Get the number of samples which can currently be pushed.
+    pub fn free(&self) -> usize {
+        self.producer.free()
+    }
+
+    /// Get the total number of samples dropped so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// A worker thread encoding PCM pushed from a real-time audio thread.
+///
+/// Dropping this stops the worker as `finish` does, discarding any error.
+#[derive(Debug)]
+pub struct RealtimeEncoder {
+    thread: Option<JoinHandle<Result<Encoder>>>,
+    stop: Arc<AtomicBool>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl RealtimeEncoder {
+    /// Start a worker encoding with `encoder`, buffering up to `capacity`
+    /// interleaved samples between the audio thread and the worker.
+    ///
+    /// `on_packet` is called on the worker thread with each packet.
+    pub fn spawn<T, F>(
+        encoder: Encoder,
+        capacity: usize,
+        mut on_packet: F,
+    ) -> io::Result<(RealtimeInput<T>, RealtimeEncoder)>
+    where
+        T: Sample + Send + 'static,
+        F: FnMut(Vec<u8>, PacketInfo) + Send + 'static,
+    {
+        let mut push = PushEncoder::<T>::new(encoder)?;
+        let (producer, mut consumer) = ring::ring(capacity);
+        let stop = Arc::new(AtomicBool::new(false));
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let worker_stop = stop.clone();
+        let thread = thread::Builder::new()
+            .name("opus-encoder".to_string())
+            .spawn(move || {
+                let mut scratch = vec![T::default(); WORKER_CHUNK];
+                loop {
+                    // read the flag first, so every sample pushed before
+                    // stopping is seen below
+                    let stopping = worker_stop.load(Ordering::Acquire);
+                    let len = consumer.pop_slice(&mut scratch);
+                    if len > 0 {
+                        push.push(&scratch[..len])?;
+                        drain(&mut push, &mut on_packet);
+                        continue;
+                    }
+                    if stopping || consumer.is_abandoned() {
+                        push.finish()?;
+                        drain(&mut push, &mut on_packet);
+                        return Ok(push.into_inner());
+                    }
+                    thread::park_timeout(WORKER_TIMEOUT);
+                }
+            })?;
+
+        let input = RealtimeInput {
+            producer,
+            worker: thread.thread().clone(),
+            dropped: dropped.clone(),
+        };
+        let encoder = RealtimeEncoder {
+            thread: Some(thread),
+            stop,
+            dropped,
+        };
+        Ok((input, encoder))
+    }
+
+    /// Get the total number of samples dropped by the input so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Determine whether the worker has exited, normally because of an
+    /// encoding error which `finish` will return.
+    pub fn is_finished(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_none_or(|thread| thread.is_finished())
+    }
+
+    /// Encode all samples pushed so far, the last frame padded with silence,
+    /// then stop the worker and return the encoder.
+    pub fn finish(mut self) -> Result<Encoder> {
+        match self.join() {
+            Some(Ok(result)) => result,
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            // the worker is only joined here or on drop
+            None => Err(Error::from_code(
+                "RealtimeEncoder::finish",
+                ffi::OPUS_INVALID_STATE,
+            )),
+        }
+    }
+
+    fn join(&mut self) -> Option<thread::Result<Result<Encoder>>> {
+        let thread = self.thread.take()?;
+        self.stop.store(true, Ordering::Release);
+        thread.thread().unpark();
+        Some(thread.join())
+    }
+}
+
+impl Drop for RealtimeEncoder {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
+
+fn drain<T: Sample, F: FnMut(Vec<u8>, PacketInfo)>(push: &mut PushEncoder<T>, on_packet: &mut F) {
+    while let Some((packet, info)) = push.poll_packet() {
+        on_packet(packet, info);
+    }
+}
+
+/// Size of the length prefix of each packet in a playout ring buffer.
+const LEN_PREFIX: usize = 2;
+
+/// The network thread's end of a `PlayoutBuffer`.
+#[derive(Debug)]
+pub struct PacketInput {
+    producer: Producer<u8>,
+    scratch: Vec<u8>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl PacketInput {
+    /// Queue a packet for playout.
+    ///
+    /// Returns `false`, counting the packet as dropped, if the ring buffer
+    /// is full or the packet is empty or too large.
+    pub fn push(&mut self, packet: &[u8]) -> bool {
+        let len = LEN_PREFIX + packet.len();
+        if packet.is_empty() || packet.len() > MAX_PACKET || self.producer.free() < len {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        // push the whole packet at once, so the reader never sees a length
+        // without its data
+        self.scratch.clear();
+        self.scratch
+            .extend_from_slice(&(packet.len() as u16).to_le_bytes());
+        self.scratch.extend_from_slice(packet);
+        self.producer.push_slice(&self.scratch);
+        true
+    }
+
+    /// Get the total number of packets dropped so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Counters describing the health of a `PlayoutBuffer`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct PlayoutStats {
+    /// The number of packets decoded.
+    pub packets: u64,
+    /// The number of times no packet was available when audio was needed.
+    pub underruns: u64,
+    /// The number of samples per channel produced by loss concealment.
+    pub concealed: u64,
+    /// The number of packets which failed to decode and were concealed.
+    pub invalid: u64,
+    /// The number of packets dropped by the `PacketInput`.
+    pub dropped: u64,
+}
+
+/// Decodes queued packets into fixed-size blocks of PCM on the audio thread.
+///
+/// Until the first packet arrives, reads produce silence. After that,
+/// whenever audio is needed but no packet is waiting, an underrun is counted
+/// and the duration of the last packet is filled in by the decoder's loss
+/// concealment.
+#[derive(Debug)]
+pub struct PlayoutBuffer {
+    consumer: Consumer<u8>,
+    decoder: Decoder,
+    channels: usize,
+    conceal_len: usize,
+    packet: Vec<u8>,
+    pcm: Vec<i16>,
+    pos: usize,
+    len: usize,
+    started: bool,
+    stats: PlayoutStats,
+    dropped: Arc<AtomicU64>,
+}
+
+impl PlayoutBuffer {
+    /// Create a playout buffer decoding with `decoder`, queueing up to
+    /// roughly `capacity` bytes of packets.
+    pub fn new(mut decoder: Decoder, capacity: usize) -> Result<(PacketInput, PlayoutBuffer)> {
+        let rate = decoder.get_sample_rate()? as usize;
+        let channels = decoder.channels as usize;
+        let (producer, consumer) = ring::ring(capacity.max(LEN_PREFIX + MAX_PACKET));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let input = PacketInput {
+            producer,
+            scratch: Vec::with_capacity(LEN_PREFIX + MAX_PACKET),
+            dropped: dropped.clone(),
+        };
+        // the longest possible packet is 120 ms
+        let max_frame = rate * 120 / 1000;
+        let playout = PlayoutBuffer {
+            consumer,
+            decoder,
+            channels,
+            conceal_len: rate / 50,
+            packet: vec![0; MAX_PACKET],
+            pcm: vec![0; max_frame * channels],
+            pos: 0,
+            len: 0,
+            started: false,
+            stats: PlayoutStats::default(),
+            dropped,
+        };
+        Ok((input, playout))
+    }
+
+    /// Fill `out` with interleaved samples, never blocking or allocating.
+    pub fn read(&mut self, out: &mut [i16]) {
+        let mut written = 0;
+        while written < out.len() {
+            if self.pos == self.len && !self.refill() {
+                for sample in &mut out[written..] {
+                    *sample = 0;
+                }
+                return;
+            }
+            let len = (self.len - self.pos).min(out.len() - written);
+            out[written..written + len].copy_from_slice(&self.pcm[self.pos..self.pos + len]);
+            self.pos += len;
+            written += len;
+        }
+    }
+
+    /// Get the number of bytes of packets waiting to be decoded.
+    pub fn queued(&self) -> usize {
+        self.consumer.available()
+    }
+
+    /// Get the counters so far.
+    pub fn stats(&self) -> PlayoutStats {
+        PlayoutStats {
+            dropped: self.dropped.load(Ordering::Relaxed),
+            ..self.stats
+        }
+    }
+
+    /// Get the decoder.
+    pub fn decoder(&mut self) -> &mut Decoder {
+        &mut self.decoder
+    }
+
+    /// Decode the next packet, or conceal a missing one. Returns `false`
+    /// before the first packet has arrived.
+    fn refill(&mut self) -> bool {
+        self.pos = 0;
+        self.len = 0;
+        let mut prefix = [0; LEN_PREFIX];
+        let missing = if self.consumer.pop_slice(&mut prefix) == LEN_PREFIX {
+            let len = u16::from_le_bytes(prefix) as usize;
+            self.consumer.pop_slice(&mut self.packet[..len]);
+            match self
+                .decoder
+                .decode(&self.packet[..len], &mut self.pcm, false)
+            {
+                Ok(samples) => {
+                    self.stats.packets += 1;
+                    self.started = true;
+                    self.conceal_len = samples;
+                    self.len = samples * self.channels;
+                    return true;
+                }
+                Err(_) => {
+                    self.stats.invalid += 1;
+                    false
+                }
+            }
+        } else {
+            true
+        };
+        if !self.started {
+            return false;
+        }
+        if missing {
+            self.stats.underruns += 1;
+        }
+        let conceal = self.conceal_len * self.channels;
+        match self.decoder.decode(&[], &mut self.pcm[..conceal], false) {
+            Ok(samples) => {
+                self.stats.concealed += samples as u64;
+                self.len = samples * self.channels;
+            }
+            Err(_) => {
+                for sample in &mut self.pcm[..conceal] {
+                    *sample = 0;
+                }
+                self.len = conceal;
+            }
+        }
+        true
+    }
+}
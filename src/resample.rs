@@ -0,0 +1,293 @@
+//! Sample rate conversion for input at rates libopus doesn't accept.
+//!
+//! libopus only encodes at 8, 12, 16, 24, or 48 kHz, while much audio is
+//! recorded at 44.1 kHz. `Resampler` is a polyphase windowed-sinc resampler
+//! between any two rates, and `ResamplingEncoder` puts one in front of a
+//! `PushEncoder` so that input at any rate can be encoded.
+
+use std::f64::consts::PI;
+
+use super::pipeline::{PacketInfo, PushEncoder};
+use super::pool::{BufferPool, VecPool};
+use super::{Encoder, Result};
+
+/// Sample rates accepted by libopus.
+pub const RATES: [u32; 5] = [8000, 12000, 16000, 24000, 48000];
+
+// Zero crossings of the sinc on each side of the center, at the lower of
+// the two rates.
+const ZERO_CROSSINGS: usize = 16;
+
+// The most filter phases kept in the table; ratios needing more have their
+// phases rounded down to the nearest of these.
+const MAX_PHASES: u64 = 1024;
+
+// The passband, as a fraction of the lower Nyquist frequency.
+const CUTOFF: f64 = 0.95;
+
+/// Get the rate to encode audio sampled at `rate` at: `rate` itself if
+/// libopus accepts it, or 48 kHz otherwise.
+pub fn encode_rate(rate: u32) -> u32 {
+    if RATES.contains(&rate) {
+        rate
+    } else {
+        48000
+    }
+}
+
+/// A streaming resampler for interleaved `f32` samples.
+///
+/// The filter delay is compensated, so output lines up with the input from
+/// its first sample, and `flush` ends the output at the duration of the
+/// input. Between equal rates samples are passed through untouched.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    from: u32,
+    to: u32,
+    channels: usize,
+    // the ratio as `up / down`, in lowest terms
+    up: u64,
+    down: u64,
+    phases: u64,
+    half: usize,
+    table: Vec<f32>,
+    // input frames from `start` onward, which may be before the first
+    buffer: Vec<f32>,
+    start: i64,
+    consumed: u64,
+    produced: u64,
+}
+
+impl Resampler {
+    /// Create a resampler from `from` to `to` Hz for `channels` interleaved
+    /// channels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either rate or `channels` is zero.
+    pub fn new(from: u32, to: u32, channels: usize) -> Resampler {
+        assert!(from > 0 && to > 0 && channels > 0);
+        let gcd = gcd(from as u64, to as u64);
+        let (up, down) = (to as u64 / gcd, from as u64 / gcd);
+        let phases = up.min(MAX_PHASES);
+        // the cutoff relative to the input Nyquist frequency
+        let cutoff = CUTOFF * (up as f64 / down as f64).min(1.0);
+        let half = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+        let mut table = Vec::with_capacity(phases as usize * 2 * half);
+        for phase in 0..phases {
+            let frac = phase as f64 / phases as f64;
+            let start = table.len();
+            for k in 0..2 * half {
+                let x = k as f64 - (half - 1) as f64 - frac;
+                table.push((cutoff * sinc(cutoff * x) * blackman(x / half as f64)) as f32);
+            }
+            let sum: f32 = table[start..].iter().sum();
+            for tap in &mut table[start..] {
+                *tap /= sum;
+            }
+        }
+        let mut resampler = Resampler {
+            from,
+            to,
+            channels,
+            up,
+            down,
+            phases,
+            half,
+            table,
+            buffer: Vec::new(),
+            start: 0,
+            consumed: 0,
+            produced: 0,
+        };
+        resampler.reset();
+        resampler
+    }
+
+    /// Get the input sample rate.
+    pub fn input_rate(&self) -> u32 {
+        self.from
+    }
+
+    /// Get the output sample rate.
+    pub fn output_rate(&self) -> u32 {
+        self.to
+    }
+
+    /// Get the number of channels.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Resample interleaved samples, appending to `output` as many as the
+    /// filter can produce so far. Any incomplete trailing frame is ignored.
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let frames = input.len() / self.channels;
+        if self.up == self.down {
+            output.extend_from_slice(&input[..frames * self.channels]);
+            return;
+        }
+        self.buffer
+            .extend_from_slice(&input[..frames * self.channels]);
+        self.consumed += frames as u64;
+        self.run(output, u64::MAX);
+    }
+
+    /// Append the rest of the output to `output` and reset for a new
+    /// stream.
+    ///
+    /// In total the output has the duration of the input, rounded up to a
+    /// whole sample.
+    pub fn flush(&mut self, output: &mut Vec<f32>) {
+        let total = (self.consumed * self.up).div_ceil(self.down);
+        let len = self.buffer.len() + (self.half + 1) * self.channels;
+        self.buffer.resize(len, 0.0);
+        self.run(output, total);
+        self.reset();
+    }
+
+    /// Discard any buffered input, for a new stream.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.buffer.resize((self.half - 1) * self.channels, 0.0);
+        self.start = -(self.half as i64 - 1);
+        self.consumed = 0;
+        self.produced = 0;
+    }
+
+    fn run(&mut self, output: &mut Vec<f32>, limit: u64) {
+        let channels = self.channels;
+        let taps = 2 * self.half;
+        let buffered = (self.buffer.len() / channels) as i64;
+        while self.produced < limit {
+            let pos = self.produced * self.down;
+            let first = self.first(pos);
+            if first + taps as i64 > self.start + buffered {
+                break;
+            }
+            let phase = (pos % self.up) * self.phases / self.up;
+            let coeffs = &self.table[phase as usize * taps..][..taps];
+            let frames = &self.buffer[(first - self.start) as usize * channels..];
+            for c in 0..channels {
+                let sum: f32 = coeffs
+                    .iter()
+                    .zip(frames[c..].iter().step_by(channels))
+                    .map(|(tap, sample)| tap * sample)
+                    .sum();
+                output.push(sum);
+            }
+            self.produced += 1;
+        }
+        // drop the frames no later output needs
+        let first = self.first(self.produced * self.down);
+        let drop = (first - self.start).clamp(0, buffered);
+        self.buffer.drain(..drop as usize * channels);
+        self.start += drop;
+    }
+
+    // The first input frame filtered for the output at input time
+    // `pos / up`.
+    fn first(&self, pos: u64) -> i64 {
+        (pos / self.up) as i64 - (self.half as i64 - 1)
+    }
+}
+
+/// A `PushEncoder` fed with samples at any rate, resampled to the rate of
+/// the encoder.
+#[derive(Debug)]
+pub struct ResamplingEncoder<P = VecPool> {
+    resampler: Resampler,
+    encoder: PushEncoder<f32, P>,
+    resampled: Vec<f32>,
+}
+
+impl ResamplingEncoder {
+    /// Create an encoder for input at `input_rate`, wrapping `encoder`.
+    pub fn new(encoder: Encoder, input_rate: u32) -> Result<ResamplingEncoder> {
+        ResamplingEncoder::with_pool(encoder, input_rate, VecPool::new())
+    }
+}
+
+impl<P: BufferPool> ResamplingEncoder<P> {
+    /// Create an encoder for input at `input_rate`, wrapping `encoder` and
+    /// taking its packet buffers from `pool`.
+    pub fn with_pool(
+        mut encoder: Encoder,
+        input_rate: u32,
+        pool: P,
+    ) -> Result<ResamplingEncoder<P>> {
+        let rate = encoder.get_sample_rate()?;
+        let channels = encoder.channels as usize;
+        Ok(ResamplingEncoder {
+            resampler: Resampler::new(input_rate, rate, channels),
+            encoder: PushEncoder::with_pool(encoder, pool)?,
+            resampled: Vec::new(),
+        })
+    }
+
+    /// Feed interleaved samples at the input rate, encoding every frame
+    /// they complete.
+    ///
+    /// Returns the number of packets which became ready.
+    pub fn push(&mut self, pcm: &[f32]) -> Result<usize> {
+        self.resampled.clear();
+        self.resampler.process(pcm, &mut self.resampled);
+        self.encoder.push(&self.resampled)
+    }
+
+    /// Encode the rest of the input, padding the final frame with silence.
+    ///
+    /// Returns the number of packets which became ready.
+    pub fn finish(&mut self) -> Result<usize> {
+        self.resampled.clear();
+        self.resampler.flush(&mut self.resampled);
+        let ready = self.encoder.push(&self.resampled)?;
+        Ok(ready + self.encoder.finish()?)
+    }
+
+    /// Take the oldest ready packet, if any.
+    pub fn poll_packet(&mut self) -> Option<(Vec<u8>, PacketInfo)> {
+        self.encoder.poll_packet()
+    }
+
+    /// Return a packet's buffer to the pool once it has been sent.
+    pub fn recycle(&mut self, packet: Vec<u8>) {
+        self.encoder.recycle(packet);
+    }
+
+    /// Get the resampler.
+    pub fn resampler(&self) -> &Resampler {
+        &self.resampler
+    }
+
+    /// Get the push encoder the resampled input is fed to.
+    pub fn push_encoder(&mut self) -> &mut PushEncoder<f32, P> {
+        &mut self.encoder
+    }
+
+    /// Unwrap the encoder, discarding any buffered samples and packets.
+    pub fn into_inner(self) -> Encoder {
+        self.encoder.into_inner()
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+fn blackman(x: f64) -> f64 {
+    0.42 + 0.5 * (PI * x).cos() + 0.08 * (2.0 * PI * x).cos()
+}
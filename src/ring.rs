@@ -0,0 +1,138 @@
+//! A lock-free single-producer, single-consumer ring buffer.
+//!
+//! Neither end ever blocks or allocates, so the producer or consumer may sit
+//! on a real-time audio thread.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug)]
+struct Shared<T> {
+    buf: Box<[UnsafeCell<T>]>,
+    // totals of items ever written and read, wrapping
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// Each slot is only accessed by one end at a time, as arbitrated by `head`
+// and `tail`.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn mask(&self) -> usize {
+        self.buf.len() - 1
+    }
+}
+
+/// Create a ring buffer holding at least `capacity` items.
+///
+/// The capacity is rounded up to a power of two.
+pub fn ring<T: Copy + Default>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    let capacity = capacity.max(1).next_power_of_two();
+    let buf: Vec<UnsafeCell<T>> = (0..capacity)
+        .map(|_| UnsafeCell::new(T::default()))
+        .collect();
+    let shared = Arc::new(Shared {
+        buf: buf.into_boxed_slice(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            shared: shared.clone(),
+        },
+        Consumer { shared },
+    )
+}
+
+/// The writing end of a ring buffer.
+#[derive(Debug)]
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Copy> Producer<T> {
+    /// Get the number of items the ring buffer holds.
+    pub fn capacity(&self) -> usize {
+        self.shared.buf.len()
+    }
+
+    /// Get the number of items which can currently be pushed.
+    pub fn free(&self) -> usize {
+        let head = self.shared.head.load(Ordering::Relaxed);
+        let tail = self.shared.tail.load(Ordering::Acquire);
+        self.capacity() - head.wrapping_sub(tail)
+    }
+
+    /// Push as many items from `data` as fit, returning the number pushed.
+    pub fn push_slice(&mut self, data: &[T]) -> usize {
+        let head = self.shared.head.load(Ordering::Relaxed);
+        let len = data.len().min(self.free());
+        let mask = self.shared.mask();
+        for (i, &item) in data[..len].iter().enumerate() {
+            let slot = &self.shared.buf[head.wrapping_add(i) & mask];
+            unsafe { *slot.get() = item };
+        }
+        self.shared
+            .head
+            .store(head.wrapping_add(len), Ordering::Release);
+        len
+    }
+
+    /// Determine whether the consumer has been dropped.
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.shared) == 1
+    }
+}
+
+/// The reading end of a ring buffer.
+#[derive(Debug)]
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Copy> Consumer<T> {
+    /// Get the number of items the ring buffer holds.
+    pub fn capacity(&self) -> usize {
+        self.shared.buf.len()
+    }
+
+    /// Get the number of items waiting to be popped.
+    pub fn available(&self) -> usize {
+        let head = self.shared.head.load(Ordering::Acquire);
+        let tail = self.shared.tail.load(Ordering::Relaxed);
+        head.wrapping_sub(tail)
+    }
+
+    /// Pop as many items into `out` as are available, returning the number
+    /// popped.
+    pub fn pop_slice(&mut self, out: &mut [T]) -> usize {
+        let tail = self.shared.tail.load(Ordering::Relaxed);
+        let len = out.len().min(self.available());
+        let mask = self.shared.mask();
+        for (i, item) in out[..len].iter_mut().enumerate() {
+            let slot = &self.shared.buf[tail.wrapping_add(i) & mask];
+            *item = unsafe { *slot.get() };
+        }
+        self.shared
+            .tail
+            .store(tail.wrapping_add(len), Ordering::Release);
+        len
+    }
+
+    /// Discard all waiting items, returning the number discarded.
+    pub fn clear(&mut self) -> usize {
+        let len = self.available();
+        let tail = self.shared.tail.load(Ordering::Relaxed);
+        self.shared
+            .tail
+            .store(tail.wrapping_add(len), Ordering::Release);
+        len
+    }
+
+    /// Determine whether the producer has been dropped.
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.shared) == 1
+    }
+}
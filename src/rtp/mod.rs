@@ -0,0 +1,84 @@
+//! RTP payload helpers for Opus (RFC 7587).
+//!
+//! Only the fixed RTP header is handled here; session management, RTCP and
+//! encryption are left to the transport.
+
+use super::{ffi, Error, Result};
+
+pub mod red;
+
+/// The RTP clock rate for Opus, regardless of the actual sample rate.
+pub use super::time::CLOCK_RATE;
+
+/// The fixed part of an RTP packet header.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct Header {
+    /// The marker bit, set on the first packet of a talk spurt.
+    pub marker: bool,
+    /// The 7-bit payload type.
+    pub payload_type: u8,
+    /// The sequence number.
+    pub sequence: u16,
+    /// The media timestamp in 48kHz units.
+    pub timestamp: u32,
+    /// The synchronization source identifier.
+    pub ssrc: u32,
+}
+
+impl Header {
+    /// Length of a header without CSRCs or extensions.
+    pub const LEN: usize = 12;
+
+    /// Parse an RTP packet, returning the header and the payload.
+    ///
+    /// CSRC lists and header extensions are skipped, and padding is removed
+    /// from the payload.
+    pub fn parse(packet: &[u8]) -> Result<(Header, &[u8])> {
+        if packet.len() < Header::LEN || packet[0] >> 6 != 2 {
+            return Err(invalid("rtp::Header::parse"));
+        }
+        let padding = packet[0] & 0x20 != 0;
+        let extension = packet[0] & 0x10 != 0;
+        let csrc_count = (packet[0] & 0x0f) as usize;
+
+        let header = Header {
+            marker: packet[1] & 0x80 != 0,
+            payload_type: packet[1] & 0x7f,
+            sequence: u16::from_be_bytes([packet[2], packet[3]]),
+            timestamp: u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]),
+            ssrc: u32::from_be_bytes([packet[8], packet[9], packet[10], packet[11]]),
+        };
+
+        let mut start = Header::LEN + 4 * csrc_count;
+        if extension {
+            if packet.len() < start + 4 {
+                return Err(invalid("rtp::Header::parse"));
+            }
+            let words = u16::from_be_bytes([packet[start + 2], packet[start + 3]]) as usize;
+            start += 4 + 4 * words;
+        }
+        let mut end = packet.len();
+        if padding {
+            end = end.saturating_sub(packet[end - 1] as usize);
+        }
+        if start > end {
+            return Err(invalid("rtp::Header::parse"));
+        }
+        Ok((header, &packet[start..end]))
+    }
+
+    /// Append this header followed by `payload` to `out`.
+    pub fn write(&self, payload: &[u8], out: &mut Vec<u8>) {
+        out.reserve(Header::LEN + payload.len());
+        out.push(0x80);
+        out.push((self.marker as u8) << 7 | (self.payload_type & 0x7f));
+        out.extend_from_slice(&self.sequence.to_be_bytes());
+        out.extend_from_slice(&self.timestamp.to_be_bytes());
+        out.extend_from_slice(&self.ssrc.to_be_bytes());
+        out.extend_from_slice(payload);
+    }
+}
+
+fn invalid(what: &'static str) -> Error {
+    Error::from_code(what, ffi::OPUS_INVALID_PACKET)
+}
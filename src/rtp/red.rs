@@ -0,0 +1,178 @@
+//! Redundant audio data (RFC 2198) encapsulation.
+//!
+//! Each RED payload carries the current Opus packet together with copies of
+//! up to `distance` earlier packets, so a receiver can recover from isolated
+//! losses without waiting for retransmission.
+
+use std::collections::VecDeque;
+
+use super::super::jitter::JitterBuffer;
+use super::{invalid, Result};
+
+/// Largest timestamp offset a redundant block header can express.
+const MAX_OFFSET: u32 = (1 << 14) - 1;
+
+/// Largest block length a redundant block header can express.
+const MAX_BLOCK_LEN: usize = (1 << 10) - 1;
+
+/// A single block within a RED payload.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Block<'a> {
+    /// The payload type of the block.
+    pub payload_type: u8,
+    /// The RTP timestamp of the block.
+    pub timestamp: u32,
+    /// The block data, normally an Opus packet.
+    pub data: &'a [u8],
+}
+
+/// Builds RED payloads from a sequence of Opus packets.
+#[derive(Debug, Clone)]
+pub struct RedEncoder {
+    payload_type: u8,
+    distance: usize,
+    history: VecDeque<(u32, Vec<u8>)>,
+}
+
+impl RedEncoder {
+    /// Create an encoder for blocks of the given payload type which repeats
+    /// each packet in the `distance` following payloads.
+    pub fn new(payload_type: u8, distance: usize) -> RedEncoder {
+        RedEncoder {
+            payload_type: payload_type & 0x7f,
+            distance,
+            history: VecDeque::with_capacity(distance),
+        }
+    }
+
+    /// Get the number of earlier packets included in each payload.
+    pub fn distance(&self) -> usize {
+        self.distance
+    }
+
+    /// Set the number of earlier packets included in each payload.
+    pub fn set_distance(&mut self, distance: usize) {
+        self.distance = distance;
+        while self.history.len() > distance {
+            self.history.pop_front();
+        }
+    }
+
+    /// Forget all earlier packets.
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    /// Append a RED payload carrying `packet` with the given RTP timestamp,
+    /// plus any earlier packets which can still be represented, to `out`.
+    pub fn encode(&mut self, timestamp: u32, packet: &[u8], out: &mut Vec<u8>) {
+        // Only the newest contiguous run of packets can be represented, so
+        // that receivers may derive their sequence numbers from position.
+        let skip = self
+            .history
+            .iter()
+            .rposition(|(ts, data)| {
+                timestamp.wrapping_sub(*ts) > MAX_OFFSET || data.len() > MAX_BLOCK_LEN
+            })
+            .map_or(0, |i| i + 1);
+
+        for (ts, data) in self.history.iter().skip(skip) {
+            let offset = timestamp.wrapping_sub(*ts);
+            let header =
+                0x8000_0000 | (self.payload_type as u32) << 24 | offset << 10 | data.len() as u32;
+            out.extend_from_slice(&header.to_be_bytes());
+        }
+        out.push(self.payload_type);
+        for (_, data) in self.history.iter().skip(skip) {
+            out.extend_from_slice(data);
+        }
+        out.extend_from_slice(packet);
+
+        if self.distance > 0 {
+            if self.history.len() == self.distance {
+                self.history.pop_front();
+            }
+            self.history.push_back((timestamp, packet.to_vec()));
+        }
+    }
+}
+
+/// Split a RED payload received with the given RTP timestamp into its
+/// blocks, oldest first. The primary block is always last.
+pub fn parse(timestamp: u32, payload: &[u8]) -> Result<Vec<Block<'_>>> {
+    let mut headers = Vec::new();
+    let mut pos = 0;
+    loop {
+        let first = match payload.get(pos) {
+            Some(&first) => first,
+            None => return Err(invalid("rtp::red::parse")),
+        };
+        if first & 0x80 == 0 {
+            headers.push((first & 0x7f, 0, None));
+            pos += 1;
+            break;
+        }
+        if payload.len() < pos + 4 {
+            return Err(invalid("rtp::red::parse"));
+        }
+        let word = u32::from_be_bytes([
+            payload[pos],
+            payload[pos + 1],
+            payload[pos + 2],
+            payload[pos + 3],
+        ]);
+        let block_pt = ((word >> 24) & 0x7f) as u8;
+        let offset = (word >> 10) & MAX_OFFSET;
+        let len = (word & MAX_BLOCK_LEN as u32) as usize;
+        headers.push((block_pt, offset, Some(len)));
+        pos += 4;
+    }
+
+    let mut blocks = Vec::with_capacity(headers.len());
+    for (payload_type, offset, len) in headers {
+        let len = len.unwrap_or(payload.len() - pos);
+        if payload.len() < pos + len {
+            return Err(invalid("rtp::red::parse"));
+        }
+        blocks.push(Block {
+            payload_type,
+            timestamp: timestamp.wrapping_sub(offset),
+            data: &payload[pos..pos + len],
+        });
+        pos += len;
+    }
+    Ok(blocks)
+}
+
+/// Feed all blocks of a RED payload of the given payload type into a jitter
+/// buffer, returning the number of packets newly accepted.
+///
+/// Redundant copies of packets which were already received or played are
+/// silently discarded. One Opus packet per RTP packet is assumed when
+/// deriving the sequence numbers of redundant blocks.
+pub fn push_to(
+    buffer: &mut JitterBuffer,
+    payload_type: u8,
+    seq: u16,
+    timestamp: u32,
+    payload: &[u8],
+) -> Result<usize> {
+    let blocks = parse(timestamp, payload)?;
+    let last = blocks.len() - 1;
+    let mut accepted = 0;
+    for (i, block) in blocks.iter().enumerate() {
+        if block.payload_type != payload_type & 0x7f || block.data.is_empty() {
+            continue;
+        }
+        let block_seq = seq.wrapping_sub((last - i) as u16);
+        let pushed = if i == last {
+            buffer.push(block_seq, block.timestamp, block.data.to_vec())
+        } else {
+            buffer.push_redundant(block_seq, block.timestamp, block.data.to_vec())
+        };
+        if pushed {
+            accepted += 1;
+        }
+    }
+    Ok(accepted)
+}
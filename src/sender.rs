@@ -0,0 +1,186 @@
+//! Loss-resilient sending on top of an `Encoder`.
+//!
+//! Receivers periodically report the fraction of packets they lost and the
+//! measured round trip time. The `Sender` smooths these reports and reacts by
+//! toggling in-band FEC, updating the encoder's expected packet loss, scaling
+//! the bitrate, and, on very lossy links, sending critical frames twice.
+
+use std::time::Duration;
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use super::{Bitrate, Encoder, Result};
+
+/// Tunables for a `Sender`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SenderConfig {
+    /// Bitrate used on a loss-free link, in bits/second.
+    pub max_bitrate: i32,
+    /// Bitrate approached as loss reaches `loss_ceiling`, in bits/second.
+    pub min_bitrate: i32,
+    /// Smoothed loss fraction at which the bitrate reaches `min_bitrate`.
+    pub loss_ceiling: f32,
+    /// Smoothed loss fraction above which in-band FEC is enabled. FEC is
+    /// disabled again once loss falls below half of this value.
+    pub fec_threshold: f32,
+    /// Smoothed loss fraction above which critical frames are duplicated, or
+    /// `None` to never duplicate.
+    pub duplicate_threshold: Option<f32>,
+    /// Round trip time above which loss is treated as more costly.
+    pub high_rtt: Duration,
+    /// Weight given to each new report when smoothing, between 0 and 1.
+    pub smoothing: f32,
+}
+
+impl Default for SenderConfig {
+    fn default() -> SenderConfig {
+        SenderConfig {
+            max_bitrate: 32000,
+            min_bitrate: 12000,
+            loss_ceiling: 0.3,
+            fec_threshold: 0.02,
+            duplicate_threshold: Some(0.15),
+            high_rtt: Duration::from_millis(300),
+            smoothing: 0.25,
+        }
+    }
+}
+
+/// An encoder wrapper which adapts its loss resilience to receiver reports.
+#[derive(Debug)]
+pub struct Sender {
+    encoder: Encoder,
+    config: SenderConfig,
+    loss: f32,
+    effective: f32,
+    rtt: Option<Duration>,
+    fec: bool,
+    prev_toc: Option<u8>,
+    prev_dtx: bool,
+}
+
+impl Sender {
+    /// Wrap an encoder, applying the loss-free settings from `config`.
+    pub fn new(mut encoder: Encoder, config: SenderConfig) -> Result<Sender> {
+        encoder.set_inband_fec(false)?;
+        encoder.set_packet_loss_perc(0)?;
+        encoder.set_bitrate(Bitrate::Bits(config.max_bitrate))?;
+        Ok(Sender {
+            encoder,
+            config,
+            loss: 0.0,
+            effective: 0.0,
+            rtt: None,
+            fec: false,
+            prev_toc: None,
+            prev_dtx: false,
+        })
+    }
+
+    /// Get the wrapped encoder.
+    pub fn encoder(&mut self) -> &mut Encoder {
+        &mut self.encoder
+    }
+
+    /// Unwrap the encoder, keeping its current settings.
+    pub fn into_encoder(self) -> Encoder {
+        self.encoder
+    }
+
+    /// Get the smoothed loss fraction.
+    pub fn loss(&self) -> f32 {
+        self.loss
+    }
+
+    /// Get the most recently reported round trip time.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    /// Determine whether in-band FEC is currently enabled.
+    pub fn fec_enabled(&self) -> bool {
+        self.fec
+    }
+
+    /// Handle a receiver report of the fraction of packets lost (between 0
+    /// and 1) and the current round trip time.
+    pub fn on_receiver_report(&mut self, loss: f32, rtt: Duration) -> Result<()> {
+        let loss = if loss.is_finite() {
+            loss.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let weight = self.config.smoothing.clamp(0.0, 1.0);
+        self.loss += (loss - self.loss) * weight;
+        self.rtt = Some(rtt);
+
+        let mut effective = self.loss;
+        if rtt > self.config.high_rtt {
+            effective = (effective * 1.25).min(1.0);
+        }
+        self.effective = effective;
+
+        let fec = if self.fec {
+            effective >= self.config.fec_threshold / 2.0
+        } else {
+            effective >= self.config.fec_threshold
+        };
+        if fec != self.fec {
+            self.encoder.set_inband_fec(fec)?;
+            self.fec = fec;
+        }
+
+        // both values are non-negative, so adding a half rounds without `std`
+        self.encoder
+            .set_packet_loss_perc((effective * 100.0 + 0.5) as i32)?;
+
+        let ceiling = self.config.loss_ceiling.max(f32::EPSILON);
+        let scale = (effective / ceiling).min(1.0);
+        let range = (self.config.max_bitrate - self.config.min_bitrate) as f32;
+        let bitrate = self.config.max_bitrate - (range * scale + 0.5) as i32;
+        self.encoder.set_bitrate(Bitrate::Bits(bitrate))
+    }
+
+    /// Encode a frame, returning the packets to send in order.
+    ///
+    /// Usually a single packet is returned. While loss is above the
+    /// duplication threshold, frames which start talk spurts or switch
+    /// modes are returned twice.
+    pub fn encode(&mut self, input: &[i16], max_size: usize) -> Result<Vec<Vec<u8>>> {
+        let output = self.encoder.encode_vec(input, max_size)?;
+        Ok(self.schedule(output))
+    }
+
+    /// Encode a frame from floating point input, returning the packets to
+    /// send in order.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_float(&mut self, input: &[f32], max_size: usize) -> Result<Vec<Vec<u8>>> {
+        let output = self.encoder.encode_vec_float(input, max_size)?;
+        Ok(self.schedule(output))
+    }
+
+    fn schedule(&mut self, output: Vec<u8>) -> Vec<Vec<u8>> {
+        // Packets of at most two bytes carry no audio and are emitted by DTX.
+        let dtx = output.len() <= 2;
+        let toc = output.first().cloned();
+        let critical = !dtx && (self.prev_dtx || toc.map(config) != self.prev_toc.map(config));
+        self.prev_dtx = dtx;
+        self.prev_toc = toc;
+
+        let duplicate = match self.config.duplicate_threshold {
+            Some(threshold) => critical && self.effective >= threshold,
+            None => false,
+        };
+        if duplicate {
+            vec![output.clone(), output]
+        } else {
+            vec![output]
+        }
+    }
+}
+
+/// Get the configuration and stereo flag from a TOC byte, ignoring the frame
+/// count code.
+fn config(toc: u8) -> u8 {
+    toc & 0xfc
+}
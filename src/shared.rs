@@ -0,0 +1,67 @@
+//! Sharing an encoder between threads.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use super::storage::{Heap, Storage};
+use super::{Encoder, Result};
+
+/// An `Encoder` behind a mutex, so that it is `Sync` and can be shared
+/// between threads, such as through an `Arc`.
+///
+/// Each call locks the encoder for its duration. A panic while the encoder
+/// is locked can't leave the libopus state inconsistent, so a poisoned lock
+/// is ignored.
+#[derive(Debug)]
+pub struct SyncEncoder<S: Storage = Heap> {
+    inner: Mutex<Encoder<S>>,
+}
+
+impl<S: Storage> SyncEncoder<S> {
+    /// Wrap an encoder for shared use.
+    pub fn new(encoder: Encoder<S>) -> SyncEncoder<S> {
+        SyncEncoder {
+            inner: Mutex::new(encoder),
+        }
+    }
+
+    /// Lock the encoder, for instance to change its settings, blocking until
+    /// no other thread is using it.
+    pub fn lock(&self) -> MutexGuard<'_, Encoder<S>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Encode an Opus frame.
+    pub fn encode(&self, input: &[i16], output: &mut [u8]) -> Result<usize> {
+        self.lock().encode(input, output)
+    }
+
+    /// Encode an Opus frame from floating point input.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_float(&self, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        self.lock().encode_float(input, output)
+    }
+
+    /// Encode an Opus frame to a new buffer.
+    pub fn encode_vec(&self, input: &[i16], max_size: usize) -> Result<Vec<u8>> {
+        self.lock().encode_vec(input, max_size)
+    }
+
+    /// Encode an Opus frame from floating point input to a new buffer.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_vec_float(&self, input: &[f32], max_size: usize) -> Result<Vec<u8>> {
+        self.lock().encode_vec_float(input, max_size)
+    }
+
+    /// Unwrap the encoder.
+    pub fn into_inner(self) -> Encoder<S> {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<S: Storage> From<Encoder<S>> for SyncEncoder<S> {
+    fn from(encoder: Encoder<S>) -> SyncEncoder<S> {
+        SyncEncoder::new(encoder)
+    }
+}
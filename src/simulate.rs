@@ -0,0 +1,342 @@
+//! Deterministic network simulation for testing loss recovery.
+//!
+//! A `Network` carries an encoded packet stream over a simulated link which
+//! loses packets at random or in bursts, delays them by a random jitter, and
+//! reorders or duplicates some of them. Its randomness comes from a seeded
+//! generator, so a given seed always damages a stream the same way and tests
+//! of FEC and PLC behave identically on every run. `play` feeds the packets
+//! which arrive through a `JitterBuffer` in simulated real time, while
+//! `decode` drives a `Decoder` directly.
+
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use super::jitter::{JitterBuffer, Playout};
+use super::time::{Timestamp48k, CLOCK_RATE};
+use super::{Decoder, Result};
+
+/// Duration of the frames assumed for invalid packets and played while
+/// buffering.
+const DEFAULT_DURATION: u32 = CLOCK_RATE / 50;
+
+/// The largest number of samples per channel a packet can decode to.
+const MAX_FRAME: usize = 5760;
+
+/// How a `Network` loses packets.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum LossModel {
+    /// No packet is lost.
+    #[default]
+    None,
+    /// Every packet is lost independently with this probability.
+    Random(f32),
+    /// The Gilbert-Elliott model: a Markov chain moving between a good and a
+    /// bad state after every packet, with a loss probability for each.
+    GilbertElliott {
+        /// Probability of moving from the good state to the bad one.
+        p: f32,
+        /// Probability of moving from the bad state back to the good one.
+        r: f32,
+        /// Loss probability in the good state.
+        good_loss: f32,
+        /// Loss probability in the bad state.
+        bad_loss: f32,
+    },
+    /// Packets are lost as marked by `true` in a pattern, which repeats.
+    Pattern(Vec<bool>),
+}
+
+impl LossModel {
+    /// Bursty loss averaging `rate`, in bursts of `mean_burst` consecutive
+    /// packets on average: a Gilbert-Elliott model whose bad state loses
+    /// every packet and whose good state loses none.
+    pub fn bursty(rate: f32, mean_burst: f32) -> LossModel {
+        let r = 1.0 / mean_burst.max(1.0);
+        LossModel::GilbertElliott {
+            p: (rate * r / (1.0 - rate)).min(1.0),
+            r,
+            good_loss: 0.0,
+            bad_loss: 1.0,
+        }
+    }
+}
+
+/// The conditions of a simulated link.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkConfig {
+    /// How packets are lost.
+    pub loss: LossModel,
+    /// The largest delay added to a packet, in 48kHz samples. Each packet is
+    /// delayed by a uniformly random amount up to this.
+    pub jitter: u32,
+    /// Probability of a packet being held back by `reorder_delay`, so that
+    /// it arrives after packets sent later.
+    pub reorder: f32,
+    /// The extra delay of reordered packets, in 48kHz samples.
+    pub reorder_delay: u32,
+    /// Probability of a packet arriving twice.
+    pub duplicate: f32,
+}
+
+/// A packet as it arrives from a `Network`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Arrival {
+    /// The RTP-style sequence number of the packet.
+    pub seq: u16,
+    /// The 48kHz timestamp of the packet's first sample.
+    pub timestamp: u32,
+    /// The time the packet arrives, in 48kHz samples since the first packet
+    /// was sent.
+    pub arrival: u64,
+    /// The Opus packet.
+    pub packet: Vec<u8>,
+}
+
+/// Counters describing what a `Network` did to the packets sent over it.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct NetworkStats {
+    /// Packets sent.
+    pub sent: u64,
+    /// Packets lost.
+    pub lost: u64,
+    /// Packets held back to arrive after later ones.
+    pub reordered: u64,
+    /// Packets which arrived twice.
+    pub duplicated: u64,
+}
+
+/// A simulated lossy link.
+#[derive(Debug, Clone)]
+pub struct Network {
+    config: NetworkConfig,
+    rng: Rng,
+    bad: bool,
+    position: usize,
+    stats: NetworkStats,
+}
+
+impl Network {
+    /// Create a link with the given conditions, whose randomness is
+    /// determined by `seed`.
+    pub fn new(config: NetworkConfig, seed: u64) -> Network {
+        Network {
+            config,
+            rng: Rng::new(seed),
+            bad: false,
+            position: 0,
+            stats: NetworkStats::default(),
+        }
+    }
+
+    /// Get the conditions of the link.
+    pub fn config(&self) -> &NetworkConfig {
+        &self.config
+    }
+
+    /// Get the counters accumulated so far.
+    pub fn stats(&self) -> NetworkStats {
+        self.stats
+    }
+
+    /// Decide whether the next packet is lost, advancing the loss model.
+    pub fn lose(&mut self) -> bool {
+        let lost = match self.config.loss {
+            LossModel::None => false,
+            LossModel::Random(rate) => self.rng.chance(rate),
+            LossModel::GilbertElliott {
+                p,
+                r,
+                good_loss,
+                bad_loss,
+            } => {
+                let lost = self.rng.chance(if self.bad { bad_loss } else { good_loss });
+                self.bad = if self.bad {
+                    !self.rng.chance(r)
+                } else {
+                    self.rng.chance(p)
+                };
+                lost
+            }
+            LossModel::Pattern(ref pattern) if !pattern.is_empty() => {
+                pattern[self.position % pattern.len()]
+            }
+            LossModel::Pattern(_) => false,
+        };
+        self.position += 1;
+        self.stats.sent += 1;
+        if lost {
+            self.stats.lost += 1;
+        }
+        lost
+    }
+
+    /// Send a stream of packets, one after the other in real time.
+    ///
+    /// The packets are numbered from 0 and timestamped by their durations,
+    /// and each is sent at its timestamp. Returns the packets which arrive,
+    /// in the order they arrive.
+    pub fn transmit<'a, I>(&mut self, packets: I) -> Vec<Arrival>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let mut arrivals = Vec::new();
+        let mut sent = 0u64;
+        for (seq, packet) in packets.into_iter().enumerate() {
+            let duration = Timestamp48k::of_packet(packet)
+                .map(|duration| duration.samples() as u32)
+                .unwrap_or(DEFAULT_DURATION);
+            if !self.lose() {
+                let copies = if self.rng.chance(self.config.duplicate) {
+                    self.stats.duplicated += 1;
+                    2
+                } else {
+                    1
+                };
+                for _ in 0..copies {
+                    let mut delay = self.rng.below(self.config.jitter as u64 + 1);
+                    if self.rng.chance(self.config.reorder) {
+                        self.stats.reordered += 1;
+                        delay += self.config.reorder_delay as u64;
+                    }
+                    arrivals.push(Arrival {
+                        seq: seq as u16,
+                        timestamp: sent as u32,
+                        arrival: sent + delay,
+                        packet: packet.to_vec(),
+                    });
+                }
+            }
+            sent += duration as u64;
+        }
+        // a stable sort keeps packets arriving together in sending order
+        arrivals.sort_by_key(|arrival| arrival.arrival);
+        arrivals
+    }
+}
+
+/// The audio produced by `play` or `decode`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Playback {
+    /// The interleaved output samples.
+    pub output: Vec<i16>,
+    /// What was done for each frame of output, in order.
+    pub playouts: Vec<Playout>,
+}
+
+/// Play packets arriving from a `Network` through a jitter buffer in
+/// simulated real time.
+///
+/// The clock starts at the first arrival. On every tick, the packets which
+/// have arrived by then are pushed into `jitter` and one frame is popped,
+/// after which the clock advances by the duration of that frame, or 20 ms
+/// while buffering. Playback ends once every packet has been pushed and the
+/// buffer has run dry.
+pub fn play(
+    arrivals: &[Arrival],
+    jitter: &mut JitterBuffer,
+    decoder: &mut Decoder,
+) -> Result<Playback> {
+    let rate = decoder.get_sample_rate()?;
+    let channels = decoder.channels as usize;
+    let mut frame = vec![0i16; MAX_FRAME * channels];
+    let mut playback = Playback::default();
+    let mut clock = arrivals.first().map_or(0, |arrival| arrival.arrival);
+    let mut next = 0;
+    while next < arrivals.len() || !jitter.is_empty() {
+        while next < arrivals.len() && arrivals[next].arrival <= clock {
+            let arrival = &arrivals[next];
+            jitter.push(arrival.seq, arrival.timestamp, arrival.packet.clone());
+            next += 1;
+        }
+        let playout = jitter.pop(decoder, &mut frame)?;
+        if playout == Playout::Buffering && next == arrivals.len() {
+            // the rest never reaches the target delay
+            break;
+        }
+        let samples = playout.samples();
+        playback
+            .output
+            .extend_from_slice(&frame[..samples * channels]);
+        playback.playouts.push(playout);
+        clock += match playout {
+            Playout::Buffering => DEFAULT_DURATION as u64,
+            _ => Timestamp48k::from_samples(samples as u64, rate).samples(),
+        };
+    }
+    Ok(playback)
+}
+
+/// Decode packets directly, losing those `network` loses.
+///
+/// A lost packet is recovered from the in-band FEC data of the next packet
+/// when `fec` is set and that packet was received, and concealed otherwise,
+/// for as long as the last packet decoded.
+pub fn decode<'a, I>(
+    packets: I,
+    network: &mut Network,
+    decoder: &mut Decoder,
+    fec: bool,
+) -> Result<Playback>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let received: Vec<Option<&[u8]>> = packets
+        .into_iter()
+        .map(|packet| if network.lose() { None } else { Some(packet) })
+        .collect();
+    let rate = decoder.get_sample_rate()?;
+    let channels = decoder.channels as usize;
+    let mut frame = vec![0i16; MAX_FRAME * channels];
+    let mut last = (rate / 50) as usize;
+    let mut playback = Playback::default();
+    for (i, packet) in received.iter().enumerate() {
+        let playout = match (packet, received.get(i + 1)) {
+            (&Some(packet), _) => Playout::Decoded(decoder.decode(packet, &mut frame, false)?),
+            (&None, Some(&Some(next))) if fec => {
+                Playout::Recovered(decoder.decode(next, &mut frame[..last * channels], true)?)
+            }
+            (&None, _) => {
+                Playout::Concealed(decoder.decode(&[], &mut frame[..last * channels], false)?)
+            }
+        };
+        let samples = playout.samples();
+        if let Playout::Decoded(_) = playout {
+            last = samples;
+        }
+        playback
+            .output
+            .extend_from_slice(&frame[..samples * channels]);
+        playback.playouts.push(playout);
+    }
+    Ok(playback)
+}
+
+/// A small deterministic generator (splitmix64).
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // A number below `bound`, which must not be 0.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    fn chance(&mut self, probability: f32) -> bool {
+        if probability <= 0.0 {
+            return false;
+        }
+        ((self.next() >> 40) as f32) < probability * (1u64 << 24) as f32
+    }
+}
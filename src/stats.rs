@@ -0,0 +1,197 @@
+//! Counters of what a coder has done, for monitoring call quality and
+//! bitrate.
+
+use std::fmt;
+
+use super::{packet, Bandwidth};
+
+/// Counts of the frames a `Decoder` has decoded, by mode and bandwidth, and
+/// of the frames it has concealed or recovered.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct DecoderStats {
+    /// Frames decoded in the SILK-only mode, used for speech at low
+    /// bitrates.
+    pub silk_frames: u64,
+    /// Frames decoded in the hybrid SILK and CELT mode.
+    pub hybrid_frames: u64,
+    /// Frames decoded in the CELT-only mode, used for music and low delay.
+    pub celt_frames: u64,
+    /// Frames decoded at each bandwidth, from narrowband to fullband.
+    pub bandwidth_frames: [u64; 5],
+    /// Calls which concealed a lost packet, given no input.
+    pub plc_calls: u64,
+    /// Calls which recovered a lost packet from the in-band FEC data of the
+    /// next one. libopus conceals the loss instead when the packet carries
+    /// no FEC data.
+    pub fec_recoveries: u64,
+}
+
+impl DecoderStats {
+    /// Get the number of frames decoded in any mode.
+    pub fn frames(&self) -> u64 {
+        self.silk_frames + self.hybrid_frames + self.celt_frames
+    }
+
+    /// Get the number of frames decoded at a bandwidth, or zero for
+    /// `Bandwidth::Auto`.
+    pub fn frames_at(&self, bandwidth: Bandwidth) -> u64 {
+        index(bandwidth).map_or(0, |i| self.bandwidth_frames[i])
+    }
+
+    /// Count a successful decode of `input`.
+    pub(crate) fn record(&mut self, input: &[u8], fec: bool) {
+        event!(trace, bytes = input.len(), fec, "decoded packet");
+        if input.is_empty() {
+            self.plc_calls += 1;
+            return;
+        }
+        if fec {
+            self.fec_recoveries += 1;
+            return;
+        }
+        let frames = match input[0] & 3 {
+            0 => 1,
+            1 | 2 => 2,
+            _ => input.get(1).map_or(0, |&count| count & 0x3f) as u64,
+        };
+        match input[0] >> 3 {
+            0..=11 => self.silk_frames += frames,
+            12..=15 => self.hybrid_frames += frames,
+            _ => self.celt_frames += frames,
+        }
+        if let Some(i) = packet::get_bandwidth(input).ok().and_then(index) {
+            self.bandwidth_frames[i] += frames;
+        }
+    }
+}
+
+// Get the index of a bandwidth in `bandwidth_frames`.
+fn index(bandwidth: Bandwidth) -> Option<usize> {
+    match bandwidth {
+        Bandwidth::Auto => None,
+        b => Some((b as i32 - Bandwidth::Narrowband as i32) as usize),
+    }
+}
+
+/// The number of recent packets which the windowed statistics of
+/// `EncoderStats` cover, 5.12 seconds of 20 ms packets.
+pub const STATS_WINDOW: usize = 256;
+
+/// Counts of the packets an `Encoder` has produced, and their sizes and
+/// durations over the last `STATS_WINDOW` packets.
+///
+/// The windowed bitrate shows whether the encoder keeps to a negotiated
+/// `maxaveragebitrate` without any bookkeeping of one's own.
+#[derive(Clone, Copy)]
+pub struct EncoderStats {
+    /// Packets produced.
+    pub packets: u64,
+    /// Total size of the packets in bytes.
+    pub bytes: u64,
+    /// Total duration of the packets in 48kHz samples.
+    pub duration: u64,
+    /// Packets of at most two bytes, sent while discontinuous transmission
+    /// is in effect.
+    pub dtx_packets: u64,
+    sizes: [u16; STATS_WINDOW],
+    durations: [u16; STATS_WINDOW],
+    next: usize,
+}
+
+impl Default for EncoderStats {
+    fn default() -> EncoderStats {
+        EncoderStats {
+            packets: 0,
+            bytes: 0,
+            duration: 0,
+            dtx_packets: 0,
+            sizes: [0; STATS_WINDOW],
+            durations: [0; STATS_WINDOW],
+            next: 0,
+        }
+    }
+}
+
+impl fmt::Debug for EncoderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncoderStats")
+            .field("packets", &self.packets)
+            .field("bytes", &self.bytes)
+            .field("duration", &self.duration)
+            .field("dtx_packets", &self.dtx_packets)
+            .finish()
+    }
+}
+
+impl EncoderStats {
+    /// Get the mean packet size in bytes, or zero before any packet.
+    pub fn mean_packet_size(&self) -> f64 {
+        match self.packets {
+            0 => 0.0,
+            n => self.bytes as f64 / n as f64,
+        }
+    }
+
+    /// Get the mean bitrate in bits per second of all packets.
+    pub fn bitrate(&self) -> u32 {
+        bitrate(self.bytes, self.duration)
+    }
+
+    /// Get the share of packets sent in discontinuous transmission, from 0
+    /// to 1.
+    pub fn dtx_ratio(&self) -> f64 {
+        match self.packets {
+            0 => 0.0,
+            n => self.dtx_packets as f64 / n as f64,
+        }
+    }
+
+    /// Get the bitrate in bits per second of the packets in the window.
+    pub fn window_bitrate(&self) -> u32 {
+        let window = self.window();
+        let bytes = self.sizes[..window].iter().map(|&s| s as u64).sum();
+        let duration = self.durations[..window].iter().map(|&d| d as u64).sum();
+        bitrate(bytes, duration)
+    }
+
+    /// Get the packet size in bytes below which `percentile` percent of the
+    /// sizes in the window fall, or zero before any packet. The 50th
+    /// percentile is the median and the 100th the largest.
+    pub fn percentile_packet_size(&self, percentile: f64) -> usize {
+        let window = self.window();
+        if window == 0 {
+            return 0;
+        }
+        let mut sizes = self.sizes;
+        let sizes = &mut sizes[..window];
+        sizes.sort_unstable();
+        let rank = percentile.clamp(0.0, 100.0) / 100.0 * (window - 1) as f64;
+        sizes[(rank + 0.5) as usize] as usize
+    }
+
+    /// Count a packet of `duration` 48kHz samples.
+    pub(crate) fn record(&mut self, packet: &[u8], duration: usize) {
+        self.packets += 1;
+        self.bytes += packet.len() as u64;
+        self.duration += duration as u64;
+        if packet.len() <= 2 {
+            self.dtx_packets += 1;
+        }
+        self.sizes[self.next] = packet.len() as u16;
+        self.durations[self.next] = duration as u16;
+        self.next = (self.next + 1) % STATS_WINDOW;
+    }
+
+    // Get the number of packets in the window.
+    fn window(&self) -> usize {
+        (self.packets as usize).min(STATS_WINDOW)
+    }
+}
+
+// Get the bitrate of `bytes` over `duration` 48kHz samples.
+fn bitrate(bytes: u64, duration: u64) -> u32 {
+    match duration {
+        0 => 0,
+        d => (bytes * 8 * 48000 / d) as u32,
+    }
+}
@@ -0,0 +1,198 @@
+//! Placing coder states in caller-provided memory.
+//!
+//! By default libopus allocates each encoder and decoder state on the heap.
+//! The `new_in` constructors of `Encoder`, `Decoder`, `MultistreamEncoder`,
+//! and `MultistreamDecoder` instead initialize the state in memory supplied
+//! by the caller, such as a static buffer or an arena, so that creating a
+//! coder performs no allocation. The `state_size` functions report how much
+//! memory each configuration needs, and `memory_requirements` totals them for
+//! a whole application.
+
+#[cfg(not(feature = "std"))]
+use std::boxed::Box;
+use std::fmt;
+use std::mem::MaybeUninit;
+
+#[cfg(not(feature = "std"))]
+use std::vec::Vec;
+
+use super::{
+    ffi, Channels, Decoder, Encoder, Error, MultistreamDecoder, MultistreamEncoder, Result,
+    StreamLayout,
+};
+
+/// The alignment required of memory holding a coder state.
+pub const STATE_ALIGN: usize = 16;
+
+/// Memory in which a coder state is placed.
+///
+/// # Safety
+///
+/// `memory` must return the same region every time it is called, and the
+/// region must not move or be used elsewhere for as long as the implementor
+/// is alive, even if the implementor itself is moved. Borrowed slices and
+/// boxed buffers meet this requirement; arrays held by value do not.
+pub unsafe trait Storage {
+    /// Get the memory to place the state in, or `None` if libopus should
+    /// allocate it.
+    fn memory(&mut self) -> Option<&mut [MaybeUninit<u8>]>;
+}
+
+/// Coder states allocated by libopus, the default.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct Heap;
+
+unsafe impl Storage for Heap {
+    fn memory(&mut self) -> Option<&mut [MaybeUninit<u8>]> {
+        None
+    }
+}
+
+unsafe impl Storage for &mut [MaybeUninit<u8>] {
+    fn memory(&mut self) -> Option<&mut [MaybeUninit<u8>]> {
+        Some(self)
+    }
+}
+
+unsafe impl<const N: usize> Storage for Box<StateBuffer<N>> {
+    fn memory(&mut self) -> Option<&mut [MaybeUninit<u8>]> {
+        Some(&mut self.0)
+    }
+}
+
+unsafe impl<const N: usize> Storage for &mut StateBuffer<N> {
+    fn memory(&mut self) -> Option<&mut [MaybeUninit<u8>]> {
+        Some(&mut self.0)
+    }
+}
+
+/// A suitably aligned buffer of `N` bytes for a coder state.
+///
+/// The buffer is used through a mutable reference or a `Box`, so it can live
+/// on the stack, in a `static`, or on the heap.
+#[repr(C, align(16))]
+pub struct StateBuffer<const N: usize>([MaybeUninit<u8>; N]);
+
+impl<const N: usize> StateBuffer<N> {
+    /// Create an uninitialized buffer.
+    pub const fn new() -> StateBuffer<N> {
+        StateBuffer([MaybeUninit::uninit(); N])
+    }
+
+    /// Get the size of the buffer in bytes.
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Determine whether the buffer is empty.
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+}
+
+impl<const N: usize> Default for StateBuffer<N> {
+    fn default() -> StateBuffer<N> {
+        StateBuffer::new()
+    }
+}
+
+impl<const N: usize> fmt::Debug for StateBuffer<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StateBuffer<{}>", N)
+    }
+}
+
+/// The coders an application creates, for `memory_requirements`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct MemoryConfig {
+    /// The channel count of each encoder.
+    pub encoders: Vec<Channels>,
+    /// The channel count of each decoder.
+    pub decoders: Vec<Channels>,
+    /// The layout of each multistream encoder.
+    pub multistream_encoders: Vec<StreamLayout>,
+    /// The layout of each multistream decoder.
+    pub multistream_decoders: Vec<StreamLayout>,
+    /// The number of repacketizers.
+    pub repacketizers: usize,
+}
+
+/// The memory needed by the coder states of a `MemoryConfig`, in bytes.
+///
+/// Each field sums the states of one kind. `total` rounds every state up to
+/// `STATE_ALIGN`, so it is enough to carve all of them out of one buffer.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct MemoryReport {
+    /// Memory for the encoders.
+    pub encoders: usize,
+    /// Memory for the decoders.
+    pub decoders: usize,
+    /// Memory for the multistream encoders.
+    pub multistream_encoders: usize,
+    /// Memory for the multistream decoders.
+    pub multistream_decoders: usize,
+    /// Memory for the repacketizers.
+    pub repacketizers: usize,
+    /// Memory for all of the states placed one after another.
+    pub total: usize,
+}
+
+impl MemoryReport {
+    fn add(&mut self, field: fn(&mut MemoryReport) -> &mut usize, size: usize) {
+        *field(self) += size;
+        self.total += size.next_multiple_of(STATE_ALIGN);
+    }
+}
+
+/// Total the state sizes of the coders in `config`, to budget memory before
+/// creating them.
+///
+/// The sizes come from libopus and so reflect how it was built, for example
+/// with the `fixed-point` feature. Scratch buffers for PCM and packets are
+/// not included.
+pub fn memory_requirements(config: &MemoryConfig) -> Result<MemoryReport> {
+    let mut report = MemoryReport::default();
+    for &channels in &config.encoders {
+        report.add(|r| &mut r.encoders, Encoder::state_size(channels));
+    }
+    for &channels in &config.decoders {
+        report.add(|r| &mut r.decoders, Decoder::state_size(channels));
+    }
+    for layout in &config.multistream_encoders {
+        let size = MultistreamEncoder::state_size(layout)?;
+        report.add(|r| &mut r.multistream_encoders, size);
+    }
+    for layout in &config.multistream_decoders {
+        let size = MultistreamDecoder::state_size(layout)?;
+        report.add(|r| &mut r.multistream_decoders, size);
+    }
+    let size = unsafe { ffi::opus_repacketizer_get_size() } as usize;
+    for _ in 0..config.repacketizers {
+        report.add(|r| &mut r.repacketizers, size);
+    }
+    Ok(report)
+}
+
+/// Get a pointer to `size` bytes of `storage` for the state initialized by
+/// `what`, checking that the memory is large enough and aligned.
+pub(crate) fn place<S: Storage>(storage: &mut S, what: &'static str, size: i32) -> Result<*mut u8> {
+    if size <= 0 {
+        return Err(Error::bad_arg(what));
+    }
+    match storage.memory() {
+        Some(memory) => {
+            let ptr = memory.as_mut_ptr() as *mut u8;
+            if memory.len() < size as usize || ptr.align_offset(STATE_ALIGN) != 0 {
+                return Err(Error::bad_arg(what));
+            }
+            Ok(ptr)
+        }
+        None => Err(Error::bad_arg(what)),
+    }
+}
+
+/// Determine whether `storage` leaves the state to libopus, so that it must
+/// be destroyed rather than simply forgotten.
+pub(crate) fn is_heap<S: Storage>(storage: &mut S) -> bool {
+    storage.memory().is_none()
+}
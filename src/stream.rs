@@ -0,0 +1,233 @@
+//! Asynchronous encoding and decoding over `futures` streams and sinks.
+//!
+//! Encoding and decoding a single frame is quick enough to run inline on an
+//! async task, so these adapters simply code each item as it passes through.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+
+use super::chunk::MAX_PACKET;
+use super::pipeline::DecodedFrame;
+use super::time::Timestamp48k;
+use super::{Decoder, Encoder, Error};
+
+/// A `Sink` of PCM frames which encodes each frame and forwards the packet to
+/// an inner `Sink`.
+///
+/// Every item must be a complete frame of interleaved samples with a duration
+/// permitted by Opus.
+#[derive(Debug)]
+pub struct OpusEncoderSink<K> {
+    encoder: Encoder,
+    inner: K,
+    buffer: Vec<u8>,
+}
+
+impl<K> OpusEncoderSink<K> {
+    /// Wrap a sink of packets.
+    pub fn new(encoder: Encoder, inner: K) -> OpusEncoderSink<K> {
+        OpusEncoderSink {
+            encoder,
+            inner,
+            buffer: vec![0; MAX_PACKET],
+        }
+    }
+
+    /// Get the encoder.
+    pub fn encoder(&mut self) -> &mut Encoder {
+        &mut self.encoder
+    }
+
+    /// Get the inner sink.
+    pub fn get_mut(&mut self) -> &mut K {
+        &mut self.inner
+    }
+
+    /// Unwrap the encoder and inner sink.
+    pub fn into_inner(self) -> (Encoder, K) {
+        (self.encoder, self.inner)
+    }
+}
+
+impl<K, T> Sink<T> for OpusEncoderSink<K>
+where
+    K: Sink<Vec<u8>> + Unpin,
+    K::Error: From<Error>,
+    T: AsRef<[i16]>,
+{
+    type Error = K::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), K::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, frame: T) -> Result<(), K::Error> {
+        let this = self.get_mut();
+        let len = this.encoder.encode(frame.as_ref(), &mut this.buffer)?;
+        Pin::new(&mut this.inner).start_send(this.buffer[..len].to_vec())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), K::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), K::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
+
+/// A `Stream` of decoded PCM frames produced from an inner `Stream` of
+/// packets.
+#[derive(Debug)]
+pub struct OpusDecoderStream<S> {
+    decoder: Decoder,
+    inner: S,
+    frame_len: usize,
+}
+
+impl<S> OpusDecoderStream<S> {
+    /// Wrap a stream of packets.
+    pub fn new(mut decoder: Decoder, inner: S) -> super::Result<OpusDecoderStream<S>> {
+        let rate = decoder.get_sample_rate()? as usize;
+        // the longest possible packet is 120 ms
+        let frame_len = rate * 120 / 1000 * decoder.channels as usize;
+        Ok(OpusDecoderStream {
+            decoder,
+            inner,
+            frame_len,
+        })
+    }
+
+    /// Get the decoder.
+    pub fn decoder(&mut self) -> &mut Decoder {
+        &mut self.decoder
+    }
+
+    /// Get the inner stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Unwrap the decoder and inner stream.
+    pub fn into_inner(self) -> (Decoder, S) {
+        (self.decoder, self.inner)
+    }
+
+    /// Yield `DecodedFrame`s carrying timing metadata instead of bare
+    /// buffers.
+    ///
+    /// Empty packets are treated as lost and concealed for the duration of
+    /// the previous packet.
+    pub fn frames(mut self) -> super::Result<DecodedFrames<S>> {
+        let rate = self.decoder.get_sample_rate()?;
+        Ok(DecodedFrames {
+            stream: self,
+            rate,
+            timestamp: 0,
+        })
+    }
+
+    fn decode(&mut self, packet: &[u8]) -> super::Result<Vec<i16>> {
+        let mut pcm = vec![0; self.frame_len];
+        let len = self.decoder.decode(packet, &mut pcm, false)?;
+        pcm.truncate(len * self.decoder.channels as usize);
+        Ok(pcm)
+    }
+}
+
+impl<S, P, E> Stream for OpusDecoderStream<S>
+where
+    S: Stream<Item = Result<P, E>> + Unpin,
+    P: AsRef<[u8]>,
+    E: From<Error>,
+{
+    type Item = Result<Vec<i16>, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(packet))) => {
+                Poll::Ready(Some(this.decode(packet.as_ref()).map_err(E::from)))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// A `Stream` of timestamped decoded frames, returned from
+/// `OpusDecoderStream::frames`.
+#[derive(Debug)]
+pub struct DecodedFrames<S> {
+    stream: OpusDecoderStream<S>,
+    rate: u32,
+    timestamp: u64,
+}
+
+impl<S> DecodedFrames<S> {
+    /// Get the decoder.
+    pub fn decoder(&mut self) -> &mut Decoder {
+        &mut self.stream.decoder
+    }
+
+    /// Get the 48kHz timestamp of the next frame.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Unwrap the decoder and inner stream.
+    pub fn into_inner(self) -> (Decoder, S) {
+        self.stream.into_inner()
+    }
+
+    fn decode(&mut self, packet: &[u8]) -> super::Result<DecodedFrame> {
+        let channels = self.stream.decoder.channels as usize;
+        let is_plc = packet.is_empty();
+        let pcm = if is_plc {
+            let mut duration = self.stream.decoder.get_last_packet_duration()? as usize;
+            if duration == 0 {
+                duration = self.rate as usize / 50;
+            }
+            let mut pcm = vec![0; duration * channels];
+            let len = self.stream.decoder.decode(&[], &mut pcm, false)?;
+            pcm.truncate(len * channels);
+            pcm
+        } else {
+            self.stream.decode(packet)?
+        };
+        let samples = pcm.len() / channels;
+        let timestamp_48k = self.timestamp;
+        self.timestamp += Timestamp48k::from_samples(samples as u64, self.rate).samples();
+        Ok(DecodedFrame {
+            pcm,
+            samples,
+            timestamp_48k,
+            is_plc,
+        })
+    }
+}
+
+impl<S, P, E> Stream for DecodedFrames<S>
+where
+    S: Stream<Item = Result<P, E>> + Unpin,
+    P: AsRef<[u8]>,
+    E: From<Error>,
+{
+    type Item = Result<DecodedFrame, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.stream.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(packet))) => {
+                Poll::Ready(Some(this.decode(packet.as_ref()).map_err(E::from)))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
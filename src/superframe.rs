@@ -0,0 +1,144 @@
+//! Encoding 120 ms super-frames, for store-and-forward uses such as voice
+//! messages where the fewer packet headers the better.
+
+use libc::c_int;
+
+use super::chunk::MAX_PACKET;
+use super::storage::Storage;
+use super::{ffi, packet, Encoder, Error, FrameDuration, Repacketizer, Result};
+use super::{
+    OPUS_AUTO, OPUS_GET_FORCE_CHANNELS, OPUS_SET_BANDWIDTH, OPUS_SET_EXPERT_FRAME_DURATION,
+    OPUS_SET_FORCE_CHANNELS,
+};
+
+// `OPUS_SET_FORCE_MODE`, private to libopus but stable across releases, and
+// the modes it accepts. libopus forces the mode the same way while merging
+// the frames of its own packets longer than 60 ms.
+const OPUS_SET_FORCE_MODE: c_int = 11002;
+const MODE_SILK_ONLY: c_int = 1000;
+const MODE_HYBRID: c_int = 1001;
+const MODE_CELT_ONLY: c_int = 1002;
+
+impl<S: Storage> Encoder<S> {
+    /// Encode 120 ms of input as a single packet, by encoding it as two
+    /// 60 ms packets and merging them with the repacketizer.
+    ///
+    /// The second half is coded with the mode, bandwidth, and channel count
+    /// of the first, as the repacketizer merges only alike packets. A
+    /// bandwidth set with `set_bandwidth` is reset to automatic afterwards;
+    /// the frame duration and forced channel count are kept.
+    pub fn encode_120ms(&mut self, input: &[i16], output: &mut [u8]) -> Result<usize> {
+        self.encode_superframe(input, output)
+    }
+
+    /// Encode 120 ms of floating point input as a single packet.
+    #[cfg(not(feature = "disable-float-api"))]
+    pub fn encode_120ms_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        self.encode_superframe(input, output)
+    }
+
+    fn encode_superframe<T: RawSample>(&mut self, input: &[T], output: &mut [u8]) -> Result<usize> {
+        let half = FrameDuration::Ms60
+            .samples(self.get_sample_rate()?)
+            .unwrap_or(0);
+        let channels = self.channels as usize;
+        if input.len() != 2 * half * channels {
+            return Err(Error::bad_arg("Encoder::encode_120ms"));
+        }
+        let (first_half, second_half) = input.split_at(half * channels);
+
+        let duration = self.get_expert_frame_duration()?;
+        let mut force_channels: i32 = 0;
+        enc_ctl!(self, OPUS_GET_FORCE_CHANNELS, &mut force_channels);
+        enc_ctl!(
+            self,
+            OPUS_SET_EXPERT_FRAME_DURATION,
+            FrameDuration::Ms60 as i32
+        );
+
+        let mut first = vec![0; MAX_PACKET];
+        let mut second = vec![0; MAX_PACKET];
+        let result = self.encode_half(first_half, &mut first).and_then(|len| {
+            first.truncate(len);
+            let mode = match first[0] >> 3 {
+                config if config < 12 => MODE_SILK_ONLY,
+                config if config < 16 => MODE_HYBRID,
+                _ => MODE_CELT_ONLY,
+            };
+            let stereo = first[0] & 0x4 != 0;
+            enc_ctl!(self, OPUS_SET_FORCE_MODE, mode);
+            enc_ctl!(
+                self,
+                OPUS_SET_BANDWIDTH,
+                packet::get_bandwidth(&first)? as i32
+            );
+            enc_ctl!(self, OPUS_SET_FORCE_CHANNELS, if stereo { 2 } else { 1 });
+            self.encode_half(second_half, &mut second)
+        });
+
+        enc_ctl!(self, OPUS_SET_FORCE_MODE, OPUS_AUTO);
+        enc_ctl!(self, OPUS_SET_BANDWIDTH, OPUS_AUTO);
+        enc_ctl!(self, OPUS_SET_FORCE_CHANNELS, force_channels);
+        enc_ctl!(self, OPUS_SET_EXPERT_FRAME_DURATION, duration as i32);
+
+        second.truncate(result?);
+        let len = Repacketizer::new()?.combine(&[&first, &second], output)?;
+        self.record(&output[..len]);
+        Ok(len)
+    }
+
+    fn encode_half<T: RawSample>(&mut self, input: &[T], output: &mut [u8]) -> Result<usize> {
+        let frame_size = (input.len() / self.channels as usize) as c_int;
+        match unsafe { T::encode(self.ptr, input, frame_size, output) } {
+            code if code < 0 => Err(Error::from_code("opus_encode", code)),
+            len => Ok(len as usize),
+        }
+    }
+}
+
+// A sample type which a bare libopus encoder accepts, encoded without the
+// statistics of `Encoder::encode`, as the halves of a super-frame are not
+// packets of their own.
+trait RawSample: Copy {
+    unsafe fn encode(
+        state: *mut ffi::OpusEncoder,
+        pcm: &[Self],
+        frame_size: c_int,
+        output: &mut [u8],
+    ) -> c_int;
+}
+
+impl RawSample for i16 {
+    unsafe fn encode(
+        state: *mut ffi::OpusEncoder,
+        pcm: &[i16],
+        frame_size: c_int,
+        output: &mut [u8],
+    ) -> c_int {
+        ffi::opus_encode(
+            state,
+            pcm.as_ptr(),
+            frame_size,
+            output.as_mut_ptr(),
+            output.len() as i32,
+        )
+    }
+}
+
+#[cfg(not(feature = "disable-float-api"))]
+impl RawSample for f32 {
+    unsafe fn encode(
+        state: *mut ffi::OpusEncoder,
+        pcm: &[f32],
+        frame_size: c_int,
+        output: &mut [u8],
+    ) -> c_int {
+        ffi::opus_encode_float(
+            state,
+            pcm.as_ptr(),
+            frame_size,
+            output.as_mut_ptr(),
+            output.len() as i32,
+        )
+    }
+}
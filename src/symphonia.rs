@@ -0,0 +1,121 @@
+//! A Symphonia `Decoder` backed by libopus.
+//!
+//! Register `OpusDecoder` with a `CodecRegistry` to decode Opus tracks
+//! demuxed by Symphonia through the reference decoder:
+//!
+//! ```ignore
+//! let mut codecs = symphonia::core::codecs::CodecRegistry::new();
+//! codecs.register_all::<opus::symphonia::OpusDecoder>();
+//! ```
+
+use symphonia_core::audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Signal, SignalSpec};
+use symphonia_core::codecs::{
+    self, CodecDescriptor, CodecParameters, DecoderOptions, FinalizeResult, CODEC_TYPE_OPUS,
+};
+use symphonia_core::errors::{unsupported_error, Error as SymphoniaError, Result};
+use symphonia_core::formats::Packet;
+use symphonia_core::support_codec;
+
+use super::ogg::OpusHead;
+use super::{Channels, Decoder, Error};
+
+/// Sample rate of the decoded output.
+const RATE: u32 = 48000;
+
+/// Samples per channel in the longest possible packet.
+const MAX_FRAME: usize = RATE as usize * 120 / 1000;
+
+/// An Opus decoder for Symphonia's codec registry.
+///
+/// The identification header, if present in the codec parameters' extra
+/// data, supplies the channel count and output gain. Only mono and stereo
+/// streams are supported.
+pub struct OpusDecoder {
+    params: CodecParameters,
+    decoder: Decoder,
+    channels: usize,
+    pcm: Vec<f32>,
+    buf: AudioBuffer<f32>,
+}
+
+// Symphonia requires decoders to be `Sync`. The libopus state is only ever
+// touched through `&mut self`, so sharing references is harmless.
+unsafe impl Sync for OpusDecoder {}
+
+impl codecs::Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        if params.codec != CODEC_TYPE_OPUS {
+            return unsupported_error("opus: invalid codec type");
+        }
+        let head = params
+            .extra_data
+            .as_ref()
+            .and_then(|data| OpusHead::parse(data).ok());
+        let count = match head {
+            Some(ref head) => head.channels as usize,
+            None => params.channels.map_or(0, |c| c.count()),
+        };
+        let (channels, layout) = match count {
+            1 => (Channels::Mono, symphonia_core::audio::Channels::FRONT_LEFT),
+            2 => (
+                Channels::Stereo,
+                symphonia_core::audio::Channels::FRONT_LEFT
+                    | symphonia_core::audio::Channels::FRONT_RIGHT,
+            ),
+            _ => return unsupported_error("opus: unsupported channel count"),
+        };
+        let mut decoder = Decoder::new(RATE, channels).map_err(error)?;
+        if let Some(ref head) = head {
+            decoder.set_gain(head.output_gain as i32).map_err(error)?;
+        }
+        Ok(OpusDecoder {
+            params: params.clone(),
+            decoder,
+            channels: count,
+            pcm: vec![0.0; MAX_FRAME * count],
+            buf: AudioBuffer::new(MAX_FRAME as u64, SignalSpec::new(RATE, layout)),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus (libopus)")]
+    }
+
+    fn reset(&mut self) {
+        let _ = self.decoder.reset_state();
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        self.buf.clear();
+        let len = self
+            .decoder
+            .decode_float(packet.buf(), &mut self.pcm, false)
+            .map_err(error)?;
+        self.buf.render_reserved(Some(len));
+        for c in 0..self.channels {
+            let plane = self.buf.chan_mut(c);
+            for (i, sample) in plane.iter_mut().enumerate() {
+                *sample = self.pcm[i * self.channels + c];
+            }
+        }
+        self.buf
+            .trim(packet.trim_start as usize, packet.trim_end as usize);
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
+
+fn error(err: Error) -> SymphoniaError {
+    SymphoniaError::DecodeError(err.description())
+}
@@ -0,0 +1,442 @@
+//! Running the official Opus test vectors against the linked libopus.
+//!
+//! The test vectors of RFC 6716, as updated by RFC 8251, are twelve
+//! bitstreams in the format of `opus_demo -d`, each with the output of the
+//! reference decoder. `run` decodes a bitstream, checks the range coder state
+//! after every packet against the one recorded, and scores the output against
+//! the reference with the quality metric of `opus_compare`. A vector passes
+//! with a quality of at least 0 against either reference output, like the
+//! `run_vectors.sh` script of libopus, so platform and SIMD regressions in the
+//! decoder show up as failures.
+//!
+//! The vectors are looked up in the directory named by `OPUS_TESTVECTORS`,
+//! or fetched from opus-codec.org by `download`.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::{Channels, Decoder};
+
+/// Where the test vectors of RFC 8251 are published.
+pub const URL: &str = "https://opus-codec.org/static/testvectors/opus_testvectors-rfc8251.tar.gz";
+
+/// The numbers of the test vectors, `testvector01` to `testvector12`.
+pub const VECTORS: [u32; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+
+/// The largest output of a packet, 120 ms at 48kHz, as `opus_demo` allows.
+const MAX_FRAME_SIZE: usize = 48000 * 2;
+
+/// A packet of a test vector bitstream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    /// The packet, empty where it was lost.
+    pub data: Vec<u8>,
+    /// The range coder state of the encoder after the packet.
+    pub final_range: u32,
+}
+
+/// How close decoded audio is to the reference output, by the metric of
+/// `opus_compare`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quality {
+    /// The quality in percent, which is at least 0 for a passing decoder.
+    pub quality: f64,
+    /// The weighted spectral error the quality is derived from.
+    pub error: f64,
+}
+
+impl Quality {
+    /// Determine whether the decoded audio is close enough to pass.
+    pub fn passes(&self) -> bool {
+        self.quality >= 0.0
+    }
+}
+
+/// Find the directory named by `OPUS_TESTVECTORS` if it holds the vectors.
+pub fn locate() -> Option<PathBuf> {
+    let dir = PathBuf::from(env::var_os("OPUS_TESTVECTORS")?);
+    find_vectors(&dir)
+}
+
+/// Download and unpack the test vectors into `dir` with `curl` and `tar`,
+/// unless they are there already, and return the directory holding them.
+pub fn download(dir: &Path) -> io::Result<PathBuf> {
+    if let Some(found) = find_vectors(dir) {
+        return Ok(found);
+    }
+    fs::create_dir_all(dir)?;
+    let archive = dir.join("opus_testvectors.tar.gz");
+    run_tool(
+        Command::new("curl")
+            .arg("-fsSL")
+            .arg("-o")
+            .arg(&archive)
+            .arg(URL),
+    )?;
+    run_tool(
+        Command::new("tar")
+            .arg("-xzf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(dir),
+    )?;
+    find_vectors(dir).ok_or_else(|| invalid("the downloaded archive holds no test vectors"))
+}
+
+/// Decode test vector `vector` in `dir` at `sample_rate` and score it against
+/// the better matching of its reference outputs.
+pub fn run(dir: &Path, vector: u32, sample_rate: u32, channels: Channels) -> io::Result<Quality> {
+    let name = format!("testvector{:02}", vector);
+    let file = fs::File::open(dir.join(format!("{}.bit", name)))?;
+    let packets = read_bitstream(io::BufReader::new(file))?;
+    let decoded = decode(&packets, sample_rate, channels)?;
+
+    let mut best: Option<Quality> = None;
+    for suffix in &["", "m"] {
+        let path = dir.join(format!("{}{}.dec", name, suffix));
+        let reference = match fs::read(&path) {
+            Ok(bytes) => read_pcm(&bytes),
+            // the alternative output of RFC 8251 may be missing
+            Err(ref err) if !suffix.is_empty() && err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        let quality = compare(&reference, &decoded, sample_rate, channels)?;
+        match best {
+            Some(best) if best.quality >= quality.quality => {}
+            _ => best = Some(quality),
+        }
+    }
+    best.ok_or_else(|| invalid("missing reference output"))
+}
+
+/// Read a bitstream in the format of `opus_demo`: each packet preceded by
+/// its length and the encoder's final range, as big-endian 32-bit integers.
+pub fn read_bitstream<R: Read>(mut reader: R) -> io::Result<Vec<Packet>> {
+    let mut packets = Vec::new();
+    let mut header = [0; 8];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err),
+        }
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let final_range = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        if len as usize > 1500 {
+            return Err(invalid("packet longer than 1500 bytes in the bitstream"));
+        }
+        let mut data = vec![0; len as usize];
+        reader.read_exact(&mut data)?;
+        packets.push(Packet { data, final_range });
+    }
+    Ok(packets)
+}
+
+/// Decode the packets of a bitstream as `opus_demo -d` does, failing if the
+/// range coder state after a packet differs from the encoder's.
+pub fn decode(packets: &[Packet], sample_rate: u32, channels: Channels) -> io::Result<Vec<i16>> {
+    let mut decoder = Decoder::new(sample_rate, channels)?;
+    let mut output = Vec::new();
+    let mut frame = vec![0i16; MAX_FRAME_SIZE * channels as usize];
+    for (index, packet) in packets.iter().enumerate() {
+        let samples = if packet.data.is_empty() {
+            let duration = decoder.get_last_packet_duration()? as usize;
+            decoder.decode(&[], &mut frame[..duration * channels as usize], false)?
+        } else {
+            let samples = decoder.decode(&packet.data, &mut frame, false)?;
+            if decoder.get_final_range()? != packet.final_range {
+                return Err(invalid(&format!(
+                    "range coder state mismatch between encoder and decoder in packet {}",
+                    index
+                )));
+            }
+            samples
+        };
+        output.extend_from_slice(&frame[..samples * channels as usize]);
+    }
+    Ok(output)
+}
+
+// `opus_compare` works on spectra of 480-sample windows 120 samples apart,
+// grouped into bands of these bins at 48kHz.
+const NBANDS: usize = 21;
+const NFREQS: usize = 240;
+const WINDOW: usize = 480;
+const STEP: usize = 120;
+const BANDS: [usize; NBANDS + 1] = [
+    0, 2, 4, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 40, 48, 56, 68, 80, 96, 120, 156, 200,
+];
+
+/// Score decoded audio against a reference output with the metric of
+/// `opus_compare`.
+///
+/// The reference is always stereo at 48kHz, and is downmixed for mono
+/// output; `decoded` is at `sample_rate` with `channels`.
+pub fn compare(
+    reference: &[i16],
+    decoded: &[i16],
+    sample_rate: u32,
+    channels: Channels,
+) -> io::Result<Quality> {
+    let nchannels = channels as usize;
+    let downsample = match sample_rate {
+        8000 | 12000 | 16000 | 24000 | 48000 => (48000 / sample_rate) as usize,
+        _ => return Err(invalid("sample rate must be 8, 12, 16, 24, or 48 kHz")),
+    };
+    let x: Vec<f32> = match channels {
+        Channels::Stereo => reference.iter().map(|&s| s as f32).collect(),
+        Channels::Mono => reference
+            .chunks_exact(2)
+            .map(|pair| 0.5 * (pair[0] as f32 + pair[1] as f32))
+            .collect(),
+    };
+    let y: Vec<f32> = decoded.iter().map(|&s| s as f32).collect();
+    let xlength = x.len() / nchannels;
+    let ylength = y.len() / nchannels;
+    if xlength != ylength * downsample {
+        return Err(invalid(&format!(
+            "sample counts do not match ({} != {})",
+            xlength,
+            ylength * downsample
+        )));
+    }
+    if xlength < WINDOW {
+        return Err(invalid("insufficient sample data"));
+    }
+
+    let yfreqs = NFREQS / downsample;
+    let ybands = (0..=NBANDS)
+        .rev()
+        .find(|&b| BANDS[b] <= yfreqs)
+        .unwrap_or(0);
+    let nframes = (xlength - WINDOW + STEP) / STEP;
+    let mut xb = vec![0f32; nframes * NBANDS * nchannels];
+    let mut xs = vec![0f32; nframes * NFREQS * nchannels];
+    let mut ys = vec![0f32; nframes * yfreqs * nchannels];
+    // the per-band energy of the reference, and the spectra of both
+    band_energy(
+        Some(&mut xb),
+        &mut xs,
+        NBANDS,
+        &x,
+        nchannels,
+        nframes,
+        WINDOW,
+        STEP,
+        1,
+    );
+    band_energy(
+        None,
+        &mut ys,
+        ybands,
+        &y,
+        nchannels,
+        nframes,
+        WINDOW / downsample,
+        STEP / downsample,
+        downsample,
+    );
+
+    let xb_at = |xi: usize, bi: usize, ci: usize| (xi * NBANDS + bi) * nchannels + ci;
+    for xi in 0..nframes {
+        // frequency masking, low to high at 10 dB/Bark and high to low at
+        // 15 dB/Bark
+        for bi in 1..NBANDS {
+            for ci in 0..nchannels {
+                xb[xb_at(xi, bi, ci)] += 0.1 * xb[xb_at(xi, bi - 1, ci)];
+            }
+        }
+        for bi in (0..NBANDS - 1).rev() {
+            for ci in 0..nchannels {
+                xb[xb_at(xi, bi, ci)] += 0.03 * xb[xb_at(xi, bi + 1, ci)];
+            }
+        }
+        // temporal masking at -3 dB/2.5 ms
+        if xi > 0 {
+            for bi in 0..NBANDS {
+                for ci in 0..nchannels {
+                    xb[xb_at(xi, bi, ci)] += 0.5 * xb[xb_at(xi - 1, bi, ci)];
+                }
+            }
+        }
+        // allow for some cross-talk
+        if nchannels == 2 {
+            for bi in 0..NBANDS {
+                let l = xb[xb_at(xi, bi, 0)];
+                let r = xb[xb_at(xi, bi, 1)];
+                xb[xb_at(xi, bi, 0)] += 0.01 * r;
+                xb[xb_at(xi, bi, 1)] += 0.01 * l;
+            }
+        }
+        for bi in 0..ybands {
+            for xj in BANDS[bi]..BANDS[bi + 1] {
+                for ci in 0..nchannels {
+                    let mask = 0.1 * xb[xb_at(xi, bi, ci)];
+                    xs[(xi * NFREQS + xj) * nchannels + ci] += mask;
+                    ys[(xi * yfreqs + xj) * nchannels + ci] += mask;
+                }
+            }
+        }
+    }
+
+    // add each frame to the next, so that the comparison is slightly less
+    // sensitive
+    for xj in 0..BANDS[ybands] {
+        for ci in 0..nchannels {
+            let mut xtmp = xs[xj * nchannels + ci];
+            let mut ytmp = ys[xj * nchannels + ci];
+            for xi in 1..nframes {
+                let xk = (xi * NFREQS + xj) * nchannels + ci;
+                let yk = (xi * yfreqs + xj) * nchannels + ci;
+                let (xtmp2, ytmp2) = (xs[xk], ys[yk]);
+                xs[xk] += xtmp;
+                ys[yk] += ytmp;
+                xtmp = xtmp2;
+                ytmp = ytmp2;
+            }
+        }
+    }
+
+    // below 48kHz the last 300 Hz are left out for the different transition
+    // bands, except at 12kHz, whose last band already leaves out 400 Hz
+    let max_compare = match sample_rate {
+        48000 => BANDS[NBANDS],
+        12000 => BANDS[ybands],
+        _ => BANDS[ybands] - 3,
+    };
+    let mut err = 0f64;
+    for xi in 0..nframes {
+        let mut ef = 0f64;
+        for bi in 0..ybands {
+            let mut eb = 0f64;
+            for xj in BANDS[bi]..BANDS[bi + 1].min(max_compare) {
+                for ci in 0..nchannels {
+                    let re = ys[(xi * yfreqs + xj) * nchannels + ci]
+                        / xs[(xi * NFREQS + xj) * nchannels + ci];
+                    let mut im = (re as f64 - (re as f64).ln() - 1.0) as f32;
+                    // less sensitive around the SILK/CELT cross-over, to
+                    // allow for mode freedom in the filters
+                    if (79..=81).contains(&xj) {
+                        im *= 0.1;
+                    }
+                    if xj == 80 {
+                        im *= 0.1;
+                    }
+                    eb += im as f64;
+                }
+            }
+            eb /= ((BANDS[bi + 1] - BANDS[bi]) * nchannels) as f64;
+            ef += eb * eb;
+        }
+        // a fixed normalization accepts slightly lower quality at lower rates
+        ef /= NBANDS as f64;
+        ef *= ef;
+        err += ef * ef;
+    }
+    let error = (err / nframes as f64).powf(1.0 / 16.0);
+    let quality = 100.0 * (1.0 - 0.5 * (1.0 + error).ln() / 1.13f64.ln());
+    Ok(Quality { quality, error })
+}
+
+// The power spectrum of each window into `ps`, with 100000 added as a noise
+// floor, and the average power of each band into `out`.
+#[allow(clippy::too_many_arguments)]
+fn band_energy(
+    mut out: Option<&mut [f32]>,
+    ps: &mut [f32],
+    nbands: usize,
+    input: &[f32],
+    nchannels: usize,
+    nframes: usize,
+    window_size: usize,
+    step: usize,
+    downsample: usize,
+) {
+    use std::f64::consts::PI;
+
+    let window: Vec<f32> = (0..window_size)
+        .map(|j| (0.5 - 0.5 * ((2.0 * PI / (window_size - 1) as f64) * j as f64).cos()) as f32)
+        .collect();
+    let c: Vec<f32> = (0..window_size)
+        .map(|j| ((2.0 * PI / window_size as f64) * j as f64).cos() as f32)
+        .collect();
+    let s: Vec<f32> = (0..window_size)
+        .map(|j| ((2.0 * PI / window_size as f64) * j as f64).sin() as f32)
+        .collect();
+    let ps_size = window_size / 2;
+    let mut x = vec![0f32; nchannels * window_size];
+    for xi in 0..nframes {
+        for ci in 0..nchannels {
+            for xk in 0..window_size {
+                x[ci * window_size + xk] = window[xk] * input[(xi * step + xk) * nchannels + ci];
+            }
+        }
+        for bi in 0..nbands {
+            let mut p = [0f32; 2];
+            for xj in BANDS[bi]..BANDS[bi + 1] {
+                for ci in 0..nchannels {
+                    let (mut re, mut im) = (0f32, 0f32);
+                    let mut ti = 0;
+                    for xk in 0..window_size {
+                        re += c[ti] * x[ci * window_size + xk];
+                        im -= s[ti] * x[ci * window_size + xk];
+                        ti += xj;
+                        if ti >= window_size {
+                            ti -= window_size;
+                        }
+                    }
+                    re *= downsample as f32;
+                    im *= downsample as f32;
+                    let power = re * re + im * im + 100000.0;
+                    ps[(xi * ps_size + xj) * nchannels + ci] = power;
+                    p[ci] += power;
+                }
+            }
+            if let Some(out) = out.as_mut() {
+                let width = (BANDS[bi + 1] - BANDS[bi]) as f32;
+                for ci in 0..nchannels {
+                    out[(xi * NBANDS + bi) * nchannels + ci] = p[ci] / width;
+                }
+            }
+        }
+    }
+}
+
+// 16-bit little-endian samples, as `opus_demo` writes them
+fn read_pcm(bytes: &[u8]) -> Vec<i16> {
+    bytes
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+fn find_vectors(dir: &Path) -> Option<PathBuf> {
+    if dir.join("testvector01.bit").is_file() {
+        return Some(dir.to_path_buf());
+    }
+    // the archive unpacks into a directory of its own
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.join("testvector01.bit").is_file())
+}
+
+fn run_tool(command: &mut Command) -> io::Result<()> {
+    let status = command.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{:?} failed ({})",
+            command, status
+        )))
+    }
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what.to_string())
+}
@@ -0,0 +1,149 @@
+//! Timestamps in the 48kHz units Opus measures time in.
+//!
+//! Whatever rate a stream is coded at, Ogg granule positions, RTP timestamps,
+//! and packet durations all count samples at 48kHz. `Timestamp48k` converts
+//! between these and sample counts at other rates or wall-clock durations,
+//! rounding down throughout so that the conversions agree with each other.
+
+use std::fmt;
+use std::ops::{Add, AddAssign};
+use std::time::Duration;
+
+use super::{packet, Result};
+
+/// The rate all Opus timestamps and durations are expressed in.
+pub const CLOCK_RATE: u32 = 48000;
+
+/// A position or duration in 48kHz samples per channel.
+///
+/// Displays as seconds, to the millisecond.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Timestamp48k(pub u64);
+
+impl Timestamp48k {
+    /// The start of a stream.
+    pub const ZERO: Timestamp48k = Timestamp48k(0);
+
+    /// Get the number of 48kHz samples.
+    pub fn samples(self) -> u64 {
+        self.0
+    }
+
+    /// Get the time of `samples` samples at `rate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is zero.
+    pub fn from_samples(samples: u64, rate: u32) -> Timestamp48k {
+        Timestamp48k(mul_div(samples, CLOCK_RATE, rate))
+    }
+
+    /// Get the number of samples at `rate` in this time.
+    pub fn to_samples(self, rate: u32) -> u64 {
+        mul_div(self.0, rate, CLOCK_RATE)
+    }
+
+    /// Get the duration of an Opus packet.
+    pub fn of_packet(packet: &[u8]) -> Result<Timestamp48k> {
+        Ok(Timestamp48k(
+            packet::get_nb_samples(packet, CLOCK_RATE)? as u64
+        ))
+    }
+
+    /// Get the time of a wall-clock duration.
+    pub fn from_duration(duration: Duration) -> Timestamp48k {
+        let samples = duration.as_nanos() * CLOCK_RATE as u128 / 1_000_000_000;
+        Timestamp48k(samples.min(u64::MAX as u128) as u64)
+    }
+
+    /// Get the wall-clock duration of this time.
+    pub fn to_duration(self) -> Duration {
+        let nanos = self.0 as u128 * 1_000_000_000 / CLOCK_RATE as u128;
+        Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+
+    /// Get the playback time of the sample at an Ogg granule position, in a
+    /// stream whose header has the given pre-skip (RFC 7845 section 4).
+    ///
+    /// Positions within the pre-skip play at time zero.
+    pub fn from_granule(granule: u64, pre_skip: u16) -> Timestamp48k {
+        Timestamp48k(granule.saturating_sub(pre_skip as u64))
+    }
+
+    /// Get the Ogg granule position of the sample played at this time.
+    pub fn to_granule(self, pre_skip: u16) -> u64 {
+        self.0.saturating_add(pre_skip as u64)
+    }
+
+    /// Get the RTP timestamp of this time, in a stream whose timestamps
+    /// start from `base`.
+    pub fn to_rtp(self, base: u32) -> u32 {
+        base.wrapping_add(self.0 as u32)
+    }
+
+    /// Get the time of an RTP timestamp in a stream whose timestamps start
+    /// from `base`.
+    ///
+    /// RTP timestamps wrap around after about a day, so the time is taken as
+    /// the one nearest `near`, such as the time of the previous packet.
+    pub fn from_rtp(timestamp: u32, base: u32, near: Timestamp48k) -> Timestamp48k {
+        let delta = timestamp.wrapping_sub(near.to_rtp(base)) as i32;
+        if delta < 0 {
+            near.saturating_sub(Timestamp48k(delta.unsigned_abs() as u64))
+        } else {
+            near + Timestamp48k(delta as u64)
+        }
+    }
+
+    /// Subtract `other`, or return `None` if it is later.
+    pub fn checked_sub(self, other: Timestamp48k) -> Option<Timestamp48k> {
+        self.0.checked_sub(other.0).map(Timestamp48k)
+    }
+
+    /// Subtract `other`, stopping at zero.
+    pub fn saturating_sub(self, other: Timestamp48k) -> Timestamp48k {
+        Timestamp48k(self.0.saturating_sub(other.0))
+    }
+}
+
+impl Add for Timestamp48k {
+    type Output = Timestamp48k;
+
+    fn add(self, other: Timestamp48k) -> Timestamp48k {
+        Timestamp48k(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Timestamp48k {
+    fn add_assign(&mut self, other: Timestamp48k) {
+        self.0 += other.0;
+    }
+}
+
+impl From<Duration> for Timestamp48k {
+    fn from(duration: Duration) -> Timestamp48k {
+        Timestamp48k::from_duration(duration)
+    }
+}
+
+impl From<Timestamp48k> for Duration {
+    fn from(time: Timestamp48k) -> Duration {
+        time.to_duration()
+    }
+}
+
+impl fmt::Display for Timestamp48k {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let millis = self.0 / (CLOCK_RATE as u64 / 1000);
+        write!(f, "{}.{:03}s", millis / 1000, millis % 1000)
+    }
+}
+
+// Compute `value * num / den` without overflowing in between.
+fn mul_div(value: u64, num: u32, den: u32) -> u64 {
+    let result = value as u128 * num as u128 / den as u128;
+    result.min(u64::MAX as u128) as u64
+}
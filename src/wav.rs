@@ -0,0 +1,283 @@
+//! Streaming adapters between WAV files, via `hound`, and Opus.
+
+use std::io::{self, Read, Seek, Write};
+
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+
+use super::chunk::{FrameChunker, MAX_PACKET};
+use super::loudness::{Loudness, LoudnessMeter};
+use super::mix::{wave_layout, Mixer, Speaker};
+use super::pipeline::{PacketSink, PacketSource, Packetizer};
+#[cfg(feature = "resample")]
+use super::resample::{encode_rate, Resampler};
+use super::{Channels, Decoder, Encoder, EncoderOptions};
+
+/// Number of interleaved samples read from a WAV file at a time.
+const READ_CHUNK: usize = 4096;
+
+/// Sample rates accepted by libopus.
+#[cfg(not(feature = "resample"))]
+const RATES: [u32; 5] = [8000, 12000, 16000, 24000, 48000];
+
+/// A WAV file being read as normalized `f32` samples for encoding, returned
+/// from `from_wav_reader`.
+pub struct WavInput<R> {
+    reader: WavReader<R>,
+    channels: Channels,
+    scale: f32,
+    gain: f32,
+    // for files of more than two channels, and a buffer of their samples
+    downmix: Option<(Mixer, Vec<f32>)>,
+}
+
+/// Check a WAV file's format and prepare to read it for encoding.
+///
+/// The file must have 8 to 32-bit integer or 32-bit float samples, at a
+/// sample rate libopus accepts unless the `resample` feature is enabled.
+/// Files of 3 to 8 channels in the default WAV order are downmixed to
+/// stereo.
+pub fn from_wav_reader<R: Read>(reader: WavReader<R>) -> io::Result<WavInput<R>> {
+    let spec = reader.spec();
+    let (channels, downmix) = match spec.channels {
+        1 => (Channels::Mono, None),
+        2 => (Channels::Stereo, None),
+        n => match wave_layout(n as usize) {
+            Some(layout) => {
+                let mixer = Mixer::downmix(layout, &[Speaker::FrontLeft, Speaker::FrontRight]);
+                let mixer = mixer.with_limiter(spec.sample_rate);
+                (Channels::Stereo, Some((mixer, Vec::new())))
+            }
+            None => return Err(unsupported("WAV file must have 1 to 8 channels")),
+        },
+    };
+    #[cfg(not(feature = "resample"))]
+    {
+        if !RATES.contains(&spec.sample_rate) {
+            return Err(unsupported(
+                "WAV sample rate must be 8, 12, 16, 24, or 48 kHz",
+            ));
+        }
+    }
+    let scale = match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Float, 32) => 1.0,
+        (SampleFormat::Int, bits @ 8..=32) => 1.0 / (1u64 << (bits - 1)) as f32,
+        _ => return Err(unsupported("unsupported WAV sample format")),
+    };
+    Ok(WavInput {
+        reader,
+        channels,
+        scale,
+        gain: 1.0,
+        downmix,
+    })
+}
+
+impl<R: Read> WavInput<R> {
+    /// Get the channel layout the file is encoded with.
+    pub fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    /// Get the sample rate of the file.
+    pub fn sample_rate(&self) -> u32 {
+        self.reader.spec().sample_rate
+    }
+
+    /// Get the sample rate the file is encoded at, which with the
+    /// `resample` feature is 48kHz if libopus doesn't accept the file's.
+    pub fn encode_rate(&self) -> u32 {
+        encode_rate(self.sample_rate())
+    }
+
+    /// Create an encoder matching the file's format with the given settings.
+    pub fn encoder(&self, options: &EncoderOptions) -> io::Result<Encoder> {
+        Ok(options.build(self.encode_rate(), self.channels)?)
+    }
+
+    /// Set a gain in dB to apply to the samples as they are read, such as
+    /// one found with `Loudness::normalize_gain`.
+    pub fn set_gain(&mut self, gain: f64) {
+        self.gain = 10f64.powf(gain / 20.0) as f32;
+    }
+
+    /// Read interleaved samples, normalized to [-1, 1], into `buf`. Returns
+    /// the number of samples read, which is zero at the end of the file.
+    pub fn read(&mut self, buf: &mut [f32]) -> io::Result<usize> {
+        let scale = self.scale * self.gain;
+        match self.downmix {
+            None => read_samples(&mut self.reader, scale, buf),
+            Some((ref mut mixer, ref mut raw)) => {
+                raw.resize(buf.len() / mixer.outputs() * mixer.inputs(), 0.0);
+                let len = read_samples(&mut self.reader, scale, raw)?;
+                Ok(mixer.process(&raw[..len], buf) * mixer.outputs())
+            }
+        }
+    }
+
+    /// Encode the rest of the file into `sink`, padding the final frame with
+    /// silence.
+    ///
+    /// With the `resample` feature the samples are resampled to the rate of
+    /// `encoder` if it differs from the file's.
+    pub fn encode<S: PacketSink>(
+        &mut self,
+        mut encoder: Encoder,
+        sink: S,
+    ) -> io::Result<(Encoder, S)> {
+        let mut chunker = FrameChunker::new(encoder.frame_size()?, self.channels);
+        #[cfg(feature = "resample")]
+        let mut resampler = Resampler::new(
+            self.sample_rate(),
+            encoder.get_sample_rate()?,
+            self.channels as usize,
+        );
+        #[cfg(feature = "resample")]
+        let mut resampled = Vec::new();
+        let mut packetizer = Packetizer::new(encoder, sink, MAX_PACKET);
+        let mut buf = vec![0.0; READ_CHUNK];
+        loop {
+            let len = self.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            #[cfg(feature = "resample")]
+            let pcm = {
+                resampled.clear();
+                resampler.process(&buf[..len], &mut resampled);
+                &resampled[..]
+            };
+            #[cfg(not(feature = "resample"))]
+            let pcm = &buf[..len];
+            chunker.push(pcm, |frame| packetizer.write_frame_float(frame).map(|_| ()))?;
+        }
+        #[cfg(feature = "resample")]
+        {
+            resampled.clear();
+            resampler.flush(&mut resampled);
+            chunker.push(&resampled, |frame| {
+                packetizer.write_frame_float(frame).map(|_| ())
+            })?;
+        }
+        chunker.flush(|frame| packetizer.write_frame_float(frame).map(|_| ()))?;
+        packetizer.flush()?;
+        Ok(packetizer.into_inner())
+    }
+
+    /// Unwrap the underlying WAV reader.
+    pub fn into_inner(self) -> WavReader<R> {
+        self.reader
+    }
+}
+
+impl<R: Read + Seek> WavInput<R> {
+    /// Measure the loudness of the whole file, with the gain applied, then
+    /// rewind to the start for encoding.
+    pub fn measure_loudness(&mut self) -> io::Result<Loudness> {
+        let mut meter = LoudnessMeter::new(self.channels as usize, self.sample_rate());
+        let mut buf = vec![0.0; READ_CHUNK];
+        loop {
+            let len = self.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            meter.push(&buf[..len]);
+        }
+        self.reader.seek(0)?;
+        Ok(meter.finish())
+    }
+}
+
+/// A WAV file being written with decoded 16-bit audio, returned from
+/// `to_wav_writer`.
+pub struct WavOutput<W: Write + Seek> {
+    writer: WavWriter<W>,
+    pcm: Vec<i16>,
+}
+
+/// Start a 16-bit WAV file matching a decoder's sample rate and channels.
+pub fn to_wav_writer<W: Write + Seek>(
+    writer: W,
+    decoder: &mut Decoder,
+) -> io::Result<WavOutput<W>> {
+    let spec = WavSpec {
+        channels: decoder.channels as u16,
+        sample_rate: decoder.get_sample_rate()?,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    Ok(WavOutput {
+        writer: WavWriter::new(writer, spec).map_err(from_hound)?,
+        pcm: Vec::new(),
+    })
+}
+
+impl<W: Write + Seek> WavOutput<W> {
+    /// Write interleaved samples.
+    pub fn write(&mut self, pcm: &[i16]) -> io::Result<()> {
+        let mut writer = self.writer.get_i16_writer(pcm.len() as u32);
+        for &sample in pcm {
+            writer.write_sample(sample);
+        }
+        writer.flush().map_err(from_hound)
+    }
+
+    /// Decode every packet from `source` into the file.
+    pub fn decode<S: PacketSource>(
+        &mut self,
+        decoder: &mut Decoder,
+        mut source: S,
+    ) -> io::Result<()> {
+        let rate = decoder.get_sample_rate()? as usize;
+        // the longest possible packet is 120 ms
+        let frame_len = rate * 120 / 1000 * decoder.channels as usize;
+        let mut pcm = std::mem::take(&mut self.pcm);
+        pcm.resize(frame_len, 0);
+        while let Some((packet, _)) = source.next_packet()? {
+            let len = decoder.decode(&packet, &mut pcm, false)?;
+            self.write(&pcm[..len * decoder.channels as usize])?;
+        }
+        self.pcm = pcm;
+        Ok(())
+    }
+
+    /// Write the final header and close the file.
+    pub fn finalize(self) -> io::Result<()> {
+        self.writer.finalize().map_err(from_hound)
+    }
+}
+
+fn read_samples<R: Read>(
+    reader: &mut WavReader<R>,
+    scale: f32,
+    buf: &mut [f32],
+) -> io::Result<usize> {
+    let mut len = 0;
+    if reader.spec().sample_format == SampleFormat::Float {
+        for (out, sample) in buf.iter_mut().zip(reader.samples::<f32>()) {
+            *out = sample.map_err(from_hound)? * scale;
+            len += 1;
+        }
+    } else {
+        for (out, sample) in buf.iter_mut().zip(reader.samples::<i32>()) {
+            *out = sample.map_err(from_hound)? as f32 * scale;
+            len += 1;
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(not(feature = "resample"))]
+fn encode_rate(rate: u32) -> u32 {
+    rate
+}
+
+fn unsupported(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, what)
+}
+
+fn from_hound(err: hound::Error) -> io::Error {
+    match err {
+        hound::Error::IoError(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
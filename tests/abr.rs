@@ -0,0 +1,45 @@
+extern crate opus;
+
+use opus::abr::Ladder;
+use opus::{Channels, EncoderOptions, ErrorCode, FrameDuration, Preset};
+
+#[test]
+fn ladder_needs_rungs() {
+    let options = EncoderOptions::default();
+    match Ladder::<i16>::new(&options, 48000, Channels::Stereo, &[]) {
+        Ok(_) => panic!("a ladder without rungs was created"),
+        Err(err) => assert_eq!(err.code(), ErrorCode::BadArg),
+    }
+}
+
+#[test]
+fn ladder_encodes_every_rung() {
+    let mut options = EncoderOptions::preset(Preset::WebRtcMusic);
+    options.frame_duration = FrameDuration::Ms10;
+    let bitrates = [24000, 64000, 128000];
+    let mut ladder = Ladder::<i16>::new(&options, 48000, Channels::Stereo, &bitrates).unwrap();
+    assert_eq!(ladder.rungs(), 3);
+    assert_eq!(ladder.bitrates(), &bitrates[..]);
+
+    let pcm: Vec<i16> = (0..2 * 4800 - 260)
+        .map(|t| (((t / 2) as f32 * 0.02).sin() * 10000.0) as i16)
+        .collect();
+    assert!(ladder.push(&pcm[..700]).unwrap().is_empty());
+    assert_eq!(ladder.pending(), 700);
+    let frames = ladder.push(&pcm[700..]).unwrap();
+    assert_eq!(frames.len(), 9);
+    assert_eq!(ladder.pending(), 700);
+
+    let mut totals = [0; 3];
+    for packets in &frames {
+        assert_eq!(packets.len(), 3);
+        for (total, packet) in totals.iter_mut().zip(packets) {
+            assert_eq!(opus::packet::get_nb_samples(packet, 48000).unwrap(), 480);
+            *total += packet.len();
+        }
+    }
+    assert!(totals[0] < totals[1] && totals[1] < totals[2]);
+
+    assert_eq!(ladder.flush().unwrap().unwrap().len(), 3);
+    assert!(ladder.flush().unwrap().is_none());
+}
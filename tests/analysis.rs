@@ -0,0 +1,89 @@
+#![cfg(feature = "std")]
+
+extern crate opus;
+
+use std::f32::consts::TAU;
+
+use opus::analysis::{self, SNR_RANGE};
+use opus::{Application, Bitrate, Channels, Decoder, Encoder};
+
+// 48000Hz * 2 channels * 20 ms / 1000
+const STEREO_20MS: usize = 960 * 2;
+
+/// A second of stereo tones over noise.
+fn signal() -> Vec<i16> {
+    let mut noise = 1u32;
+    (0..50 * STEREO_20MS)
+        .map(|i| {
+            noise = noise.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let t = (i / 2) as f32 / 48000.0;
+            let tone = (t * 440.0 * TAU).sin() * 8000.0 + (t * 3000.0 * TAU).sin() * 2000.0;
+            (tone + ((noise >> 16) as f32 - 32768.0) * 0.05) as i16
+        })
+        .collect()
+}
+
+fn round_trip(pcm: &[i16], bitrate: i32) -> (Vec<i16>, usize) {
+    let mut encoder = Encoder::new(48000, Channels::Stereo, Application::Audio).unwrap();
+    encoder.set_bitrate(Bitrate::Bits(bitrate)).unwrap();
+    let lookahead = encoder.get_lookahead().unwrap() as usize;
+    let mut decoder = Decoder::new(48000, Channels::Stereo).unwrap();
+    let mut output = vec![0i16; pcm.len()];
+    for (frame, out) in pcm.chunks(STEREO_20MS).zip(output.chunks_mut(STEREO_20MS)) {
+        let packet = encoder.encode_vec(frame, 1500).unwrap();
+        decoder.decode(&packet, out, false).unwrap();
+    }
+    (output, lookahead)
+}
+
+#[test]
+fn identical_audio() {
+    let pcm = signal();
+    let metrics = analysis::measure(&pcm, &pcm, 2, 48000);
+    assert_eq!(metrics.delay, 0);
+    assert_eq!(metrics.segmental_snr, SNR_RANGE.1);
+    assert_eq!(metrics.spectral_distance, 0.0);
+}
+
+#[test]
+fn finds_delay() {
+    let pcm = signal();
+    let mut delayed = vec![0i16; 2 * 100];
+    delayed.extend_from_slice(&pcm);
+    assert_eq!(analysis::delay(&pcm, &delayed, 2, 960), 100);
+    let metrics = analysis::measure(&pcm, &delayed, 2, 48000);
+    assert_eq!(metrics.delay, 100);
+    assert_eq!(metrics.segmental_snr, SNR_RANGE.1);
+}
+
+#[test]
+fn degradation_lowers_quality() {
+    let pcm = signal();
+    let halved: Vec<i16> = pcm.iter().map(|&s| s / 2).collect();
+    let metrics = analysis::measure(&pcm, &halved, 2, 48000);
+    // half the amplitude leaves an error of half the signal, 6 dB down
+    assert!((metrics.segmental_snr - 6.02).abs() < 0.1);
+    assert!(metrics.spectral_distance > 4.0);
+
+    let silence = vec![0i16; pcm.len()];
+    assert_eq!(analysis::segmental_snr(&silence, &pcm, 2, 960), SNR_RANGE.1);
+    assert_eq!(analysis::log_spectral_distance(&silence, &pcm, 2, 960), 0.0);
+}
+
+#[test]
+fn opus_round_trip_quality() {
+    let pcm = signal();
+    let (high, lookahead) = round_trip(&pcm, 128000);
+    let (low, _) = round_trip(&pcm, 16000);
+    let high = analysis::measure(&pcm, &high, 2, 48000);
+    let low = analysis::measure(&pcm, &low, 2, 48000);
+
+    assert!(
+        (high.delay as isize - lookahead as isize).abs() <= 2,
+        "{:?}",
+        high
+    );
+    assert!(high.segmental_snr > 10.0, "{:?}", high);
+    assert!(high.spectral_distance < 10.0, "{:?}", high);
+    assert!(high.spectral_distance < low.spectral_distance);
+}
@@ -0,0 +1,22 @@
+#![cfg(feature = "rayon")]
+
+extern crate opus;
+extern crate rayon;
+
+use opus::batch::encode_batch;
+use opus::{Channels, EncoderOptions};
+use rayon::prelude::*;
+
+#[test]
+fn segments_keep_order() {
+    let segments: Vec<Vec<i16>> = (1..=8).map(|n| vec![0; n * 960]).collect();
+    let encoded = encode_batch(
+        &EncoderOptions::default(),
+        48000,
+        Channels::Mono,
+        segments.par_iter(),
+    )
+    .unwrap();
+    let counts: Vec<usize> = encoded.iter().map(|packets| packets.len()).collect();
+    assert_eq!(counts, (1..=8).collect::<Vec<_>>());
+}
@@ -0,0 +1,54 @@
+extern crate opus;
+
+use opus::chunk::FrameChunker;
+use opus::{Application, Channels, Encoder, FrameDuration};
+
+#[test]
+fn chunker_buffers_partial_frames() {
+    let mut chunker = FrameChunker::new(4, Channels::Stereo);
+    let mut frames: Vec<Vec<i16>> = Vec::new();
+
+    let input: Vec<i16> = (1..=20).collect();
+    chunker
+        .push(&input[..5], |f| -> Result<(), ()> {
+            frames.push(f.to_vec());
+            Ok(())
+        })
+        .unwrap();
+    assert!(frames.is_empty());
+    assert_eq!(chunker.pending(), 5);
+
+    chunker
+        .push(&input[5..], |f| -> Result<(), ()> {
+            frames.push(f.to_vec());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(frames, vec![input[..8].to_vec(), input[8..16].to_vec()]);
+    assert_eq!(chunker.pending(), 4);
+
+    chunker
+        .flush(|f| -> Result<(), ()> {
+            frames.push(f.to_vec());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(frames[2], vec![17, 18, 19, 20, 0, 0, 0, 0]);
+    assert_eq!(chunker.pending(), 0);
+}
+
+#[test]
+fn encode_iter_pads_tail() {
+    let mut encoder = Encoder::new(48000, Channels::Mono, Application::Audio).unwrap();
+    let pcm = vec![0i16; 960 * 2 + 100];
+    let packets: Vec<Vec<u8>> = encoder.encode_iter(&pcm).map(|p| p.unwrap()).collect();
+    assert_eq!(packets.len(), 3);
+    for packet in &packets {
+        assert_eq!(opus::packet::get_nb_samples(packet, 48000).unwrap(), 960);
+    }
+
+    encoder
+        .set_expert_frame_duration(FrameDuration::Ms10)
+        .unwrap();
+    assert_eq!(encoder.encode_iter(&pcm).count(), 5);
+}
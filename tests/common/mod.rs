@@ -0,0 +1,34 @@
+//! Fixtures shared by the integration tests.
+
+#![allow(dead_code)]
+
+use opus::{Application, Channels, Encoder, Result};
+
+// 48000Hz * 1 channel * 20 ms / 1000
+pub const MONO_20MS: usize = 960;
+
+/// Encode `count` 20 ms frames of a mono tone with a VoIP encoder, configured
+/// first by `setup`.
+pub fn packets<F>(count: usize, setup: F) -> Vec<Vec<u8>>
+where
+    F: FnOnce(&mut Encoder) -> Result<()>,
+{
+    let mut encoder = Encoder::new(48000, Channels::Mono, Application::Voip).unwrap();
+    setup(&mut encoder).unwrap();
+    (0..count)
+        .map(|i| {
+            let input: Vec<i16> = (0..MONO_20MS)
+                .map(|t| (((i * MONO_20MS + t) as f32 * 0.05).sin() * 8000.0) as i16)
+                .collect();
+            encoder.encode_vec(&input, 1500).unwrap()
+        })
+        .collect()
+}
+
+/// Encode `count` 20 ms frames of a mono tone carrying in-band FEC.
+pub fn fec_packets(count: usize) -> Vec<Vec<u8>> {
+    packets(count, |encoder| {
+        encoder.set_inband_fec(true)?;
+        encoder.set_packet_loss_perc(20)
+    })
+}
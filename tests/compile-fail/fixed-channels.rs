@@ -0,0 +1,6 @@
+extern crate opus;
+
+fn main() {
+    let _ = opus::FixedEncoder::<3>::new(48000, opus::Application::Audio);
+    //~^^^^ ERROR only mono and stereo are supported
+}
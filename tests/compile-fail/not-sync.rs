@@ -0,0 +1,10 @@
+extern crate opus;
+
+fn assert_sync<T: Sync>(_: &T) {}
+
+fn main() {
+    let encoder =
+        opus::Encoder::new(48000, opus::Channels::Mono, opus::Application::Audio).unwrap();
+    assert_sync(&encoder);
+    //~^ ERROR cannot be shared between threads safely
+}
@@ -0,0 +1,76 @@
+extern crate opus;
+
+use opus::convert::{self, scalar};
+
+fn samples(len: usize) -> Vec<f32> {
+    let mut state = 0x1234_5678u32;
+    (0..len)
+        .map(|i| match i % 17 {
+            0 => f32::NAN,
+            1 => 3.5,
+            2 => -2.0,
+            3 => 0.5 / 32768.0,
+            _ => {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 8) as f32 / (1 << 23) as f32 - 1.0
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn conversions_match_scalar() {
+    for len in 0..40 {
+        let input = samples(len);
+        let mut fast = vec![0i16; len];
+        let mut reference = vec![0i16; len];
+        convert::f32_to_i16(&input, &mut fast);
+        scalar::f32_to_i16(&input, &mut reference);
+        assert_eq!(fast, reference);
+
+        let mut fast_f32 = vec![0.0; len];
+        let mut reference_f32 = vec![0.0; len];
+        convert::i16_to_f32(&reference, &mut fast_f32);
+        scalar::i16_to_f32(&reference, &mut reference_f32);
+        assert_eq!(fast_f32, reference_f32);
+    }
+    let mut out = [0i16; 4];
+    convert::f32_to_i16(&[1.0, -1.0, f32::NAN, 0.5 / 32768.0], &mut out);
+    assert_eq!(out, [32767, -32768, 32767, 0]);
+}
+
+#[test]
+fn interleave_round_trip() {
+    for channels in 1..4 {
+        for frames in 0..21 {
+            let planes: Vec<Vec<i16>> = (0..channels)
+                .map(|c| (0..frames).map(|i| (i * channels + c) as i16).collect())
+                .collect();
+            let refs: Vec<&[i16]> = planes.iter().map(|p| &p[..]).collect();
+            let mut interleaved = vec![0; frames * channels];
+            convert::interleave(&refs, &mut interleaved);
+            let expected: Vec<i16> = (0..(frames * channels) as i16).collect();
+            assert_eq!(interleaved, expected);
+
+            let mut split = vec![vec![0; frames]; channels];
+            {
+                let mut refs: Vec<&mut [i16]> = split.iter_mut().map(|p| &mut p[..]).collect();
+                convert::deinterleave(&interleaved, &mut refs);
+            }
+            assert_eq!(split, planes);
+        }
+    }
+}
+
+#[test]
+fn interleave_stereo_f32() {
+    let left: Vec<f32> = (0..11).map(|i| i as f32).collect();
+    let right: Vec<f32> = (0..11).map(|i| -(i as f32)).collect();
+    let mut out = vec![0.0; 22];
+    convert::interleave(&[&left, &right], &mut out);
+    let (mut l, mut r) = (vec![0.0; 11], vec![0.0; 11]);
+    convert::deinterleave(&out, &mut [&mut l, &mut r]);
+    assert_eq!(l, left);
+    assert_eq!(r, right);
+    assert_eq!(&out[..4], &[0.0, 0.0, 1.0, -1.0]);
+}
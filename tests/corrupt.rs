@@ -0,0 +1,187 @@
+#![cfg(feature = "std")]
+
+//! Malformed input must be reported as an error, never panic.
+
+extern crate opus;
+
+use opus::ogg::{OggDemuxer, OpusHead, OpusTags, Page, PageRef};
+use opus::rtp::{red, Header};
+use opus::{packet, Channels, Decoder, Repacketizer};
+
+/// Deterministic noise for filling corrupt packets.
+struct Lcg(u32);
+
+impl Lcg {
+    fn next(&mut self) -> u8 {
+        self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (self.0 >> 24) as u8
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next()).collect()
+    }
+}
+
+/// Call `f` with every prefix of `bytes`, and with `bytes` with each byte
+/// replaced by a few other values.
+fn mutations<F: FnMut(&[u8])>(bytes: &[u8], mut f: F) {
+    for len in 0..bytes.len() {
+        f(&bytes[..len]);
+    }
+    let mut copy = bytes.to_vec();
+    for i in 0..bytes.len() {
+        for &value in &[0x00, 0x7f, 0x80, 0xff, bytes[i] ^ 0x01] {
+            copy[i] = value;
+            f(&copy);
+        }
+        copy[i] = bytes[i];
+    }
+}
+
+#[test]
+fn corrupt_headers() {
+    let mut head = OpusHead::new(2, 312, 44100);
+    head.mapping_family = 1;
+    head.stream_count = 1;
+    head.coupled_count = 1;
+    head.mapping = vec![0, 1];
+    let mut bytes = Vec::new();
+    head.write(&mut bytes);
+    mutations(&bytes, |bytes| {
+        let _ = OpusHead::parse(bytes);
+    });
+    assert!(OpusHead::parse(&bytes[..bytes.len() - 1]).is_err());
+
+    let mut tags = OpusTags::new("opus-rs");
+    tags.comments
+        .push(("TITLE".to_string(), "Test".to_string()));
+    bytes.clear();
+    tags.write(&mut bytes);
+    mutations(&bytes, |bytes| {
+        let _ = OpusTags::parse(bytes);
+    });
+
+    // a vendor string claiming to run far past the packet
+    bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(OpusTags::parse(&bytes).is_err());
+}
+
+#[test]
+fn corrupt_pages() {
+    let mut page = Page {
+        granule_position: Some(960),
+        serial: 7,
+        ..Page::default()
+    };
+    page.push_packet(&[0x08; 300]);
+    page.push_packet(&[0x08; 10]);
+    let mut bytes = Vec::new();
+    page.write(&mut bytes);
+    mutations(&bytes, |bytes| {
+        if let Ok(Some((page, _))) = PageRef::parse(bytes) {
+            page.fragments().count();
+        }
+    });
+
+    // lacing values promising more than the body holds
+    let page = Page {
+        lacing: vec![255, 255, 10],
+        body: vec![0; 300],
+        ..Page::default()
+    };
+    let fragments: Vec<_> = page.fragments().collect();
+    assert_eq!(fragments.len(), 1);
+    assert_eq!(fragments[0].0.len(), 300);
+}
+
+#[test]
+fn corrupt_rtp() {
+    let header = Header {
+        marker: false,
+        payload_type: 111,
+        sequence: 1,
+        timestamp: 960,
+        ssrc: 42,
+    };
+    let mut bytes = Vec::new();
+    header.write(&[0x08, 1, 2, 3], &mut bytes);
+    mutations(&bytes, |bytes| {
+        let _ = Header::parse(bytes);
+    });
+
+    // CSRCs, an extension, and padding, each longer than the packet
+    for &first in &[0x8f, 0x90, 0xa0] {
+        bytes[0] = first;
+        *bytes.last_mut().unwrap() = 0xff;
+        assert!(Header::parse(&bytes).is_err());
+    }
+
+    let mut encoder = red::RedEncoder::new(111, 1);
+    let mut payload = Vec::new();
+    encoder.encode(0, &[0x08, 1, 2], &mut payload);
+    payload.clear();
+    encoder.encode(960, &[0x08, 4, 5], &mut payload);
+    mutations(&payload, |payload| {
+        let _ = red::parse(960, payload);
+    });
+}
+
+#[test]
+fn corrupt_packets() {
+    let mut decoder = Decoder::new(48000, Channels::Stereo).unwrap();
+    let mut pcm = vec![0i16; 5760 * 2];
+    #[cfg(not(feature = "disable-float-api"))]
+    let mut pcm_float = vec![0f32; 5760 * 2];
+    let mut repacketizer = Repacketizer::new().unwrap();
+    let mut noise = Lcg(1);
+    for len in 0..300 {
+        let data = noise.bytes(len % 64 + len / 64 * 300);
+        let _ = decoder.decode(&data, &mut pcm, false);
+        let _ = decoder.decode(&data, &mut pcm, true);
+        #[cfg(not(feature = "disable-float-api"))]
+        let _ = decoder.decode_float(&data, &mut pcm_float, false);
+        // an output buffer too short for the packet
+        let _ = decoder.decode(&data, &mut pcm[..2], false);
+        let _ = packet::parse(&data);
+        let _ = packet::get_nb_samples(&data, 48000);
+        let _ = packet::get_bandwidth(&data);
+        let _ = packet::get_nb_channels(&data);
+        let _ = repacketizer.combine(&[&data, &data], &mut vec![0; 1500]);
+    }
+    // every code 3 packet claiming more frames than it has
+    for toc in 0..=255u8 {
+        let data = [toc | 3, 0xff, 0xff, 0xff];
+        assert!(decoder.decode(&data, &mut pcm, false).is_err());
+        assert!(packet::parse(&data).is_err());
+    }
+}
+
+#[test]
+fn corrupt_ogg_stream() {
+    let mut bytes = Vec::new();
+    let mut page = Page {
+        flags: opus::ogg::page::BOS,
+        granule_position: Some(0),
+        serial: 7,
+        ..Page::default()
+    };
+    let mut packet = Vec::new();
+    OpusHead::new(1, 312, 48000).write(&mut packet);
+    page.push_packet(&packet);
+    page.write(&mut bytes);
+    let mut page = Page {
+        granule_position: Some(960),
+        serial: 7,
+        sequence: 1,
+        ..Page::default()
+    };
+    packet.clear();
+    OpusTags::new("opus-rs").write(&mut packet);
+    page.push_packet(&packet);
+    page.push_packet(&[0x08]);
+    page.write(&mut bytes);
+
+    mutations(&bytes, |bytes| {
+        let _ = OggDemuxer::new().push_bytes(bytes);
+    });
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "custom")]
+
+extern crate opus;
+
+use opus::custom::{CustomConfig, CustomMode, CustomPreset};
+use opus::Channels;
+
+#[test]
+fn presets_round_trip() {
+    for &preset in &[
+        CustomPreset::Intercom,
+        CustomPreset::UltraLowLatency,
+        CustomPreset::Embedded,
+    ] {
+        let config = CustomConfig::preset(preset);
+        let mode = config.mode().unwrap();
+        let mut encoder = config.encoder(&mode).unwrap();
+        let mut decoder = config.decoder(&mode).unwrap();
+
+        let input = vec![0i16; config.frame_size];
+        let mut packet = [0; 1275];
+        let len = encoder.encode(&input, &mut packet).unwrap();
+        assert_eq!(len, config.packet_size());
+
+        let mut output = vec![0i16; config.frame_size];
+        let samples = decoder.decode(&packet[..len], &mut output).unwrap();
+        assert_eq!(samples, config.frame_size);
+        // concealment of a lost frame
+        assert_eq!(decoder.decode(&[], &mut output).unwrap(), config.frame_size);
+
+        assert!(config.state_size().unwrap() > 0);
+    }
+}
+
+#[test]
+fn smaller_configs_use_less_memory() {
+    let intercom = CustomConfig::preset(CustomPreset::Intercom);
+    let embedded = CustomConfig::preset(CustomPreset::Embedded);
+    assert!(embedded.state_size().unwrap() < intercom.state_size().unwrap());
+
+    let mode = intercom.mode().unwrap();
+    assert!(mode.encoder_state_size(Channels::Mono) < mode.encoder_state_size(Channels::Stereo));
+}
+
+#[test]
+fn rejects_bad_modes_and_short_frames() {
+    assert!(CustomMode::new(48000, 41).is_err());
+    assert!(CustomMode::new(4000, 40).is_err());
+
+    let config = CustomConfig::preset(CustomPreset::Intercom);
+    let mode = config.mode().unwrap();
+    let mut encoder = config.encoder(&mode).unwrap();
+    let mut packet = [0; 1275];
+    let err = encoder.encode(&[0; 60], &mut packet).unwrap_err();
+    assert_eq!(err.code(), opus::ErrorCode::BadArg);
+}
@@ -0,0 +1,24 @@
+#![cfg(feature = "dasp")]
+
+extern crate opus;
+
+use opus::{Application, Channels, Decoder, Encoder};
+
+#[test]
+fn stereo_frames_round_trip() {
+    let mut encoder = Encoder::new(48000, Channels::Stereo, Application::Audio).unwrap();
+    let input = [[0i16; 2]; 960];
+    let packet = encoder.encode_vec_frames(&input, 4000).unwrap();
+
+    let mut decoder = Decoder::new(48000, Channels::Stereo).unwrap();
+    let frames: Vec<[f32; 2]> = decoder.decode_vec_frames(&packet, false).unwrap();
+    assert_eq!(frames.len(), 960);
+}
+
+#[test]
+fn channel_mismatch() {
+    let mut encoder = Encoder::new(48000, Channels::Mono, Application::Audio).unwrap();
+    let input = [[0.0f32; 2]; 960];
+    let err = encoder.encode_vec_frames(&input, 4000).unwrap_err();
+    assert_eq!(err.code(), opus::ErrorCode::BadArg);
+}
@@ -0,0 +1,70 @@
+#![cfg(opus_ge_1_5)]
+
+extern crate opus;
+
+mod common;
+
+use common::{packets, MONO_20MS};
+use opus::dred::{Dred, DredDecoder};
+use opus::jitter::{JitterBuffer, Playout};
+use opus::{Channels, Decoder, Encoder, ErrorCode};
+
+// Configure an encoder to send `duration` 10 ms units of DRED.
+fn dred_setup(encoder: &mut Encoder, duration: i32) -> opus::Result<()> {
+    encoder.set_packet_loss_perc(20)?;
+    encoder.set_dred_duration(duration)
+}
+
+// libopus built without DRED
+fn unimplemented<T>(result: &opus::Result<T>) -> bool {
+    match result {
+        Err(err) => err.code() == ErrorCode::Unimplemented,
+        Ok(_) => false,
+    }
+}
+
+#[test]
+fn parse_without_dred() {
+    let decoder = DredDecoder::new();
+    if unimplemented(&decoder) {
+        return;
+    }
+    let mut decoder = decoder.unwrap();
+    let mut dred = Dred::new().unwrap();
+    let packets = packets(1, |encoder| dred_setup(encoder, 0));
+    assert_eq!(
+        decoder.parse(&mut dred, &packets[0], 48000, 48000).unwrap(),
+        0
+    );
+    assert_eq!(dred.available(), 0);
+}
+
+#[test]
+fn jitter_buffer_reconstructs() {
+    let mut jb = JitterBuffer::new(0);
+    if unimplemented(&jb.enable_dred()) {
+        return;
+    }
+    let packets = packets(30, |encoder| dred_setup(encoder, 100));
+    let mut decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let mut output = [0i16; MONO_20MS];
+
+    // packets 20 to 23 are lost; 23 is recovered from the FEC of 24
+    for (seq, packet) in packets.iter().enumerate() {
+        if !(20..24).contains(&seq) {
+            jb.push(seq as u16, (seq * MONO_20MS) as u32, packet.clone());
+        }
+    }
+    let mut results = Vec::new();
+    for _ in 0..30 {
+        results.push(jb.pop(&mut decoder, &mut output).unwrap());
+    }
+    assert_eq!(results[23], Playout::Recovered(MONO_20MS));
+    assert_eq!(results[24], Playout::Decoded(MONO_20MS));
+
+    let stats = jb.stats();
+    assert_eq!(stats.lost, 4);
+    assert_eq!(stats.fec_recovered, 1);
+    assert_eq!(stats.dred_recovered + stats.concealed, 3);
+    assert!(stats.dred_recovered > 0);
+}
@@ -0,0 +1,43 @@
+extern crate opus;
+
+use opus::dtx::DtxFiller;
+use opus::{Application, Channels, Decoder, Encoder};
+
+const MONO_20MS: usize = 960;
+
+#[test]
+fn fills_gaps() {
+    let mut encoder = Encoder::new(48000, Channels::Mono, Application::Voip).unwrap();
+    encoder.set_dtx(true).unwrap();
+    let packet = encoder.encode_vec(&[0i16; MONO_20MS], 1500).unwrap();
+
+    let mut decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let mut filler = DtxFiller::new();
+    let mut output = Vec::new();
+
+    assert_eq!(
+        filler
+            .decode(&mut decoder, 0, &packet, &mut output)
+            .unwrap(),
+        MONO_20MS
+    );
+    assert_eq!(filler.next_timestamp(), Some(960));
+
+    // Four frames were not transmitted.
+    let len = filler
+        .decode(&mut decoder, 5 * 960, &packet, &mut output)
+        .unwrap();
+    assert_eq!(len, 5 * MONO_20MS);
+    assert_eq!(output.len(), 6 * MONO_20MS);
+    assert_eq!(filler.concealed(), 4 * 960);
+
+    // A tick conceals a slot, and a packet covering that slot is trimmed.
+    let mut slot = [0i16; MONO_20MS];
+    assert_eq!(filler.tick(&mut decoder, &mut slot).unwrap(), MONO_20MS);
+    output.clear();
+    let len = filler
+        .decode(&mut decoder, 6 * 960, &packet, &mut output)
+        .unwrap();
+    assert_eq!(len, 0);
+    assert_eq!(filler.next_timestamp(), Some(7 * 960));
+}
@@ -0,0 +1,27 @@
+extern crate opus;
+
+use opus::convert;
+use opus::{Application, StereoDecoder, StereoEncoder};
+
+#[test]
+fn stereo_frames_round_trip() {
+    let mut encoder = StereoEncoder::new(48000, Application::Audio).unwrap();
+    let packet = encoder.encode_vec(&[[0; 2]; 960], 4000).unwrap();
+
+    let mut decoder = StereoDecoder::new(48000).unwrap();
+    let mut output = [[0; 2]; 960];
+    assert_eq!(decoder.decode(&packet, &mut output, false).unwrap(), 960);
+}
+
+#[test]
+fn fixed_interleave() {
+    let left = [1, 2, 3];
+    let right = [4, 5, 6, 7];
+    let mut frames = [[0; 2]; 4];
+    convert::interleave_frames([&left[..], &right[..]], &mut frames);
+    assert_eq!(frames, [[1, 4], [2, 5], [3, 6], [0, 0]]);
+
+    let (mut l, mut r) = ([0; 3], [0; 3]);
+    convert::deinterleave_frames(&frames, [&mut l[..], &mut r[..]]);
+    assert_eq!((l, r), ([1, 2, 3], [4, 5, 6]));
+}
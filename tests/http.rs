@@ -0,0 +1,105 @@
+#![cfg(feature = "http")]
+
+extern crate opus;
+
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use opus::http::HttpReader;
+
+/// Serve `data` on a local port, honoring range requests if `ranges` is
+/// set, and return its URL.
+fn serve(data: Vec<u8>, ranges: bool) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/file.opus", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let data = data.clone();
+            thread::spawn(move || respond(stream.unwrap(), &data, ranges));
+        }
+    });
+    url
+}
+
+fn respond(mut stream: TcpStream, data: &[u8], ranges: bool) {
+    let mut start = None;
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap() == 0 || line.trim().is_empty() {
+            break;
+        }
+        let line = line.to_ascii_lowercase();
+        if let Some(range) = line.strip_prefix("range: bytes=") {
+            start = range.trim().trim_end_matches('-').parse::<usize>().ok();
+        }
+    }
+    let head = match start {
+        Some(start) if ranges => format!(
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\n\
+             Content-Length: {}\r\n",
+            start,
+            data.len() - 1,
+            data.len(),
+            data.len() - start
+        ),
+        _ => format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n", data.len()),
+    };
+    let body = &data[start.filter(|_| ranges).unwrap_or(0)..];
+    let _ = write!(stream, "{}Connection: close\r\n\r\n", head);
+    let _ = stream.write_all(body);
+}
+
+fn sample_data() -> Vec<u8> {
+    (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+#[test]
+fn reads_and_seeks_with_ranges() {
+    let data = sample_data();
+    let mut reader = HttpReader::open(&serve(data.clone(), true)).unwrap();
+    assert!(reader.is_seekable());
+    assert_eq!(reader.len(), Some(data.len() as u64));
+
+    let mut buf = vec![0; 1000];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, &data[..1000]);
+
+    // far ahead, a new request
+    reader.seek(SeekFrom::Start(150_000)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, &data[150_000..151_000]);
+
+    // a little ahead, within the same response
+    reader.seek(SeekFrom::Current(500)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, &data[151_500..152_500]);
+
+    // back, a new request
+    reader.seek(SeekFrom::Start(10)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, &data[10..1010]);
+
+    assert_eq!(reader.seek(SeekFrom::End(-100)).unwrap(), 199_900);
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &data[199_900..]);
+    assert!(reader.seek(SeekFrom::Current(-300_000)).is_err());
+}
+
+#[test]
+fn reads_without_ranges() {
+    let data = sample_data();
+    let mut reader = HttpReader::open(&serve(data.clone(), false)).unwrap();
+    assert!(!reader.is_seekable());
+    assert_eq!(reader.len(), Some(data.len() as u64));
+
+    let mut buf = vec![0; 1000];
+    reader.seek(SeekFrom::Start(2000)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, &data[2000..3000]);
+
+    reader.seek(SeekFrom::Start(100_000)).unwrap();
+    assert!(reader.read(&mut buf).is_err());
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "std")]
+
+extern crate opus;
+
+use std::collections::VecDeque;
+use std::io::Read;
+
+use opus::io::{DecodedReader, SampleFormat};
+use opus::pipeline::{PacketInfo, Packetizer};
+use opus::{Application, Channels, Decoder, Encoder};
+
+fn packets(count: usize) -> VecDeque<(Vec<u8>, PacketInfo)> {
+    let encoder = Encoder::new(48000, Channels::Stereo, Application::Audio).unwrap();
+    let mut packetizer = Packetizer::new(encoder, VecDeque::new(), 1500);
+    for _ in 0..count {
+        packetizer.write_frame(&[0i16; 2 * 960]).unwrap();
+    }
+    packetizer.into_inner().1
+}
+
+#[test]
+fn decoded_reader_i16() {
+    let decoder = Decoder::new(48000, Channels::Stereo).unwrap();
+    let mut reader = DecodedReader::new(decoder, packets(3), SampleFormat::I16).unwrap();
+    let mut pcm = Vec::new();
+    reader.read_to_end(&mut pcm).unwrap();
+    assert_eq!(pcm.len(), 3 * 960 * 2 * 2);
+}
+
+#[cfg(not(feature = "disable-float-api"))]
+#[test]
+fn decoded_reader_f32() {
+    let decoder = Decoder::new(24000, Channels::Stereo).unwrap();
+    let mut reader = DecodedReader::new(decoder, packets(2), SampleFormat::F32).unwrap();
+    let mut pcm = [0u8; 7];
+    let mut total = 0;
+    loop {
+        match reader.read(&mut pcm).unwrap() {
+            0 => break,
+            n => total += n,
+        }
+    }
+    assert_eq!(total, 2 * 480 * 2 * 4);
+}
+
+#[test]
+fn encoding_writer_chunks_bytes() {
+    use opus::io::EncodingWriter;
+    use std::io::Write;
+
+    let encoder = Encoder::new(48000, Channels::Mono, Application::Audio).unwrap();
+    let sink: Vec<(Vec<u8>, PacketInfo)> = Vec::new();
+    let mut writer = EncodingWriter::new(encoder, sink, SampleFormat::I16).unwrap();
+
+    // 2.5 frames of mono 16-bit audio, written in odd-sized pieces.
+    let pcm = vec![0u8; 960 * 2 * 5 / 2];
+    for piece in pcm.chunks(333) {
+        writer.write_all(piece).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let (_, packets) = writer.into_inner();
+    assert_eq!(packets.len(), 3);
+    assert_eq!(packets[2].1.timestamp, 1920);
+}
+
+#[cfg(not(feature = "disable-float-api"))]
+#[test]
+fn encoding_writer_round_trip() {
+    use opus::io::EncodingWriter;
+
+    let encoder = Encoder::new(48000, Channels::Stereo, Application::Audio).unwrap();
+    let mut writer = EncodingWriter::new(encoder, VecDeque::new(), SampleFormat::F32).unwrap();
+    let pcm = vec![0u8; 960 * 2 * 4 * 4];
+    std::io::copy(&mut &pcm[..], &mut writer).unwrap();
+    writer.finish().unwrap();
+    let (_, packets) = writer.into_inner();
+    assert_eq!(packets.len(), 4);
+
+    let decoder = Decoder::new(48000, Channels::Stereo).unwrap();
+    let mut reader = DecodedReader::new(decoder, packets, SampleFormat::F32).unwrap();
+    let mut decoded = Vec::new();
+    reader.read_to_end(&mut decoded).unwrap();
+    assert_eq!(decoded.len(), pcm.len());
+}
+
+#[test]
+fn encoding_writer_reports_failed_frames() {
+    use opus::io::EncodingWriter;
+    use opus::pipeline::PacketSink;
+    use std::io::{self, Write};
+
+    // Fails to send the second packet only.
+    struct FlakySink(usize);
+
+    impl PacketSink for FlakySink {
+        fn send_packet(&mut self, _: &[u8], _: PacketInfo) -> io::Result<()> {
+            self.0 += 1;
+            match self.0 {
+                2 => Err(io::ErrorKind::BrokenPipe.into()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    let encoder = Encoder::new(48000, Channels::Mono, Application::Audio).unwrap();
+    let mut writer = EncodingWriter::new(encoder, FlakySink(0), SampleFormat::I16).unwrap();
+    let pcm = vec![0u8; 3 * 960 * 2];
+
+    // a split sample, then three frames of which the second fails
+    assert_eq!(writer.write(&pcm[..1]).unwrap(), 1);
+    assert_eq!(writer.write(&pcm[1..]).unwrap(), 2 * 960 * 2 - 1);
+    assert_eq!(
+        writer.write(&pcm[..1]).unwrap_err().kind(),
+        io::ErrorKind::BrokenPipe
+    );
+    assert_eq!(writer.write(&pcm[2 * 960 * 2..]).unwrap(), 960 * 2);
+    writer.finish().unwrap();
+    assert_eq!(writer.into_inner().1 .0, 3);
+}
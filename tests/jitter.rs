@@ -0,0 +1,109 @@
+extern crate opus;
+
+mod common;
+
+use common::{fec_packets, MONO_20MS};
+use opus::jitter::{AdaptiveDelay, JitterBuffer, Playout};
+use opus::{Channels, Decoder};
+
+#[test]
+fn reorders_packets() {
+    let packets = fec_packets(4);
+    let mut decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let mut jb = JitterBuffer::new(2 * MONO_20MS as u32);
+    let mut output = [0i16; MONO_20MS];
+
+    assert!(jb.push(1, 960, packets[1].clone()));
+    assert_eq!(
+        jb.pop(&mut decoder, &mut output).unwrap(),
+        Playout::Buffering
+    );
+    assert!(jb.push(0, 0, packets[0].clone()));
+    assert!(jb.push(3, 2880, packets[3].clone()));
+    assert!(jb.push(2, 1920, packets[2].clone()));
+
+    for ts in [0, 960, 1920, 2880].iter() {
+        let playout = jb.pop(&mut decoder, &mut output).unwrap();
+        assert_eq!(playout, Playout::Decoded(MONO_20MS));
+        assert_eq!(jb.last_timestamp(), Some(*ts));
+    }
+    assert_eq!(jb.stats().received, 4);
+    assert_eq!(jb.stats().lost, 0);
+}
+
+#[test]
+fn recovers_and_conceals() {
+    let packets = fec_packets(5);
+    let mut decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let mut jb = JitterBuffer::new(0);
+    let mut output = [0i16; MONO_20MS];
+
+    jb.push(10, 0, packets[0].clone());
+    jb.push(12, 1920, packets[2].clone());
+    jb.push(15, 4800, packets[4].clone());
+
+    let mut results = Vec::new();
+    for _ in 0..6 {
+        results.push(jb.pop(&mut decoder, &mut output).unwrap());
+    }
+    assert_eq!(
+        results,
+        vec![
+            Playout::Decoded(MONO_20MS),
+            Playout::Recovered(MONO_20MS),
+            Playout::Decoded(MONO_20MS),
+            Playout::Concealed(MONO_20MS),
+            Playout::Recovered(MONO_20MS),
+            Playout::Decoded(MONO_20MS),
+        ]
+    );
+
+    let stats = jb.stats();
+    assert_eq!(stats.lost, 3);
+    assert_eq!(stats.fec_recovered, 2);
+    assert_eq!(stats.concealed, 1);
+
+    // Packet 11 already had its slot concealed.
+    assert!(!jb.push(11, 960, packets[1].clone()));
+    assert_eq!(jb.stats().late, 1);
+}
+
+#[test]
+fn underrun_and_duplicates() {
+    let packets = fec_packets(2);
+    let mut decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let mut jb = JitterBuffer::new(MONO_20MS as u32);
+    jb.set_policy(AdaptiveDelay::new(MONO_20MS as u32, 4 * MONO_20MS as u32));
+    let mut output = [0i16; MONO_20MS];
+
+    assert!(jb.push(65535, 0, packets[0].clone()));
+    assert!(!jb.push(65535, 0, packets[0].clone()));
+    assert_eq!(jb.stats().duplicates, 1);
+
+    assert_eq!(
+        jb.pop(&mut decoder, &mut output).unwrap(),
+        Playout::Decoded(MONO_20MS)
+    );
+    assert_eq!(
+        jb.pop(&mut decoder, &mut output).unwrap(),
+        Playout::Concealed(MONO_20MS)
+    );
+    assert_eq!(jb.stats().underruns, 1);
+    assert_eq!(jb.target_delay(), 2 * MONO_20MS as u32);
+
+    // Sequence numbers wrap around.
+    assert!(jb.push(0, 960, packets[1].clone()));
+    assert_eq!(
+        jb.pop(&mut decoder, &mut output).unwrap(),
+        Playout::Decoded(MONO_20MS)
+    );
+}
+
+#[test]
+fn adaptive_delay_for_short_frames() {
+    let policy = AdaptiveDelay::for_frames(120, 960, 120);
+    assert_eq!(policy.step, 120);
+    // the delay decays after as much time as with 20 ms frames
+    assert_eq!(policy.decay_after, 4000);
+    assert_eq!(AdaptiveDelay::for_frames(0, 0, 960).decay_after, 500);
+}
@@ -0,0 +1,112 @@
+#![cfg(feature = "std")]
+
+extern crate opus;
+
+use opus::loudness::{self, LoudnessMeter, R128_REFERENCE, R128_TRACK_GAIN};
+use opus::ogg::OpusTags;
+
+fn sine(freq: f32, amplitude: f32, seconds: f32, channels: usize, rate: u32) -> Vec<f32> {
+    let len = (seconds * rate as f32) as usize;
+    (0..len * channels)
+        .map(|i| {
+            let t = (i / channels) as f32 / rate as f32;
+            (t * freq * 2.0 * std::f32::consts::PI).sin() * amplitude
+        })
+        .collect()
+}
+
+#[test]
+fn sine_reference_levels() {
+    // a 1 kHz sine in both channels measures at its peak level
+    let stereo = loudness::measure(&sine(1000.0, 0.1, 5.0, 2, 48000), 2, 48000);
+    assert!((stereo.integrated + 20.0).abs() < 0.1, "{:?}", stereo);
+    assert!((stereo.true_peak + 20.0).abs() < 0.1, "{:?}", stereo);
+
+    // and 3 dB quieter in one channel
+    let mono = loudness::measure(&sine(1000.0, 0.1, 5.0, 1, 44100), 1, 44100);
+    assert!((mono.integrated + 23.0).abs() < 0.1, "{:?}", mono);
+
+    let mut meter = LoudnessMeter::new(2, 48000);
+    let pcm: Vec<i16> = sine(1000.0, 0.1, 5.0, 2, 48000)
+        .iter()
+        .map(|&s| (s * 32768.0) as i16)
+        .collect();
+    meter.push_i16(&pcm);
+    assert!((meter.finish().integrated + 20.0).abs() < 0.1);
+}
+
+#[test]
+fn true_peak_between_samples() {
+    // a quarter of the sample rate, sampled 45 degrees off its peaks
+    let pcm: Vec<f32> = (0..48000)
+        .map(|i| (i as f32 * std::f32::consts::FRAC_PI_2 + std::f32::consts::FRAC_PI_4).sin())
+        .collect();
+    let sample_peak = 20.0 * pcm.iter().fold(0f32, |m, s| m.max(s.abs())).log10() as f64;
+    let measured = loudness::measure(&pcm, 1, 48000);
+    assert!((sample_peak + 3.0).abs() < 0.1);
+    assert!(measured.true_peak.abs() < 0.2, "{:?}", measured);
+}
+
+#[test]
+fn gating() {
+    let mut pcm = sine(1000.0, 0.1, 10.0, 2, 48000);
+    let loud = loudness::measure(&pcm, 2, 48000).integrated;
+    // silence is below the absolute gate, and a quiet passage the relative
+    pcm.extend(vec![0.0; 2 * 48000 * 10]);
+    pcm.extend(sine(1000.0, 0.001, 10.0, 2, 48000));
+    assert!((loudness::measure(&pcm, 2, 48000).integrated - loud).abs() < 0.1);
+
+    let silence = loudness::measure(&[0.0; 2 * 48000], 2, 48000);
+    assert_eq!(silence.integrated, f64::NEG_INFINITY);
+    assert_eq!(silence.true_peak, f64::NEG_INFINITY);
+    assert_eq!(silence.r128_track_gain(), 0);
+    assert_eq!(silence.normalize_gain(-16.0, -1.0), 0.0);
+
+    // shorter than one block
+    let short = loudness::measure(&sine(1000.0, 0.1, 0.3, 2, 48000), 2, 48000);
+    assert_eq!(short.integrated, f64::NEG_INFINITY);
+}
+
+#[test]
+fn normalization() {
+    let mut pcm = sine(1000.0, 0.1, 5.0, 2, 48000);
+    let measured = loudness::measure(&pcm, 2, 48000);
+
+    // 4 dB up to -16 LUFS, unless the peak would go over -18 dBTP
+    let gain = measured.normalize_gain(-16.0, -1.0);
+    assert!((gain - 4.0).abs() < 0.1);
+    assert!((measured.normalize_gain(-16.0, -18.0) - 2.0).abs() < 0.1);
+
+    loudness::apply_gain(&mut pcm, gain);
+    let normalized = loudness::measure(&pcm, 2, 48000);
+    assert!((normalized.integrated - measured.with_gain(gain).integrated).abs() < 0.01);
+    assert!((normalized.integrated + 16.0).abs() < 0.01);
+
+    let mut pcm = [1000i16, -1000, 20000, i16::MIN];
+    loudness::apply_gain_i16(&mut pcm, 20.0);
+    assert_eq!(pcm, [10000, -10000, i16::MAX, i16::MIN]);
+}
+
+#[test]
+fn track_gain_tag() {
+    let measured = loudness::measure(&sine(1000.0, 0.1, 5.0, 2, 48000), 2, 48000);
+    // -3 dB to the reference level of -23 LUFS, in Q7.8
+    assert!((measured.r128_track_gain() + 768).abs() <= 4);
+    assert_eq!(
+        measured
+            .with_gain(R128_REFERENCE - measured.integrated)
+            .r128_track_gain(),
+        0
+    );
+
+    let mut tags = OpusTags::new("test");
+    tags.comments
+        .push(("TITLE".to_string(), "tone".to_string()));
+    tags.comments
+        .push(("r128_track_gain".to_string(), "100".to_string()));
+    measured.write_tags(&mut tags);
+    assert_eq!(tags.comments.len(), 2);
+    assert_eq!(tags.comments[0].0, "TITLE");
+    assert_eq!(tags.comments[1].0, R128_TRACK_GAIN);
+    assert_eq!(tags.comments[1].1, measured.r128_track_gain().to_string());
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "std")]
+
+extern crate opus;
+
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use opus::mix::{vorbis_layout, wave_layout, Limiter, Mixer, Speaker};
+
+const STEREO: &[Speaker] = &[Speaker::FrontLeft, Speaker::FrontRight];
+const MONO: &[Speaker] = &[Speaker::FrontCenter];
+
+fn close(a: &[f32], b: &[f32]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-6)
+}
+
+#[test]
+fn layouts() {
+    for channels in 1..=8 {
+        assert_eq!(vorbis_layout(channels).unwrap().len(), channels);
+        assert_eq!(wave_layout(channels).unwrap().len(), channels);
+    }
+    assert!(vorbis_layout(0).is_none());
+    assert!(wave_layout(9).is_none());
+
+    // 5.1 from WAV order into the Vorbis order of Ogg Opus
+    let mut mixer = Mixer::remap(wave_layout(6).unwrap(), vorbis_layout(6).unwrap());
+    let mut output = [0.0; 6];
+    // L R C LFE Ls Rs
+    mixer.process(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6], &mut output);
+    // L C R Ls Rs LFE
+    assert_eq!(output, [0.1, 0.3, 0.2, 0.5, 0.6, 0.4]);
+}
+
+#[test]
+fn surround_to_stereo() {
+    let mixer = Mixer::downmix(vorbis_layout(6).unwrap(), STEREO);
+    let h = FRAC_1_SQRT_2;
+    // L C R Ls Rs LFE
+    #[rustfmt::skip]
+    let expected = [
+        1.0, h, 0.0, h, 0.0, 0.0,
+        0.0, h, 1.0, 0.0, h, 0.0,
+    ];
+    assert!(close(mixer.matrix(), &expected), "{:?}", mixer.matrix());
+
+    // the rear center of 6.1 splits between both sides
+    let mixer = Mixer::downmix(vorbis_layout(7).unwrap(), STEREO);
+    assert!(close(
+        &mixer.matrix()[..7],
+        &[1.0, h, 0.0, h, 0.0, 0.5, 0.0]
+    ));
+
+    // 7.1 to 5.1 folds the sides into the rears
+    let mixer = Mixer::downmix(vorbis_layout(8).unwrap(), vorbis_layout(6).unwrap());
+    assert_eq!(
+        &mixer.matrix()[3 * 8..4 * 8],
+        &[0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0]
+    );
+}
+
+#[test]
+fn mono_and_stereo() {
+    let h = FRAC_1_SQRT_2;
+    let mixer = Mixer::downmix(STEREO, MONO);
+    assert!(close(mixer.matrix(), &[h, h]));
+
+    // mono is played at full level on both sides
+    let mut mixer = Mixer::downmix(MONO, STEREO);
+    assert_eq!(mixer.matrix(), &[1.0, 1.0]);
+    let mut output = [0i16; 4];
+    assert_eq!(mixer.process_i16(&[1000, -2000, 5], &mut output), 2);
+    assert_eq!(output, [1000, 1000, -2000, -2000]);
+}
+
+#[test]
+fn limiter_prevents_clipping() {
+    let mut mixer = Mixer::downmix(STEREO, MONO).with_limiter(48000);
+    let input: Vec<f32> = (0..4800).flat_map(|_| vec![0.9, 0.9]).collect();
+    let mut output = vec![0.0; 4800];
+    assert_eq!(mixer.process(&input, &mut output), 4800);
+    assert!(output.iter().all(|s| s.abs() <= 1.0));
+    assert!((output[0] - 1.0).abs() < 1e-6);
+
+    // then recovers once the signal is quiet
+    let mut limiter = Limiter::new(1, 48000, 50);
+    limiter.apply(&mut [2.0]);
+    assert_eq!(limiter.gain(), 0.5);
+    let mut quiet = vec![0.1; 48000];
+    limiter.apply(&mut quiet);
+    assert!(limiter.gain() > 0.999);
+    assert!((quiet[0] - 0.05).abs() < 1e-4);
+    assert!((quiet[47999] - 0.1).abs() < 1e-4);
+}
@@ -0,0 +1,218 @@
+extern crate opus;
+
+use opus::storage::StateBuffer;
+use opus::{Application, MultistreamDecoder, MultistreamEncoder, StreamLayout};
+
+fn tone(channels: usize, frame_size: usize) -> Vec<i16> {
+    (0..frame_size * channels)
+        .map(|i| (((i / channels) as f32 * 0.05 * (1 + i % channels) as f32).sin() * 8000.0) as i16)
+        .collect()
+}
+
+#[test]
+fn surround_round_trip() {
+    let mut encoder = MultistreamEncoder::surround(48000, 6, 1, Application::Audio).unwrap();
+    let layout = encoder.layout().clone();
+    assert_eq!(layout.mapping.len(), 6);
+    assert_eq!(layout.streams, 4);
+    assert_eq!(layout.coupled_streams, 2);
+
+    let packet = encoder.encode_vec(&tone(6, 960), 4000).unwrap();
+    let mut decoder = MultistreamDecoder::new(48000, layout).unwrap();
+    let mut output = vec![0i16; 960 * 6];
+    assert_eq!(decoder.decode(&packet, &mut output, false).unwrap(), 960);
+}
+
+#[test]
+fn caller_storage_round_trip() {
+    let layout = StreamLayout {
+        channels: 3,
+        streams: 2,
+        coupled_streams: 1,
+        mapping: vec![0, 1, 2],
+    };
+    let mut enc_state = Box::new(StateBuffer::<{ 256 * 1024 }>::new());
+    let mut dec_state = Box::new(StateBuffer::<{ 256 * 1024 }>::new());
+    assert!(MultistreamEncoder::state_size(&layout).unwrap() <= enc_state.len());
+    assert!(MultistreamDecoder::state_size(&layout).unwrap() <= dec_state.len());
+
+    let mut encoder =
+        MultistreamEncoder::new_in(&mut *enc_state, 48000, layout.clone(), Application::Audio)
+            .unwrap();
+    let packet = encoder.encode_vec(&tone(3, 960), 4000).unwrap();
+    let mut decoder = MultistreamDecoder::new_in(&mut *dec_state, 48000, layout).unwrap();
+    let mut output = vec![0i16; 960 * 3];
+    assert_eq!(decoder.decode(&packet, &mut output, false).unwrap(), 960);
+
+    drop(encoder);
+    let size = MultistreamEncoder::surround_state_size(6, 1).unwrap();
+    assert!(size <= enc_state.len());
+    let encoder =
+        MultistreamEncoder::surround_in(enc_state, 48000, 6, 1, Application::Audio).unwrap();
+    assert_eq!(encoder.layout().mapping.len(), 6);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_packets_decode() {
+    use opus::{Bitrate, ParallelMultistreamEncoder};
+
+    let mut encoder =
+        ParallelMultistreamEncoder::surround(48000, 6, 1, Application::Audio).unwrap();
+    encoder.set_bitrate(Bitrate::Bits(256000)).unwrap();
+    let mut decoder = MultistreamDecoder::new(48000, encoder.layout().clone()).unwrap();
+    let mut output = vec![0i16; 960 * 6];
+    for _ in 0..5 {
+        let packet = encoder.encode_vec(&tone(6, 960), 4000).unwrap();
+        assert_eq!(decoder.decode(&packet, &mut output, false).unwrap(), 960);
+    }
+    assert!(encoder.encode_vec(&tone(6, 960), 10).is_err());
+}
+
+#[test]
+fn packet_split_and_join() {
+    use opus::MultistreamPacket;
+
+    // a single frame, two VBR frames with a two-byte size, and two CBR frames
+    let first = [0x08, 1, 2, 3];
+    let mut second = vec![0x0b, 0x82, 252, 12];
+    second.extend((0..302).map(|i| i as u8));
+    let last = [0x09, 5, 6, 7, 8];
+
+    let mut output = vec![0; 400];
+    let len = MultistreamPacket::join(&[&first, &second, &last], &mut output).unwrap();
+    let joined = &output[..len];
+    assert_eq!(len, 5 + 307 + 5);
+    assert_eq!(&joined[..5], &[0x08, 3, 1, 2, 3]);
+    assert_eq!(&joined[5..10], &[0x0b, 0x82, 252, 12, 2]);
+
+    let packet = MultistreamPacket::new(joined, 3);
+    let split = packet.split().unwrap();
+    assert_eq!(split.len(), 3);
+    assert_eq!(split[0], &joined[..5]);
+    assert_eq!(split[1], &joined[5..312]);
+    assert_eq!(split[2], &last);
+    assert_eq!(
+        packet.unpack().unwrap(),
+        vec![first.to_vec(), second, last.to_vec()]
+    );
+
+    // a self-delimited code 1 packet gives one size for both frames, and is
+    // unpacked as a code 3 packet
+    let cbr = [0x09, 2, 5, 6, 7, 8, 0x08, 9];
+    let packet = MultistreamPacket::new(&cbr, 2);
+    assert_eq!(packet.split().unwrap(), vec![&cbr[..6], &cbr[6..]]);
+    assert_eq!(packet.unpack().unwrap()[0], vec![0x0b, 0x02, 5, 6, 7, 8]);
+}
+
+#[test]
+fn packet_split_rejects_bad_input() {
+    use opus::MultistreamPacket;
+
+    let packet = [0x08, 3, 1, 2, 3, 0x08, 4];
+    assert!(MultistreamPacket::new(&packet, 0).split().is_err());
+    assert!(MultistreamPacket::new(&packet, 3).split().is_err());
+    assert!(MultistreamPacket::new(&packet[..4], 2).split().is_err());
+    assert!(MultistreamPacket::new(&[0x09, 1, 2, 3], 1).split().is_err());
+    assert!(MultistreamPacket::new(&[0x0b, 0], 1).split().is_err());
+
+    let mut output = [0; 8];
+    assert!(MultistreamPacket::join(&[], &mut output).is_err());
+    assert!(MultistreamPacket::join(&[&[0x08, 1, 2, 3], &[0x08; 6]], &mut output).is_err());
+    assert_eq!(
+        MultistreamPacket::join(&[&[0x08, 1, 2], &[0x08, 4]], &mut output).unwrap(),
+        6
+    );
+}
+
+#[test]
+fn packet_streams_decode_alone() {
+    use opus::{Channels, Decoder, MultistreamPacket};
+
+    let mut encoder = MultistreamEncoder::surround(48000, 6, 1, Application::Audio).unwrap();
+    let layout = encoder.layout().clone();
+    let packet = encoder.encode_vec(&tone(6, 960), 4000).unwrap();
+    let streams = MultistreamPacket::new(&packet, layout.streams)
+        .unpack()
+        .unwrap();
+    assert_eq!(streams.len(), layout.streams as usize);
+
+    let mut output = vec![0i16; 960 * 2];
+    for (i, stream) in streams.iter().enumerate() {
+        let channels = if i < layout.coupled_streams as usize {
+            Channels::Stereo
+        } else {
+            Channels::Mono
+        };
+        let mut decoder = Decoder::new(48000, channels).unwrap();
+        assert_eq!(decoder.decode(stream, &mut output, false).unwrap(), 960);
+    }
+
+    let mut padded = packet.clone();
+    padded.resize(packet.len() + 100, 0);
+    let len = opus::packet::multistream_pad(&mut padded, packet.len(), layout.streams).unwrap();
+    assert_eq!(len, packet.len() + 100);
+    let len = opus::packet::multistream_unpad(&mut padded, layout.streams).unwrap();
+    assert!(len <= packet.len());
+    let split = MultistreamPacket::new(&padded[..len], layout.streams)
+        .split()
+        .unwrap();
+    assert_eq!(split.len(), layout.streams as usize);
+}
+
+#[test]
+fn weighted_bitrates_favor_coupled_and_starve_lfe() {
+    use opus::Bitrate;
+
+    // 5.1: two coupled streams, the center, and the LFE
+    let layout = StreamLayout {
+        channels: 6,
+        streams: 4,
+        coupled_streams: 2,
+        mapping: vec![0, 4, 1, 2, 3, 5],
+    };
+    let bitrates = layout.weighted_bitrates(330_000, Some(3));
+    assert_eq!(
+        bitrates,
+        vec![
+            Bitrate::Bits(120_000),
+            Bitrate::Bits(120_000),
+            Bitrate::Bits(80_000),
+            Bitrate::Bits(10_000),
+        ]
+    );
+
+    // no stream falls below the libopus minimum
+    let bitrates = layout.weighted_bitrates(1000, Some(3));
+    assert!(bitrates.iter().all(|&b| b == Bitrate::Bits(500)));
+}
+
+#[test]
+fn weighted_bitrate_forwards() {
+    use opus::Bitrate;
+
+    let mut encoder = MultistreamEncoder::surround(48000, 6, 1, Application::Audio).unwrap();
+    let layout = encoder.layout().clone();
+    assert!(encoder
+        .set_stream_bitrates(&[Bitrate::Bits(64000)])
+        .is_err());
+
+    encoder.set_weighted_bitrate(330_000).unwrap();
+    assert_eq!(encoder.get_bitrate().unwrap(), Bitrate::Bits(330_000));
+    let mut decoder = MultistreamDecoder::new(48000, layout.clone()).unwrap();
+    let mut output = vec![0i16; 960 * 6];
+    for _ in 0..5 {
+        let packet = encoder.encode_vec(&tone(6, 960), 4000).unwrap();
+        assert_eq!(decoder.decode(&packet, &mut output, false).unwrap(), 960);
+        let streams = opus::MultistreamPacket::new(&packet, layout.streams)
+            .split()
+            .unwrap();
+        // the LFE stream is coded at a fraction of the others, whichever
+        // split libopus settles on
+        assert!(streams[3].len() < streams[0].len());
+    }
+
+    encoder.set_bitrate(Bitrate::Bits(256000)).unwrap();
+    let packet = encoder.encode_vec(&tone(6, 960), 4000).unwrap();
+    assert_eq!(decoder.decode(&packet, &mut output, false).unwrap(), 960);
+}
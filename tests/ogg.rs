@@ -0,0 +1,830 @@
+#![cfg(feature = "std")]
+
+extern crate opus;
+
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::time::Duration;
+
+use opus::ogg::{
+    encode_voice_message, Chapter, Event, GainPolicy, OggDemuxer, OggOpusReader, OggSliceReader,
+    OpusFile, OpusHead, OpusTags, PacketAssembler, Page, PageRef, ReplayGain, VoiceQuality,
+};
+use opus::pipeline::PacketSource;
+use opus::pool::BufferPool;
+use opus::{Application, MultistreamEncoder, StreamLayout};
+
+#[test]
+fn page_round_trip() {
+    let mut page = Page {
+        flags: opus::ogg::page::BOS,
+        granule_position: Some(0),
+        serial: 0x1234,
+        ..Page::default()
+    };
+    assert!(page.push_packet(b"hello"));
+    let mut bytes = Vec::new();
+    page.write(&mut bytes);
+    assert_eq!(bytes.len(), page.len());
+
+    let (parsed, len) = Page::parse(&bytes).unwrap().unwrap();
+    assert_eq!(len, bytes.len());
+    assert_eq!(parsed, page);
+    assert!(parsed.is_bos());
+
+    // incomplete input
+    assert!(Page::parse(&bytes[..bytes.len() - 1]).unwrap().is_none());
+    assert!(Page::parse(&bytes[..3]).unwrap().is_none());
+
+    // corrupted checksum
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    assert!(Page::parse(&bytes).is_err());
+}
+
+#[test]
+fn packets_spanning_pages() {
+    let big = vec![7u8; 600];
+    let mut first = Page {
+        lacing: vec![255, 255],
+        body: big[..510].to_vec(),
+        ..Page::default()
+    };
+    let mut second = Page {
+        flags: opus::ogg::page::CONTINUED,
+        lacing: vec![90],
+        body: big[510..].to_vec(),
+        ..Page::default()
+    };
+    assert!(second.push_packet(b"tail"));
+
+    let mut packets = Vec::new();
+    let mut assembler = PacketAssembler::new();
+    assembler.push(&first, |p| packets.push(p.to_vec()));
+    assert!(packets.is_empty());
+    assembler.push(&second, |p| packets.push(p.to_vec()));
+    assert_eq!(packets, vec![big.clone(), b"tail".to_vec()]);
+
+    // a continuation with nothing to continue is dropped
+    let mut packets = Vec::new();
+    let mut assembler = PacketAssembler::new();
+    assembler.push(&second, |p| packets.push(p.to_vec()));
+    assert_eq!(packets, vec![b"tail".to_vec()]);
+
+    // an unfinished packet is dropped if the next page does not continue it
+    first.flags = 0;
+    second.flags = 0;
+    let mut packets = Vec::new();
+    let mut assembler = PacketAssembler::new();
+    assembler.push(&first, |p| packets.push(p.to_vec()));
+    assembler.push(&second, |p| packets.push(p.to_vec()));
+    assert_eq!(packets, vec![big[510..].to_vec(), b"tail".to_vec()]);
+}
+
+#[test]
+fn headers_round_trip() {
+    let head = OpusHead::new(2, 312, 44100);
+    let mut bytes = Vec::new();
+    head.write(&mut bytes);
+    assert_eq!(bytes.len(), 19);
+    assert_eq!(OpusHead::parse(&bytes).unwrap(), head);
+    assert!(OpusHead::parse(&bytes[..18]).is_err());
+
+    let mut tags = OpusTags::new("opus-rs");
+    tags.comments.push(("TITLE".to_string(), "a=b".to_string()));
+    let mut bytes = Vec::new();
+    tags.write(&mut bytes);
+    assert_eq!(OpusTags::parse(&bytes).unwrap(), tags);
+    assert!(OpusTags::parse(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn discrete_head_round_trip() {
+    let layout = StreamLayout::discrete(16);
+    assert!(layout.is_valid());
+    let head = OpusHead::multistream(255, &layout, 312, 48000).unwrap();
+    assert_eq!(head.layout(), layout);
+    let mut bytes = Vec::new();
+    head.write(&mut bytes);
+    assert_eq!(bytes.len(), 21 + 16);
+    assert_eq!(OpusHead::parse(&bytes).unwrap(), head);
+
+    // a channel mapped past the last stream
+    bytes[21 + 15] = 16;
+    assert!(OpusHead::parse(&bytes).is_err());
+    bytes[21 + 15] = 255;
+    assert!(OpusHead::parse(&bytes).is_ok());
+    // more coupled streams than streams
+    bytes[20] = 17;
+    assert!(OpusHead::parse(&bytes).is_err());
+
+    assert!(OpusHead::multistream(0, &layout, 312, 48000).is_err());
+    let mut bad = layout.clone();
+    bad.mapping.pop();
+    assert!(!bad.is_valid());
+    assert!(OpusHead::multistream(255, &bad, 312, 48000).is_err());
+}
+
+/// Build an Ogg Opus stream with three 20ms packets on two pages, the last
+/// trimmed to 100 samples.
+fn sample_stream() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut head = Page {
+        flags: opus::ogg::page::BOS,
+        granule_position: Some(0),
+        serial: 7,
+        ..Page::default()
+    };
+    let mut packet = Vec::new();
+    OpusHead::new(1, 312, 48000).write(&mut packet);
+    head.push_packet(&packet);
+    head.write(&mut bytes);
+
+    let mut tags = Page {
+        granule_position: Some(0),
+        serial: 7,
+        sequence: 1,
+        ..Page::default()
+    };
+    packet.clear();
+    OpusTags::new("opus-rs").write(&mut packet);
+    tags.push_packet(&packet);
+    tags.write(&mut bytes);
+
+    // a TOC byte for a single 20ms SILK frame
+    let frame = [0x08];
+    let mut audio = Page {
+        granule_position: Some(2 * 960),
+        serial: 7,
+        sequence: 2,
+        ..Page::default()
+    };
+    audio.push_packet(&frame);
+    audio.push_packet(&frame);
+    audio.write(&mut bytes);
+    let mut last = Page {
+        flags: opus::ogg::page::EOS,
+        granule_position: Some(2 * 960 + 100),
+        serial: 7,
+        sequence: 3,
+        ..Page::default()
+    };
+    last.push_packet(&frame);
+    last.write(&mut bytes);
+    bytes
+}
+
+#[test]
+fn tag_accessors() {
+    let mut tags = OpusTags::new("opus-rs");
+    tags.set_title("Episode 12");
+    tags.add("Artist", "Alice");
+    tags.add("ARTIST", "Bob");
+    tags.set_track_number(3);
+    tags.set_chapter(1, "00:00:00.000", "Intro");
+    assert_eq!(tags.title(), Some("Episode 12"));
+    assert_eq!(tags.get("title"), Some("Episode 12"));
+    assert_eq!(tags.artist(), Some("Alice"));
+    assert_eq!(tags.get_all("artist"), vec!["Alice", "Bob"]);
+    assert_eq!(tags.track_number(), Some(3));
+    assert_eq!(tags.chapter_time(1), Some("00:00:00.000"));
+    assert_eq!(tags.chapter_name(1), Some("Intro"));
+    assert_eq!(tags.get("CHAPTER001NAME"), Some("Intro"));
+    assert_eq!(tags.album(), None);
+
+    // replacing keeps the first value's place
+    tags.set_artist("Carol");
+    assert_eq!(
+        tags.comments[1],
+        ("Artist".to_string(), "Carol".to_string())
+    );
+    assert_eq!(tags.get_all("ARTIST"), vec!["Carol"]);
+    tags.set("TRACKNUMBER", "4/12");
+    assert_eq!(tags.track_number(), Some(4));
+    assert_eq!(tags.remove("tracknumber"), 1);
+    assert_eq!(tags.track_number(), None);
+
+    let mut bytes = Vec::new();
+    tags.write(&mut bytes);
+    assert_eq!(OpusTags::parse(&bytes).unwrap(), tags);
+}
+
+#[test]
+fn chapters() {
+    let mut tags = OpusTags::new("opus-rs");
+    tags.set_title("Episode 12");
+    tags.add("chapter002", "1:02:03.5");
+    tags.add("CHAPTER002NAME", "Interview");
+    tags.add("CHAPTER001", "00:00:00.000");
+    tags.add("CHAPTER001NAME", "Intro");
+    tags.add("CHAPTER003", "soon");
+    tags.add("CHAPTER004", "90");
+    assert_eq!(
+        tags.chapters(),
+        vec![
+            Chapter {
+                start: Duration::from_secs(0),
+                title: "Intro".to_string(),
+            },
+            Chapter {
+                start: Duration::from_millis(3_723_500),
+                title: "Interview".to_string(),
+            },
+            Chapter {
+                start: Duration::from_secs(90),
+                title: String::new(),
+            },
+        ]
+    );
+
+    let chapters = vec![
+        Chapter {
+            start: Duration::from_millis(1500),
+            title: "Opening".to_string(),
+        },
+        Chapter {
+            start: Duration::from_millis(3_723_004),
+            title: "Questions".to_string(),
+        },
+    ];
+    tags.set_chapters(&chapters);
+    assert_eq!(tags.comments.len(), 5);
+    assert_eq!(tags.title(), Some("Episode 12"));
+    assert_eq!(tags.chapter_time(1), Some("00:00:01.500"));
+    assert_eq!(tags.chapter_time(2), Some("01:02:03.004"));
+    assert_eq!(tags.chapter_name(2), Some("Questions"));
+    assert_eq!(tags.chapters(), chapters);
+}
+
+/// Build a base64 FLAC picture block.
+fn picture_block(picture_type: u32, mime: &str, description: &str, data: &[u8]) -> String {
+    let mut block = Vec::new();
+    block.extend_from_slice(&picture_type.to_be_bytes());
+    for text in &[mime, description] {
+        block.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        block.extend_from_slice(text.as_bytes());
+    }
+    for &value in &[640u32, 480, 24, 0, data.len() as u32] {
+        block.extend_from_slice(&value.to_be_bytes());
+    }
+    block.extend_from_slice(data);
+
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in block.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn pictures() {
+    let png = b"\x89PNG\r\n\x1a\n rest of the image";
+    let mut tags = OpusTags::new("opus-rs");
+    tags.add(
+        "METADATA_BLOCK_PICTURE",
+        picture_block(3, "image/jpg", "Cover", png),
+    );
+    tags.add(
+        "metadata_block_picture",
+        picture_block(4, "image/x-unknown", "", b"ab"),
+    );
+    tags.add("METADATA_BLOCK_PICTURE", "not base64!");
+    tags.add(
+        "METADATA_BLOCK_PICTURE",
+        picture_block(0, "-->", "", b"https://example.com/cover.png"),
+    );
+
+    let pictures = tags.pictures();
+    assert_eq!(pictures.len(), 3);
+    assert!(pictures[0].is_front_cover());
+    // the declared type is wrong
+    assert_eq!(pictures[0].mime, "image/png");
+    assert_eq!(pictures[0].description, "Cover");
+    assert_eq!((pictures[0].width, pictures[0].height), (640, 480));
+    assert_eq!(pictures[0].data, &png[..]);
+    assert_eq!(pictures[1].mime, "image/x-unknown");
+    assert_eq!(pictures[1].data, b"ab");
+    assert_eq!(pictures[2].mime, "-->");
+
+    let small = tags.pictures_with_limit(10);
+    assert_eq!(small.len(), 1);
+    assert_eq!(small[0].data, b"ab");
+
+    // a data length running past the block
+    let mut block = picture_block(3, "image/png", "", png);
+    block.truncate(block.len() - 8);
+    tags.comments.clear();
+    tags.add("METADATA_BLOCK_PICTURE", block);
+    assert!(tags.pictures().is_empty());
+}
+
+#[test]
+#[should_panic]
+fn tag_field_names_are_checked() {
+    OpusTags::new("opus-rs").add("TITLE=", "x");
+}
+
+#[test]
+fn gain_policy() {
+    let mut head = OpusHead::new(2, 312, 48000);
+    head.output_gain = 256;
+    let mut tags = OpusTags::new("opus-rs");
+    tags.comments
+        .push(("r128_track_gain".to_string(), "-1280".to_string()));
+    assert_eq!(tags.r128_track_gain(), Some(-1280));
+    assert_eq!(tags.r128_album_gain(), None);
+
+    assert_eq!(GainPolicy::default().gain_db(&head, Some(&tags)), 1.0);
+    assert_eq!(GainPolicy::track().gain_db(&head, Some(&tags)), -4.0);
+    // the album gain falls back to the track gain
+    assert_eq!(GainPolicy::album().gain_db(&head, Some(&tags)), -4.0);
+    tags.comments
+        .push(("R128_ALBUM_GAIN".to_string(), "+512".to_string()));
+    assert_eq!(tags.r128_album_gain(), None);
+    tags.comments[1].1 = "512".to_string();
+    assert_eq!(GainPolicy::album().gain_db(&head, Some(&tags)), 3.0);
+
+    let policy = GainPolicy {
+        header_gain: false,
+        replay_gain: ReplayGain::Track,
+        user_gain: 6.0,
+    };
+    assert_eq!(policy.gain_db(&head, Some(&tags)), 1.0);
+    assert_eq!(policy.gain_db(&head, None), 6.0);
+    assert_eq!(policy.split(&head, Some(&tags)), (256, 1.0));
+
+    // beyond the decoder's range, the rest is a linear scale
+    let policy = GainPolicy {
+        user_gain: -140.0,
+        ..GainPolicy::default()
+    };
+    let (gain, scale) = policy.split(&head, None);
+    assert_eq!(gain, -32768);
+    assert!((scale - 10f32.powf(-11.0 / 20.0)).abs() < 1e-6);
+}
+
+#[test]
+fn demux_byte_by_byte() {
+    let bytes = sample_stream();
+    let mut demuxer = OggDemuxer::new();
+    let mut events = Vec::new();
+    for byte in &bytes {
+        events.extend(demuxer.push_bytes(&[*byte]).unwrap());
+    }
+    assert_eq!(demuxer.buffered(), 0);
+    assert_eq!(events.len(), 6);
+    match events[0] {
+        Event::Header(ref head) => assert_eq!(head.pre_skip, 312),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    match events[1] {
+        Event::Tags(ref tags) => assert_eq!(tags.vendor, "opus-rs"),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    let audio: Vec<_> = events[2..5]
+        .iter()
+        .map(|event| match *event {
+            Event::Audio(ref audio) => audio.clone(),
+            ref other => panic!("unexpected {:?}", other),
+        })
+        .collect();
+    let timestamps: Vec<_> = audio.iter().map(|a| a.info.timestamp).collect();
+    assert_eq!(timestamps, vec![0, 960, 1920]);
+    let playable: Vec<_> = audio.iter().map(|a| a.playable()).collect();
+    assert_eq!(playable, vec![960 - 312, 960, 100]);
+    assert_eq!(events[5], Event::Eos);
+}
+
+struct CountingPool {
+    free: Vec<Vec<u8>>,
+    allocated: usize,
+}
+
+impl BufferPool for CountingPool {
+    fn take(&mut self) -> Vec<u8> {
+        self.free.pop().unwrap_or_else(|| {
+            self.allocated += 1;
+            Vec::new()
+        })
+    }
+
+    fn recycle(&mut self, mut buf: Vec<u8>) {
+        buf.clear();
+        self.free.push(buf);
+    }
+}
+
+#[test]
+fn demux_recycles_buffers() {
+    let pool = CountingPool {
+        free: Vec::new(),
+        allocated: 0,
+    };
+    let mut demuxer = OggDemuxer::with_pool(pool);
+    let mut events = Vec::new();
+    for _ in 0..3 {
+        demuxer
+            .push_bytes_into(&sample_stream(), &mut events)
+            .unwrap();
+        for event in events.drain(..) {
+            if let Event::Audio(audio) = event {
+                demuxer.recycle(audio.data);
+            }
+        }
+    }
+    // only the first chain link needs fresh buffers, one per audio packet
+    assert_eq!(demuxer.pool().allocated, 3);
+}
+
+#[test]
+fn sync_reader() {
+    let bytes = sample_stream();
+    let mut reader = OggOpusReader::new(Cursor::new(bytes));
+    assert_eq!(reader.read_headers().unwrap().channels, 1);
+    assert_eq!(reader.tags().unwrap().vendor, "opus-rs");
+    let mut count = 0;
+    while let Some((packet, info)) = reader.next_packet().unwrap() {
+        assert_eq!(packet, vec![0x08]);
+        assert_eq!(info.sequence, count);
+        count += 1;
+    }
+    assert_eq!(count, 3);
+}
+
+/// Build an Ogg Opus stream with one 20ms packet on each of `pages` audio
+/// pages, returning it and the offset of each audio page.
+fn paged_stream(pages: u32) -> (Vec<u8>, Vec<usize>) {
+    let mut bytes = Vec::new();
+    let mut packet = Vec::new();
+    let mut head = Page {
+        flags: opus::ogg::page::BOS,
+        granule_position: Some(0),
+        serial: 7,
+        ..Page::default()
+    };
+    OpusHead::new(1, 0, 48000).write(&mut packet);
+    head.push_packet(&packet);
+    head.write(&mut bytes);
+    let mut tags = Page {
+        granule_position: Some(0),
+        serial: 7,
+        sequence: 1,
+        ..Page::default()
+    };
+    packet.clear();
+    OpusTags::new("opus-rs").write(&mut packet);
+    tags.push_packet(&packet);
+    tags.write(&mut bytes);
+
+    let mut offsets = Vec::new();
+    for i in 1..=pages {
+        let mut audio = Page {
+            flags: if i == pages { opus::ogg::page::EOS } else { 0 },
+            granule_position: Some(i as u64 * 960),
+            serial: 7,
+            sequence: i + 1,
+            ..Page::default()
+        };
+        audio.push_packet(&[0x08]);
+        offsets.push(bytes.len());
+        audio.write(&mut bytes);
+    }
+    (bytes, offsets)
+}
+
+#[test]
+fn lenient_demux_skips_damaged_pages() {
+    let (mut bytes, offsets) = paged_stream(4);
+    // break the checksum of the second audio page
+    let last = offsets[2] - 1;
+    bytes[last] ^= 0xff;
+    assert!(OggDemuxer::new().push_bytes(&bytes).is_err());
+
+    let mut demuxer = OggDemuxer::new();
+    demuxer.set_lenient(true);
+    let mut events = Vec::new();
+    for chunk in bytes.chunks(7) {
+        events.extend(demuxer.push_bytes(chunk).unwrap());
+    }
+    let timeline: Vec<_> = events[2..]
+        .iter()
+        .map(|event| match *event {
+            Event::Audio(ref audio) => (audio.info.timestamp, 0),
+            Event::DataLoss { samples } => (0, samples),
+            Event::Eos => (0, 0),
+            ref other => panic!("unexpected {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        timeline,
+        vec![(0, 0), (0, 960), (1920, 0), (2880, 0), (0, 0)]
+    );
+}
+
+#[test]
+fn lenient_reader_salvages_truncated_stream() {
+    let (bytes, offsets) = paged_stream(3);
+    // cut the last page off after its header
+    let bytes = bytes[..offsets[2] + 28].to_vec();
+    let mut reader = OggOpusReader::new(Cursor::new(bytes.clone()));
+    assert_eq!(reader.next_audio().unwrap().unwrap().info.timestamp, 0);
+    assert_eq!(reader.next_audio().unwrap().unwrap().info.timestamp, 960);
+    assert!(reader.next_audio().is_err());
+
+    let mut reader = OggOpusReader::new(Cursor::new(bytes));
+    reader.set_lenient(true);
+    let mut count = 0;
+    while reader.next_audio().unwrap().is_some() {
+        assert_eq!(reader.take_data_loss(), 0);
+        count += 1;
+    }
+    assert_eq!(count, 2);
+    // the granule position of a cut-off page can't be trusted
+    assert_eq!(reader.take_data_loss(), 0);
+}
+
+#[test]
+fn lenient_demux_salvages_pages_after_damaged_lacing() {
+    let (mut bytes, offsets) = paged_stream(5);
+    // the second audio page claims a segment longer than the rest of the input
+    bytes[offsets[1] + 27] = 255;
+
+    let mut demuxer = OggDemuxer::new();
+    demuxer.set_lenient(true);
+    let mut events = demuxer.push_bytes(&bytes).unwrap();
+    demuxer.finish_into(&mut events).unwrap();
+    let timeline: Vec<_> = events[2..]
+        .iter()
+        .map(|event| match *event {
+            Event::Audio(ref audio) => (audio.info.timestamp, 0),
+            Event::DataLoss { samples } => (0, samples),
+            Event::Eos => (0, 0),
+            ref other => panic!("unexpected {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        timeline,
+        vec![(0, 0), (0, 960), (1920, 0), (2880, 0), (3840, 0), (0, 0)]
+    );
+}
+
+#[test]
+fn reader_resyncs_after_seeking() {
+    let (bytes, offsets) = paged_stream(10);
+    let mut reader = OggOpusReader::new(Cursor::new(bytes));
+    reader.read_headers().unwrap();
+    assert_eq!(reader.serial(), Some(7));
+    reader.get_mut().set_position(offsets[6] as u64 + 3);
+    reader.resync();
+    // the rest of the seventh page is skipped
+    let audio = reader.next_audio().unwrap().unwrap();
+    assert_eq!(audio.info.timestamp, 7 * 960);
+    assert_eq!(reader.head().unwrap().pre_skip, 0);
+
+    reader.get_mut().set_position(0);
+    reader.reset();
+    assert!(reader.head().is_none());
+    reader.read_headers().unwrap();
+    let audio = reader.next_audio().unwrap().unwrap();
+    assert_eq!(audio.info.timestamp, 0);
+}
+
+#[test]
+fn slice_reader_borrows_packets() {
+    let bytes = sample_stream();
+    let expected: Vec<_> = OggDemuxer::new()
+        .push_bytes(&bytes)
+        .unwrap()
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::Audio(audio) => Some(audio),
+            _ => None,
+        })
+        .collect();
+
+    let mut reader = OggSliceReader::new(&bytes);
+    assert_eq!(reader.read_headers().unwrap().pre_skip, 312);
+    assert_eq!(reader.tags().unwrap().vendor, "opus-rs");
+    let mut audio = Vec::new();
+    while let Some(packet) = reader.next_audio().unwrap() {
+        match packet.data {
+            Cow::Borrowed(data) => assert_eq!(data, &[0x08]),
+            Cow::Owned(_) => panic!("single-page packet was copied"),
+        }
+        audio.push(packet);
+    }
+    assert_eq!(reader.offset(), bytes.len());
+    assert_eq!(audio.len(), expected.len());
+    for (audio, expected) in audio.iter().zip(&expected) {
+        assert_eq!(audio.info, expected.info);
+        assert_eq!(audio.trim_start, expected.trim_start);
+        assert_eq!(audio.trim_end, expected.trim_end);
+    }
+
+    // truncated input
+    let mut reader = OggSliceReader::new(&bytes[..bytes.len() - 1]);
+    reader.read_headers().unwrap();
+    assert!(reader.next_audio().unwrap().is_some());
+    assert!(reader.next_audio().unwrap().is_some());
+    assert!(reader.next_audio().is_err());
+}
+
+#[test]
+fn borrowed_packets_spanning_pages() {
+    let big = vec![7u8; 600];
+    let first = Page {
+        lacing: vec![255, 255],
+        body: big[..510].to_vec(),
+        ..Page::default()
+    };
+    let mut second = Page {
+        flags: opus::ogg::page::CONTINUED,
+        lacing: vec![90],
+        body: big[510..].to_vec(),
+        ..Page::default()
+    };
+    assert!(second.push_packet(b"tail"));
+    let mut bytes = Vec::new();
+    first.write(&mut bytes);
+    second.write(&mut bytes);
+
+    let (first, len) = PageRef::parse(&bytes).unwrap().unwrap();
+    let (second, _) = PageRef::parse(&bytes[len..]).unwrap().unwrap();
+    assert!(second.is_continued());
+    assert_eq!(
+        second.to_page(),
+        Page::parse(&bytes[len..]).unwrap().unwrap().0
+    );
+
+    let mut packets = Vec::new();
+    let mut assembler = PacketAssembler::new();
+    assembler.push_ref(&first, |p| packets.push(p));
+    assert!(packets.is_empty());
+    assembler.push_ref(&second, |p| packets.push(p));
+    assert_eq!(packets.len(), 2);
+    match (&packets[0], &packets[1]) {
+        (Cow::Owned(joined), Cow::Borrowed(tail)) => {
+            assert_eq!(*joined, big);
+            assert_eq!(tail, b"tail");
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn opus_file_samples() {
+    let mut file = OpusFile::new(Cursor::new(sample_stream())).unwrap();
+    assert_eq!(file.channels(), 1);
+    assert_eq!(file.head().pre_skip, 312);
+    assert_eq!(file.by_ref().count(), 960 - 312 + 960 + 100);
+    assert!(file.take_error().is_none());
+}
+
+#[test]
+fn opus_file_seeks() {
+    let (bytes, _) = paged_stream(100);
+    let mut file = OpusFile::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(file.duration().unwrap(), Duration::from_secs(2));
+    assert_eq!(file.frames().next().unwrap().unwrap().len(), 960);
+
+    file.seek(Duration::from_millis(1010)).unwrap();
+    assert_eq!(file.decode_all().unwrap().len(), 2 * 48000 - 48480);
+    assert_eq!(file.frames().count(), 0);
+
+    // back to within the first page
+    file.seek(Duration::from_millis(5)).unwrap();
+    assert_eq!(file.frames().next().unwrap().unwrap().len(), 960 - 240);
+    assert_eq!(file.duration().unwrap(), Duration::from_secs(2));
+}
+
+#[test]
+fn opus_file_downmixes_surround() {
+    let mut encoder = MultistreamEncoder::surround(48000, 6, 1, Application::Audio).unwrap();
+    let layout = encoder.layout().clone();
+    let mut head = OpusHead::new(6, 312, 48000);
+    head.mapping_family = 1;
+    head.stream_count = layout.streams;
+    head.coupled_count = layout.coupled_streams;
+    head.mapping = layout.mapping.clone();
+    assert_eq!(head.layout(), layout);
+
+    let mut bytes = Vec::new();
+    let mut page = Page {
+        flags: opus::ogg::page::BOS,
+        granule_position: Some(0),
+        serial: 3,
+        ..Page::default()
+    };
+    let mut packet = Vec::new();
+    head.write(&mut packet);
+    page.push_packet(&packet);
+    page.write(&mut bytes);
+    page = Page {
+        granule_position: Some(0),
+        serial: 3,
+        sequence: 1,
+        ..Page::default()
+    };
+    packet.clear();
+    OpusTags::new("opus-rs").write(&mut packet);
+    page.push_packet(&packet);
+    page.write(&mut bytes);
+    page = Page {
+        flags: opus::ogg::page::EOS,
+        granule_position: Some(3 * 960),
+        serial: 3,
+        sequence: 2,
+        ..Page::default()
+    };
+    let pcm = vec![1000i16; 960 * 6];
+    for _ in 0..3 {
+        page.push_packet(&encoder.encode_vec(&pcm, 4000).unwrap());
+    }
+    page.write(&mut bytes);
+
+    let mut file = OpusFile::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(file.head().channels, 6);
+    assert_eq!(file.channels(), 2);
+    assert_eq!(file.by_ref().count(), 2 * (3 * 960 - 312));
+    assert!(file.take_error().is_none());
+}
+
+#[test]
+fn opus_file_decodes_discrete_channels() {
+    let mut encoder = MultistreamEncoder::surround(48000, 16, 255, Application::Audio).unwrap();
+    let layout = encoder.layout().clone();
+    assert_eq!(layout, StreamLayout::discrete(16));
+    let head = OpusHead::multistream(255, &layout, 312, 48000).unwrap();
+
+    let mut bytes = Vec::new();
+    let mut page = Page {
+        flags: opus::ogg::page::BOS,
+        granule_position: Some(0),
+        serial: 5,
+        ..Page::default()
+    };
+    let mut packet = Vec::new();
+    head.write(&mut packet);
+    page.push_packet(&packet);
+    page.write(&mut bytes);
+    page = Page {
+        granule_position: Some(0),
+        serial: 5,
+        sequence: 1,
+        ..Page::default()
+    };
+    packet.clear();
+    OpusTags::new("opus-rs").write(&mut packet);
+    page.push_packet(&packet);
+    page.write(&mut bytes);
+    page = Page {
+        flags: opus::ogg::page::EOS,
+        granule_position: Some(2 * 960),
+        serial: 5,
+        sequence: 2,
+        ..Page::default()
+    };
+    let pcm = vec![1000i16; 960 * 16];
+    for _ in 0..2 {
+        page.push_packet(&encoder.encode_vec(&pcm, 8000).unwrap());
+    }
+    page.write(&mut bytes);
+
+    let mut file = OpusFile::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(file.head().mapping_family, 255);
+    assert_eq!(file.channels(), 16);
+    assert_eq!(file.by_ref().count(), 16 * (2 * 960 - 312));
+    assert!(file.take_error().is_none());
+}
+
+#[test]
+fn voice_message_round_trip() {
+    // a second of speech-like tone and a second of silence
+    let mut pcm: Vec<i16> = (0..16000)
+        .map(|t| ((t as f32 * 0.1).sin() * 6000.0) as i16)
+        .collect();
+    pcm.resize(32000, 0);
+
+    for &quality in [VoiceQuality::Low, VoiceQuality::Medium, VoiceQuality::High].iter() {
+        let bytes = encode_voice_message(&pcm, quality).unwrap();
+        // well under the 64 kbit/s of the raw input
+        assert!(bytes.len() < 8000);
+
+        let mut file = OpusFile::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(file.head().input_sample_rate, 16000);
+        assert_eq!(file.channels(), 1);
+        assert_eq!(file.decode_all().unwrap().len(), 3 * pcm.len());
+        assert!(file.take_error().is_none());
+    }
+}
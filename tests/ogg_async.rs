@@ -0,0 +1,50 @@
+#![cfg(feature = "tokio")]
+
+extern crate futures;
+extern crate opus;
+
+use futures::executor::block_on;
+use futures::StreamExt;
+use opus::ogg::{AsyncOggReader, OpusHead, OpusTags, Page};
+use opus::{Application, Channels, Encoder};
+
+#[test]
+fn decode_stream() {
+    let mut encoder = Encoder::new(48000, Channels::Mono, Application::Audio).unwrap();
+    let mut bytes = Vec::new();
+    let mut sequence = 0;
+    let mut page = |flags, granule, packet: &[u8], bytes: &mut Vec<u8>| {
+        let mut page = Page {
+            flags,
+            granule_position: Some(granule),
+            serial: 1,
+            sequence,
+            ..Page::default()
+        };
+        page.push_packet(packet);
+        page.write(bytes);
+        sequence += 1;
+    };
+
+    let mut head = Vec::new();
+    OpusHead::new(1, 312, 48000).write(&mut head);
+    page(opus::ogg::page::BOS, 0, &head, &mut bytes);
+    let mut tags = Vec::new();
+    OpusTags::new("opus-rs").write(&mut tags);
+    page(0, 0, &tags, &mut bytes);
+    for i in 0..3u64 {
+        let packet = encoder.encode_vec(&[0; 960], 4000).unwrap();
+        let (flags, granule) = if i == 2 {
+            // the final packet carries only 100 samples of audio
+            (opus::ogg::page::EOS, 2 * 960 + 312 + 100)
+        } else {
+            (0, (i + 1) * 960)
+        };
+        page(flags, granule, &packet, &mut bytes);
+    }
+
+    let reader = AsyncOggReader::new(&bytes[..]);
+    let frames: Vec<_> = block_on(reader.collect());
+    let lens: Vec<_> = frames.into_iter().map(|f| f.unwrap().len()).collect();
+    assert_eq!(lens, vec![960 - 312, 960, 312 + 100]);
+}
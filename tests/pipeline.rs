@@ -0,0 +1,174 @@
+#![cfg(feature = "std")]
+
+extern crate opus;
+
+use opus::jitter::{AdaptiveDelay, JitterBuffer, Playout};
+use opus::pipeline::{
+    HighPass, PacketInfo, PacketSink, Packetizer, PcmProcessor, PushEncoder, Timestamper,
+};
+use opus::{Application, Channels, Decoder, Encoder, EncoderOptions, FrameDuration, Preset};
+
+const MONO_20MS: usize = 960;
+
+#[test]
+fn packetizer_stamps_packets() {
+    let encoder = Encoder::new(48000, Channels::Mono, Application::Audio).unwrap();
+    let mut packetizer = Packetizer::new(encoder, Vec::new(), 1500);
+    for _ in 0..3 {
+        packetizer.write_frame(&[0i16; MONO_20MS]).unwrap();
+    }
+    packetizer.write_frame(&[0i16; 2 * MONO_20MS]).unwrap();
+
+    let (_, packets) = packetizer.into_inner();
+    let infos: Vec<PacketInfo> = packets.iter().map(|p| p.1).collect();
+    assert_eq!(
+        infos,
+        vec![
+            PacketInfo {
+                sequence: 0,
+                timestamp: 0,
+                duration: 960
+            },
+            PacketInfo {
+                sequence: 1,
+                timestamp: 960,
+                duration: 960
+            },
+            PacketInfo {
+                sequence: 2,
+                timestamp: 1920,
+                duration: 960
+            },
+            PacketInfo {
+                sequence: 3,
+                timestamp: 2880,
+                duration: 1920
+            },
+        ]
+    );
+}
+
+#[test]
+fn timestamper_skips() {
+    let mut ts = Timestamper::starting_at(480);
+    assert_eq!(ts.advance(960).timestamp, 480);
+    ts.skip(960);
+    let info = ts.advance(960);
+    assert_eq!(info.sequence, 1);
+    assert_eq!(info.timestamp, 2400);
+
+    let mut sink: Vec<(Vec<u8>, PacketInfo)> = Vec::new();
+    sink.send_packet(&[248, 255, 254], info).unwrap();
+    assert_eq!(sink[0].1, info);
+}
+
+#[test]
+fn jitter_sink_counts_drops() {
+    let mut jb = JitterBuffer::new(0);
+    let mut ts = Timestamper::new();
+    let info = ts.advance(960);
+    jb.send_packet(&[248, 255, 254], info).unwrap();
+    jb.send_packet(&[248, 255, 254], info).unwrap();
+    // code 3 without a frame count byte
+    jb.send_packet(&[251], ts.advance(960)).unwrap();
+    let stats = jb.stats();
+    assert_eq!(stats.received, 1);
+    assert_eq!(stats.duplicates, 1);
+    assert_eq!(stats.malformed, 1);
+}
+
+#[test]
+fn push_encoder_queues_packets() {
+    let encoder = Encoder::new(48000, Channels::Mono, Application::Audio).unwrap();
+    let mut push = PushEncoder::<i16>::new(encoder).unwrap();
+    assert_eq!(push.push(&[0; 500]).unwrap(), 0);
+    assert_eq!(push.pending(), 500);
+    assert_eq!(push.push(&[0; 1500]).unwrap(), 2);
+    assert_eq!(push.pending(), 80);
+    assert_eq!(push.finish().unwrap(), 1);
+    assert_eq!(push.ready(), 3);
+
+    let mut timestamps = Vec::new();
+    while let Some((packet, info)) = push.poll_packet() {
+        assert!(!packet.is_empty());
+        assert_eq!(info.duration, 960);
+        timestamps.push(info.timestamp);
+    }
+    assert_eq!(timestamps, vec![0, 960, 1920]);
+}
+
+#[cfg(not(feature = "disable-float-api"))]
+#[test]
+fn push_encoder_runs_processors() {
+    use std::sync::{Arc, Mutex};
+
+    let encoder = Encoder::new(48000, Channels::Stereo, Application::Audio).unwrap();
+    let mut push = PushEncoder::<f32>::new(encoder).unwrap();
+    let frames = Arc::new(Mutex::new(Vec::new()));
+    let seen = frames.clone();
+    // silence the input, then record what the next stage sees
+    push.add_processor(Box::new(|frame: &mut [f32]| {
+        for sample in frame.iter_mut() {
+            *sample = 0.0;
+        }
+    }));
+    push.add_processor(Box::new(move |frame: &mut [f32]| {
+        let silent = frame.iter().all(|&s| s == 0.0);
+        seen.lock().unwrap().push((frame.len(), silent));
+    }));
+    assert_eq!(push.push(&[0.5; 3000]).unwrap(), 1);
+    assert_eq!(push.finish().unwrap(), 1);
+    assert_eq!(*frames.lock().unwrap(), vec![(1920, true), (1920, true)]);
+}
+
+#[test]
+fn high_pass_removes_dc() {
+    let mut filter = HighPass::new(Channels::Stereo, 48000, 20.0);
+    let mut pcm = vec![0.5f32; 2 * 48000];
+    filter.process(&mut pcm);
+    // the step passes at first, then decays away in both channels
+    assert_eq!(&pcm[..2], &[0.5, 0.5]);
+    assert!(pcm[2 * 4800..].iter().all(|s| s.abs() < 0.01));
+
+    let mut filter = HighPass::new(Channels::Mono, 48000, 20.0);
+    let mut pcm = vec![-10000i16; 48000];
+    filter.process(&mut pcm);
+    assert_eq!(pcm[0], -10000);
+    assert!(pcm[4800..].iter().all(|s| s.abs() < 100));
+}
+
+#[test]
+fn game_voice_short_frames() {
+    let mut options = EncoderOptions::preset(Preset::GameVoice);
+    options.frame_duration = FrameDuration::Ms2_5;
+    let encoder = options.build(48000, Channels::Mono).unwrap();
+    let mut push = PushEncoder::<i16>::new(encoder).unwrap();
+    let input: Vec<i16> = (0..480)
+        .map(|t| ((t as f32 * 0.05).sin() * 8000.0) as i16)
+        .collect();
+    assert_eq!(push.push(&input).unwrap(), 4);
+
+    let mut decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let mut jb = JitterBuffer::new(120);
+    jb.set_policy(AdaptiveDelay::for_frames(120, 960, 120));
+    let mut timestamps = Vec::new();
+    while let Some((packet, info)) = push.poll_packet() {
+        assert_eq!(info.duration, 120);
+        timestamps.push(info.timestamp);
+        jb.push(info.sequence as u16, info.timestamp as u32, packet);
+    }
+    assert_eq!(timestamps, vec![0, 120, 240, 360]);
+
+    let mut output = [0i16; 120];
+    for _ in 0..4 {
+        assert_eq!(
+            jb.pop(&mut decoder, &mut output).unwrap(),
+            Playout::Decoded(120)
+        );
+    }
+    assert_eq!(
+        jb.pop(&mut decoder, &mut output).unwrap(),
+        Playout::Concealed(120)
+    );
+    assert_eq!(jb.target_delay(), 240);
+}
@@ -0,0 +1,76 @@
+#![cfg(all(feature = "projection", feature = "std"))]
+
+extern crate opus;
+
+use opus::ogg::OpusHead;
+use opus::projection::{DemixingMatrix, ProjectionEncoder};
+use opus::Application;
+
+#[test]
+fn matrix_bytes_round_trip() {
+    let matrix = DemixingMatrix::new(2, 3, vec![1, -2, 300, -400, 32767, -32768]).unwrap();
+    assert_eq!(matrix.get(1, 0), Some(-2));
+    assert_eq!(matrix.get(0, 2), Some(32767));
+    assert_eq!(matrix.get(2, 0), None);
+    let bytes = matrix.to_bytes();
+    assert_eq!(&bytes[..4], &[1, 0, 0xfe, 0xff]);
+    assert_eq!(DemixingMatrix::from_bytes(2, 3, &bytes).unwrap(), matrix);
+    assert!(DemixingMatrix::from_bytes(2, 3, &bytes[1..]).is_err());
+    assert!(DemixingMatrix::new(2, 3, vec![0; 5]).is_err());
+}
+
+#[test]
+fn family_3_head_round_trip() {
+    // first order ambisonics: two coupled streams
+    let coefficients = (0..16).map(|i| i * 1000 - 8000).collect();
+    let mut matrix = DemixingMatrix::new(4, 4, coefficients).unwrap();
+    matrix.gain = -512;
+    let head = OpusHead::ambisonics(2, 2, &matrix, 312, 48000).unwrap();
+    assert_eq!(head.mapping_family, 3);
+    assert_eq!(head.channels, 4);
+    assert_eq!(head.output_gain, -512);
+
+    let mut bytes = Vec::new();
+    head.write(&mut bytes);
+    assert_eq!(bytes.len(), 21 + 2 * 4 * 4);
+    let parsed = OpusHead::parse(&bytes).unwrap();
+    assert_eq!(parsed, head);
+    matrix.gain = 0;
+    assert_eq!(parsed.demixing_matrix(), Some(matrix.clone()));
+    assert!(OpusHead::parse(&bytes[..bytes.len() - 1]).is_err());
+
+    assert!(OpusHead::ambisonics(3, 0, &matrix, 312, 48000).is_err());
+    assert!(OpusHead::new(2, 312, 48000).demixing_matrix().is_none());
+}
+
+#[test]
+fn encoder_matrix_decodes_from_head() {
+    let mut encoder = ProjectionEncoder::ambisonics(48000, 9, 3, Application::Audio).unwrap();
+    let matrix = encoder.demixing_matrix().unwrap();
+    assert_eq!(matrix.channels(), 9);
+    assert_eq!(
+        matrix.stream_channels(),
+        encoder.streams() + encoder.coupled_streams()
+    );
+    let head = OpusHead::ambisonics(
+        encoder.streams(),
+        encoder.coupled_streams(),
+        &matrix,
+        312,
+        48000,
+    )
+    .unwrap();
+    let mut bytes = Vec::new();
+    head.write(&mut bytes);
+    let head = OpusHead::parse(&bytes).unwrap();
+
+    let mut decoder = head.projection_decoder().unwrap();
+    let pcm: Vec<i16> = (0..960 * 9)
+        .map(|i| ((i % 200) as i16 - 100) * 50)
+        .collect();
+    let mut output = vec![0i16; 960 * 9];
+    for _ in 0..3 {
+        let packet = encoder.encode_vec(&pcm, 8000).unwrap();
+        assert_eq!(decoder.decode(&packet, &mut output, false).unwrap(), 960);
+    }
+}
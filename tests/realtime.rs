@@ -0,0 +1,68 @@
+#![cfg(all(feature = "std", not(target_family = "wasm")))]
+
+extern crate opus;
+
+use std::sync::mpsc;
+
+use opus::realtime::RealtimeEncoder;
+use opus::ring::ring;
+use opus::{Application, Channels, Encoder};
+
+#[test]
+fn ring_wraps_around() {
+    let (mut producer, mut consumer) = ring::<i16>(5);
+    assert_eq!(producer.capacity(), 8);
+    let mut out = [0; 8];
+    for round in 0..4 {
+        let data: Vec<i16> = (0..6).map(|i| round * 10 + i).collect();
+        assert_eq!(producer.push_slice(&data), 6);
+        assert_eq!(producer.push_slice(&data), 2);
+        assert_eq!(consumer.pop_slice(&mut out), 8);
+        assert_eq!(&out[..6], &data[..]);
+        assert_eq!(&out[6..], &data[..2]);
+    }
+    drop(producer);
+    assert!(consumer.is_abandoned());
+}
+
+#[test]
+fn realtime_encodes_on_worker() {
+    let encoder = Encoder::new(48000, Channels::Mono, Application::Audio).unwrap();
+    let (tx, rx) = mpsc::channel();
+    let (mut input, worker) =
+        RealtimeEncoder::spawn::<i16, _>(encoder, 48000, move |packet, info| {
+            tx.send((packet, info)).unwrap();
+        })
+        .unwrap();
+    for _ in 0..10 {
+        assert_eq!(input.push(&[0; 480]), 480);
+    }
+    worker.finish().unwrap();
+    let timestamps: Vec<u64> = rx.iter().map(|(_, info)| info.timestamp).collect();
+    assert_eq!(timestamps, vec![0, 960, 1920, 2880, 3840]);
+    assert_eq!(input.dropped(), 0);
+}
+
+#[test]
+fn playout_conceals_underruns() {
+    let decoder = opus::Decoder::new(48000, Channels::Mono).unwrap();
+    let (mut input, mut playout) = opus::realtime::PlayoutBuffer::new(decoder, 0).unwrap();
+    let mut out = [1; 480];
+    playout.read(&mut out);
+    assert!(out.iter().all(|&s| s == 0));
+    assert_eq!(playout.stats().underruns, 0);
+
+    let mut encoder = Encoder::new(48000, Channels::Mono, Application::Audio).unwrap();
+    let packet = encoder.encode_vec(&[0; 960], 4000).unwrap();
+    assert!(input.push(&packet));
+    assert!(input.push(&packet));
+    assert!(!input.push(&[]));
+    for _ in 0..6 {
+        playout.read(&mut out);
+    }
+    let stats = playout.stats();
+    assert_eq!(stats.packets, 2);
+    assert_eq!(stats.underruns, 1);
+    assert_eq!(stats.concealed, 960);
+    assert_eq!(stats.dropped, 1);
+}
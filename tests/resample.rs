@@ -0,0 +1,107 @@
+#![cfg(feature = "resample")]
+
+extern crate opus;
+
+use std::f64::consts::PI;
+
+use opus::resample::{encode_rate, Resampler, ResamplingEncoder};
+use opus::{Application, Channels, Encoder};
+
+/// A second of a 1 kHz tone, inverted in the second channel.
+fn tone(rate: u32) -> Vec<f32> {
+    (0..rate)
+        .flat_map(|i| {
+            let s = (2.0 * PI * 1000.0 * i as f64 / rate as f64).sin() as f32 * 0.5;
+            vec![s, -s]
+        })
+        .collect()
+}
+
+fn resample(from: u32, to: u32, chunk: usize) -> Vec<f32> {
+    let mut resampler = Resampler::new(from, to, 2);
+    let mut output = Vec::new();
+    for input in tone(from).chunks(chunk) {
+        resampler.process(input, &mut output);
+    }
+    resampler.flush(&mut output);
+    output
+}
+
+#[test]
+fn converts_tone() {
+    for &(from, to) in &[
+        (44100, 48000),
+        (22050, 48000),
+        (48000, 8000),
+        (44101, 48000),
+    ] {
+        let output = resample(from, to, 2000);
+        assert_eq!(output.len(), 2 * to as usize, "{} to {}", from, to);
+        // the same output however the input is split
+        assert_eq!(output, resample(from, to, 74));
+
+        let expected = tone(to);
+        let (mut signal, mut noise) = (0.0, 0.0);
+        // away from the edges, where the filter sees the silence around it
+        for (i, (&out, &exp)) in output.iter().zip(&expected).enumerate() {
+            assert_eq!(output[i ^ 1], -out);
+            if i > 400 && i < output.len() - 400 {
+                signal += (exp * exp) as f64;
+                noise += ((out - exp) * (out - exp)) as f64;
+            }
+        }
+        let snr = 10.0 * (signal / noise).log10();
+        assert!(snr > 70.0, "{} to {}: {} dB", from, to, snr);
+    }
+}
+
+#[test]
+fn passthrough_and_reset() {
+    let mut resampler = Resampler::new(48000, 48000, 2);
+    let mut output = Vec::new();
+    // the trailing half frame is dropped
+    resampler.process(&[0.5, -0.5, 0.25], &mut output);
+    resampler.flush(&mut output);
+    assert_eq!(output, [0.5, -0.5]);
+
+    let mut resampler = Resampler::new(44100, 48000, 1);
+    assert_eq!(
+        (
+            resampler.input_rate(),
+            resampler.output_rate(),
+            resampler.channels()
+        ),
+        (44100, 48000, 1)
+    );
+    let mut first = Vec::new();
+    resampler.process(&[1.0; 441], &mut first);
+    resampler.flush(&mut first);
+    assert_eq!(first.len(), 480);
+    let mut second = Vec::new();
+    resampler.process(&[1.0; 441], &mut second);
+    resampler.flush(&mut second);
+    assert_eq!(first, second);
+
+    assert_eq!(encode_rate(16000), 16000);
+    assert_eq!(encode_rate(44100), 48000);
+}
+
+#[test]
+fn encodes_other_rates() {
+    let encoder = Encoder::new(48000, Channels::Stereo, Application::Audio).unwrap();
+    let mut encoder = ResamplingEncoder::new(encoder, 44100).unwrap();
+    let pcm = tone(44100);
+    let mut ready = 0;
+    for chunk in pcm.chunks(882) {
+        ready += encoder.push(chunk).unwrap();
+    }
+    ready += encoder.finish().unwrap();
+    // one second at 48kHz is exactly 50 packets of 20 ms
+    assert_eq!(ready, 50);
+    let mut duration = 0;
+    while let Some((packet, info)) = encoder.poll_packet() {
+        duration += info.duration;
+        encoder.recycle(packet);
+    }
+    assert_eq!(duration, 48000);
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "std")]
+
+extern crate opus;
+
+use opus::jitter::{JitterBuffer, Playout};
+use opus::rtp::{red, Header};
+use opus::{Channels, Decoder};
+
+#[test]
+fn header_round_trip() {
+    let header = Header {
+        marker: true,
+        payload_type: 111,
+        sequence: 65535,
+        timestamp: 0xdead_beef,
+        ssrc: 42,
+    };
+    let mut packet = Vec::new();
+    header.write(&[248, 255, 254], &mut packet);
+    assert_eq!(packet.len(), Header::LEN + 3);
+
+    let (parsed, payload) = Header::parse(&packet).unwrap();
+    assert_eq!(parsed, header);
+    assert_eq!(payload, &[248, 255, 254]);
+
+    assert!(Header::parse(&packet[..8]).is_err());
+}
+
+#[test]
+fn red_round_trip() {
+    let mut encoder = red::RedEncoder::new(111, 2);
+    let frames: [&[u8]; 3] = [&[248, 1], &[248, 2, 2], &[248, 3, 3, 3]];
+    let mut payloads = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        let mut payload = Vec::new();
+        encoder.encode(960 * i as u32, frame, &mut payload);
+        payloads.push(payload);
+    }
+    assert_eq!(payloads[0], vec![111, 248, 1]);
+
+    let blocks = red::parse(1920, &payloads[2]).unwrap();
+    assert_eq!(blocks.len(), 3);
+    for (i, block) in blocks.iter().enumerate() {
+        assert_eq!(block.payload_type, 111);
+        assert_eq!(block.timestamp, 960 * i as u32);
+        assert_eq!(block.data, frames[i]);
+    }
+
+    assert!(red::parse(0, &[0x80 | 111, 0, 0]).is_err());
+}
+
+#[test]
+fn red_fills_jitter_buffer() {
+    let mut encoder = red::RedEncoder::new(111, 1);
+    let frames: [&[u8]; 3] = [&[248, 255, 254], &[248, 255, 254], &[248, 255, 254]];
+    let mut payloads = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        let mut payload = Vec::new();
+        encoder.encode(960 * i as u32, frame, &mut payload);
+        payloads.push(payload);
+    }
+
+    let mut jb = JitterBuffer::new(0);
+    assert_eq!(red::push_to(&mut jb, 111, 0, 0, &payloads[0]).unwrap(), 1);
+    // The packet with sequence number 1 is lost, but carried by the next.
+    assert_eq!(
+        red::push_to(&mut jb, 111, 2, 1920, &payloads[2]).unwrap(),
+        2
+    );
+    assert_eq!(jb.stats().duplicates, 0);
+
+    let mut decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let mut output = [0i16; 960];
+    for _ in 0..3 {
+        assert_eq!(
+            jb.pop(&mut decoder, &mut output).unwrap(),
+            Playout::Decoded(960)
+        );
+    }
+}
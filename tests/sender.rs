@@ -0,0 +1,89 @@
+extern crate opus;
+
+use std::time::Duration;
+
+use opus::sender::{Sender, SenderConfig};
+use opus::{Application, Bandwidth, Bitrate, Channels, Encoder};
+
+const MONO_20MS: usize = 960;
+
+#[test]
+fn adapts_to_reports() {
+    let encoder = Encoder::new(48000, Channels::Mono, Application::Voip).unwrap();
+    let config = SenderConfig {
+        smoothing: 1.0,
+        ..SenderConfig::default()
+    };
+    let mut sender = Sender::new(encoder, config).unwrap();
+    assert!(!sender.fec_enabled());
+    assert_eq!(
+        sender.encoder().get_bitrate().unwrap(),
+        Bitrate::Bits(32000)
+    );
+
+    sender
+        .on_receiver_report(0.1, Duration::from_millis(50))
+        .unwrap();
+    assert!(sender.fec_enabled());
+    assert!(sender.encoder().get_inband_fec().unwrap());
+    assert_eq!(sender.encoder().get_packet_loss_perc().unwrap(), 10);
+    match sender.encoder().get_bitrate().unwrap() {
+        Bitrate::Bits(bits) => assert!(bits < 32000 && bits > 12000),
+        other => panic!("unexpected bitrate {:?}", other),
+    }
+
+    sender
+        .on_receiver_report(0.0, Duration::from_millis(50))
+        .unwrap();
+    assert!(!sender.fec_enabled());
+    assert_eq!(
+        sender.encoder().get_bitrate().unwrap(),
+        Bitrate::Bits(32000)
+    );
+}
+
+#[test]
+fn duplicates_critical_frames() {
+    // CELT at a forced bandwidth keeps the TOC byte steady.
+    let mut encoder = Encoder::new(48000, Channels::Mono, Application::LowDelay).unwrap();
+    encoder.set_bandwidth(Bandwidth::Fullband).unwrap();
+    let config = SenderConfig {
+        smoothing: 1.0,
+        ..SenderConfig::default()
+    };
+    let mut sender = Sender::new(encoder, config).unwrap();
+    let input: Vec<i16> = (0..MONO_20MS)
+        .map(|t| ((t as f32 * 0.05).sin() * 8000.0) as i16)
+        .collect();
+
+    // The first frame is a mode switch, but the link is clean.
+    assert_eq!(sender.encode(&input, 1500).unwrap().len(), 1);
+
+    sender
+        .on_receiver_report(0.5, Duration::from_millis(400))
+        .unwrap();
+    assert_eq!(sender.encode(&input, 1500).unwrap().len(), 1);
+
+    sender.encoder().set_bandwidth(Bandwidth::Wideband).unwrap();
+    let packets = sender.encode(&input, 1500).unwrap();
+    assert_eq!(packets.len(), 2);
+    assert_eq!(packets[0], packets[1]);
+    assert_eq!(sender.encode(&input, 1500).unwrap().len(), 1);
+}
+
+#[test]
+fn duplicates_on_rtt_weighted_loss() {
+    let encoder = Encoder::new(48000, Channels::Mono, Application::LowDelay).unwrap();
+    let config = SenderConfig {
+        smoothing: 1.0,
+        ..SenderConfig::default()
+    };
+    let mut sender = Sender::new(encoder, config).unwrap();
+    let input = vec![1000i16; MONO_20MS];
+
+    // 0.13 is below the threshold of 0.15, but not once weighted by the RTT.
+    sender
+        .on_receiver_report(0.13, Duration::from_millis(400))
+        .unwrap();
+    assert_eq!(sender.encode(&input, 1500).unwrap().len(), 2);
+}
@@ -0,0 +1,28 @@
+#![cfg(all(feature = "serde", feature = "std"))]
+
+extern crate opus;
+extern crate serde_json;
+
+use opus::ogg::{OpusHead, OpusTags};
+use opus::{EncoderOptions, Preset};
+
+#[test]
+fn encoder_options_round_trip() {
+    let options = EncoderOptions::preset(Preset::WebRtcVoice);
+    let json = serde_json::to_string(&options).unwrap();
+    let parsed: EncoderOptions = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, options);
+}
+
+#[test]
+fn headers_round_trip() {
+    let head = OpusHead::new(2, 312, 44100);
+    let json = serde_json::to_string(&head).unwrap();
+    assert_eq!(serde_json::from_str::<OpusHead>(&json).unwrap(), head);
+
+    let mut tags = OpusTags::new("opus-rs");
+    tags.comments
+        .push(("TITLE".to_string(), "Test".to_string()));
+    let json = serde_json::to_string(&tags).unwrap();
+    assert_eq!(serde_json::from_str::<OpusTags>(&json).unwrap(), tags);
+}
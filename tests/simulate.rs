@@ -0,0 +1,139 @@
+extern crate opus;
+
+mod common;
+
+use common::{fec_packets, MONO_20MS};
+use opus::jitter::{JitterBuffer, Playout};
+use opus::simulate::{self, LossModel, Network, NetworkConfig};
+use opus::{Channels, Decoder};
+
+fn lossy(loss: LossModel) -> NetworkConfig {
+    NetworkConfig {
+        loss,
+        ..NetworkConfig::default()
+    }
+}
+
+#[test]
+fn deterministic() {
+    let packets = fec_packets(50);
+    let config = NetworkConfig {
+        loss: LossModel::Random(0.2),
+        jitter: 3 * MONO_20MS as u32,
+        reorder: 0.1,
+        reorder_delay: 5 * MONO_20MS as u32,
+        duplicate: 0.05,
+    };
+    let send = |seed| Network::new(config.clone(), seed).transmit(packets.iter().map(|p| &p[..]));
+    assert_eq!(send(7), send(7));
+    assert_ne!(send(7), send(8));
+}
+
+#[test]
+fn loss_rates() {
+    let count = 20000;
+    let mut random = Network::new(lossy(LossModel::Random(0.1)), 1);
+    let lost = (0..count).filter(|_| random.lose()).count();
+    assert!(
+        lost > count * 8 / 100 && lost < count * 12 / 100,
+        "{}",
+        lost
+    );
+    assert_eq!(random.stats().lost, lost as u64);
+
+    let mut bursty = Network::new(lossy(LossModel::bursty(0.1, 4.0)), 1);
+    let losses: Vec<bool> = (0..count).map(|_| bursty.lose()).collect();
+    let lost = losses.iter().filter(|&&lost| lost).count();
+    assert!(
+        lost > count * 7 / 100 && lost < count * 13 / 100,
+        "{}",
+        lost
+    );
+    let bursts = losses.windows(2).filter(|w| !w[0] && w[1]).count();
+    let mean_burst = lost as f32 / bursts as f32;
+    assert!(mean_burst > 3.0 && mean_burst < 5.0, "{}", mean_burst);
+
+    let pattern = vec![false, true, true];
+    let mut network = Network::new(lossy(LossModel::Pattern(pattern.clone())), 1);
+    let losses: Vec<bool> = (0..6).map(|_| network.lose()).collect();
+    assert_eq!(losses, [&pattern[..], &pattern[..]].concat());
+}
+
+#[test]
+fn transmit_reorders() {
+    let packets = fec_packets(20);
+    let config = NetworkConfig {
+        reorder: 0.3,
+        reorder_delay: 2 * MONO_20MS as u32,
+        ..NetworkConfig::default()
+    };
+    let mut network = Network::new(config, 3);
+    let arrivals = network.transmit(packets.iter().map(|p| &p[..]));
+    assert_eq!(arrivals.len(), 20);
+    assert!(network.stats().reordered > 0);
+    assert!(arrivals.windows(2).any(|w| w[1].seq < w[0].seq));
+    assert!(arrivals.windows(2).all(|w| w[0].arrival <= w[1].arrival));
+    for arrival in &arrivals {
+        assert_eq!(arrival.timestamp, arrival.seq as u32 * MONO_20MS as u32);
+        assert_eq!(arrival.packet, packets[arrival.seq as usize]);
+    }
+}
+
+#[test]
+fn play_through_jitter_buffer() {
+    let packets = fec_packets(50);
+    let config = NetworkConfig {
+        jitter: MONO_20MS as u32,
+        reorder: 0.1,
+        reorder_delay: MONO_20MS as u32,
+        ..NetworkConfig::default()
+    };
+    let arrivals = Network::new(config, 5).transmit(packets.iter().map(|p| &p[..]));
+    let mut jb = JitterBuffer::new(3 * MONO_20MS as u32);
+    let mut decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let playback = simulate::play(&arrivals, &mut jb, &mut decoder).unwrap();
+
+    // enough delay to absorb the jitter, so every packet is played
+    let decoded = playback
+        .playouts
+        .iter()
+        .filter(|p| **p == Playout::Decoded(MONO_20MS))
+        .count();
+    assert_eq!(decoded, 50);
+    assert_eq!(jb.stats().lost, 0);
+    assert_eq!(playback.output.len(), 50 * MONO_20MS);
+}
+
+#[test]
+fn decode_recovers_with_fec() {
+    let packets = fec_packets(12);
+    let pattern = vec![false, false, true, false];
+    let mut decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let mut network = Network::new(lossy(LossModel::Pattern(pattern.clone())), 0);
+    let playback = simulate::decode(
+        packets.iter().map(|p| &p[..]),
+        &mut network,
+        &mut decoder,
+        true,
+    )
+    .unwrap();
+    assert_eq!(playback.output.len(), 12 * MONO_20MS);
+    for (i, playout) in playback.playouts.iter().enumerate() {
+        if i % 4 == 2 {
+            assert_eq!(*playout, Playout::Recovered(MONO_20MS));
+        } else {
+            assert_eq!(*playout, Playout::Decoded(MONO_20MS));
+        }
+    }
+
+    let mut decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let mut network = Network::new(lossy(LossModel::Pattern(pattern)), 0);
+    let playback = simulate::decode(
+        packets.iter().map(|p| &p[..]),
+        &mut network,
+        &mut decoder,
+        false,
+    )
+    .unwrap();
+    assert_eq!(playback.playouts[2], Playout::Concealed(MONO_20MS));
+}
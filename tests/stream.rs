@@ -0,0 +1,80 @@
+#![cfg(feature = "tokio")]
+
+extern crate futures;
+extern crate opus;
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::executor::block_on;
+use futures::{stream, Sink, SinkExt, StreamExt};
+use opus::stream::{OpusDecoderStream, OpusEncoderSink};
+use opus::{Application, Channels, Decoder, Encoder};
+
+#[derive(Default)]
+struct Collect(Vec<Vec<u8>>);
+
+impl Sink<Vec<u8>> for Collect {
+    type Error = opus::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), opus::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Vec<u8>) -> Result<(), opus::Error> {
+        self.get_mut().0.push(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), opus::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), opus::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[test]
+fn encode_then_decode() {
+    let encoder = Encoder::new(48000, Channels::Stereo, Application::Audio).unwrap();
+    let mut sink = OpusEncoderSink::new(encoder, Collect::default());
+    for _ in 0..3 {
+        block_on(sink.send(vec![0i16; 2 * 960])).unwrap();
+    }
+    let (_, Collect(packets)) = sink.into_inner();
+    assert_eq!(packets.len(), 3);
+
+    let decoder = Decoder::new(48000, Channels::Stereo).unwrap();
+    let packets = stream::iter(packets.into_iter().map(Ok::<_, opus::Error>));
+    let decoded = OpusDecoderStream::new(decoder, packets).unwrap();
+    let frames: Vec<_> = block_on(decoded.collect());
+    assert_eq!(frames.len(), 3);
+    for frame in frames {
+        assert_eq!(frame.unwrap().len(), 2 * 960);
+    }
+}
+
+#[test]
+fn decoded_frames_carry_timing() {
+    let mut encoder = Encoder::new(48000, Channels::Mono, Application::Audio).unwrap();
+    let packet = encoder.encode_vec(&[0; 960], 4000).unwrap();
+    let packets = vec![packet.clone(), Vec::new(), packet];
+
+    let decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let packets = stream::iter(packets.into_iter().map(Ok::<_, opus::Error>));
+    let frames = OpusDecoderStream::new(decoder, packets)
+        .unwrap()
+        .frames()
+        .unwrap();
+    let frames: Vec<_> = block_on(frames.collect());
+    let frames: Vec<_> = frames.into_iter().map(|f| f.unwrap()).collect();
+    let timing: Vec<_> = frames
+        .iter()
+        .map(|f| (f.timestamp_48k, f.samples, f.is_plc))
+        .collect();
+    assert_eq!(
+        timing,
+        vec![(0, 960, false), (960, 960, true), (1920, 960, false)]
+    );
+}
@@ -0,0 +1,39 @@
+#![cfg(feature = "symphonia")]
+
+extern crate opus;
+extern crate symphonia_core;
+
+use opus::ogg::OpusHead;
+use opus::symphonia::OpusDecoder;
+use opus::{Application, Channels, Encoder};
+use symphonia_core::audio::{AudioBufferRef, Signal};
+use symphonia_core::codecs::{CodecParameters, Decoder, DecoderOptions, CODEC_TYPE_OPUS};
+use symphonia_core::formats::Packet;
+
+#[test]
+fn decodes_through_symphonia() {
+    let mut head = Vec::new();
+    OpusHead::new(2, 0, 48000).write(&mut head);
+    let mut params = CodecParameters::new();
+    params
+        .for_codec(CODEC_TYPE_OPUS)
+        .with_extra_data(head.into_boxed_slice());
+    let mut decoder = OpusDecoder::try_new(&params, &DecoderOptions::default()).unwrap();
+
+    let mut encoder = Encoder::new(48000, Channels::Stereo, Application::Audio).unwrap();
+    let data = encoder.encode_vec(&[0; 2 * 960], 4000).unwrap();
+    let packet = Packet::new_from_slice(0, 0, 960, &data);
+    match decoder.decode(&packet).unwrap() {
+        AudioBufferRef::F32(buf) => {
+            assert_eq!(buf.spec().channels.count(), 2);
+            assert_eq!(buf.frames(), 960);
+        }
+        _ => panic!("expected f32 output"),
+    }
+}
+
+#[test]
+fn rejects_other_codecs() {
+    let params = CodecParameters::new();
+    assert!(OpusDecoder::try_new(&params, &DecoderOptions::default()).is_err());
+}
@@ -110,6 +110,153 @@ fn encode_bad_buffer() {
     }
 }
 
+#[test]
+fn decode_uninit() {
+    let mut encoder =
+        opus::Encoder::new(48000, opus::Channels::Stereo, opus::Application::Audio).unwrap();
+    let packet = encoder.encode_vec(&[17_i16; 2 * MONO_20MS], 4000).unwrap();
+
+    let mut decoder = opus::Decoder::new(48000, opus::Channels::Stereo).unwrap();
+    let mut output = [std::mem::MaybeUninit::uninit(); 2 * MONO_20MS * 6];
+    let pcm = decoder
+        .decode_into_uninit(&packet, &mut output, false)
+        .unwrap();
+    assert_eq!(pcm.len(), 2 * MONO_20MS);
+
+    #[cfg(not(feature = "disable-float-api"))]
+    {
+        let mut output = [std::mem::MaybeUninit::uninit(); 2 * MONO_20MS];
+        let pcm = decoder
+            .decode_float_into_uninit(&[], &mut output, false)
+            .unwrap();
+        assert_eq!(pcm.len(), 2 * MONO_20MS);
+    }
+}
+
+#[test]
+fn coders_in_caller_storage() {
+    use opus::storage::StateBuffer;
+    use std::mem::MaybeUninit;
+
+    let mut enc_state = StateBuffer::<{ 64 * 1024 }>::new();
+    assert!(opus::Encoder::state_size(opus::Channels::Stereo) <= enc_state.len());
+    let mut encoder = opus::Encoder::new_in(
+        &mut enc_state,
+        48000,
+        opus::Channels::Stereo,
+        opus::Application::Audio,
+    )
+    .unwrap();
+    let packet = encoder.encode_vec(&[17_i16; 2 * MONO_20MS], 4000).unwrap();
+
+    let mut dec_state = Box::new(StateBuffer::<{ 64 * 1024 }>::new());
+    assert!(opus::Decoder::state_size(opus::Channels::Stereo) <= dec_state.len());
+    let mut decoder =
+        opus::Decoder::new_in(&mut *dec_state, 48000, opus::Channels::Stereo).unwrap();
+    let mut output = [0i16; 2 * MONO_20MS];
+    assert_eq!(
+        decoder.decode(&packet, &mut output, false).unwrap(),
+        MONO_20MS
+    );
+    drop(decoder);
+    assert!(opus::Decoder::new_in(dec_state, 48000, opus::Channels::Mono).is_ok());
+
+    // too small
+    let mut small = [MaybeUninit::uninit(); 4];
+    match opus::Decoder::new_in(&mut small[..], 48000, opus::Channels::Mono) {
+        Ok(_) => panic!("undersized storage was accepted"),
+        Err(err) => assert_eq!(err.code(), opus::ErrorCode::BadArg),
+    }
+
+    // misaligned
+    let mut memory = vec![MaybeUninit::uninit(); 64 * 1024 + opus::storage::STATE_ALIGN];
+    let offset = (memory.as_ptr() as *const u8).align_offset(opus::storage::STATE_ALIGN) + 1;
+    assert!(opus::Decoder::new_in(&mut memory[offset..], 48000, opus::Channels::Mono).is_err());
+    assert!(opus::Decoder::new_in(&mut memory[offset - 1..], 48000, opus::Channels::Mono).is_ok());
+}
+
+#[test]
+fn memory_report() {
+    let stereo = opus::Encoder::state_size(opus::Channels::Stereo);
+    let mono = opus::Decoder::state_size(opus::Channels::Mono);
+    let config = opus::MemoryConfig {
+        encoders: vec![opus::Channels::Stereo, opus::Channels::Stereo],
+        decoders: vec![opus::Channels::Mono],
+        ..opus::MemoryConfig::default()
+    };
+    let report = opus::memory_requirements(&config).unwrap();
+    assert_eq!(report.encoders, 2 * stereo);
+    assert_eq!(report.decoders, mono);
+    assert_eq!(report.multistream_encoders, 0);
+    assert!(report.total >= 2 * stereo + mono);
+    assert_eq!(report.total % opus::storage::STATE_ALIGN, 0);
+
+    let bad = opus::MemoryConfig {
+        multistream_decoders: vec![opus::StreamLayout {
+            channels: 1,
+            streams: 0,
+            coupled_streams: 0,
+            mapping: vec![0],
+        }],
+        ..opus::MemoryConfig::default()
+    };
+    assert!(opus::memory_requirements(&bad).is_err());
+}
+
+#[test]
+fn decode_all_policies() {
+    let mut encoder =
+        opus::Encoder::new(48000, opus::Channels::Mono, opus::Application::Audio).unwrap();
+    let packet = encoder.encode_vec(&[0_i16; MONO_20MS], 4000).unwrap();
+    let packets: Vec<&[u8]> = vec![&packet, &[], &packet];
+
+    let mut decoder = opus::Decoder::new(48000, opus::Channels::Mono).unwrap();
+    let mut out = Vec::new();
+    let len = decoder
+        .decode_all(packets.iter().cloned(), &mut out, opus::ErrorPolicy::Skip)
+        .unwrap();
+    assert_eq!(len, 2 * MONO_20MS);
+    assert_eq!(out.len(), 2 * MONO_20MS);
+
+    out.clear();
+    let len = decoder
+        .decode_all(packets.iter().cloned(), &mut out, opus::ErrorPolicy::Plc)
+        .unwrap();
+    assert_eq!(len, 3 * MONO_20MS);
+
+    out.clear();
+    assert!(decoder
+        .decode_all(packets.iter().cloned(), &mut out, opus::ErrorPolicy::Fail)
+        .is_err());
+    assert_eq!(out.len(), MONO_20MS);
+}
+
+#[test]
+fn decoder_stats() {
+    let mut decoder = opus::Decoder::new(48000, opus::Channels::Mono).unwrap();
+    let mut output = [0i16; 1920];
+    // CELT fullband, one frame and then two
+    decoder
+        .decode(&[248, 255, 254], &mut output, false)
+        .unwrap();
+    decoder
+        .decode(&[249, 255, 254, 255, 254], &mut output, false)
+        .unwrap();
+    decoder.decode(&[], &mut output[..960], false).unwrap();
+    decoder.decode(&[248, 255, 254], &mut output, true).unwrap();
+
+    let stats = decoder.stats();
+    assert_eq!(stats.celt_frames, 3);
+    assert_eq!(stats.frames(), 3);
+    assert_eq!(stats.frames_at(opus::Bandwidth::Fullband), 3);
+    assert_eq!(stats.frames_at(opus::Bandwidth::Wideband), 0);
+    assert_eq!(stats.plc_calls, 1);
+    assert_eq!(stats.fec_recoveries, 1);
+
+    decoder.reset_stats();
+    assert_eq!(decoder.stats(), opus::DecoderStats::default());
+}
+
 #[test]
 fn repacketizer() {
     let mut rp = opus::Repacketizer::new().unwrap();
@@ -151,3 +298,213 @@ fn repacketizer() {
         assert_eq!(&out[..len], &[249, 255, 254, 71, 71]);
     }
 }
+
+#[test]
+fn encode_120ms_superframe() {
+    let mut encoder =
+        opus::Encoder::new(48000, opus::Channels::Stereo, opus::Application::Voip).unwrap();
+    encoder
+        .set_expert_frame_duration(opus::FrameDuration::Ms20)
+        .unwrap();
+    let input: Vec<i16> = (0..2 * 5760)
+        .map(|t| (((t / 2) as f32 * 0.03).sin() * 8000.0) as i16)
+        .collect();
+    let mut output = [0; 4000];
+    let len = encoder.encode_120ms(&input, &mut output).unwrap();
+    let packet = &output[..len];
+    assert_eq!(opus::packet::get_nb_samples(packet, 48000).unwrap(), 5760);
+    assert_eq!(
+        encoder.get_expert_frame_duration().unwrap(),
+        opus::FrameDuration::Ms20
+    );
+
+    let mut decoder = opus::Decoder::new(48000, opus::Channels::Stereo).unwrap();
+    let mut pcm = [0i16; 2 * 5760];
+    assert_eq!(decoder.decode(packet, &mut pcm, false).unwrap(), 5760);
+
+    match encoder.encode_120ms(&input[..2 * 2880], &mut output) {
+        Ok(_) => panic!("60 ms of input was accepted"),
+        Err(err) => assert_eq!(err.code(), opus::ErrorCode::BadArg),
+    }
+}
+
+#[test]
+fn encoder_stats() {
+    let mut encoder =
+        opus::Encoder::new(48000, opus::Channels::Mono, opus::Application::Voip).unwrap();
+    encoder.set_bitrate(opus::Bitrate::Bits(16000)).unwrap();
+    encoder.set_dtx(true).unwrap();
+    let tone: Vec<i16> = (0..960)
+        .map(|t| ((t as f32 * 0.05).sin() * 8000.0) as i16)
+        .collect();
+    let mut output = [0; 1500];
+    for _ in 0..50 {
+        encoder.encode(&tone, &mut output).unwrap();
+    }
+    for _ in 0..250 {
+        encoder.encode(&[0; 960], &mut output).unwrap();
+    }
+
+    let stats = encoder.stats();
+    assert_eq!(stats.packets, 300);
+    assert_eq!(stats.duration, 300 * 960);
+    assert_eq!(
+        stats.bitrate() as u64,
+        stats.bytes * 8 * 48000 / stats.duration
+    );
+    assert!(stats.dtx_ratio() > 0.0 && stats.dtx_ratio() < 1.0);
+    assert!(stats.mean_packet_size() > 2.0);
+    // the window holds only the last 256 packets, mostly silence
+    assert!(stats.window_bitrate() < stats.bitrate());
+    assert!(stats.percentile_packet_size(50.0) <= stats.percentile_packet_size(100.0));
+    assert!(stats.percentile_packet_size(100.0) > 2);
+
+    encoder.reset_stats();
+    assert_eq!(encoder.stats().packets, 0);
+    assert_eq!(encoder.stats().percentile_packet_size(50.0), 0);
+}
+
+#[test]
+fn encoder_ctls() {
+    let mut encoder =
+        opus::Encoder::new(48000, opus::Channels::Mono, opus::Application::Voip).unwrap();
+
+    encoder.set_complexity(3).unwrap();
+    assert_eq!(encoder.get_complexity().unwrap(), 3);
+    encoder.set_dtx(true).unwrap();
+    assert!(encoder.get_dtx().unwrap());
+    encoder.set_signal(opus::Signal::Voice).unwrap();
+    assert_eq!(encoder.get_signal().unwrap(), opus::Signal::Voice);
+    encoder
+        .set_max_bandwidth(opus::Bandwidth::Wideband)
+        .unwrap();
+    assert_eq!(
+        encoder.get_max_bandwidth().unwrap(),
+        opus::Bandwidth::Wideband
+    );
+    encoder
+        .set_expert_frame_duration(opus::FrameDuration::Ms10)
+        .unwrap();
+    assert_eq!(
+        encoder.get_expert_frame_duration().unwrap(),
+        opus::FrameDuration::Ms10
+    );
+    assert_eq!(opus::FrameDuration::Ms2_5.samples(48000), Some(120));
+    assert_eq!(opus::FrameDuration::Arg.samples(48000), None);
+}
+
+#[cfg(opus_ge_1_4)]
+#[test]
+fn encoder_in_dtx() {
+    let mut encoder =
+        opus::Encoder::new(48000, opus::Channels::Mono, opus::Application::Voip).unwrap();
+    encoder.set_dtx(true).unwrap();
+    assert!(!encoder.in_dtx().unwrap());
+
+    // DTX starts after a few hundred milliseconds of silence
+    let silence = [0i16; 960];
+    for _ in 0..50 {
+        encoder.encode(&silence, &mut [0; 1500]).unwrap();
+    }
+    assert!(encoder.in_dtx().unwrap());
+}
+
+#[cfg(opus_ge_1_5)]
+#[test]
+fn encoder_dred_duration() {
+    let mut encoder =
+        opus::Encoder::new(48000, opus::Channels::Mono, opus::Application::Voip).unwrap();
+    match encoder.set_dred_duration(50) {
+        Ok(()) => {
+            assert_eq!(encoder.get_dred_duration().unwrap(), 50);
+            encoder.set_packet_loss_perc(20).unwrap();
+            encoder.set_bitrate(opus::Bitrate::Bits(64000)).unwrap();
+            let mut output = [0; 1500];
+            for _ in 0..10 {
+                encoder.encode(&[0i16; 960], &mut output).unwrap();
+            }
+        }
+        // libopus was built without DRED
+        Err(err) => assert_eq!(err.code(), opus::ErrorCode::Unimplemented),
+    }
+}
+
+#[test]
+fn default_encoder_options() {
+    use opus::EncoderOptions;
+
+    let options = EncoderOptions::default();
+    let mut encoder = opus::Encoder::new(48000, opus::Channels::Mono, options.application).unwrap();
+    assert_eq!(
+        encoder.get_expert_frame_duration().unwrap(),
+        options.frame_duration
+    );
+    options.apply(&mut encoder).unwrap();
+    // 10 ms frames are still accepted
+    assert!(encoder.encode(&[0i16; 480], &mut [0; 1500]).unwrap() > 0);
+}
+
+#[test]
+fn encoder_presets() {
+    use opus::{EncoderOptions, Preset};
+
+    let presets = [
+        Preset::WebRtcVoice,
+        Preset::WebRtcMusic,
+        Preset::Voip8k,
+        Preset::Broadcast,
+        Preset::LowDelayGame,
+        Preset::GameVoice,
+    ];
+    for &preset in presets.iter() {
+        let options = EncoderOptions::preset(preset);
+        let mut encoder = options.build(48000, opus::Channels::Stereo).unwrap();
+        assert_eq!(encoder.get_inband_fec().unwrap(), options.inband_fec);
+        assert_eq!(encoder.get_dtx().unwrap(), options.dtx);
+
+        let frame_size = options.frame_size(48000).unwrap();
+        let input = vec![0i16; 2 * frame_size];
+        let len = encoder.encode(&input, &mut [0; 1500]).unwrap();
+        assert!(len > 0);
+    }
+}
+
+#[test]
+fn capabilities() {
+    let capabilities = opus::capabilities();
+    if capabilities.dred {
+        assert_eq!(capabilities.deep_plc, Some(true));
+    }
+    let version = opus::version();
+    if version.starts_with("libopus 1.3") || version.starts_with("libopus 1.4") {
+        assert!(!capabilities.dred);
+    }
+}
+
+#[cfg(opus_ge_1_5)]
+#[test]
+fn decoder_complexity() {
+    let mut decoder = opus::Decoder::new(48000, opus::Channels::Mono).unwrap();
+    assert_eq!(decoder.get_complexity().unwrap(), 0);
+    decoder.set_complexity(7).unwrap();
+    assert_eq!(decoder.get_complexity().unwrap(), 7);
+    assert!(decoder.set_complexity(11).is_err());
+}
+
+#[test]
+fn runtime_info() {
+    let info = opus::runtime_info();
+    assert_eq!(info.version, opus::version());
+    assert_eq!(info.fixed_point, opus::version().contains("-fixed"));
+    assert_eq!(info.rtcd.is_some(), info.vendored);
+    for path in &info.simd {
+        assert_eq!(path.compiled.is_some(), info.vendored);
+        if path.active == Some(true) {
+            assert_ne!(path.compiled, Some(false));
+            assert_ne!(path.supported, Some(false));
+        }
+    }
+    let report = info.to_string();
+    assert!(report.starts_with(info.version));
+    println!("\n{}", report);
+}
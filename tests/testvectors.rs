@@ -0,0 +1,78 @@
+#![cfg(feature = "testvectors")]
+
+extern crate opus;
+
+use std::env;
+use std::path::PathBuf;
+
+use opus::testvectors::{self, Packet};
+use opus::{Application, Channels, Encoder};
+
+#[test]
+fn compare_scores_identical_audio() {
+    let reference: Vec<i16> = (0..9600)
+        .map(|i| (((i / 2) as f32 * 0.05).sin() * 8000.0) as i16)
+        .collect();
+    let quality = testvectors::compare(&reference, &reference, 48000, Channels::Stereo).unwrap();
+    assert_eq!(quality.error, 0.0);
+    assert!(quality.passes());
+
+    let silence = vec![0i16; reference.len()];
+    let quality = testvectors::compare(&reference, &silence, 48000, Channels::Stereo).unwrap();
+    assert!(!quality.passes());
+
+    // the lengths must match at the decoded rate
+    assert!(testvectors::compare(&reference, &silence[..2400], 24000, Channels::Stereo).is_err());
+}
+
+#[test]
+fn bitstream_round_trip() {
+    let mut encoder = Encoder::new(48000, Channels::Mono, Application::Audio).unwrap();
+    let mut bitstream = Vec::new();
+    for _ in 0..5 {
+        let packet = encoder.encode_vec(&[0i16; 960], 1500).unwrap();
+        bitstream.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+        bitstream.extend_from_slice(&encoder.get_final_range().unwrap().to_be_bytes());
+        bitstream.extend_from_slice(&packet);
+    }
+    let packets = testvectors::read_bitstream(&bitstream[..]).unwrap();
+    assert_eq!(packets.len(), 5);
+    let decoded = testvectors::decode(&packets, 48000, Channels::Mono).unwrap();
+    assert_eq!(decoded.len(), 5 * 960);
+
+    let corrupt = vec![Packet {
+        final_range: packets[0].final_range ^ 1,
+        ..packets[0].clone()
+    }];
+    assert!(testvectors::decode(&corrupt, 48000, Channels::Mono).is_err());
+}
+
+// Runs the official vectors found in OPUS_TESTVECTORS, or downloaded into
+// OPUS_TESTVECTORS_DOWNLOAD, and is skipped otherwise.
+#[test]
+fn official_vectors() {
+    let dir = match testvectors::locate() {
+        Some(dir) => dir,
+        None => match env::var_os("OPUS_TESTVECTORS_DOWNLOAD") {
+            Some(dir) => testvectors::download(&PathBuf::from(dir)).unwrap(),
+            None => {
+                eprintln!("skipping: set OPUS_TESTVECTORS or OPUS_TESTVECTORS_DOWNLOAD");
+                return;
+            }
+        },
+    };
+    let mut failures = Vec::new();
+    for &vector in &testvectors::VECTORS {
+        for &channels in &[Channels::Stereo, Channels::Mono] {
+            let quality = testvectors::run(&dir, vector, 48000, channels).unwrap();
+            eprintln!(
+                "testvector{:02} {:?}: quality {:.1} %",
+                vector, channels, quality.quality
+            );
+            if !quality.passes() {
+                failures.push((vector, channels));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "failing vectors: {:?}", failures);
+}
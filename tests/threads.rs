@@ -0,0 +1,74 @@
+#![cfg(feature = "std")]
+
+extern crate opus;
+
+use std::sync::Arc;
+use std::thread;
+
+use opus::{
+    Application, Channels, Decoder, Encoder, MultistreamDecoder, MultistreamEncoder, Repacketizer,
+    SyncEncoder,
+};
+
+fn assert_send<T: Send>() {}
+fn assert_sync<T: Sync>() {}
+
+#[test]
+fn thread_safety_bounds() {
+    assert_send::<Encoder>();
+    assert_send::<Decoder>();
+    assert_send::<MultistreamEncoder>();
+    assert_send::<MultistreamDecoder>();
+    assert_send::<Repacketizer>();
+    assert_send::<SyncEncoder>();
+    assert_sync::<SyncEncoder>();
+}
+
+#[test]
+fn states_move_between_threads() {
+    let encoder = Encoder::new(48000, Channels::Mono, Application::Voip).unwrap();
+    let decoder = Decoder::new(48000, Channels::Mono).unwrap();
+    let (encoder, packet) = thread::spawn(move || {
+        let mut encoder = encoder;
+        let packet = encoder.encode_vec(&[0i16; 960], 1500).unwrap();
+        (encoder, packet)
+    })
+    .join()
+    .unwrap();
+    let samples = thread::spawn(move || {
+        let mut decoder = decoder;
+        let mut pcm = [0i16; 960];
+        decoder.decode(&packet, &mut pcm, false).unwrap()
+    })
+    .join()
+    .unwrap();
+    assert_eq!(samples, 960);
+    drop(encoder);
+}
+
+#[test]
+fn shared_encoder() {
+    let encoder = Encoder::new(48000, Channels::Stereo, Application::Audio).unwrap();
+    let shared = Arc::new(SyncEncoder::new(encoder));
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let pcm = vec![i as i16 * 1000; 2 * 960];
+                (0..25)
+                    .map(|_| shared.encode_vec(&pcm, 4000).unwrap().len())
+                    .filter(|&len| len > 0)
+                    .count()
+            })
+        })
+        .collect();
+    let packets: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+    assert_eq!(packets, 100);
+
+    shared
+        .lock()
+        .set_bitrate(opus::Bitrate::Bits(32000))
+        .unwrap();
+    let mut encoder = Arc::try_unwrap(shared).unwrap().into_inner();
+    assert_eq!(encoder.get_bitrate().unwrap(), opus::Bitrate::Bits(32000));
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "std")]
+
+extern crate opus;
+
+use std::time::Duration;
+
+use opus::time::{Timestamp48k, CLOCK_RATE};
+
+#[test]
+fn samples() {
+    assert_eq!(Timestamp48k::from_samples(160, 8000), Timestamp48k(960));
+    assert_eq!(Timestamp48k::from_samples(441, 44100).samples(), 480);
+    assert_eq!(Timestamp48k(960).to_samples(16000), 320);
+    assert_eq!(Timestamp48k(1).to_samples(8000), 0);
+    for &rate in &[8000, 12000, 16000, 24000, 48000] {
+        let time = Timestamp48k::from_samples(12345, rate);
+        assert_eq!(time.to_samples(rate), 12345);
+    }
+    // no overflow on the way through 48kHz
+    let far = Timestamp48k(u64::MAX / 2);
+    assert_eq!(far.to_samples(CLOCK_RATE), u64::MAX / 2);
+}
+
+#[test]
+fn durations() {
+    assert_eq!(Timestamp48k(960).to_duration(), Duration::from_millis(20));
+    assert_eq!(
+        Timestamp48k::from(Duration::from_millis(2500)),
+        Timestamp48k(120_000)
+    );
+    assert_eq!(Duration::from(Timestamp48k(48)), Duration::from_millis(1));
+    assert_eq!(Timestamp48k(1).to_duration(), Duration::from_nanos(20_833));
+    assert_eq!(Timestamp48k(90_000).to_string(), "1.875s");
+    assert_eq!(Timestamp48k::ZERO.to_string(), "0.000s");
+}
+
+#[test]
+fn granules() {
+    // RFC 7845: the first sample played is at granule position pre-skip
+    assert_eq!(Timestamp48k::from_granule(312, 312), Timestamp48k::ZERO);
+    assert_eq!(Timestamp48k::from_granule(1272, 312), Timestamp48k(960));
+    assert_eq!(Timestamp48k::from_granule(100, 312), Timestamp48k::ZERO);
+    assert_eq!(Timestamp48k(960).to_granule(312), 1272);
+}
+
+#[test]
+fn rtp() {
+    let base = u32::MAX - 479;
+    assert_eq!(Timestamp48k::ZERO.to_rtp(base), base);
+    assert_eq!(Timestamp48k(960).to_rtp(base), 480);
+
+    // unwrapped relative to the previous packet, across the wraparound
+    let near = Timestamp48k(960);
+    assert_eq!(Timestamp48k::from_rtp(1440, base, near), Timestamp48k(1920));
+    assert_eq!(Timestamp48k::from_rtp(base, base, near), Timestamp48k::ZERO);
+    let near = Timestamp48k(1 << 32);
+    assert_eq!(
+        Timestamp48k::from_rtp(base.wrapping_add(960), base, near),
+        Timestamp48k((1 << 32) + 960)
+    );
+    assert_eq!(
+        Timestamp48k::from_rtp(base.wrapping_sub(960), base, near),
+        Timestamp48k((1 << 32) - 960)
+    );
+}
+
+#[test]
+fn arithmetic() {
+    let mut time = Timestamp48k(960);
+    time += Timestamp48k(480);
+    assert_eq!(time + Timestamp48k(60), Timestamp48k(1500));
+    assert_eq!(
+        time.checked_sub(Timestamp48k(1440)),
+        Some(Timestamp48k::ZERO)
+    );
+    assert_eq!(time.checked_sub(Timestamp48k(1441)), None);
+    assert_eq!(time.saturating_sub(Timestamp48k(2000)), Timestamp48k::ZERO);
+}
@@ -0,0 +1,72 @@
+#![cfg(all(feature = "tracing", feature = "std"))]
+
+extern crate opus;
+extern crate tracing;
+
+use std::sync::{Arc, Mutex};
+
+use opus::jitter::JitterBuffer;
+use opus::ogg::Page;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+// Collects the targets and messages of events.
+#[derive(Clone, Default)]
+struct Collector {
+    events: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+struct Message(String);
+
+impl tracing::field::Visit for Message {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = Message(String::new());
+        event.record(&mut message);
+        let target = event.metadata().target().to_string();
+        self.events.lock().unwrap().push((target, message.0));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn pages_and_jitter_decisions_are_traced() {
+    let collector = Collector::default();
+    let events = collector.events.clone();
+    tracing::subscriber::with_default(collector, || {
+        let mut page = Page::default();
+        assert!(page.push_packet(b"hello"));
+        page.write(&mut Vec::new());
+
+        let mut jb = JitterBuffer::new(0);
+        assert!(jb.push(0, 0, vec![248, 255, 254]));
+        assert!(!jb.push(0, 0, vec![248, 255, 254]));
+    });
+
+    let events = events.lock().unwrap();
+    assert!(events.iter().all(|(target, _)| target == "opus"));
+    let messages: Vec<&str> = events.iter().map(|(_, m)| m.as_str()).collect();
+    assert_eq!(messages, ["wrote Ogg page", "dropped duplicate packet"]);
+}
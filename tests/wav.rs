@@ -0,0 +1,87 @@
+#![cfg(feature = "hound")]
+
+extern crate hound;
+extern crate opus;
+
+use std::io::Cursor;
+
+use opus::wav::{from_wav_reader, to_wav_writer};
+use opus::{Channels, Decoder, EncoderOptions};
+
+fn wav(spec: hound::WavSpec, samples: usize) -> Vec<u8> {
+    let mut bytes = Cursor::new(Vec::new());
+    {
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for i in 0..samples {
+            writer.write_sample((i % 100) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+    bytes.into_inner()
+}
+
+#[test]
+fn wav_round_trip() {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 16000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let reader = hound::WavReader::new(Cursor::new(wav(spec, 16000))).unwrap();
+    let mut input = from_wav_reader(reader).unwrap();
+    assert_eq!(input.channels(), Channels::Mono);
+    let encoder = input.encoder(&EncoderOptions::default()).unwrap();
+    let (_, packets) = input.encode(encoder, Vec::new()).unwrap();
+    // one second of 20 ms frames
+    assert_eq!(packets.len(), 50);
+
+    let mut decoder = Decoder::new(16000, Channels::Mono).unwrap();
+    let mut bytes = Cursor::new(Vec::new());
+    let mut output = to_wav_writer(&mut bytes, &mut decoder).unwrap();
+    output
+        .decode(
+            &mut decoder,
+            packets
+                .into_iter()
+                .collect::<std::collections::VecDeque<_>>(),
+        )
+        .unwrap();
+    output.finalize().unwrap();
+
+    let reader = hound::WavReader::new(Cursor::new(bytes.into_inner())).unwrap();
+    assert_eq!(reader.spec().sample_rate, 16000);
+    assert_eq!(reader.len(), 16000);
+}
+
+#[test]
+#[cfg(not(feature = "resample"))]
+fn rejects_unsupported_rate() {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: 44100,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let reader = hound::WavReader::new(Cursor::new(wav(spec, 100))).unwrap();
+    assert!(from_wav_reader(reader).is_err());
+}
+
+#[test]
+#[cfg(feature = "resample")]
+fn resamples_unsupported_rate() {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: 44100,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let reader = hound::WavReader::new(Cursor::new(wav(spec, 2 * 44100))).unwrap();
+    let mut input = from_wav_reader(reader).unwrap();
+    assert_eq!(input.sample_rate(), 44100);
+    assert_eq!(input.encode_rate(), 48000);
+    let encoder = input.encoder(&EncoderOptions::default()).unwrap();
+    let (_, packets) = input.encode(encoder, Vec::new()).unwrap();
+    // one second at 48kHz is exactly 50 packets of 20 ms
+    assert_eq!(packets.len(), 50);
+}